@@ -1,14 +1,24 @@
 use core::fmt;
 
+use crate::error::SizeError;
+
 /// Size of an CBUS CAN ID in octets.
 pub const CANID_SIZE: usize = 1;
 pub const CANID_MASK: u8 = 0x7f;
 
+/// Lowest CAN ID a node can be assigned.
+///
+/// CAN ID 0 is reserved for SLiM consumer nodes.
+pub const CANID_MIN: u8 = 1;
+/// Highest CAN ID a node can be assigned.
+pub const CANID_MAX: u8 = 99;
+
 /// A 7-bit CAN ID for CBUS.
 ///
 /// Used to identify nodes on a CAN network
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VlcbCanId(pub [u8; CANID_SIZE]);
 
 impl VlcbCanId {
@@ -22,6 +32,14 @@ impl VlcbCanId {
         Self(bytes.map(|x| x & CANID_MASK))
     }
 
+    /// Construct an CAN address from an octet, failing if `data` is not one octet long.
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self, SizeError> {
+        let bytes: [u8; CANID_SIZE] = data
+            .try_into()
+            .map_err(|_| SizeError::new(CANID_SIZE, data.len()))?;
+        Ok(Self(bytes.map(|x| x & CANID_MASK)))
+    }
+
     /// Return an CAN address as an octet.
     pub const fn as_bytes(&self) -> &[u8] {
         &self.0
@@ -34,6 +52,14 @@ impl fmt::Display for VlcbCanId {
     }
 }
 
+impl TryFrom<&[u8]> for VlcbCanId {
+    type Error = SizeError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(data)
+    }
+}
+
 impl From<VlcbCanId> for u8 {
     fn from(value: VlcbCanId) -> Self {
         value.0[0]
@@ -51,4 +77,22 @@ mod test {
         assert_eq!(addr.as_bytes(), &[0x7F]);
         assert_eq!(addr.to_string(), "7F");
     }
+
+    #[test]
+    fn test_try_from_bytes() {
+        assert_eq!(VlcbCanId::try_from_bytes(&[0xFF]), Ok(VlcbCanId([0x7F])));
+        assert_eq!(VlcbCanId::try_from_bytes(&[]), Err(SizeError::new(1, 0)));
+        assert_eq!(VlcbCanId::try_from(&[0x01, 0x02][..]), Err(SizeError::new(1, 2)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let addr = VlcbCanId::from_bytes(&[0x2A]);
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(serde_json::from_str::<VlcbCanId>(&json).unwrap(), addr);
+        let bytes = postcard::to_allocvec(&addr).unwrap();
+        assert_eq!(bytes, [0x2A]);
+        assert_eq!(postcard::from_bytes::<VlcbCanId>(&bytes).unwrap(), addr);
+    }
 }
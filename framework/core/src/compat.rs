@@ -0,0 +1,50 @@
+//! Deprecated names of the [`vlcb_defs`] types from the CBUS era.
+//!
+//! The aliases keep the code written against the old names building, new code should
+//! use the VLCB names they point to.
+
+#![allow(deprecated)]
+
+#[deprecated(note = "use `vlcb_defs::OpCode`")]
+pub type CbusOpCodes = vlcb_defs::OpCode;
+#[deprecated(note = "use `vlcb_defs::CommandError`")]
+pub type CbusErrs = vlcb_defs::CommandError;
+#[deprecated(note = "use `vlcb_defs::ModuleParam`")]
+pub type CbusParams = vlcb_defs::ModuleParam;
+#[deprecated(note = "use `vlcb_defs::ServiceType`")]
+pub type VlcbServiceTypes = vlcb_defs::ServiceType;
+#[deprecated(note = "use `vlcb_defs::ModuleMode`")]
+pub type VlcbModeParams = vlcb_defs::ModuleMode;
+#[deprecated(note = "use `vlcb_defs::Manufacturer`")]
+pub type CbusManufacturer = vlcb_defs::Manufacturer;
+#[deprecated(note = "use `vlcb_defs::MergModuleType`")]
+pub type CbusMergModuleTypes = vlcb_defs::MergModuleType;
+#[deprecated(note = "use `vlcb_defs::SprogModuleType`")]
+pub type CbusSprogModuleTypes = vlcb_defs::SprogModuleType;
+#[deprecated(note = "use `vlcb_defs::RocRailModuleType`")]
+pub type CbusRocRailModuleTypes = vlcb_defs::RocRailModuleType;
+#[deprecated(note = "use `vlcb_defs::SpectrumModuleType`")]
+pub type CbusSpectrumModuleTypes = vlcb_defs::SpectrumModuleType;
+#[deprecated(note = "use `vlcb_defs::SysPixieModuleType`")]
+pub type CbusSysPixieModuleTypes = vlcb_defs::SysPixieModuleType;
+
+#[cfg(test)]
+mod test {
+    use vlcb_defs::{CommandError, Manufacturer, ModuleMode, ModuleParam, OpCode, ServiceType};
+
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let opcode: CbusOpCodes = OpCode::QueryNodeInfo;
+        assert_eq!(u8::from(opcode), 0x0D);
+
+        let err: CbusErrs = CommandError::InvalidEvent;
+        assert_eq!(err, CommandError::InvalidEvent);
+
+        let _: CbusParams = ModuleParam::ModuleManufacturer;
+        let _: VlcbServiceTypes = ServiceType::MinimumNodeService;
+        let _: VlcbModeParams = ModuleMode::Normal;
+        let _: CbusManufacturer = Manufacturer::Development;
+    }
+}
@@ -1,6 +1,13 @@
 use byteorder::{ByteOrder, NetworkEndian};
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
 use num_enum::{FromPrimitive, IntoPrimitive};
+use vlcb_defs::DccError;
 
+use crate::time::millis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct LocoAddress([u8;2], bool);
 
 impl LocoAddress {
@@ -79,4 +86,379 @@ pub enum SessionQueryMode {
     Default = 0x00,
     Steal = 0x01,
     Share = 0x02,
-}
\ No newline at end of file
+}
+
+/// Identifier of a loco session, as carried by the DCC opcodes.
+pub type SessionId = u8;
+
+/// Number of the function ranges, see [`EngineFunctionRange`].
+const FUNCTION_RANGES: usize = 5;
+
+/// Errors returned when allocating a session, see [`SessionTable::allocate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AllocError {
+    /// The loco is controlled by another cab.
+    AddressTaken,
+    /// All the sessions are in use.
+    StackFull,
+    /// The loco to steal or share has no session.
+    SessionNotPresent,
+}
+
+/// Map the error to the ERR reported to the cab.
+impl From<AllocError> for DccError {
+    fn from(value: AllocError) -> Self {
+        match value {
+            AllocError::AddressTaken => DccError::LocoAddressIsTaken,
+            AllocError::StackFull => DccError::LocoStackIsFull,
+            AllocError::SessionNotPresent => DccError::SessionIsNotPresent,
+        }
+    }
+}
+
+/// Error returned for a session that is not allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SessionNotPresent;
+
+impl From<SessionNotPresent> for DccError {
+    fn from(_: SessionNotPresent) -> Self {
+        DccError::SessionIsNotPresent
+    }
+}
+
+/// State of a loco controlled by a cab.
+#[derive(Debug, Clone)]
+pub struct Session<C: Clock> {
+    address: LocoAddress,
+    /// Speed and direction as in DSPD, the direction is the top bit
+    speed_dir: u8,
+    functions: [u8; FUNCTION_RANGES],
+    /// Consist address with the reversed direction in the top bit, as in PCON
+    consist: Option<u8>,
+    /// Number of cabs sharing the session
+    cabs: u8,
+    last_seen: Instant<C>,
+}
+
+impl<C: Clock> Session<C> {
+    /// Return the address of the loco.
+    pub fn address(&self) -> LocoAddress {
+        self.address
+    }
+
+    /// Return the speed and direction, as in DSPD.
+    pub fn speed_dir(&self) -> u8 {
+        self.speed_dir
+    }
+
+    /// Return the state of the functions in the range, as in DFUN.
+    pub fn functions(&self, range: EngineFunctionRange) -> u8 {
+        self.functions[u8::from(range) as usize - 1]
+    }
+
+    /// Return the consist address and whether the loco runs reversed in the consist.
+    pub fn consist(&self) -> Option<(u8, bool)> {
+        self.consist.map(|consist| (consist & 0x7F, consist & 0x80 != 0))
+    }
+}
+
+/// Loco sessions of a command station.
+///
+/// Tracks up to `N` sessions, each controlling a loco, and expires the ones that were not
+/// kept alive (DKEEP) within the timeout. The session IDs are the indexes of the table.
+///
+/// The table does not transmit anything, the caller answers the cabs with the
+/// `loco_ctrl` packets, e.g. PLOC after a successful [`allocate`](Self::allocate) or the
+/// ERR mapped from the returned error.
+pub struct SessionTable<C: Clock, const N: usize> {
+    sessions: [Option<Session<C>>; N],
+    timeout: Milliseconds<u32>,
+}
+
+impl<C: Clock, const N: usize> SessionTable<C, N> {
+    /// Compile time check of the session count, session IDs are single octets.
+    const SANITY: () = assert!(N <= 256, "N must be at most 256");
+
+    /// Create an empty table expiring the sessions not kept alive for the `timeout`.
+    pub fn new(timeout: Milliseconds<u32>) -> Self {
+        let () = Self::SANITY;
+        Self {
+            sessions: core::array::from_fn(|_| None),
+            timeout,
+        }
+    }
+
+    /// Allocate a session for the loco, as requested by GLOC (or RLOC with
+    /// [`SessionQueryMode::Default`]).
+    ///
+    /// * [`SessionQueryMode::Default`] allocates a new session unless the loco has one,
+    /// * [`SessionQueryMode::Steal`] hands the session of the loco over to the requesting
+    ///   cab, the caller cancels it for the previous cab with `error::session_cancelled`,
+    /// * [`SessionQueryMode::Share`] returns the session of the loco, it is kept until every
+    ///   sharing cab releases it.
+    pub fn allocate(
+        &mut self,
+        address: LocoAddress,
+        mode: SessionQueryMode,
+        now: Instant<C>,
+    ) -> Result<SessionId, AllocError> {
+        if let Some(id) = self.find(address) {
+            let session = self.sessions[id as usize].as_mut().unwrap();
+            match mode {
+                SessionQueryMode::Default => return Err(AllocError::AddressTaken),
+                SessionQueryMode::Steal => session.cabs = 1,
+                SessionQueryMode::Share => session.cabs = session.cabs.saturating_add(1),
+            }
+            session.last_seen = now;
+            return Ok(id);
+        }
+        if mode != SessionQueryMode::Default {
+            return Err(AllocError::SessionNotPresent);
+        }
+
+        let (id, slot) = self
+            .sessions
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| slot.is_none())
+            .ok_or(AllocError::StackFull)?;
+        *slot = Some(Session {
+            address,
+            speed_dir: 0x80,
+            functions: [0; FUNCTION_RANGES],
+            consist: None,
+            cabs: 1,
+            last_seen: now,
+        });
+        Ok(id as SessionId)
+    }
+
+    /// Return the session controlling the loco.
+    pub fn find(&self, address: LocoAddress) -> Option<SessionId> {
+        self.sessions
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|s| s.address == address))
+            .map(|id| id as SessionId)
+    }
+
+    /// Return the allocated session.
+    pub fn get(&self, session: SessionId) -> Option<&Session<C>> {
+        self.sessions.get(session as usize)?.as_ref()
+    }
+
+    fn get_mut(&mut self, session: SessionId) -> Result<&mut Session<C>, SessionNotPresent> {
+        self.sessions
+            .get_mut(session as usize)
+            .and_then(Option::as_mut)
+            .ok_or(SessionNotPresent)
+    }
+
+    /// Keep the session alive, as requested by DKEEP.
+    pub fn touch(&mut self, session: SessionId, now: Instant<C>) -> Result<(), SessionNotPresent> {
+        self.get_mut(session)?.last_seen = now;
+        Ok(())
+    }
+
+    /// Release the sessions not kept alive within the timeout.
+    ///
+    /// Returns the released sessions.
+    pub fn expire(&mut self, now: Instant<C>) -> impl Iterator<Item = SessionId> {
+        let timeout = millis::<C>(self.timeout);
+        let mut expired = heapless::Vec::<SessionId, N>::new();
+        for (id, slot) in self.sessions.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|s| now >= s.last_seen + timeout) {
+                *slot = None;
+                // cannot overflow, there are N slots
+                let _ = expired.push(id as SessionId);
+            }
+        }
+        expired.into_iter()
+    }
+
+    /// Release the session for a cab, as requested by KLOC.
+    ///
+    /// A shared session is kept until the last of the cabs releases it.
+    pub fn release(&mut self, session: SessionId) -> Result<(), SessionNotPresent> {
+        let state = self.get_mut(session)?;
+        state.cabs -= 1;
+        if state.cabs == 0 {
+            self.sessions[session as usize] = None;
+        }
+        Ok(())
+    }
+
+    /// Set the speed and direction, as requested by DSPD.
+    pub fn set_speed_dir(&mut self, session: SessionId, speed_dir: u8) -> Result<(), SessionNotPresent> {
+        self.get_mut(session)?.speed_dir = speed_dir;
+        Ok(())
+    }
+
+    /// Set the state of the functions in the range, as requested by DFUN.
+    pub fn set_functions(
+        &mut self,
+        session: SessionId,
+        range: EngineFunctionRange,
+        functions: u8,
+    ) -> Result<(), SessionNotPresent> {
+        self.get_mut(session)?.functions[u8::from(range) as usize - 1] = functions;
+        Ok(())
+    }
+
+    /// Add the loco to the consist, as requested by PCON.
+    ///
+    /// The top bit of `consist` is the reversed direction of the loco in the consist.
+    pub fn add_to_consist(&mut self, session: SessionId, consist: u8) -> Result<(), SessionNotPresent> {
+        self.get_mut(session)?.consist = Some(consist);
+        Ok(())
+    }
+
+    /// Remove the loco from the consist, as requested by KCON.
+    ///
+    /// The direction bit of `consist` is ignored, a loco of another consist is kept.
+    pub fn remove_from_consist(&mut self, session: SessionId, consist: u8) -> Result<(), SessionNotPresent> {
+        let state = self.get_mut(session)?;
+        if state.consist.is_some_and(|c| c & 0x7F == consist & 0x7F) {
+            state.consist = None;
+        }
+        Ok(())
+    }
+
+    /// Iterate the sessions of the consist members.
+    pub fn consist_members(&self, consist: u8) -> impl Iterator<Item = SessionId> + '_ {
+        self.sessions
+            .iter()
+            .enumerate()
+            .filter(move |(_, slot)| {
+                slot.as_ref()
+                    .and_then(Session::consist)
+                    .is_some_and(|(address, _)| address == consist & 0x7F)
+            })
+            .map(|(id, _)| id as SessionId)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::time::TestClock;
+
+    const TIMEOUT: Milliseconds<u32> = Milliseconds(10_000);
+
+    #[test]
+    fn test_allocate_taken_address() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 4>::new(TIMEOUT);
+
+        let session = table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now());
+        assert_eq!(session, Ok(0));
+        assert_eq!(
+            table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now()),
+            Err(AllocError::AddressTaken)
+        );
+        assert_eq!(
+            table.allocate(LocoAddress::new_long(3), SessionQueryMode::Default, clock.now()),
+            Ok(1),
+            "the long address 3 is another loco"
+        );
+        assert_eq!(DccError::from(AllocError::AddressTaken), DccError::LocoAddressIsTaken);
+    }
+
+    #[test]
+    fn test_stack_full() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 2>::new(TIMEOUT);
+        table.allocate(LocoAddress::new(1), SessionQueryMode::Default, clock.now()).unwrap();
+        table.allocate(LocoAddress::new(2), SessionQueryMode::Default, clock.now()).unwrap();
+
+        assert_eq!(
+            table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now()),
+            Err(AllocError::StackFull)
+        );
+
+        // a released session is reused
+        table.release(0).unwrap();
+        assert_eq!(table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now()), Ok(0));
+        assert_eq!(table.release(5), Err(SessionNotPresent));
+    }
+
+    #[test]
+    fn test_steal() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 4>::new(TIMEOUT);
+        assert_eq!(
+            table.allocate(LocoAddress::new(3), SessionQueryMode::Steal, clock.now()),
+            Err(AllocError::SessionNotPresent)
+        );
+
+        let session = table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now()).unwrap();
+        table.set_speed_dir(session, 0x85).unwrap();
+        table.allocate(LocoAddress::new(3), SessionQueryMode::Share, clock.now()).unwrap();
+
+        // the session is handed over, the previous cabs are cancelled by the caller
+        let stolen = table.allocate(LocoAddress::new(3), SessionQueryMode::Steal, clock.now());
+        assert_eq!(stolen, Ok(session));
+        assert_eq!(table.get(session).unwrap().speed_dir(), 0x85, "the loco keeps running");
+
+        table.release(session).unwrap();
+        assert!(table.get(session).is_none(), "the thief owns the session alone");
+    }
+
+    #[test]
+    fn test_share() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 4>::new(TIMEOUT);
+        let session = table.allocate(LocoAddress::new(3), SessionQueryMode::Default, clock.now()).unwrap();
+
+        assert_eq!(table.allocate(LocoAddress::new(3), SessionQueryMode::Share, clock.now()), Ok(session));
+        table.release(session).unwrap();
+        assert_eq!(table.find(LocoAddress::new(3)), Some(session), "kept for the other cab");
+        table.release(session).unwrap();
+        assert_eq!(table.find(LocoAddress::new(3)), None);
+    }
+
+    #[test]
+    fn test_timeout_expiry() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 4>::new(TIMEOUT);
+        let first = table.allocate(LocoAddress::new(1), SessionQueryMode::Default, clock.now()).unwrap();
+        let second = table.allocate(LocoAddress::new(2), SessionQueryMode::Default, clock.now()).unwrap();
+
+        clock.advance(6_000);
+        table.touch(second, clock.now()).unwrap();
+        assert_eq!(table.expire(clock.now()).count(), 0);
+
+        clock.advance(4_000);
+        assert_eq!(table.expire(clock.now()).collect::<Vec<_>>(), [first]);
+        assert!(table.get(first).is_none());
+        assert_eq!(table.touch(first, clock.now()), Err(SessionNotPresent));
+
+        clock.advance(6_000);
+        assert_eq!(table.expire(clock.now()).collect::<Vec<_>>(), [second]);
+    }
+
+    #[test]
+    fn test_functions_and_consist() {
+        let clock = TestClock::new();
+        let mut table = SessionTable::<TestClock, 4>::new(TIMEOUT);
+        let lead = table.allocate(LocoAddress::new(1), SessionQueryMode::Default, clock.now()).unwrap();
+        let trailing = table.allocate(LocoAddress::new(2), SessionQueryMode::Default, clock.now()).unwrap();
+
+        table.set_functions(lead, EngineFunctionRange::F5ToF8, 0x03).unwrap();
+        assert_eq!(table.get(lead).unwrap().functions(EngineFunctionRange::F5ToF8), 0x03);
+        assert_eq!(table.get(lead).unwrap().functions(EngineFunctionRange::F0ToF4), 0x00);
+
+        table.add_to_consist(lead, 10).unwrap();
+        table.add_to_consist(trailing, 0x80 | 10).unwrap();
+        assert_eq!(table.get(trailing).unwrap().consist(), Some((10, true)));
+        assert_eq!(table.consist_members(10).collect::<Vec<_>>(), [lead, trailing]);
+
+        table.remove_from_consist(trailing, 11).unwrap();
+        assert_eq!(table.get(trailing).unwrap().consist(), Some((10, true)), "another consist");
+        table.remove_from_consist(trailing, 10).unwrap();
+        assert_eq!(table.consist_members(10).collect::<Vec<_>>(), [lead]);
+    }
+}
@@ -0,0 +1,23 @@
+use core::fmt;
+
+/// Error returned when constructing a fixed-size type from a slice of the wrong length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SizeError {
+    /// Number of octets the type is made of.
+    pub expected: usize,
+    /// Number of octets that were provided.
+    pub actual: usize,
+}
+
+impl SizeError {
+    pub const fn new(expected: usize, actual: usize) -> Self {
+        Self { expected, actual }
+    }
+}
+
+impl fmt::Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {} octets, got {}", self.expected, self.actual)
+    }
+}
@@ -1,4 +1,6 @@
+use embedded_time::duration::Milliseconds;
 use num_enum::{FromPrimitive, IntoPrimitive};
+use vlcb_defs::OpCode;
 
 /// Week day for fast clock implementation
 ///
@@ -19,6 +21,19 @@ pub enum FastClockWeekday {
     Saturday = 7,
 }
 
+impl FastClockWeekday {
+    /// Convert the octet of the fast clock protocol, rejecting the values outside of 1-7
+    /// instead of falling back to the default like [`From<u8>`](FastClockWeekday::from).
+    ///
+    /// `TryFrom<u8>` can not be implemented next to the `From<u8>` of the enum.
+    pub fn try_from_primitive(value: u8) -> Result<Self, FastClockError> {
+        match value {
+            1..=7 => Ok(Self::from(value)),
+            _ => Err(FastClockError::InvalidWeekday(value)),
+        }
+    }
+}
+
 /// Month for fast clock implementation
 ///
 /// The enum values represent the VLCB fast clock protocol specification
@@ -42,3 +57,380 @@ pub enum FastClockMonth {
     November = 11,
     December = 12,
 }
+
+impl FastClockMonth {
+    /// Convert the octet of the fast clock protocol, rejecting the values outside of 1-12
+    /// instead of falling back to the default like [`From<u8>`](FastClockMonth::from).
+    ///
+    /// `TryFrom<u8>` can not be implemented next to the `From<u8>` of the enum.
+    pub fn try_from_primitive(value: u8) -> Result<Self, FastClockError> {
+        match value {
+            1..=12 => Ok(Self::from(value)),
+            _ => Err(FastClockError::InvalidMonth(value)),
+        }
+    }
+
+    /// Return the amount of days in the month.
+    ///
+    /// The fast clock does not carry a year, so February always has 28 days.
+    pub fn days(self) -> u8 {
+        match self {
+            FastClockMonth::February => 28,
+            FastClockMonth::April
+            | FastClockMonth::June
+            | FastClockMonth::September
+            | FastClockMonth::November => 30,
+            _ => 31,
+        }
+    }
+}
+
+/// Size of the fast clock packet data in octets.
+pub const FAST_CLOCK_DATA_SIZE: usize = 6;
+
+const MS_PER_MINUTE: u64 = 60_000;
+
+/// Errors returned when constructing or parsing a fast clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FastClockError {
+    /// Minutes are larger than 59.
+    InvalidMinutes(u8),
+    /// Hours are larger than 23.
+    InvalidHours(u8),
+    /// Day of the month is outside of 1-31 range (inclusive).
+    InvalidMonthDay(u8),
+    /// Week day is outside of 1-7 range (inclusive).
+    InvalidWeekday(u8),
+    /// Month is outside of 1-12 range (inclusive).
+    InvalidMonth(u8),
+    /// The packet is not a fast clock packet.
+    InvalidPacket,
+}
+
+impl core::fmt::Display for FastClockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            FastClockError::InvalidMinutes(v) => write!(f, "invalid minutes ({})", v),
+            FastClockError::InvalidHours(v) => write!(f, "invalid hours ({})", v),
+            FastClockError::InvalidMonthDay(v) => write!(f, "invalid day of month ({})", v),
+            FastClockError::InvalidWeekday(v) => write!(f, "invalid week day ({})", v),
+            FastClockError::InvalidMonth(v) => write!(f, "invalid month ({})", v),
+            FastClockError::InvalidPacket => write!(f, "not a fast clock packet"),
+        }
+    }
+}
+
+/// Layout fast clock state as broadcasted in [`OpCode::FastClock`] packets.
+///
+/// The clock can be kept running locally between the broadcasts
+/// with [`advance`](FastClock::advance).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastClock {
+    mins: u8,
+    hours: u8,
+    week_day: FastClockWeekday,
+    month: FastClockMonth,
+    month_day: u8,
+    accel_coefficient: u8,
+    temperature: i8,
+    /// Fast milliseconds elapsed since the last full minute.
+    remainder_ms: u64,
+}
+
+impl FastClock {
+    /// Construct a fast clock.
+    ///
+    /// `hours` parameter should be in 24 hour format
+    ///
+    /// `accel_coefficient` is a time acceleration coefficient value `0` freezes the clock
+    ///     `1` is realtime and `2` and above means accelerated by the factor of N
+    pub fn new(
+        mins: u8,
+        hours: u8,
+        accel_coefficient: u8,
+        week_day: FastClockWeekday,
+        month: FastClockMonth,
+        month_day: u8,
+        temperature: i8,
+    ) -> Result<Self, FastClockError> {
+        if mins > 59 {
+            return Err(FastClockError::InvalidMinutes(mins));
+        }
+        if hours > 23 {
+            return Err(FastClockError::InvalidHours(hours));
+        }
+        if !(1..=31).contains(&month_day) {
+            return Err(FastClockError::InvalidMonthDay(month_day));
+        }
+
+        Ok(Self {
+            mins,
+            hours,
+            week_day,
+            month,
+            month_day,
+            accel_coefficient,
+            temperature,
+            remainder_ms: 0,
+        })
+    }
+
+    /// Parse a fast clock packet, the opcode followed by its data octets.
+    pub fn parse(packet: &[u8]) -> Result<Self, FastClockError> {
+        let data = match packet.split_first() {
+            Some((&opc, data))
+                if opc == u8::from(OpCode::FastClock) && data.len() == FAST_CLOCK_DATA_SIZE =>
+            {
+                data
+            }
+            _ => return Err(FastClockError::InvalidPacket),
+        };
+
+        let week_day = FastClockWeekday::try_from_primitive(data[2] & 0x07)?;
+        let month = FastClockMonth::try_from_primitive((data[2] >> 3) & 0x0F)?;
+
+        Self::new(
+            data[0],
+            data[1],
+            data[3],
+            week_day,
+            month,
+            data[4],
+            data[5] as i8,
+        )
+    }
+
+    /// Return the data octets of a fast clock packet.
+    pub fn to_bytes(&self) -> [u8; FAST_CLOCK_DATA_SIZE] {
+        let wdmon = u8::from(self.week_day) | (u8::from(self.month) << 3);
+
+        [
+            self.mins,
+            self.hours,
+            wdmon,
+            self.accel_coefficient,
+            self.month_day,
+            self.temperature as u8,
+        ]
+    }
+
+    /// Advance the clock by the real time elapsed, multiplied by the acceleration coefficient.
+    ///
+    /// Passing midnight moves the clock to the next day. A frozen clock (coefficient `0`)
+    /// does not move.
+    pub fn advance(&mut self, real_elapsed: Milliseconds<u32>) {
+        let elapsed = self.remainder_ms
+            + u64::from(real_elapsed.0) * u64::from(self.accel_coefficient);
+        self.remainder_ms = elapsed % MS_PER_MINUTE;
+
+        let total_mins = u64::from(self.mins) + elapsed / MS_PER_MINUTE;
+        self.mins = (total_mins % 60) as u8;
+
+        let total_hours = u64::from(self.hours) + total_mins / 60;
+        self.hours = (total_hours % 24) as u8;
+
+        for _ in 0..total_hours / 24 {
+            self.next_day();
+        }
+    }
+
+    fn next_day(&mut self) {
+        self.week_day = match self.week_day {
+            FastClockWeekday::Saturday => FastClockWeekday::Sunday,
+            d => FastClockWeekday::from(u8::from(d) + 1),
+        };
+
+        if self.month_day >= self.month.days() {
+            self.month_day = 1;
+            self.month = match self.month {
+                FastClockMonth::December => FastClockMonth::January,
+                m => FastClockMonth::from(u8::from(m) + 1),
+            };
+        } else {
+            self.month_day += 1;
+        }
+    }
+
+    /// Return the minutes.
+    pub fn mins(&self) -> u8 {
+        self.mins
+    }
+
+    /// Return the hours in 24 hour format.
+    pub fn hours(&self) -> u8 {
+        self.hours
+    }
+
+    /// Return the day of the week.
+    pub fn week_day(&self) -> FastClockWeekday {
+        self.week_day
+    }
+
+    /// Return the month.
+    pub fn month(&self) -> FastClockMonth {
+        self.month
+    }
+
+    /// Return the day of the month.
+    pub fn month_day(&self) -> u8 {
+        self.month_day
+    }
+
+    /// Return the time acceleration coefficient.
+    pub fn accel_coefficient(&self) -> u8 {
+        self.accel_coefficient
+    }
+
+    /// Return the temperature.
+    pub fn temperature(&self) -> i8 {
+        self.temperature
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        let new = |mins, hours, month_day| {
+            FastClock::new(
+                mins,
+                hours,
+                1,
+                FastClockWeekday::Monday,
+                FastClockMonth::May,
+                month_day,
+                20,
+            )
+        };
+
+        assert!(new(59, 23, 31).is_ok());
+        assert!(new(0, 0, 1).is_ok());
+        assert_eq!(new(60, 0, 1), Err(FastClockError::InvalidMinutes(60)));
+        assert_eq!(new(0, 24, 1), Err(FastClockError::InvalidHours(24)));
+        assert_eq!(new(0, 0, 0), Err(FastClockError::InvalidMonthDay(0)));
+        assert_eq!(new(0, 0, 32), Err(FastClockError::InvalidMonthDay(32)));
+    }
+
+    #[test]
+    fn test_bit_packing() {
+        let clock = FastClock::new(
+            30,
+            12,
+            4,
+            FastClockWeekday::Saturday,
+            FastClockMonth::December,
+            31,
+            -5,
+        )
+        .unwrap();
+
+        let bytes = clock.to_bytes();
+        assert_eq!(bytes, [30, 12, 0x67, 4, 31, 0xFB]);
+
+        let packet = [OpCode::FastClock.into(), 30, 12, 0x67, 4, 31, 0xFB];
+        assert_eq!(FastClock::parse(&packet), Ok(clock));
+
+        let clock = FastClock::parse(&[OpCode::FastClock.into(), 0, 0, 0x09, 1, 1, 0]).unwrap();
+        assert_eq!(clock.week_day(), FastClockWeekday::Sunday);
+        assert_eq!(clock.month(), FastClockMonth::January);
+    }
+
+    #[test]
+    fn test_weekday_try_from_primitive() {
+        assert_eq!(FastClockWeekday::try_from_primitive(7), Ok(FastClockWeekday::Saturday));
+        assert_eq!(FastClockWeekday::try_from_primitive(1), Ok(FastClockWeekday::Sunday));
+        assert_eq!(FastClockWeekday::try_from_primitive(0), Err(FastClockError::InvalidWeekday(0)));
+        assert_eq!(FastClockWeekday::try_from_primitive(9), Err(FastClockError::InvalidWeekday(9)));
+        // the lenient conversion is kept
+        assert_eq!(FastClockWeekday::from(9), FastClockWeekday::Sunday);
+    }
+
+    #[test]
+    fn test_month_try_from_primitive() {
+        assert_eq!(FastClockMonth::try_from_primitive(12), Ok(FastClockMonth::December));
+        assert_eq!(FastClockMonth::try_from_primitive(13), Err(FastClockError::InvalidMonth(13)));
+        assert_eq!(FastClockMonth::try_from_primitive(0), Err(FastClockError::InvalidMonth(0)));
+        assert_eq!(FastClockMonth::from(13), FastClockMonth::January);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let fclk: u8 = OpCode::FastClock.into();
+
+        assert_eq!(FastClock::parse(&[]), Err(FastClockError::InvalidPacket));
+        assert_eq!(
+            FastClock::parse(&[fclk, 0, 0, 0x09, 1, 1]),
+            Err(FastClockError::InvalidPacket)
+        );
+        assert_eq!(
+            FastClock::parse(&[OpCode::GeneralAck.into(), 0, 0, 0x09, 1, 1, 0]),
+            Err(FastClockError::InvalidPacket)
+        );
+        assert_eq!(
+            FastClock::parse(&[fclk, 0, 0, 0x08, 1, 1, 0]),
+            Err(FastClockError::InvalidWeekday(0))
+        );
+        assert_eq!(
+            FastClock::parse(&[fclk, 0, 0, 0x69, 1, 1, 0]),
+            Err(FastClockError::InvalidMonth(13))
+        );
+        assert_eq!(
+            FastClock::parse(&[fclk, 60, 0, 0x09, 1, 1, 0]),
+            Err(FastClockError::InvalidMinutes(60))
+        );
+    }
+
+    #[test]
+    fn test_advance_across_midnight() {
+        let mut clock = FastClock::new(
+            50,
+            23,
+            4,
+            FastClockWeekday::Saturday,
+            FastClockMonth::December,
+            31,
+            0,
+        )
+        .unwrap();
+
+        // 2.5 real minutes are 10 fast minutes
+        clock.advance(Milliseconds(150_000));
+        assert_eq!((clock.hours(), clock.mins()), (0, 0));
+        assert_eq!(clock.week_day(), FastClockWeekday::Sunday);
+        assert_eq!(clock.month(), FastClockMonth::January);
+        assert_eq!(clock.month_day(), 1);
+
+        // sub-minute remainders accumulate
+        clock.advance(Milliseconds(10_000));
+        assert_eq!(clock.mins(), 0);
+        clock.advance(Milliseconds(5_000));
+        assert_eq!(clock.mins(), 1);
+
+        // two full days
+        clock.advance(Milliseconds(2 * 24 * 60 * 60_000 / 4));
+        assert_eq!((clock.hours(), clock.mins()), (0, 1));
+        assert_eq!(clock.week_day(), FastClockWeekday::Tuesday);
+        assert_eq!(clock.month_day(), 3);
+    }
+
+    #[test]
+    fn test_frozen_clock() {
+        let mut clock = FastClock::new(
+            0,
+            8,
+            0,
+            FastClockWeekday::Monday,
+            FastClockMonth::February,
+            28,
+            0,
+        )
+        .unwrap();
+
+        clock.advance(Milliseconds(u32::MAX));
+        assert_eq!((clock.hours(), clock.mins()), (8, 0));
+        assert_eq!(clock.month_day(), 28);
+    }
+}
@@ -5,9 +5,13 @@
 extern crate alloc;
 
 pub mod macros;
+pub mod error;
 pub mod service;
 pub mod can;
+pub mod compat;
 pub mod vlcb;
 pub mod dcc;
 pub mod fast_clock;
 pub mod module;
+pub mod opcode;
+pub mod time;
@@ -1,9 +1,305 @@
 use bitflags::bitflags;
+use vlcb_defs::ModuleParam;
+
+use crate::error::SizeError;
 
 bitflags! {
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
     pub struct NodeFlags: u8 {
         const Heartbeat = 0b00000001;
         const EventAck = 0b00000010;
     }
-}
\ No newline at end of file
+}
+
+bitflags! {
+    /// Flags reported in the [`ModuleParam::NodeFlags`] parameter.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+    pub struct ParamFlags: u8 {
+        /// The module consumes events.
+        const Consumer = 0b00000001;
+        /// The module produces events.
+        const Producer = 0b00000010;
+        /// The module is in FLiM (normal mode in VLCB).
+        const FLiM = 0b00000100;
+        /// The module can be updated by the FCU bootloader.
+        const Bootable = 0b00001000;
+        /// The module consumes its own events.
+        const ConsumeOwnEvents = 0b00010000;
+        /// The module is in learn mode.
+        const Learn = 0b00100000;
+        /// The module is VLCB compatible.
+        const Vlcb = 0b01000000;
+    }
+}
+
+/// Amount of module parameters.
+pub const MODULE_PARAMS_COUNT: usize = 20;
+
+/// Size of the CPU manufacturer's id parameter in octets.
+pub const CPU_MANUFACTURER_ID_SIZE: usize = 4;
+
+/// Amount of parameters reported in a PARAMS response.
+pub const PARAMS_REPORT_COUNT: usize = 7;
+
+/// Module parameter block.
+///
+/// Parameters are numbered from 1 as in the CBUS parameter table, parameter 0
+/// is the amount of parameters and is not stored in the block.
+///
+/// The block keeps the checksum expected by the FCU bootloader up to date as
+/// the parameters are set, see [`ModuleParams::checksum`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleParams {
+    params: [u8; MODULE_PARAMS_COUNT],
+    checksum: u16,
+}
+
+impl Default for ModuleParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleParams {
+    /// Create a parameter block with all parameters zeroed.
+    pub const fn new() -> Self {
+        Self {
+            params: [0; MODULE_PARAMS_COUNT],
+            checksum: MODULE_PARAMS_COUNT as u16,
+        }
+    }
+
+    /// Return the position of a parameter in the parameter block.
+    fn index(param: ModuleParam) -> Option<usize> {
+        let index = (param as usize).checked_sub(1)?;
+        debug_assert!(
+            index < MODULE_PARAMS_COUNT,
+            "parameter {:?} is outside of the parameter block",
+            param
+        );
+        (index < MODULE_PARAMS_COUNT).then_some(index)
+    }
+
+    /// Return the amount of parameters, reported as parameter 0.
+    pub const fn count(&self) -> u8 {
+        MODULE_PARAMS_COUNT as u8
+    }
+
+    /// Return the value of a parameter.
+    ///
+    /// [`ModuleParam::ModuleParameterCount`] returns the amount of parameters.
+    pub fn get_param(&self, param: ModuleParam) -> u8 {
+        match param {
+            ModuleParam::ModuleParameterCount => self.count(),
+            _ => Self::index(param).map_or(0, |i| self.params[i]),
+        }
+    }
+
+    /// Return the value of a parameter by its index as requested by RQNPN.
+    ///
+    /// Index 0 returns the amount of parameters, `None` is returned for indexes
+    /// past the last parameter.
+    pub fn get(&self, index: u8) -> Option<u8> {
+        match index {
+            0 => Some(self.count()),
+            _ => self.params.get(index as usize - 1).copied(),
+        }
+    }
+
+    /// Set the value of a parameter.
+    ///
+    /// Parameters without a position in the parameter block are ignored.
+    pub fn set_param(&mut self, param: ModuleParam, value: u8) {
+        match Self::index(param) {
+            Some(i) => self.params[i] = value,
+            None => debug_assert!(false, "parameter {:?} can not be set", param),
+        }
+        self.finalize();
+    }
+
+    /// Set the values of consecutive parameters starting at `first`.
+    ///
+    /// Returns a [`SizeError`] with the amount of parameters from `first` to the end of the
+    /// parameter block when the `values` do not fit, nothing is set then.
+    pub fn set_params(&mut self, first: ModuleParam, values: &[u8]) -> Result<(), SizeError> {
+        match Self::index(first) {
+            Some(i) => {
+                let params = self
+                    .params
+                    .get_mut(i..i + values.len())
+                    .ok_or(SizeError::new(MODULE_PARAMS_COUNT - i, values.len()))?;
+                params.copy_from_slice(values);
+            }
+            None => debug_assert!(false, "parameter {:?} can not be set", first),
+        }
+        self.finalize();
+        Ok(())
+    }
+
+    /// Set the load address of the firmware, stored little endian in the four
+    /// [`ModuleParam::LoadAddress`] parameters.
+    pub fn set_load_address(&mut self, address: u32) {
+        // note(discard): the load address parameters are inside of the parameter block
+        let _ = self.set_params(ModuleParam::LoadAddress, &address.to_le_bytes());
+    }
+
+    /// Return the load address of the firmware.
+    pub fn load_address(&self) -> u32 {
+        let mut address = [0; 4];
+        address.copy_from_slice(&self.params[Self::range(ModuleParam::LoadAddress, 4)]);
+        u32::from_le_bytes(address)
+    }
+
+    /// Recompute the parameter checksum.
+    ///
+    /// Setting a parameter already keeps the checksum up to date, the module calls this
+    /// once more when its parameter block is complete.
+    pub fn finalize(&mut self) {
+        let cpu_manufacturer_id =
+            Self::range(ModuleParam::CpuManufacturerId, CPU_MANUFACTURER_ID_SIZE);
+        self.checksum = self
+            .params
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !cpu_manufacturer_id.contains(i))
+            .fold(u16::from(self.count()), |sum, (_, &v)| sum.wrapping_add(v.into()));
+    }
+
+    /// Return the parameter checksum checked by the FCU bootloader.
+    ///
+    /// The checksum is the 16 bit sum of the parameter count and all parameters except the
+    /// CPU manufacturer's id, which is read from the processor at runtime.
+    pub fn checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// Return the positions of `len` parameters starting at `first`.
+    fn range(first: ModuleParam, len: usize) -> core::ops::Range<usize> {
+        let first = first as usize - 1;
+        first..first + len
+    }
+
+    /// Return the first 7 parameters in the order of the PARAMS response.
+    ///
+    /// Manufacturer, minor version, module type, number of events, event variables per event,
+    /// number of node variables and major version.
+    pub fn first_seven(&self) -> [u8; PARAMS_REPORT_COUNT] {
+        let mut params = [0; PARAMS_REPORT_COUNT];
+        params.copy_from_slice(&self.params[..PARAMS_REPORT_COUNT]);
+        params
+    }
+
+    /// Return the parameter block, starting with parameter 1.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.params
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_param_boundaries() {
+        let mut params = ModuleParams::new();
+
+        params.set_param(ModuleParam::ModuleManufacturer, 0xA5);
+        params.set_param(ModuleParam::BetaVersion, 0x5A);
+
+        assert_eq!(params.as_bytes()[0], 0xA5);
+        assert_eq!(params.as_bytes()[MODULE_PARAMS_COUNT - 1], 0x5A);
+        assert_eq!(params.get_param(ModuleParam::ModuleManufacturer), 0xA5);
+        assert_eq!(params.get_param(ModuleParam::BetaVersion), 0x5A);
+        assert_eq!(params.get_param(ModuleParam::ModuleParameterCount), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not be set")]
+    fn test_set_param_count() {
+        let mut params = ModuleParams::new();
+        params.set_param(ModuleParam::ModuleParameterCount, 1);
+    }
+
+    #[test]
+    fn test_set_params_past_the_block() {
+        let mut params = ModuleParams::new();
+        params.set_params(ModuleParam::CpuManufacturerId, b"ATMG").unwrap();
+
+        // the beta version is the last parameter
+        assert_eq!(
+            params.set_params(ModuleParam::BetaVersion, &[1, 2]),
+            Err(SizeError::new(1, 2))
+        );
+        assert_eq!(
+            params.set_params(ModuleParam::CpuManufacturerId, b"ATMEGA8"),
+            Err(SizeError::new(6, 7))
+        );
+        assert_eq!(&params.as_bytes()[14..18], b"ATMG", "nothing is set");
+        assert_eq!(params.get_param(ModuleParam::BetaVersion), 0);
+    }
+
+    #[test]
+    fn test_get_by_index() {
+        let mut params = ModuleParams::new();
+        params.set_param(ModuleParam::ModuleManufacturer, 0xA5);
+        params.set_param(ModuleParam::BetaVersion, 0x5A);
+
+        assert_eq!(params.get(0), Some(20));
+        assert_eq!(params.get(1), Some(0xA5));
+        assert_eq!(params.get(20), Some(0x5A));
+        assert_eq!(params.get(21), None);
+        assert_eq!(params.get(u8::MAX), None);
+    }
+
+    #[test]
+    fn test_first_seven() {
+        let mut params = ModuleParams::new();
+        params.set_param(ModuleParam::ModuleManufacturer, 1);
+        params.set_param(ModuleParam::MinorVersion, 2);
+        params.set_param(ModuleParam::ModuleType, 3);
+        params.set_param(ModuleParam::MaxEventCount, 4);
+        params.set_param(ModuleParam::EventVariableCount, 5);
+        params.set_param(ModuleParam::NodeVariableCount, 6);
+        params.set_param(ModuleParam::MajorVersion, 7);
+        params.set_param(ModuleParam::NodeFlags, 8);
+
+        assert_eq!(params.first_seven(), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut params = ModuleParams::new();
+        assert_eq!(params.checksum(), 20);
+
+        params.set_param(ModuleParam::ModuleManufacturer, 0xA5);
+        params.set_param(ModuleParam::BetaVersion, 0xFF);
+        assert_eq!(params.checksum(), 20 + 0xA5 + 0xFF);
+
+        params.set_params(ModuleParam::CpuManufacturerId, b"ATMG").unwrap();
+        assert_eq!(params.checksum(), 20 + 0xA5 + 0xFF, "CPU manufacturer id is not summed");
+
+        params.set_load_address(0x0000_0800);
+        assert_eq!(params.as_bytes()[10..14], [0x00, 0x08, 0x00, 0x00]);
+        assert_eq!(params.load_address(), 0x0800);
+        assert_eq!(params.checksum(), 20 + 0xA5 + 0xFF + 0x08);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let flags = NodeFlags::Heartbeat | NodeFlags::EventAck;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, "\"Heartbeat | EventAck\"");
+        assert_eq!(serde_json::from_str::<NodeFlags>(&json).unwrap(), flags);
+        let bytes = postcard::to_allocvec(&flags).unwrap();
+        assert_eq!(postcard::from_bytes::<NodeFlags>(&bytes).unwrap(), flags);
+
+        let flags = ParamFlags::Consumer | ParamFlags::FLiM;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(serde_json::from_str::<ParamFlags>(&json).unwrap(), flags);
+        let bytes = postcard::to_allocvec(&flags).unwrap();
+        assert_eq!(postcard::from_bytes::<ParamFlags>(&bytes).unwrap(), flags);
+    }
+}
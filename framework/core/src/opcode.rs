@@ -0,0 +1,231 @@
+//! Opcode metadata: mnemonic, number of data octets and CAN priority class.
+
+use vlcb_defs::OpCode;
+
+/// Priority class of an opcode, the default CAN priority its packets are sent with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PriorityClass {
+    /// DCC (loco control) packets.
+    High,
+    /// Accessory events.
+    Normal,
+    /// Everything else, mostly module configuration.
+    Low,
+}
+
+/// Metadata of an opcode, see [`opcode_info`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OpcodeInfo {
+    /// Number of data octets following the opcode.
+    pub data_len: u8,
+    pub priority: PriorityClass,
+    /// The CBUS mnemonic of the opcode, e.g. `ACON`, or `UNKNOWN`.
+    pub name: &'static str,
+}
+
+/// Return the number of data octets encoded in the top 3 bits of an opcode.
+pub const fn encoded_data_len(op: u8) -> u8 {
+    op >> 5
+}
+
+/// Return the metadata of the opcode `op`.
+///
+/// Opcodes unknown to the library have the name `UNKNOWN`, the data length encoded
+/// in the opcode and the low priority class.
+pub fn opcode_info(op: u8) -> OpcodeInfo {
+    match OpCode::try_from(op) {
+        Ok(opcode) => info(opcode),
+        Err(_) => OpcodeInfo {
+            data_len: encoded_data_len(op),
+            priority: PriorityClass::Low,
+            name: "UNKNOWN",
+        },
+    }
+}
+
+macro_rules! opcode_table {
+    ($($opcode:ident => $name:literal, $data_len:literal, $priority:ident;)*) => {
+        const fn info(opcode: OpCode) -> OpcodeInfo {
+            match opcode {
+                $(OpCode::$opcode => OpcodeInfo {
+                    data_len: $data_len,
+                    priority: PriorityClass::$priority,
+                    name: $name,
+                },)*
+            }
+        }
+    };
+}
+
+opcode_table! {
+    GeneralAck => "ACK", 0, Low;
+    GeneralNack => "NAK", 0, Low;
+    BusHalt => "HLT", 0, Low;
+    BusResume => "BON", 0, Low;
+    DccTrackPoweredOff => "TOF", 0, High;
+    DccTrackPoweredOn => "TON", 0, High;
+    DccEmergencyStopEngaged => "ESTOP", 0, High;
+    RestartAllNodes => "ARST", 0, Low;
+    DccTrackPowerOff => "RTOF", 0, High;
+    DccTrackPowerOn => "RTON", 0, High;
+    DccEmergencyStop => "RESTP", 0, High;
+    DccQueryCommandStationStatus => "RSTAT", 0, High;
+    QueryNodeInfo => "QNN", 0, Low;
+    QueryNodeParameters => "RQNP", 0, Low;
+    QueryModuleName => "RQMN", 0, Low;
+    DccReleaseSession => "KLOC", 1, High;
+    DccQueryLocoStatus => "QLOC", 1, High;
+    DccSessionKeepAlive => "DKEEP", 1, High;
+    DebugMsg1 => "DBG1", 1, Low;
+    ExtOpCode => "EXTC", 1, Low;
+    DccRequestNewSession => "RLOC", 2, High;
+    DccQueryConsist => "QCON", 2, High;
+    SetNodeNumber => "SNN", 2, Low;
+    DccAllocateLocoToActivity => "ALOC", 2, High;
+    DccSetThrottleMode => "STMOD", 2, High;
+    DccConsistAddLoco => "PCON", 2, High;
+    DccConsistRemoveLoco => "KCON", 2, High;
+    DccSetLocoThrottle => "DSPD", 2, High;
+    DccSetLocoFlags => "DFLG", 2, High;
+    DccLocoFunctionOn => "DFNON", 2, High;
+    DccLocoFunctionOff => "DFNOF", 2, High;
+    DccServiceModeStatus => "SSTAT", 2, High;
+    ResetModuleToFactory => "NNRSM", 2, Low;
+    RequestNewNodeNumber => "RQNN", 2, Low;
+    NodeNumberReleased => "NNREL", 2, Low;
+    NodeNumberAck => "NNACK", 2, Low;
+    PutNodeIntoLearnMode => "NNLRN", 2, Low;
+    ReleaseNodeFromLearnMode => "NNULN", 2, Low;
+    ForgetAllLearnedEvents => "NNCLR", 2, Low;
+    QueryAvailableEventSlots => "NNEVN", 2, Low;
+    QueryAllLearnedEvents => "NERD", 2, Low;
+    QueryLearnedEventCount => "RQEVN", 2, Low;
+    WriteAck => "WRACK", 2, Low;
+    QueryNodeData => "RQDAT", 2, Low;
+    RequestDeviceDataShortMode => "RQDDS", 2, Low;
+    RebootIntoBootloader => "BOOTM", 2, Low;
+    ForceCanEnumeration => "ENUM", 2, Low;
+    RestartNode => "NNRST", 2, Low;
+    ExtOpCode1 => "EXTC1", 2, Low;
+    DccSetLocoFunctions => "DFUN", 3, High;
+    DccQueryLocoSession => "GLOC", 3, High;
+    DccCommandStationError => "ERR", 3, High;
+    NodeConfigurationError => "CMDERR", 3, Low;
+    AvailableEventSlots => "EVNLF", 3, Low;
+    QueryNodeVariable => "NVRD", 3, Low;
+    QueryLearnedEventByIndex => "NENRD", 3, Low;
+    QueryNodeParameterByIndex => "RQNPN", 3, Low;
+    LearnedEventCount => "NUMEV", 3, Low;
+    SetNodeCanId => "CANID", 3, Low;
+    PutNodeIntoMode => "MODE", 3, Low;
+    ServiceDiscoveryQuery => "RQSD", 3, Low;
+    ExtOpCode2 => "EXTC2", 3, Low;
+    DccSendRawPacket3 => "RDCC3", 4, High;
+    DccWriteCvByteInOpsMode => "WCVO", 4, High;
+    DcWriteCvBitInOpsMode => "WCVB", 4, High;
+    DccReadCv => "QCVS", 4, High;
+    DccCvValue => "PCVS", 4, High;
+    QueryDiagnosticData => "RDGN", 4, Low;
+    SetNodeVariable => "NVSETRD", 4, Low;
+    LongEventAccessoryOn => "ACON", 4, Normal;
+    LongEventAccessoryOff => "ACOF", 4, Normal;
+    QueryLongEventAccessoryState => "AREQ", 4, Normal;
+    LongEventAccessoryStateOn => "ARON", 4, Normal;
+    LongEventAccessoryStateOff => "AROF", 4, Normal;
+    ForgetLearnedEvent => "EVULN", 4, Low;
+    LegacySetNodeVariable => "NVSET", 4, Low;
+    NodeVariableValue => "NVANS", 4, Low;
+    ShortEventAccessoryOn => "ASON", 4, Normal;
+    ShortEventAccessoryOff => "ASOF", 4, Normal;
+    QueryShortEventAccessoryState => "ASRQ", 4, Normal;
+    NodeParameterValue => "PARAN", 4, Low;
+    QueryEventVariable => "REVAL", 4, Low;
+    ShortEventAccessoryStateOn => "ARSON", 4, Normal;
+    ShortEventAccessoryStateOff => "ARSOF", 4, Normal;
+    ExtOpCode3 => "EXTC3", 4, Low;
+    DccSendRawPacket4 => "RDCC4", 5, High;
+    DccWriteCvInServiceMode => "WCVS", 5, High;
+    Heartbeat => "HEARTB", 5, Low;
+    ServiceDiscoveryResponse => "SD", 5, Low;
+    GenericResponse => "GRSP", 5, Low;
+    LongEventAccessoryOn1 => "ACON1", 5, Normal;
+    LongEventAccessoryOff1 => "ACOF1", 5, Normal;
+    QueryEventVariableInLearnMode => "REQEV", 5, Low;
+    LongEventAccessoryStateOn1 => "ARON1", 5, Normal;
+    LongEventAccessoryStateOff1 => "AROF1", 5, Normal;
+    EventVariableValue => "NEVAL", 5, Low;
+    NodeInfo => "PNN", 5, Low;
+    ShortEventAccessoryOn1 => "ASON1", 5, Normal;
+    ShortEventAccessoryOff1 => "ASOF1", 5, Normal;
+    ShortEventAccessoryStateOn1 => "ARSON1", 5, Normal;
+    ShortEventAccessoryStateOff1 => "ARSOF1", 5, Normal;
+    ExtOpCode4 => "EXTC4", 5, Low;
+    DccSendRawPacket5 => "RDCC5", 6, High;
+    DccWriteCvByteInOpsModeByAddress => "WCVOA", 6, High;
+    DccSendDataToCab => "CABDAT", 6, High;
+    DiagnosticData => "DGN", 6, Low;
+    FastClock => "FCLK", 6, Low;
+    LongEventAccessoryOn2 => "ACON2", 6, Normal;
+    LongEventAccessoryOff2 => "ACOF2", 6, Normal;
+    TeachEvent => "EVLRN", 6, Low;
+    EventVariableValueInLearnMode => "EVANS", 6, Low;
+    LongEventAccessoryStateOn2 => "ARON2", 6, Normal;
+    LongEventAccessoryStateOff2 => "AROF2", 6, Normal;
+    ShortEventAccessoryOn2 => "ASON2", 6, Normal;
+    ShortEventAccessoryOff2 => "ASOF2", 6, Normal;
+    ShortEventAccessoryStateOn2 => "ARSON2", 6, Normal;
+    ShortEventAccessoryStateOff2 => "ARSOF2", 6, Normal;
+    ExtOpCode5 => "EXTC5", 6, Low;
+    DccSendRawPacket6 => "RDCC6", 7, High;
+    DccLocoReport => "PLOC", 7, High;
+    ModuleName => "NAME", 7, Low;
+    DccCommandStationStatus => "STAT", 7, High;
+    EventAck => "ENACK", 7, Low;
+    ExtendedServiceDiscoveryResponse => "ESD", 7, Low;
+    StreamPacket => "DTXC", 7, Low;
+    NodeParametersReport => "PARAMS", 7, Low;
+    LongEventAccessoryOn3 => "ACON3", 7, Normal;
+    LongEventAccessoryOff3 => "ACOF3", 7, Normal;
+    LearnedEventResponse => "ENRSP", 7, Low;
+    LongEventAccessoryStateOn3 => "ARON3", 7, Normal;
+    LongEventAccessoryStateOff3 => "AROF3", 7, Normal;
+    TeachEventByIndex => "EVLRNI", 7, Low;
+    DataEventAccessory => "ACDAT", 7, Low;
+    NodeDataEventResponse => "ARDAT", 7, Low;
+    ShortEventAccessoryOn3 => "ASON3", 7, Normal;
+    ShortEventAccessoryOff3 => "ASOF3", 7, Normal;
+    DeviceDataEventShortMode => "DDES", 7, Low;
+    DeviceDataResponseShortMode => "DDRS", 7, Low;
+    WriteData => "DDWS", 7, Low;
+    ShortEventAccessoryStateOn3 => "ARSON3", 7, Normal;
+    ShortEventAccessoryStateOff3 => "ARSOF3", 7, Normal;
+    ExtOpCode6 => "EXTC6", 7, Low;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_data_len_matches_opcode_bits() {
+        for op in 0..=u8::MAX {
+            let info = opcode_info(op);
+            assert_eq!(info.data_len, encoded_data_len(op), "opcode {op:#04X} ({})", info.name);
+        }
+    }
+
+    #[test]
+    fn test_known_opcodes() {
+        let acon = opcode_info(OpCode::LongEventAccessoryOn.into());
+        assert_eq!(acon.name, "ACON");
+        assert_eq!(acon.data_len, 4);
+        assert_eq!(acon.priority, PriorityClass::Normal);
+
+        let dspd = opcode_info(OpCode::DccSetLocoThrottle.into());
+        assert_eq!((dspd.name, dspd.priority), ("DSPD", PriorityClass::High));
+
+        assert_eq!(opcode_info(OpCode::QueryNodeInfo.into()).priority, PriorityClass::Low);
+    }
+}
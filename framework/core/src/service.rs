@@ -18,3 +18,35 @@ pub trait VlcbService {
         0
     }
 }
+
+/// A diagnostic counter of a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Diagnostic {
+    /// Name of the counter, for logs and tooling; it is not sent over the bus.
+    pub name: &'static str,
+    pub value: u16,
+}
+
+impl Diagnostic {
+    pub const fn new(name: &'static str, value: u16) -> Self {
+        Self { name, value }
+    }
+}
+
+/// Diagnostic counters of a service, read by the diagnostics service (RDGN).
+///
+/// Diagnostic codes start at 1, the code 0 is reserved for requesting
+/// all diagnostics of a service.
+pub trait Diagnostics {
+    /// Returns the number of diagnostic counters of the service
+    fn diagnostic_count(&self) -> u8 {
+        0
+    }
+
+    /// Returns the diagnostic counter with the `code`
+    fn diagnostic(&self, code: u8) -> Option<Diagnostic> {
+        let _ = code;
+        None
+    }
+}
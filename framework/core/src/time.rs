@@ -0,0 +1,127 @@
+//! Time related helpers.
+//!
+//! The timings of the stack are given in milliseconds, as [`Milliseconds<u32>`] constants or
+//! plain numbers of a runtime config, and converted to the duration of the [`Clock`] of the
+//! application with [`millis`] where they are compared against its instants.
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::Clock;
+
+#[cfg(any(test, feature = "test-clock"))]
+pub use self::test_clock::TestClock;
+
+/// Convert a duration in milliseconds to a duration of the clock `C`.
+///
+/// ```
+/// use embedded_time::duration::Milliseconds;
+/// use embedded_time::fraction::Fraction;
+/// use embedded_time::{clock, Clock, Instant};
+/// use vlcb_core::time::millis;
+///
+/// struct MillisClock;
+///
+/// impl Clock for MillisClock {
+///     type T = u64;
+///     const SCALING_FACTOR: Fraction = Fraction::new(1, 1000);
+///
+///     fn try_now(&self) -> Result<Instant<Self>, clock::Error> {
+///         Ok(Instant::new(1_000))
+///     }
+/// }
+///
+/// const HOLD: Milliseconds<u32> = Milliseconds(500);
+/// assert_eq!(millis::<MillisClock>(HOLD), Milliseconds(500u64));
+/// ```
+pub fn millis<C: Clock>(duration: Milliseconds<u32>) -> Milliseconds<C::T> {
+    Milliseconds::new(C::T::from(duration.0))
+}
+
+#[cfg(any(test, feature = "test-clock"))]
+mod test_clock {
+    use core::cell::Cell;
+
+    use embedded_time::clock::Error;
+    use embedded_time::fraction::Fraction;
+    use embedded_time::{Clock, Instant};
+
+    /// A manually driven millisecond clock for tests.
+    ///
+    /// The clock never moves on its own, time only passes when [`advance`](TestClock::advance)
+    /// is called, which makes timers deterministic in tests.
+    ///
+    /// ```
+    /// use embedded_time::duration::Milliseconds;
+    /// use vlcb_core::time::TestClock;
+    ///
+    /// let clock = TestClock::new();
+    /// let start = clock.now();
+    ///
+    /// clock.advance(1500);
+    /// let elapsed: Milliseconds<u64> = (clock.now() - start).try_into().unwrap();
+    /// assert_eq!(elapsed, Milliseconds(1500u64));
+    /// ```
+    #[derive(Debug, Default)]
+    pub struct TestClock {
+        ticks: Cell<u64>,
+    }
+
+    impl TestClock {
+        /// Create a clock starting at zero.
+        pub const fn new() -> Self {
+            Self::starting_at(0)
+        }
+
+        /// Create a clock starting at the given amount of milliseconds.
+        pub const fn starting_at(ms: u64) -> Self {
+            Self {
+                ticks: Cell::new(ms),
+            }
+        }
+
+        /// Move the clock forward by the given amount of milliseconds.
+        pub fn advance(&self, ms: u64) {
+            self.ticks.set(self.ticks.get() + ms);
+        }
+
+        /// Return the current instant.
+        pub fn now(&self) -> Instant<Self> {
+            Instant::new(self.ticks.get())
+        }
+    }
+
+    impl Clock for TestClock {
+        type T = u64;
+        const SCALING_FACTOR: Fraction = Fraction::new(1, 1000);
+
+        fn try_now(&self) -> Result<Instant<Self>, Error> {
+            Ok(self.now())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_time::duration::Milliseconds;
+    use embedded_time::Clock;
+
+    use super::*;
+
+    #[test]
+    fn test_clock_advances_only_manually() {
+        let clock = TestClock::new();
+        let start = clock.try_now().unwrap();
+        assert_eq!(clock.try_now().unwrap(), start);
+
+        clock.advance(100);
+        clock.advance(6000);
+        let elapsed: Milliseconds<u64> = (clock.now() - start).try_into().unwrap();
+        assert_eq!(elapsed, Milliseconds(6100u64));
+    }
+
+    #[test]
+    fn test_clock_starting_at() {
+        let clock = TestClock::starting_at(u32::MAX as u64);
+        clock.advance(1);
+        assert_eq!(clock.now(), embedded_time::Instant::new(u32::MAX as u64 + 1));
+    }
+}
@@ -1,10 +1,18 @@
+use core::fmt;
+use core::str::FromStr;
+
 use byteorder::{ByteOrder, NetworkEndian};
+use vlcb_defs::{CommandError, GenericResponseStatus};
+
+use crate::error::SizeError;
 
 /// Size of an CBUS node number in octets.
 pub const NODENUM_SIZE: usize = 2;
 
 /// A two-octet CBUS node number.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VlcbNodeNumber(pub [u8; NODENUM_SIZE]);
 
 impl VlcbNodeNumber {
@@ -23,10 +31,28 @@ impl VlcbNodeNumber {
         Self(bytes)
     }
 
+    /// Construct an CBUS node number from a sequence of octets, in big-endian,
+    /// failing if `data` is not two octets long.
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self, SizeError> {
+        data.try_into()
+            .map(Self)
+            .map_err(|_| SizeError::new(NODENUM_SIZE, data.len()))
+    }
+
     /// Return an CBUS node number as a sequence of octets, in big-endian.
     pub const fn as_bytes(&self) -> &[u8] {
         &self.0
     }
+
+    /// Construct an CBUS node number from its numeric value.
+    pub const fn from_u16(value: u16) -> Self {
+        Self(value.to_be_bytes())
+    }
+
+    /// Return the numeric value of an CBUS node number.
+    pub const fn to_u16(&self) -> u16 {
+        u16::from_be_bytes(self.0)
+    }
 }
 
 impl Default for VlcbNodeNumber {
@@ -35,11 +61,68 @@ impl Default for VlcbNodeNumber {
     }
 }
 
+impl TryFrom<&[u8]> for VlcbNodeNumber {
+    type Error = SizeError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl From<VlcbNodeNumber> for u16 {
+    fn from(value: VlcbNodeNumber) -> Self {
+        value.to_u16()
+    }
+}
+
+impl fmt::Display for VlcbNodeNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", u16::from(*self))
+    }
+}
+
+/// Error returned when parsing a node number from a string fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseNodeNumberError {
+    /// The string is not a decimal number.
+    Invalid,
+    /// The number is outside of 1-65535 range (inclusive).
+    OutOfRange,
+}
+
+impl fmt::Display for ParseNodeNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseNodeNumberError::Invalid => write!(f, "invalid node number"),
+            ParseNodeNumberError::OutOfRange => write!(f, "node number out of range (1-65535)"),
+        }
+    }
+}
+
+impl FromStr for VlcbNodeNumber {
+    type Err = ParseNodeNumberError;
+
+    /// Parse a decimal node number in 1-65535 range (inclusive).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseNodeNumberError::Invalid);
+        }
+
+        match s.parse::<u32>() {
+            Ok(num @ 1..=0xFFFF) => Ok(Self((num as u16).to_be_bytes())),
+            // only digits are left, so the number is either zero or too large
+            _ => Err(ParseNodeNumberError::OutOfRange),
+        }
+    }
+}
+
 /// Size of an CBUS P / C event in octets.
 pub const EVENT_SIZE: usize = 4;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventType {
     /// Event type unknown to the library implementation
     Unknown,
@@ -62,8 +145,11 @@ pub enum EventType {
 }
 
 /// A four-octet CBUS P / C event.
+///
+/// Displayed and parsed as `NN:EN` with both numbers in decimal, followed by `L` for long
+/// events and `S` for short events, e.g. `1234:7L`. Short events display the node number 0.
 #[derive(Debug, Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Default)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventId {
     data: [u8; EVENT_SIZE],
     is_short: bool,
@@ -94,30 +180,69 @@ impl EventId {
         }
     }
 
-    /// Construct a short CBUS P / C event from a sequence of octets, in big-endian.
+    /// Construct a long CBUS P / C event from a sequence of octets, in big-endian,
+    /// failing if `data` is not four octets long.
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self, SizeError> {
+        let bytes = data
+            .try_into()
+            .map_err(|_| SizeError::new(EVENT_SIZE, data.len()))?;
+        Ok(Self {
+            data: bytes,
+            is_short: false,
+        })
+    }
+
+    /// Construct a short CBUS P / C event from the two octets of its device number, in big-endian.
     ///
-    /// Short event is essentially the same size af "long" events, but node number is ignored.
-    /// The data is still 4 octets long, but with the node number part null-ed.
+    /// The node number half of the event is zeroed, see [`EventId::short`].
     ///
     /// # Panics
     /// The function panics if `data` is not two octets long.
     pub fn short_from_bytes(data: &[u8]) -> Self {
         let mut bytes = [0; EVENT_SIZE];
-        bytes[2..].copy_from_slice(&data[2..]);
+        bytes[2..].copy_from_slice(data);
         Self {
             data: bytes,
             is_short: true
         }
     }
 
+    /// Construct a short CBUS P / C event from the two octets of its device number, in big-endian,
+    /// failing if `data` is not two octets long.
+    pub fn try_short_from_bytes(data: &[u8]) -> Result<Self, SizeError> {
+        let [a2, a3] = data
+            .try_into()
+            .map_err(|_| SizeError::new(EVENT_SIZE - NODENUM_SIZE, data.len()))?;
+        Ok(Self::new(true, 0, 0, a2, a3))
+    }
+
+    /// Construct a short CBUS P / C event from the four octets of an event on the bus,
+    /// e.g. the data of ASON.
+    ///
+    /// The node number octets are ignored, the event keeps only the device number.
+    pub const fn short_from_wire_bytes(data: &[u8; EVENT_SIZE]) -> Self {
+        Self::new(true, 0, 0, data[2], data[3])
+    }
+
+    /// Construct a long CBUS P / C event produced by the node `node_num`.
+    pub const fn long(node_num: VlcbNodeNumber, event_num: u16) -> Self {
+        let [a0, a1] = node_num.0;
+        let [a2, a3] = event_num.to_be_bytes();
+        Self::new(false, a0, a1, a2, a3)
+    }
+
+    /// Construct a short CBUS P / C event with the device number `device_num`.
+    pub const fn short(device_num: u16) -> Self {
+        let [a2, a3] = device_num.to_be_bytes();
+        Self::new(true, 0, 0, a2, a3)
+    }
+
     /// Construct an CBUS P / C event from a node number and event id.
+    #[deprecated(note = "use `EventId::long` or `EventId::short` instead")]
     pub fn from_node_and_id(node_num: &VlcbNodeNumber, evt_id: u16, short: bool) -> Self {
-        let mut bytes = [0; EVENT_SIZE];
-        bytes.copy_from_slice(node_num.as_bytes());
-        NetworkEndian::write_u16(&mut bytes[2..], evt_id);
-        Self {
-            data: bytes,
-            is_short: short,
+        match short {
+            true => Self::short(evt_id),
+            false => Self::long(*node_num, evt_id),
         }
     }
 
@@ -136,6 +261,11 @@ impl EventId {
         NetworkEndian::read_u16(&self.data[2..])
     }
 
+    /// Return a CBUS device number, the event number of a short event
+    pub fn device_number(&self) -> u16 {
+        self.event_num()
+    }
+
     /// Check whether the event is short
     pub fn is_short(&self) -> bool {
         self.is_short
@@ -146,3 +276,405 @@ impl EventId {
         !self.is_short
     }
 }
+
+impl fmt::Display for EventId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = if self.is_short { 'S' } else { 'L' };
+        write!(f, "{}:{}{}", self.node_num(), self.event_num(), suffix)
+    }
+}
+
+/// Error returned when parsing an event from a string fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseEventIdError {
+    /// The string is not in the `NN:EN` notation.
+    Invalid,
+    /// The node number is outside of 1-65535 range, or the event number
+    /// outside of 0-65535 range (inclusive).
+    OutOfRange,
+}
+
+impl fmt::Display for ParseEventIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseEventIdError::Invalid => write!(f, "invalid event"),
+            ParseEventIdError::OutOfRange => write!(f, "event number out of range"),
+        }
+    }
+}
+
+impl From<ParseNodeNumberError> for ParseEventIdError {
+    fn from(value: ParseNodeNumberError) -> Self {
+        match value {
+            ParseNodeNumberError::Invalid => ParseEventIdError::Invalid,
+            ParseNodeNumberError::OutOfRange => ParseEventIdError::OutOfRange,
+        }
+    }
+}
+
+impl FromStr for EventId {
+    type Err = ParseEventIdError;
+
+    /// Parse an event in the `NN:EN` notation with both numbers in decimal, as displayed.
+    ///
+    /// An `S` suffix parses a short event, whose node number is ignored, an `L` suffix
+    /// or no suffix parses a long event.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, short) = match s.as_bytes().last() {
+            Some(b'S') => (&s[..s.len() - 1], true),
+            Some(b'L') => (&s[..s.len() - 1], false),
+            _ => (s, false),
+        };
+        let (node_num, event_num) = s.split_once(':').ok_or(ParseEventIdError::Invalid)?;
+
+        if event_num.is_empty() || !event_num.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseEventIdError::Invalid);
+        }
+        let event_num = match event_num.parse::<u32>() {
+            Ok(num @ 0..=0xFFFF) => num as u16,
+            // only digits are left, so the number is too large
+            _ => return Err(ParseEventIdError::OutOfRange),
+        };
+
+        match short {
+            true => match node_num.parse::<VlcbNodeNumber>() {
+                Ok(_) | Err(ParseNodeNumberError::OutOfRange) => Ok(Self::short(event_num)),
+                Err(err) => Err(err.into()),
+            },
+            false => Ok(Self::long(node_num.parse()?, event_num)),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for EventId {
+    type Error = SizeError;
+
+    /// Construct a long event, see [`EventId::try_from_bytes`].
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_bytes(data)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for EventId {
+    fn format(&self, f: defmt::Formatter) {
+        let suffix = if self.is_short { "S" } else { "L" };
+        defmt::write!(f, "{=u16}:{=u16}{=str}", u16::from(self.node_num()), self.event_num(), suffix)
+    }
+}
+
+/// Result code of a VLCB command.
+///
+/// VLCB generic responses (GRSP) carry either a success code, one of the CBUS
+/// command error codes also used by CMDERR, or one of the GRSP specific codes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VlcbResultCode {
+    /// Command was carried out successfully
+    Ok,
+    /// Unknown or unsupported opcode
+    InvalidCommand,
+    /// The module is not in learn mode
+    NotInLearnMode,
+    /// The module is not in setup mode
+    NotInSetupMode,
+    /// The event storage is exhausted
+    TooManyEvents,
+    /// No event variable
+    NoEv,
+    /// Invalid event variable index
+    InvalidEvIndex,
+    /// Invalid event
+    InvalidEvent,
+    /// Invalid event index
+    InvalidEventIndex,
+    /// Invalid parameter index
+    InvalidParamIndex,
+    /// Invalid node variable index
+    InvalidNvIndex,
+    /// Invalid event variable value
+    InvalidEvValue,
+    /// Invalid node variable value
+    InvalidNvValue,
+    /// Another module is already in learn mode
+    AnotherModuleIsInLearnMode,
+    /// The requested mode is invalid
+    InvalidMode,
+    /// Invalid parameter in command
+    InvalidCommandParameter,
+    /// Invalid service
+    InvalidService,
+    /// Invalid diagnostic
+    InvalidDiagnostic,
+    /// Unknown non volatile memory type
+    UnknownPersistentMemoryType,
+    /// Result code unknown to the library implementation
+    Unknown(u8),
+}
+
+impl VlcbResultCode {
+    /// Check whether the code signals success.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+
+    /// Return the command error equivalent of the result code, if there is one.
+    ///
+    /// Only the codes shared with CMDERR can be converted.
+    pub fn command_error(&self) -> Option<CommandError> {
+        CommandError::try_from(u8::from(*self)).ok()
+    }
+}
+
+impl From<u8> for VlcbResultCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Ok,
+            1 => Self::InvalidCommand,
+            2 => Self::NotInLearnMode,
+            3 => Self::NotInSetupMode,
+            4 => Self::TooManyEvents,
+            5 => Self::NoEv,
+            6 => Self::InvalidEvIndex,
+            7 => Self::InvalidEvent,
+            8 => Self::InvalidEventIndex,
+            9 => Self::InvalidParamIndex,
+            10 => Self::InvalidNvIndex,
+            11 => Self::InvalidEvValue,
+            12 => Self::InvalidNvValue,
+            13 => Self::AnotherModuleIsInLearnMode,
+            250 => Self::InvalidMode,
+            251 => Self::InvalidCommandParameter,
+            252 => Self::InvalidService,
+            253 => Self::InvalidDiagnostic,
+            254 => Self::UnknownPersistentMemoryType,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<VlcbResultCode> for u8 {
+    fn from(value: VlcbResultCode) -> Self {
+        match value {
+            VlcbResultCode::Ok => 0,
+            VlcbResultCode::InvalidCommand => 1,
+            VlcbResultCode::NotInLearnMode => 2,
+            VlcbResultCode::NotInSetupMode => 3,
+            VlcbResultCode::TooManyEvents => 4,
+            VlcbResultCode::NoEv => 5,
+            VlcbResultCode::InvalidEvIndex => 6,
+            VlcbResultCode::InvalidEvent => 7,
+            VlcbResultCode::InvalidEventIndex => 8,
+            VlcbResultCode::InvalidParamIndex => 9,
+            VlcbResultCode::InvalidNvIndex => 10,
+            VlcbResultCode::InvalidEvValue => 11,
+            VlcbResultCode::InvalidNvValue => 12,
+            VlcbResultCode::AnotherModuleIsInLearnMode => 13,
+            VlcbResultCode::InvalidMode => 250,
+            VlcbResultCode::InvalidCommandParameter => 251,
+            VlcbResultCode::InvalidService => 252,
+            VlcbResultCode::InvalidDiagnostic => 253,
+            VlcbResultCode::UnknownPersistentMemoryType => 254,
+            VlcbResultCode::Unknown(other) => other,
+        }
+    }
+}
+
+impl From<CommandError> for VlcbResultCode {
+    fn from(value: CommandError) -> Self {
+        Self::from(u8::from(value))
+    }
+}
+
+impl From<GenericResponseStatus> for VlcbResultCode {
+    fn from(value: GenericResponseStatus) -> Self {
+        Self::from(u8::from(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_node_number_display_parse() {
+        let node_num: VlcbNodeNumber = "300".parse().unwrap();
+        assert_eq!(node_num, VlcbNodeNumber::new(0x01, 0x2C));
+        assert_eq!(node_num.to_string(), "300");
+        assert_eq!(u16::from(node_num), 300);
+        assert_eq!(node_num.to_u16(), 300);
+        assert_eq!(VlcbNodeNumber::from_u16(300), node_num);
+
+        assert_eq!("65535".parse(), Ok(VlcbNodeNumber::new(0xFF, 0xFF)));
+        assert_eq!(VlcbNodeNumber::new(0xFF, 0xFF).to_string(), "65535");
+    }
+
+    #[test]
+    fn test_node_number_parse_rejects() {
+        assert_eq!("0".parse::<VlcbNodeNumber>(), Err(ParseNodeNumberError::OutOfRange));
+        assert_eq!("70000".parse::<VlcbNodeNumber>(), Err(ParseNodeNumberError::OutOfRange));
+        assert_eq!(
+            "99999999999999999999".parse::<VlcbNodeNumber>(),
+            Err(ParseNodeNumberError::OutOfRange)
+        );
+        assert_eq!("".parse::<VlcbNodeNumber>(), Err(ParseNodeNumberError::Invalid));
+        assert_eq!("+300".parse::<VlcbNodeNumber>(), Err(ParseNodeNumberError::Invalid));
+        assert_eq!("0x12C".parse::<VlcbNodeNumber>(), Err(ParseNodeNumberError::Invalid));
+    }
+
+    #[test]
+    fn test_event_display() {
+        let long = EventId::new(false, 0x01, 0x2C, 0x00, 0x05);
+        assert_eq!(long.to_string(), "300:5L");
+
+        let short = EventId::short_from_wire_bytes(&[0x01, 0x2C, 0x01, 0x00]);
+        assert_eq!(short.to_string(), "0:256S");
+        assert_eq!(short.device_number(), 256);
+    }
+
+    #[test]
+    fn test_event_helpers() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x2C);
+        assert_eq!(EventId::long(node_num, 5), EventId::new(false, 0x01, 0x2C, 0x00, 0x05));
+        assert_eq!(EventId::short(256), EventId::new(true, 0x00, 0x00, 0x01, 0x00));
+
+        #[allow(deprecated)]
+        let event = EventId::from_node_and_id(&node_num, 5, false);
+        assert_eq!(event, EventId::long(node_num, 5));
+    }
+
+    #[test]
+    fn test_event_parse_roundtrip() {
+        for event in [
+            EventId::long(VlcbNodeNumber::new(0x04, 0xD2), 7),
+            EventId::long(VlcbNodeNumber::new(0xFF, 0xFF), 0xFFFF),
+            EventId::short(0),
+            EventId::short(256),
+        ] {
+            assert_eq!(event.to_string().parse(), Ok(event));
+        }
+
+        assert_eq!("1234:7".parse(), Ok(EventId::long(VlcbNodeNumber::from_u16(1234), 7)));
+        assert_eq!("1234:7S".parse(), Ok(EventId::short(7)));
+    }
+
+    #[test]
+    fn test_event_parse_rejects() {
+        assert_eq!("0:7L".parse::<EventId>(), Err(ParseEventIdError::OutOfRange));
+        assert_eq!("65536:7".parse::<EventId>(), Err(ParseEventIdError::OutOfRange));
+        assert_eq!("1234:65536".parse::<EventId>(), Err(ParseEventIdError::OutOfRange));
+        assert_eq!("1234:65536S".parse::<EventId>(), Err(ParseEventIdError::OutOfRange));
+        assert_eq!("1234".parse::<EventId>(), Err(ParseEventIdError::Invalid));
+        assert_eq!("1234:".parse::<EventId>(), Err(ParseEventIdError::Invalid));
+        assert_eq!(":7S".parse::<EventId>(), Err(ParseEventIdError::Invalid));
+        assert_eq!("1234:7X".parse::<EventId>(), Err(ParseEventIdError::Invalid));
+        assert_eq!("1234:-7".parse::<EventId>(), Err(ParseEventIdError::Invalid));
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        assert_eq!(
+            VlcbNodeNumber::try_from_bytes(&[0x01, 0x2C]),
+            Ok(VlcbNodeNumber::new(0x01, 0x2C))
+        );
+        assert_eq!(VlcbNodeNumber::try_from_bytes(&[0x01]), Err(SizeError::new(2, 1)));
+        assert_eq!(
+            VlcbNodeNumber::try_from(&[0x01, 0x2C, 0x00][..]),
+            Err(SizeError::new(2, 3))
+        );
+
+        let data = [0x01, 0x2C, 0x00, 0x05];
+        assert_eq!(EventId::try_from(&data[..]), Ok(EventId::new(false, 0x01, 0x2C, 0x00, 0x05)));
+        assert_eq!(
+            EventId::try_short_from_bytes(&data[2..]),
+            Ok(EventId::new(true, 0x00, 0x00, 0x00, 0x05))
+        );
+        assert_eq!(EventId::try_from_bytes(&data[..3]), Err(SizeError::new(4, 3)));
+        assert_eq!(EventId::try_from_bytes(&[0; 5]), Err(SizeError::new(4, 5)));
+        assert_eq!(EventId::try_short_from_bytes(&data), Err(SizeError::new(2, 4)));
+        assert_eq!(EventId::try_short_from_bytes(&data[..1]), Err(SizeError::new(2, 1)));
+    }
+
+    #[test]
+    fn test_short_from_bytes_every_device_number() {
+        for device_num in 0..=u16::MAX {
+            let bytes = device_num.to_be_bytes();
+            let event = EventId::short_from_bytes(&bytes);
+            assert_eq!(event, EventId::short(device_num));
+            assert_eq!(event.as_bytes()[..2], [0, 0]);
+            assert_eq!(EventId::try_short_from_bytes(&bytes), Ok(event));
+        }
+    }
+
+    #[test]
+    fn test_short_from_wire_bytes_ignores_node_number() {
+        for node_num in [0x0000, 0x0001, 0x012C, 0x8000, 0xFFFF] {
+            for device_num in (0..=u16::MAX).step_by(251) {
+                let [a0, a1] = u16::to_be_bytes(node_num);
+                let [a2, a3] = device_num.to_be_bytes();
+                let event = EventId::short_from_wire_bytes(&[a0, a1, a2, a3]);
+                assert_eq!(event, EventId::short(device_num));
+                assert_eq!(event, EventId::short_from_bytes(&[a2, a3]));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_short_from_bytes_rejects_wire_bytes() {
+        EventId::short_from_bytes(&[0x01, 0x2C, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn test_result_code_raw_roundtrip() {
+        for raw in 0..=u8::MAX {
+            assert_eq!(u8::from(VlcbResultCode::from(raw)), raw);
+        }
+        assert_eq!(VlcbResultCode::from(42), VlcbResultCode::Unknown(42));
+    }
+
+    #[test]
+    fn test_result_code_conversions() {
+        assert_eq!(
+            VlcbResultCode::from(CommandError::InvalidNvIndex),
+            VlcbResultCode::InvalidNvIndex
+        );
+        assert_eq!(
+            VlcbResultCode::from(GenericResponseStatus::InvalidMode),
+            VlcbResultCode::InvalidMode
+        );
+        assert_eq!(
+            VlcbResultCode::InvalidEvent.command_error(),
+            Some(CommandError::InvalidEvent)
+        );
+        assert_eq!(VlcbResultCode::InvalidService.command_error(), None);
+        assert_eq!(VlcbResultCode::Ok.command_error(), None);
+        assert!(VlcbResultCode::Ok.is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x2C);
+        let json = serde_json::to_string(&node_num).unwrap();
+        assert_eq!(serde_json::from_str::<VlcbNodeNumber>(&json).unwrap(), node_num);
+        let bytes = postcard::to_allocvec(&node_num).unwrap();
+        assert_eq!(bytes, [0x01, 0x2C]);
+        assert_eq!(postcard::from_bytes::<VlcbNodeNumber>(&bytes).unwrap(), node_num);
+
+        for event in [EventId::long(node_num, 7), EventId::short(7)] {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<EventId>(&json).unwrap(), event);
+            let bytes = postcard::to_allocvec(&event).unwrap();
+            assert_eq!(postcard::from_bytes::<EventId>(&bytes).unwrap(), event);
+        }
+
+        let json = serde_json::to_string(&EventType::AccessoryOn).unwrap();
+        assert_eq!(json, "\"AccessoryOn\"");
+        assert_eq!(serde_json::from_str::<EventType>(&json).unwrap(), EventType::AccessoryOn);
+        let bytes = postcard::to_allocvec(&EventType::AccessoryOn).unwrap();
+        assert_eq!(postcard::from_bytes::<EventType>(&bytes).unwrap(), EventType::AccessoryOn);
+    }
+}
@@ -1,62 +1,54 @@
 extern crate proc_macro;
+use pad::PadStr;
 use quote::quote;
 use proc_macro::TokenStream;
-use syn::{parse::{Error, ParseStream}, parse_macro_input, LitStr};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Error, Ident, LitInt, LitStr, Token};
+
+/// Length of a module name in octets, excluding the `CAN`/`ETH` prefix.
+const MODULE_NAME_LEN: usize = 7;
+
+/// Prefixes of module names that are not a part of the name sent in the NAME response.
+const MODULE_NAME_PREFIXES: [&str; 2] = ["CAN", "ETH"];
 
 /// Helper macro for converting string to
 /// a module name and ensuring it has the correct length
-// #[macro_export]
-// macro_rules! module_name {
-//     ($s:expr) => {{
-//         let mut array = [b' '; MODULE_NAME_LEN];
-//         let bytes = $s.as_bytes();
-//         for i in 0..bytes.len().min(7) {
-//             array[i] = bytes[i];
-//         }
-//         array
-//     }};
-// }
-
-// /// Helper macro for converting string to
-// /// a module name and ensuring it has the correct length
-// /// and format
-// #[proc_macro]
-// pub fn module_name(item: TokenStream) -> TokenStream {
-//     // TODO: make sure the string is padded, has length of max 7(excl the prefix CAN|ETH)
-
-//     let input = parse_macro_input!(item as LitStr);
-
-
-//     let value = input.value();
-//     // todo: this stripping code should probably be shared somewhere because
-//     // it will be easier to maintain
-//     let prefixes = ["CAN", "ETH"];
-
-//     let found_prefix = prefixes.iter().find(|v| value.to_uppercase().starts_with(*v));
-
-//     let sanitized_val;
-//     // strip the prefix length from the literal
-//     if let Some(prefix) = found_prefix {
-//         sanitized_val = &value[prefix.len()..];
-//     } else {
-//         sanitized_val = &value;
-//     }
-
-//     if sanitized_val.len() > 7 {
-//         return Error::new_spanned(
-//             input,
-//             "The name literal should be maximum of 7 characters long (this excludes prefixes 'CAN', 'ETH')"
-//         ).to_compile_error().into();
-//     }
-
-//     let value = value.pad_to_width_with_char(value.len() + (7 - sanitized_val.len()), ' ');
-
-//     let value = quote! {
-//         #value
-//     };
-
-//     TokenStream::from(value)
-// }
+/// and format
+///
+/// Strips the `CAN`/`ETH` prefix and pads the name with spaces to 7 octets,
+/// e.g. `module_name!("CANMERG")` expands to `*b"MERG   "`.
+#[proc_macro]
+pub fn module_name(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as LitStr);
+    let value = input.value();
+
+    if !value.is_ascii() {
+        return Error::new_spanned(input, "The name literal should only contain ASCII characters")
+            .to_compile_error()
+            .into();
+    }
+
+    let sanitized_val = MODULE_NAME_PREFIXES
+        .iter()
+        .find(|prefix| value.to_ascii_uppercase().starts_with(*prefix))
+        .map_or(value.as_str(), |prefix| &value[prefix.len()..]);
+
+    if sanitized_val.len() > MODULE_NAME_LEN {
+        return Error::new_spanned(
+            input,
+            "The name literal should be maximum of 7 characters long (this excludes prefixes 'CAN', 'ETH')"
+        ).to_compile_error().into();
+    }
+
+    let name = sanitized_val.pad_to_width(MODULE_NAME_LEN);
+    let bytes = name.as_bytes();
+
+    let value = quote! {
+        [#(#bytes),*]
+    };
+
+    TokenStream::from(value)
+}
 
 #[proc_macro]
 pub fn str_to_array(input: TokenStream) -> TokenStream {
@@ -70,4 +62,96 @@ pub fn str_to_array(input: TokenStream) -> TokenStream {
     };
 
     TokenStream::from(expanded)
-}
\ No newline at end of file
+}
+
+/// A `key = value` pair of a 16 bit number.
+struct Field {
+    key: Ident,
+    value: u16,
+}
+
+impl Field {
+    fn parse_named(input: ParseStream, name: &str) -> syn::Result<u16> {
+        let field: Field = input.parse()?;
+        if field.key != name {
+            return Err(Error::new_spanned(
+                &field.key,
+                format!("expected `{}`", name),
+            ));
+        }
+        Ok(field.value)
+    }
+}
+
+impl Parse for Field {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let lit: LitInt = input.parse()?;
+        let value = lit
+            .base10_parse::<u16>()
+            .map_err(|_| Error::new_spanned(&lit, "the value should fit into 16 bits"))?;
+        Ok(Self { key, value })
+    }
+}
+
+/// Long event made of a node number and an event number.
+struct LongEvent {
+    nn: u16,
+    en: u16,
+}
+
+impl Parse for LongEvent {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let nn = Field::parse_named(input, "nn")?;
+        input.parse::<Token![,]>()?;
+        let en = Field::parse_named(input, "en")?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { nn, en })
+    }
+}
+
+/// Short event made of a device number.
+struct ShortEvent {
+    dn: u16,
+}
+
+impl Parse for ShortEvent {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let dn = Field::parse_named(input, "dn")?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { dn })
+    }
+}
+
+/// Construct a long `vlcb_core::vlcb::EventId` from a node number and an event number.
+///
+/// `event!(nn = 0x1234, en = 0x0005)` expands to an expression usable in constants.
+#[proc_macro]
+pub fn event(input: TokenStream) -> TokenStream {
+    let LongEvent { nn, en } = parse_macro_input!(input as LongEvent);
+    let [nn_hi, nn_lo] = nn.to_be_bytes();
+    let [en_hi, en_lo] = en.to_be_bytes();
+
+    let expanded = quote! {
+        ::vlcb_core::vlcb::EventId::new(false, #nn_hi, #nn_lo, #en_hi, #en_lo)
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Construct a short `vlcb_core::vlcb::EventId` from a device number.
+///
+/// `short_event!(dn = 0x0005)` expands to an expression usable in constants,
+/// the node number part of the event is zeroed.
+#[proc_macro]
+pub fn short_event(input: TokenStream) -> TokenStream {
+    let ShortEvent { dn } = parse_macro_input!(input as ShortEvent);
+    let [dn_hi, dn_lo] = dn.to_be_bytes();
+
+    let expanded = quote! {
+        ::vlcb_core::vlcb::EventId::new(true, 0u8, 0u8, #dn_hi, #dn_lo)
+    };
+
+    TokenStream::from(expanded)
+}
@@ -0,0 +1,8 @@
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/module-name-valid.rs");
+    t.compile_fail("tests/ui/module-name-too-long.rs");
+    t.pass("tests/ui/event-valid.rs");
+    t.compile_fail("tests/ui/event-invalid.rs");
+}
@@ -0,0 +1,8 @@
+use vlcb_macros::{event, short_event};
+
+fn main() {
+    let _event = event!(nn = 0x10000, en = 1);
+    let _event = event!(en = 1, nn = 1);
+    let _event = event!(nn = 1);
+    let _event = short_event!(nn = 1);
+}
@@ -0,0 +1,18 @@
+use vlcb_core::vlcb::EventId;
+use vlcb_macros::{event, short_event};
+
+const DEFAULT_EVENT: EventId = event!(nn = 0x1234, en = 0x0005);
+
+fn main() {
+    assert!(DEFAULT_EVENT.is_long());
+    assert_eq!(DEFAULT_EVENT.as_bytes(), &[0x12, 0x34, 0x00, 0x05]);
+
+    let event = event!(nn = 256, en = 65535,);
+    assert_eq!(event.as_bytes(), &[0x01, 0x00, 0xFF, 0xFF]);
+
+    let short = short_event!(dn = 0x0005);
+    assert!(short.is_short());
+    assert_eq!(short.as_bytes(), &[0x00, 0x00, 0x00, 0x05]);
+    assert_eq!(short, EventId::short_from_bytes(&[0x00, 0x05]));
+    assert_eq!(short, EventId::short_from_wire_bytes(&[0x12, 0x34, 0x00, 0x05]));
+}
@@ -0,0 +1,6 @@
+use vlcb_macros::module_name;
+
+fn main() {
+    let _name = module_name!("TOOLONG8");
+    let _name = module_name!("CANTOOLONG8");
+}
@@ -0,0 +1,12 @@
+use vlcb_macros::module_name;
+
+fn main() {
+    let prefixed: [u8; 7] = module_name!("CANMERG");
+    assert_eq!(&prefixed, b"MERG   ");
+
+    let full: [u8; 7] = module_name!("MIO4GPL");
+    assert_eq!(&full, b"MIO4GPL");
+
+    let lowercase_prefix: [u8; 7] = module_name!("ethSWITCH");
+    assert_eq!(&lowercase_prefix, b"SWITCH ");
+}
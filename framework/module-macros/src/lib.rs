@@ -1,23 +1,53 @@
 extern crate proc_macro;
 use quote::quote;
 use proc_macro::TokenStream;
-use syn::{parse::{Error, ParseStream}, parse_macro_input, LitStr};
+use syn::{parse_macro_input, Error, LitStr};
 
+/// Construct a `vlcb_module::ModuleVersion` from a `"major.minor.beta"` string.
+///
+/// `major` and `beta` are numbers in the `0..=255` range and `minor` is a single
+/// ASCII alphabetic character, e.g. `module_version!("1.a.33")`.
 #[proc_macro]
 pub fn module_version(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as LitStr);
 
-    let major: LitStr = input.parse()?;
-    input.parse::<Token![.]>()?;
-    let minor: LitStr = input.parse()?;
-    input.parse::<Token![.]>()?;
-    let patch: LitStr = input.parse()?;
+    match parse_version(&input) {
+        Ok((major, minor, beta)) => TokenStream::from(quote! {
+            ::vlcb_module::ModuleVersion::new(#major, #minor, #beta)
+        }),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
 
+fn parse_version(input: &LitStr) -> Result<(u8, char, u8), Error> {
+    let value = input.value();
+    let parts: Vec<&str> = value.split('.').collect();
 
+    let [major, minor, beta] = parts[..] else {
+        return Err(Error::new(
+            input.span(),
+            "expected a version in the \"major.minor.beta\" format, e.g. \"1.a.33\"",
+        ));
+    };
+
+    let major = major
+        .parse::<u8>()
+        .map_err(|_| Error::new(input.span(), "major version must be a number in the 0..=255 range"))?;
 
-    let value = quote! {
-        vlcb_module::ModuleVersion::new(major, minor, patch)
+    let mut chars = minor.chars();
+    let minor = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => c,
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "minor version must be a single ASCII alphabetic character",
+            ))
+        }
     };
 
-    TokenStream::from(value)
-}
\ No newline at end of file
+    let beta = beta
+        .parse::<u8>()
+        .map_err(|_| Error::new(input.span(), "beta version must be a number in the 0..=255 range"))?;
+
+    Ok((major, minor, beta))
+}
@@ -0,0 +1,7 @@
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/version-valid.rs");
+    t.compile_fail("tests/ui/version-bad-minor.rs");
+    t.compile_fail("tests/ui/version-bad-format.rs");
+}
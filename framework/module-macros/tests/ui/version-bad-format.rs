@@ -0,0 +1,6 @@
+use vlcb_module_macros::module_version;
+
+fn main() {
+    let _version = module_version!("1.a");
+    let _version = module_version!("256.a.0");
+}
@@ -0,0 +1,6 @@
+use vlcb_module_macros::module_version;
+
+fn main() {
+    let _version = module_version!("1.3.33");
+    let _version = module_version!("1.ab.33");
+}
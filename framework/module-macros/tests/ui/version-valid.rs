@@ -0,0 +1,7 @@
+use vlcb_module::ModuleVersion;
+use vlcb_module_macros::module_version;
+
+fn main() {
+    let _version: ModuleVersion = module_version!("1.a.33");
+    let _version: ModuleVersion = module_version!("255.Z.0");
+}
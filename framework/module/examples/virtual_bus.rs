@@ -0,0 +1,164 @@
+//! Three modules on a virtual bus resolving their CAN IDs.
+//!
+//! The modules start with the same CAN ID, e.g. as configured by a careless user. A module
+//! hearing its CAN ID from another one starts the CAN self-enumeration, the modules keep
+//! sending heartbeats until every one of them has a distinct CAN ID. The simulation is
+//! driven by a manual clock, so it prints the same on every run.
+//!
+//! Run with `cargo run -p vlcb-module --example virtual_bus --features alloc`.
+
+use std::cell::RefCell;
+
+use embedded_storage::{ReadStorage, Storage};
+use embedded_time::duration::Milliseconds;
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::time::TestClock;
+use vlcb_core::vlcb::VlcbNodeNumber;
+use vlcb_defs::Manufacturer;
+use vlcb_module::{Module, ModuleVersion, ParamFlags, Processor};
+use vlcb_network::iface::{Interface, SocketSet};
+use vlcb_network::phy::{VirtualBus, VirtualBusPort};
+use vlcb_network::socket::module::{Filter, PacketBuffer, PacketMetadata, Socket};
+use vlcb_network::wire::HardwareAddress;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::node_config_storage;
+use vlcb_ui::NullUi;
+
+/// Real module should use EEPROM or flash or similar for persistence
+struct RamStorage([u8; 64]);
+
+impl ReadStorage for RamStorage {
+    type Error = ();
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get(offset..offset + bytes.len()).ok_or(())?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Storage for RamStorage {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get_mut(offset..offset + bytes.len()).ok_or(())?;
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+type Config = node_config_storage!(RamStorage, 0, 4, 1, 2);
+
+struct Node<'c> {
+    name: &'static str,
+    port: VirtualBusPort<'c, TestClock>,
+    iface: Interface<TestClock>,
+    sockets: SocketSet<'static>,
+    module: Module<'static, NullUi, TestClock, Config>,
+}
+
+impl Node<'_> {
+    fn can_id(&self) -> u8 {
+        self.module.can_id().expect("the virtual bus is a CAN bus").into()
+    }
+}
+
+/// Steps of 5 ms between the first polls of the modules, and so between their heartbeats
+const SLOT_STEPS: usize = 30;
+
+fn main() {
+    let clock = TestClock::new();
+    let bus = VirtualBus::new(&clock).with_latency(Milliseconds(2));
+    let names = ["command station", "panel", "points"];
+    let heartbeat_interval = (SLOT_STEPS * names.len() * 5) as u32;
+
+    let mut nodes: Vec<Node> = names
+        .into_iter()
+        .zip(1u8..)
+        .map(|(name, n)| {
+            let port = bus.port();
+            let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[5]));
+            let iface = Interface::new(&port, None, hw_addr);
+
+            let mut socket = Socket::new(
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+            );
+            socket.bind(Filter::AddressedToNode).unwrap();
+            let mut sockets = SocketSet::new(vec![]);
+            let socket = sockets.add(socket);
+
+            let config = Config::new(rclite::Rc::new(RefCell::new(RamStorage([0xFF; 64]))));
+            let mut module = Module::builder()
+                .name("SIM")
+                .version(ModuleVersion::new(1, 'a', 0))
+                .manufacturer(Manufacturer::Development)
+                .flags(ParamFlags::Producer | ParamFlags::Vlcb)
+                .cpu(Processor::Atmel)
+                .ui(NullUi)
+                .config(config)
+                .interface(&iface)
+                .socket(socket)
+                .heartbeat_interval(heartbeat_interval)
+                .build()
+                .unwrap()
+                .init();
+
+            // the modules were set up before, all of them with the same CAN ID
+            let config = module.config_mut();
+            config.set_mode_normal(VlcbNodeNumber::new(0x01, n));
+            config.set_can_id(VlcbCanId::from_bytes(&[5]));
+            config.set_heartbeat(true);
+
+            Node {
+                name,
+                port,
+                iface,
+                sockets,
+                module,
+            }
+        })
+        .collect();
+
+    let mut can_ids: Vec<u8> = nodes.iter().map(Node::can_id).collect();
+    for step in 0..SLOT_STEPS * nodes.len() * 10 {
+        clock.advance(5);
+        let now = clock.now();
+
+        for (slot, node) in nodes.iter_mut().enumerate() {
+            // the heartbeats are spread out by starting the modules one after another, an
+            // enumeration ends before the next heartbeat
+            if step < slot * SLOT_STEPS {
+                continue;
+            }
+            node.module
+                .poll(now, &mut node.iface, &mut node.port, &mut node.sockets);
+
+            let can_id = node.can_id();
+            if can_id != can_ids[slot] {
+                println!(
+                    "{:>6} ms {:<16} enumerated CAN ID {}",
+                    now.duration_since_epoch().integer(),
+                    node.name,
+                    can_id
+                );
+                can_ids[slot] = can_id;
+            }
+        }
+
+        let mut distinct = can_ids.clone();
+        distinct.sort();
+        distinct.dedup();
+        if distinct.len() == nodes.len() {
+            break;
+        }
+    }
+
+    for node in &nodes {
+        println!("{:<16} CAN ID {}", node.name, node.can_id());
+    }
+}
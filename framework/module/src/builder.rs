@@ -0,0 +1,301 @@
+use core::marker::PhantomData;
+
+use embedded_time::Clock;
+use heapless::Deque;
+use vlcb_core::module::ParamFlags;
+use vlcb_defs::{BusType, Manufacturer, MergModuleType, ModuleParam};
+use vlcb_network::iface::{Interface, InterfaceStats, SocketHandle};
+use vlcb_network::phy::Medium;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::config::HEARTBEAT_INTERVAL_MS;
+use crate::events::DataEvents;
+use crate::flush::{Flush, FlushPolicy};
+use crate::heartbeat::Heartbeat;
+use crate::service_set::ServiceSet;
+use crate::setup::Setup;
+use crate::{
+    module_params, CpuIdResolver, DefaultEvent, Diagnostics, Module, ModuleInner, ModuleVersion,
+    Processor,
+};
+
+/// Error returned by [`ModuleBuilder::build`] when a required part of the module is missing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BuildError {
+    MissingName,
+    MissingVersion,
+    MissingManufacturer,
+    MissingUi,
+    MissingConfig,
+    MissingCpu,
+    MissingInterface,
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let part = match self {
+            BuildError::MissingName => "name",
+            BuildError::MissingVersion => "version",
+            BuildError::MissingManufacturer => "manufacturer",
+            BuildError::MissingUi => "ui",
+            BuildError::MissingConfig => "config",
+            BuildError::MissingCpu => "cpu",
+            BuildError::MissingInterface => "interface",
+        };
+        write!(f, "missing module {}", part)
+    }
+}
+
+/// A builder for [`Module`].
+///
+/// The name, version, manufacturer, UI, config, CPU and interface are required,
+/// node flags default to none, the load address to `0`, the heartbeat interval to [`HEARTBEAT_INTERVAL_MS`], the flush
+/// policy to [`FlushPolicy::default`] and the CPU ID resolver, services, module socket and
+/// default events are optional.
+///
+/// ```ignore
+/// let module = Module::builder()
+///     .name("MYMOD")
+///     .version(ModuleVersion::new(1, 'a', 0))
+///     .manufacturer(Manufacturer::Development)
+///     .ui(ui)
+///     .config(config)
+///     .cpu(Processor::Atmel)
+///     .interface(&interface)
+///     .build()?;
+/// ```
+pub struct ModuleBuilder<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig> {
+    name: Option<&'static str>,
+    version: Option<ModuleVersion>,
+    manufacturer: Option<Manufacturer>,
+    flags: ParamFlags,
+    load_address: u32,
+    ui: Option<UI>,
+    config: Option<S>,
+    cpu: Option<Processor>,
+    cpu_id_resolver: Option<CpuIdResolver>,
+    medium: Option<Medium>,
+    _clock: PhantomData<C>,
+    services: Option<ServiceSet<'a>>,
+    socket: Option<SocketHandle>,
+    default_events: &'static [DefaultEvent],
+    heartbeat_interval_ms: u32,
+    flush_policy: FlushPolicy,
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> ModuleBuilder<'a, UI, C, S> {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            version: None,
+            manufacturer: None,
+            flags: ParamFlags::empty(),
+            load_address: 0,
+            ui: None,
+            config: None,
+            cpu: None,
+            cpu_id_resolver: None,
+            medium: None,
+            _clock: PhantomData,
+            services: None,
+            socket: None,
+            default_events: &[],
+            heartbeat_interval_ms: HEARTBEAT_INTERVAL_MS,
+            flush_policy: FlushPolicy::default(),
+        }
+    }
+
+    /// Set the module name.
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set the module version.
+    pub fn version(mut self, version: ModuleVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Set the module manufacturer.
+    pub fn manufacturer(mut self, manufacturer: Manufacturer) -> Self {
+        self.manufacturer = Some(manufacturer);
+        self
+    }
+
+    /// Set the node flags parameter.
+    pub fn flags(mut self, flags: ParamFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the address the firmware is loaded at by the bootloader.
+    pub fn load_address(mut self, address: u32) -> Self {
+        self.load_address = address;
+        self
+    }
+
+    /// Set the module user interface.
+    pub fn ui(mut self, ui: UI) -> Self {
+        self.ui = Some(ui);
+        self
+    }
+
+    /// Set the node configuration storage.
+    pub fn config(mut self, config: S) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Set the processor the module runs on.
+    pub fn cpu(mut self, cpu: Processor) -> Self {
+        self.cpu = Some(cpu);
+        self
+    }
+
+    /// Set the resolver of the processor ID.
+    pub fn cpu_id_resolver(mut self, resolver: CpuIdResolver) -> Self {
+        self.cpu_id_resolver = Some(resolver);
+        self
+    }
+
+    /// Set the network interface the module is polled with.
+    ///
+    /// The module only takes the medium of the interface for the bus type parameter, the
+    /// interface itself is passed to every [`Module::poll`].
+    pub fn interface(mut self, interface: &Interface<C>) -> Self {
+        self.medium = Some(interface.device_caps().medium);
+        self
+    }
+
+    /// Set the module services, the module has none by default.
+    pub fn services(mut self, services: ServiceSet<'a>) -> Self {
+        self.services = Some(services);
+        self
+    }
+
+    /// Set the module socket the module and its services receive the packets from and
+    /// transmit their responses to, see [`Module::poll`].
+    ///
+    /// The socket should be bound with [`Filter::All`](vlcb_network::socket::module::Filter::All),
+    /// the requests of a configuration tool don't always carry the node number.
+    pub fn socket(mut self, socket: SocketHandle) -> Self {
+        self.socket = Some(socket);
+        self
+    }
+
+    /// Set the events consumed and produced while the module has no node number.
+    pub fn default_events(mut self, events: &'static [DefaultEvent]) -> Self {
+        self.default_events = events;
+        self
+    }
+
+    /// Set the interval between two heartbeats, in milliseconds.
+    pub fn heartbeat_interval(mut self, interval_ms: u32) -> Self {
+        self.heartbeat_interval_ms = interval_ms;
+        self
+    }
+
+    /// Set when the poll writes the changed node config to the persistent storage.
+    pub fn flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.flush_policy = policy;
+        self
+    }
+
+    /// Build the module.
+    ///
+    /// Returns an error naming the first required part that was not set.
+    pub fn build(self) -> Result<Module<'a, UI, C, S>, BuildError> {
+        let name = self.name.ok_or(BuildError::MissingName)?;
+        let version = self.version.ok_or(BuildError::MissingVersion)?;
+        let manufacturer = self.manufacturer.ok_or(BuildError::MissingManufacturer)?;
+        let ui = self.ui.ok_or(BuildError::MissingUi)?;
+        let config = self.config.ok_or(BuildError::MissingConfig)?;
+        let cpu = self.cpu.ok_or(BuildError::MissingCpu)?;
+        let medium = self.medium.ok_or(BuildError::MissingInterface)?;
+
+        let mut params = module_params(cpu, self.cpu_id_resolver);
+
+        params.set_param(ModuleParam::ModuleType, MergModuleType::VLCB.into());
+        params.set_param(ModuleParam::NodeFlags, self.flags.bits());
+        params.set_load_address(self.load_address);
+
+        version.emit(&mut params);
+
+        params.set_param(ModuleParam::ModuleManufacturer, manufacturer.into());
+        params.set_param(
+            ModuleParam::BusType,
+            BusType::from(medium).into(),
+        );
+
+        params.set_param(ModuleParam::MaxEventCount, S::MAX_EVENTS);
+        params.set_param(ModuleParam::EventVariableCount, S::EVENT_VAR_COUNT);
+        params.set_param(ModuleParam::NodeVariableCount, S::NODE_VAR_COUNT);
+        params.finalize();
+
+        Ok(Module {
+            name,
+            params,
+            default_events: self.default_events,
+            inner: ModuleInner {
+                now: None,
+                started: None,
+                config,
+                ui,
+                services: self.services.unwrap_or_else(|| ServiceSet::new(&mut [][..])),
+                socket: self.socket,
+                diagnostics: Diagnostics::default(),
+                stats: InterfaceStats::default(),
+                setup: Setup::Idle,
+                link_down: false,
+                data: DataEvents::default(),
+                consumed: Deque::new(),
+                heartbeat: Heartbeat::new(self.heartbeat_interval_ms),
+                flush: Flush::new(self.flush_policy),
+            },
+        })
+    }
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Default
+    for ModuleBuilder<'a, UI, C, S>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+
+    use super::*;
+    use crate::test_utils::{config, TestConfig, TestUi};
+
+    fn builder<'a>() -> ModuleBuilder<'a, TestUi, TestClock, TestConfig> {
+        Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 3))
+            .manufacturer(Manufacturer::Development)
+            .ui(TestUi::default())
+            .cpu(Processor::Atmel)
+    }
+
+    #[test]
+    fn test_build_missing_parts() {
+        let missing_name = ModuleBuilder::<TestUi, TestClock, TestConfig>::new()
+            .ui(TestUi::default())
+            .build();
+        assert_eq!(missing_name.err(), Some(BuildError::MissingName));
+
+        assert_eq!(builder().build().err(), Some(BuildError::MissingConfig));
+        assert_eq!(
+            builder().config(config()).build().err(),
+            Some(BuildError::MissingInterface)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Module diagnostics counters.
+
+use vlcb_network::iface::InterfaceEvent;
+
+/// Counters of notable network events seen by the module.
+///
+/// All counters saturate instead of wrapping around.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Diagnostics {
+    can_id_conflicts: u16,
+    can_enumerations: u16,
+    can_enumeration_failures: u16,
+    lost_events: u16,
+}
+
+impl Diagnostics {
+    /// Return how many times another node was seen using our CAN ID.
+    pub fn can_id_conflicts(&self) -> u16 {
+        self.can_id_conflicts
+    }
+
+    /// Return how many CAN ID enumerations completed with a new CAN ID.
+    pub fn can_enumerations(&self) -> u16 {
+        self.can_enumerations
+    }
+
+    /// Return how many CAN ID enumerations failed to find a vacant CAN ID.
+    pub fn can_enumeration_failures(&self) -> u16 {
+        self.can_enumeration_failures
+    }
+
+    /// Return how many times interface events were dropped before the module drained them.
+    pub fn lost_events(&self) -> u16 {
+        self.lost_events
+    }
+
+    pub(crate) fn record(&mut self, event: &InterfaceEvent) {
+        let counter = match event {
+            InterfaceEvent::CanIdConflict { .. } => &mut self.can_id_conflicts,
+            InterfaceEvent::CanEnumerationCompleted { .. } => &mut self.can_enumerations,
+            InterfaceEvent::CanEnumerationFailed => &mut self.can_enumeration_failures,
+            _ => return,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    pub(crate) fn record_lost_events(&mut self) {
+        self.lost_events = self.lost_events.saturating_add(1);
+    }
+}
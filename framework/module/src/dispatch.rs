@@ -0,0 +1,224 @@
+//! Dispatch of the received packets to the module and its services.
+
+use embedded_time::Clock;
+use vlcb_defs::OpCode;
+use vlcb_network::data::packet::construct::OutgoingPacket;
+use vlcb_network::socket::module::{RecvError, Socket};
+use vlcb_network::wire::VLCB_MAX_PAYLOAD;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_svc_all::Service;
+use vlcb_ui::VlcbUi;
+
+use crate::{ConsumedEvent, Module};
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Dispatch the packets received by the module `socket` and queue the responses to it.
+    pub(crate) fn process_socket(&mut self, socket: &mut Socket<'_>) {
+        let mut buffer = [0u8; VLCB_MAX_PAYLOAD];
+        loop {
+            let len = match socket.recv_slice(&mut buffer) {
+                Ok(len) => len,
+                // a packet longer than any VLCB packet is not for us
+                Err(RecvError::Truncated) => continue,
+                Err(_) => break,
+            };
+            self.dispatch(&buffer[..len], |response| {
+                // note(discard): a response that does not fit the socket is dropped, the
+                // configuration tool repeats the request
+                let _ = socket.send_packet(&response);
+            });
+        }
+    }
+
+    /// Pass a received packet to the services of the module and to the module itself, and
+    /// `send` their responses.
+    ///
+    /// The services get the packet in a fixed order, so the responses of a packet handled
+    /// by more of them are always sent in the same order. A consumed event is acknowledged
+    /// last and queued for [`Module::take_consumed_event`].
+    pub(crate) fn dispatch(&mut self, packet: &[u8], mut send: impl FnMut(OutgoingPacket)) {
+        let node_num = self.node_number();
+        // RQNP carries no node number, only the node in setup answers it
+        let setup_only = packet.first() == Some(&OpCode::QueryNodeParameters.into());
+        let answers_params = !setup_only || self.is_in_setup();
+
+        let services = &mut self.inner.services;
+        let config = &mut self.inner.config;
+
+        if let Some(mns) = services.get::<vlcb_svc_mns::Service>() {
+            if answers_params {
+                mns.process(node_num, &self.params, packet).into_iter().for_each(&mut send);
+            }
+        }
+        if let Some(nv) = services.get::<vlcb_svc_nv::Service>() {
+            nv.process(node_num, config, packet).into_iter().flatten().for_each(&mut send);
+        }
+        if let Some(teaching) = services.get_mut::<vlcb_svc_event_teaching::Service>() {
+            let responses = teaching.process(node_num, config, packet);
+            responses.into_iter().flatten().for_each(&mut send);
+        }
+        if let Some(producer) = services.get::<vlcb_svc_event_producer::Service>() {
+            producer.process(packet).into_iter().for_each(&mut send);
+        }
+        if let Some(discovery) = services.get::<vlcb_svc_discovery::Service>() {
+            let records = services.iter().map(Service::record);
+            let responses = discovery.process(node_num, records, packet);
+            responses.into_iter().flatten().for_each(&mut send);
+        }
+        if let Some(diagnostics) = services.get::<vlcb_svc_diagnostics::Service>() {
+            let responses = diagnostics.process(node_num, services.iter(), packet);
+            responses.into_iter().flatten().for_each(&mut send);
+        }
+
+        self.process(packet).into_iter().for_each(&mut send);
+
+        if let Some(consumed) = self.consume_event(packet) {
+            self.acknowledge_event(&consumed).into_iter().for_each(send);
+            if self.inner.consumed.is_full() {
+                // the application did not keep up, the oldest event is the least relevant
+                self.inner.consumed.pop_front();
+            }
+            // note(discard): a slot was freed above
+            let _ = self.inner.consumed.push_back(consumed);
+        }
+    }
+
+    /// Take the oldest event consumed from the module socket.
+    ///
+    /// The application takes the events after each [`Module::poll`] and acts on them. The
+    /// last [`config::CONSUMED_EVENT_SLOTS`] events are kept until then. Without a module
+    /// socket no event is kept, see [`Module::consume_event`].
+    ///
+    /// [`config::CONSUMED_EVENT_SLOTS`]: crate::config::CONSUMED_EVENT_SLOTS
+    pub fn take_consumed_event(&mut self) -> Option<ConsumedEvent> {
+        self.inner.consumed.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::can::VlcbCanId;
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
+    use vlcb_defs::Manufacturer;
+    use vlcb_network::iface::{Interface, SocketHandle, SocketSet, SocketStorage};
+    use vlcb_network::phy::loopback::Loopback;
+    use vlcb_network::socket::module::{Filter, PacketBuffer, PacketMetadata};
+    use vlcb_network::wire::{CanFrame, HardwareAddress};
+
+    use super::*;
+    use crate::config::HEARTBEAT_INTERVAL_MS;
+    use crate::service_set::{ServiceSet, ServiceStorage};
+    use crate::test_utils::{config, interface, TestConfig, TestUi};
+    use crate::{ModuleVersion, Processor};
+
+    fn module<'a>(
+        services: ServiceSet<'a>,
+        socket: SocketHandle,
+    ) -> Module<'a, TestUi, TestClock, TestConfig> {
+        let mut module = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .services(services)
+            .socket(socket)
+            .build()
+            .unwrap();
+        module.config_mut().set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        module
+    }
+
+    fn inject(device: &mut Loopback<8>, payload: &[u8]) {
+        let mut buffer = [0u8; 14];
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..payload.len() + 2]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x7D]));
+        frame.payload_mut().copy_from_slice(payload);
+        device.inject(&buffer[..payload.len() + 2]).unwrap();
+    }
+
+    #[test]
+    fn test_socket_dispatched_to_services() {
+        let (mut rx_meta, mut rx_data) = ([PacketMetadata::EMPTY; 4], [0u8; 64]);
+        let (mut tx_meta, mut tx_data) = ([PacketMetadata::EMPTY; 4], [0u8; 64]);
+        let mut socket = Socket::new(
+            PacketBuffer::new(&mut rx_meta[..], &mut rx_data[..]),
+            PacketBuffer::new(&mut tx_meta[..], &mut tx_data[..]),
+        );
+        socket.bind(Filter::All).unwrap();
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+        let handle = sockets.add(socket);
+
+        let mut service_storage = [ServiceStorage::EMPTY, ServiceStorage::EMPTY];
+        let mut services = ServiceSet::new(&mut service_storage[..]);
+        services.add(vlcb_svc_mns::Service::new());
+        services.add(vlcb_svc_nv::Service::new());
+
+        let mut module = module(services, handle);
+        module.config_mut().set_nv(1, 0x42).unwrap();
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(&device, None, HardwareAddress::default());
+        inject(&mut device, &[OpCode::QueryNodeVariable.into(), 0x01, 0x02, 1]);
+
+        // received on the first poll, answered on the next one
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert!(sockets.get::<Socket>(handle).can_recv());
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+
+        // the loopback device hands the transmitted response back to the socket
+        let socket = sockets.get_mut::<Socket>(handle);
+        assert_eq!(socket.recv(), Ok(&[OpCode::NodeVariableValue.into(), 0x01, 0x02, 1, 0x42][..]));
+        assert!(!socket.can_recv());
+    }
+
+    #[test]
+    fn test_socket_events_acknowledged_and_heartbeat_sent() {
+        let (mut rx_meta, mut rx_data) = ([PacketMetadata::EMPTY; 4], [0u8; 64]);
+        let (mut tx_meta, mut tx_data) = ([PacketMetadata::EMPTY; 4], [0u8; 64]);
+        let mut socket = Socket::new(
+            PacketBuffer::new(&mut rx_meta[..], &mut rx_data[..]),
+            PacketBuffer::new(&mut tx_meta[..], &mut tx_data[..]),
+        );
+        socket.bind(Filter::All).unwrap();
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+        let handle = sockets.add(socket);
+
+        let mut module = module(ServiceSet::new(&mut [][..]), handle);
+        let event = EventId::long(VlcbNodeNumber::new(0x0A, 0x0B), 1);
+        module.config_mut().save_event(&event, &[0x05, 0x00]).unwrap();
+        module.config_mut().set_event_ack(true);
+        module.config_mut().set_heartbeat(true);
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(&device, None, HardwareAddress::default());
+        let acon = [OpCode::LongEventAccessoryOn.into(), 0x0A, 0x0B, 0x00, 0x01];
+        inject(&mut device, &acon);
+
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert!(module.take_consumed_event().is_none(), "dispatched on the next poll");
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+
+        let consumed = module.take_consumed_event().unwrap();
+        assert_eq!((consumed.event, consumed.on, consumed.behaviour), (event, true, Some(0x05)));
+        assert!(module.take_consumed_event().is_none());
+
+        let socket = sockets.get_mut::<Socket>(handle);
+        let ack = [OpCode::EventAck.into(), 0x01, 0x02, acon[0], 0x0A, 0x0B, 0x00, 0x01];
+        assert_eq!(socket.recv(), Ok(&ack[..]));
+        assert!(!socket.can_recv());
+
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64);
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        let socket = sockets.get_mut::<Socket>(handle);
+        assert_eq!(socket.recv(), Ok(&[OpCode::Heartbeat.into(), 0x01, 0x02, 0, 0, 0][..]));
+    }
+}
@@ -0,0 +1,406 @@
+//! Consumed and produced events, including the default events of an uninitialized node.
+
+use embedded_time::Clock;
+use heapless::Vec;
+use vlcb_core::vlcb::{EventId, EventType, EVENT_SIZE, NODENUM_SIZE};
+use vlcb_defs::{ModuleMode, OpCode};
+use vlcb_network::data::packet::construct::layout_ctrl::{produce, response, DATA_EVENT_SIZE};
+use vlcb_network::data::packet::construct::{ConstructError, OutgoingPacket};
+use vlcb_persistence::node_config::{LearnedEvent, NodeConfig};
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::{config, Module};
+
+/// An event the module consumes and produces before it has a node number.
+///
+/// Default events are short events matched on the device number only. They are provided
+/// by the application at compile time and moved into the learned events with the
+/// behaviour as the first event variable when the node gets its first node number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DefaultEvent {
+    pub device_number: u16,
+    /// Application defined behaviour triggered by the event.
+    pub behaviour: u8,
+}
+
+impl DefaultEvent {
+    pub const fn new(device_number: u16, behaviour: u8) -> Self {
+        Self {
+            device_number,
+            behaviour,
+        }
+    }
+
+    fn event_id(&self) -> EventId {
+        EventId::short(self.device_number)
+    }
+}
+
+/// An accessory event consumed by the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConsumedEvent {
+    /// Opcode the event was received with.
+    pub opcode: OpCode,
+    pub event: EventId,
+    /// `true` for an "on" event, `false` for an "off" event.
+    pub on: bool,
+    /// Behaviour of a default event, or the first event variable of a learned event.
+    ///
+    /// `None` when the module has no event variables.
+    pub behaviour: Option<u8>,
+}
+
+/// The data last produced by the module, returned to the data requests.
+#[derive(Debug, Default)]
+pub(crate) struct DataEvents {
+    node: Option<[u8; DATA_EVENT_SIZE]>,
+    /// Data of the devices by their device number, the least recently sent first.
+    devices: Vec<(u16, [u8; DATA_EVENT_SIZE]), { config::DEVICE_DATA_SLOTS }>,
+}
+
+impl DataEvents {
+    fn set_device(&mut self, device_number: u16, data: [u8; DATA_EVENT_SIZE]) {
+        if let Some(pos) = self.devices.iter().position(|(n, _)| *n == device_number) {
+            self.devices.remove(pos);
+        } else if self.devices.is_full() {
+            self.devices.remove(0);
+        }
+        // note(discard): a slot was freed above
+        let _ = self.devices.push((device_number, data));
+    }
+
+    fn device(&self, device_number: u16) -> Option<[u8; DATA_EVENT_SIZE]> {
+        self.devices
+            .iter()
+            .find(|(n, _)| *n == device_number)
+            .map(|(_, data)| *data)
+    }
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Return the default events of the module.
+    pub fn default_events(&self) -> &'static [DefaultEvent] {
+        self.default_events
+    }
+
+    /// Match an incoming VLCB packet (the opcode followed by its data octets)
+    /// against the events consumed by the module.
+    ///
+    /// An uninitialized module consumes only the short forms of its default events,
+    /// a module in normal mode consumes its learned events.
+    ///
+    /// [`Module::poll`] consumes the packets of the module socket itself, the application
+    /// without one calls this for every received packet.
+    pub fn consume_event(&self, packet: &[u8]) -> Option<ConsumedEvent> {
+        let (&opcode, data) = packet.split_first()?;
+        let data: &[u8; EVENT_SIZE] = data.get(..EVENT_SIZE)?.try_into().ok()?;
+
+        let opcode = OpCode::try_from(opcode).ok()?;
+        let (event, on) = match opcode {
+            OpCode::LongEventAccessoryOn => (EventId::from_bytes(data), true),
+            OpCode::LongEventAccessoryOff => (EventId::from_bytes(data), false),
+            OpCode::ShortEventAccessoryOn => (EventId::short_from_wire_bytes(data), true),
+            OpCode::ShortEventAccessoryOff => (EventId::short_from_wire_bytes(data), false),
+            _ => return None,
+        };
+
+        let behaviour = match self.inner.config.mode() {
+            ModuleMode::Uninitialized if event.is_short() => self
+                .default_events
+                .iter()
+                .find(|e| e.device_number == event.device_number())
+                .map(|e| Some(e.behaviour))?,
+            ModuleMode::Normal => self.inner.config.get_event(&event)?.vars().first().copied(),
+            _ => return None,
+        };
+
+        Some(ConsumedEvent {
+            opcode,
+            event,
+            on,
+            behaviour,
+        })
+    }
+
+    /// Acknowledge a consumed event.
+    ///
+    /// Returns the ENACK packet to transmit when the event acknowledgement is turned on
+    /// in the node config and the node is in normal mode. [`Module::poll`] queues it to the
+    /// module socket itself.
+    pub fn acknowledge_event(&self, consumed: &ConsumedEvent) -> Option<OutgoingPacket> {
+        let config = &self.inner.config;
+        if !config.is_event_ack_on() || config.mode() != ModuleMode::Normal {
+            return None;
+        }
+
+        Some(response::event_ack(*config.node_number(), consumed.opcode, consumed.event))
+    }
+
+    /// Produce an accessory event with the event number `event_num`.
+    ///
+    /// An uninitialized module produces the short form with the event number as the
+    /// device number, a module in normal mode the long form with its node number.
+    pub fn raise_event(&self, event_num: u16, on: bool) -> OutgoingPacket {
+        let event = match self.inner.config.mode() {
+            ModuleMode::Normal => EventId::long(*self.inner.config.node_number(), event_num),
+            _ => EventId::short(event_num),
+        };
+        let event_type = match on {
+            true => EventType::AccessoryOn,
+            false => EventType::AccessoryOff,
+        };
+
+        produce::accessory(event_type, event, None)
+    }
+
+    /// Produce the node data event (ACDAT) with the `data`, e.g. an RFID tag read by the node.
+    ///
+    /// The data is kept to answer the data requests (RQDAT). Returns `None` unless the node
+    /// is in normal mode.
+    pub fn send_node_data(&mut self, data: &[u8; DATA_EVENT_SIZE]) -> Option<OutgoingPacket> {
+        if self.inner.config.mode() != ModuleMode::Normal {
+            return None;
+        }
+
+        self.inner.data.node = Some(*data);
+        Some(produce::node_data(*self.inner.config.node_number(), *data))
+    }
+
+    /// Produce the device data event (DDES) of the device `device_number` attached to the
+    /// node, e.g. one of its RFID readers.
+    ///
+    /// The data of the last [`config::DEVICE_DATA_SLOTS`] devices is kept to answer the
+    /// device data requests (RQDDS). Returns `Err(ConstructError::InvalidArgument)` for the
+    /// device number zero.
+    pub fn send_device_data(
+        &mut self,
+        device_number: u16,
+        data: &[u8; DATA_EVENT_SIZE],
+    ) -> Result<OutgoingPacket, ConstructError> {
+        let packet = produce::try_device_data(device_number, *data)?;
+        self.inner.data.set_device(device_number, *data);
+        Ok(packet)
+    }
+
+    /// Answer RQDAT addressed to this node with ARDAT, once the node produced its data.
+    pub(crate) fn process_query_node_data(&self, data: &[u8]) -> Option<OutgoingPacket> {
+        let node_num = *self.inner.config.node_number();
+        if self.inner.config.mode() != ModuleMode::Normal
+            || data.get(..NODENUM_SIZE)? != node_num.as_bytes()
+        {
+            return None;
+        }
+
+        Some(response::node_data(node_num, self.inner.data.node?))
+    }
+
+    /// Answer RQDDS with DDRS, for the devices whose data the node keeps.
+    pub(crate) fn process_query_device_data(&self, data: &[u8]) -> Option<OutgoingPacket> {
+        let device_number = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+        let device_data = self.inner.data.device(device_number)?;
+        response::try_device_data(device_number, device_data).ok()
+    }
+
+    /// Move the default events into the learned events, as far as there is room for them.
+    ///
+    /// Events which were already learned are kept as they are.
+    pub(crate) fn migrate_default_events(&mut self) {
+        let mut vars = [0u8; u8::MAX as usize];
+        let vars = &mut vars[..S::EVENT_VAR_COUNT as usize];
+
+        for default in self.default_events {
+            let event = default.event_id();
+            if self.inner.config.has_event(&event) {
+                continue;
+            }
+            if self.inner.config.stored_event_count() >= S::MAX_EVENTS {
+                break;
+            }
+
+            if let Some(first) = vars.first_mut() {
+                *first = default.behaviour;
+            }
+            // note(discard): there is room for the event, it was checked above
+            let _ = self.inner.config.save_event(&event, vars);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::RefCell;
+
+    use rclite::Rc;
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::VlcbNodeNumber;
+    use vlcb_defs::Manufacturer;
+
+    use super::*;
+    use crate::test_utils::{config, interface, RamStorage, TestConfig, TestUi, MAX_EVENTS};
+    use crate::{ModuleVersion, Processor};
+
+    const DEFAULT_EVENTS: [DefaultEvent; 2] = [DefaultEvent::new(1, 10), DefaultEvent::new(2, 20)];
+
+    fn module(default_events: &'static [DefaultEvent]) -> Module<'static, TestUi, TestClock, TestConfig> {
+        Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .default_events(default_events)
+            .build()
+            .unwrap()
+    }
+
+    fn short_on(device_number: u16) -> [u8; 5] {
+        let [hi, lo] = device_number.to_be_bytes();
+        [OpCode::ShortEventAccessoryOn.into(), 0x0A, 0x0B, hi, lo]
+    }
+
+    #[test]
+    fn test_default_events_survive_setup() {
+        let clock = TestClock::new();
+        let mut module = module(&DEFAULT_EVENTS);
+        assert_eq!(module.inner.config.mode(), ModuleMode::Uninitialized);
+
+        let consumed = module.consume_event(&short_on(2)).unwrap();
+        assert_eq!(consumed.behaviour, Some(20));
+        assert!(consumed.on);
+        assert!(module.consume_event(&short_on(3)).is_none());
+        assert!(module
+            .consume_event(&[OpCode::LongEventAccessoryOn.into(), 0x0A, 0x0B, 0x00, 0x02])
+            .is_none());
+
+        module.start_setup(clock.now()).unwrap();
+        module
+            .process(&[OpCode::SetNodeNumber.into(), 0x01, 0x02])
+            .unwrap();
+        assert_eq!(module.inner.config.mode(), ModuleMode::Normal);
+        assert_eq!(module.inner.config.stored_event_count(), 2);
+
+        let consumed = module.consume_event(&short_on(2)).unwrap();
+        assert_eq!(consumed.behaviour, Some(20));
+        assert!(module.consume_event(&short_on(3)).is_none());
+    }
+
+    #[test]
+    fn test_default_events_consumed_after_restart() {
+        let clock = TestClock::new();
+        let storage = Rc::new(RefCell::new(RamStorage::new()));
+        let mut first = module(&DEFAULT_EVENTS);
+        first.inner.config = TestConfig::new(storage.clone());
+
+        first.start_setup(clock.now()).unwrap();
+        first.process(&[OpCode::SetNodeNumber.into(), 0x01, 0x02]).unwrap();
+
+        // the migrated events are flushed with the node number and restored as short events
+        let mut restarted = module(&DEFAULT_EVENTS);
+        restarted.inner.config = TestConfig::new(storage);
+        let restarted = restarted.init();
+        assert_eq!(restarted.inner.config.mode(), ModuleMode::Normal);
+        assert_eq!(restarted.consume_event(&short_on(2)).unwrap().behaviour, Some(20));
+    }
+
+    #[test]
+    fn test_migration_respects_max_events() {
+        static MANY: [DefaultEvent; MAX_EVENTS + 1] = {
+            let mut events = [DefaultEvent::new(0, 0); MAX_EVENTS + 1];
+            let mut i = 0;
+            while i < events.len() {
+                events[i] = DefaultEvent::new(i as u16 + 1, i as u8);
+                i += 1;
+            }
+            events
+        };
+        let clock = TestClock::new();
+        let mut module = module(&MANY);
+
+        module.start_setup(clock.now()).unwrap();
+        module.process(&[OpCode::SetNodeNumber.into(), 0x01, 0x02]).unwrap();
+
+        assert_eq!(module.inner.config.stored_event_count(), MAX_EVENTS as u8);
+        assert!(module.consume_event(&short_on(MAX_EVENTS as u16 + 1)).is_none());
+    }
+
+    #[test]
+    fn test_device_data_kept_for_requests() {
+        const DATA: [u8; DATA_EVENT_SIZE] = [0xD1, 0xD2, 0xD3, 0xD4, 0xD5];
+        let mut module = module(&[]);
+        let request = |n: u16| {
+            let [hi, lo] = n.to_be_bytes();
+            [OpCode::RequestDeviceDataShortMode.into(), hi, lo]
+        };
+
+        assert_eq!(module.send_device_data(0, &DATA), Err(ConstructError::InvalidArgument));
+        assert!(module.process(&request(1)).is_none(), "nothing sent yet");
+
+        for n in 1..=config::DEVICE_DATA_SLOTS as u16 + 1 {
+            let packet = module.send_device_data(n, &[n as u8; DATA_EVENT_SIZE]).unwrap();
+            assert_eq!(packet.opcode(), Some(OpCode::DeviceDataEventShortMode));
+        }
+        assert!(module.process(&request(1)).is_none(), "the oldest device is dropped");
+        let response = module.process(&request(2)).unwrap();
+        assert_eq!(
+            &response.payload[..],
+            &[OpCode::DeviceDataResponseShortMode.into(), 0x00, 0x02, 2, 2, 2, 2, 2]
+        );
+
+        module.send_device_data(2, &DATA).unwrap();
+        let response = module.process(&request(2)).unwrap();
+        assert_eq!(&response.payload[3..], &DATA, "the latest data is returned");
+    }
+
+    #[test]
+    fn test_event_ack() {
+        let mut module = module(&[]);
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+        let event = EventId::long(VlcbNodeNumber::new(0x0A, 0x0B), 1);
+        module.inner.config.set_mode_normal(node_num);
+        module.inner.config.save_event(&event, &[0x05, 0x00]).unwrap();
+
+        let acon = [OpCode::LongEventAccessoryOn.into(), 0x0A, 0x0B, 0x00, 0x01];
+        let consumed = module.consume_event(&acon).unwrap();
+        assert!(module.acknowledge_event(&consumed).is_none());
+
+        module.inner.config.set_event_ack(true);
+        let consumed = module.consume_event(&acon).unwrap();
+        let ack = module.acknowledge_event(&consumed).unwrap();
+        assert_eq!(
+            &ack.payload[..],
+            &[
+                OpCode::EventAck.into(),
+                0x01,
+                0x02,
+                OpCode::LongEventAccessoryOn.into(),
+                0x0A,
+                0x0B,
+                0x00,
+                0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raise_event_form() {
+        let mut module = module(&DEFAULT_EVENTS);
+
+        let short = module.raise_event(1, true);
+        assert_eq!(
+            &short.payload[..],
+            &[OpCode::ShortEventAccessoryOn.into(), 0x00, 0x00, 0x00, 0x01]
+        );
+
+        module.inner.config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        let long = module.raise_event(1, false);
+        assert_eq!(
+            &long.payload[..],
+            &[OpCode::LongEventAccessoryOff.into(), 0x01, 0x02, 0x00, 0x01]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Deferred flushing of the node config to the persistent storage.
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
+use vlcb_core::time::millis;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::Module;
+
+/// When [`Module::poll`] writes the changed node config to the persistent storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlushPolicy {
+    /// Flush on every poll the config has changed.
+    Immediate,
+    /// Flush once the config has stayed changed for `min_interval_ms`, so bursts of changes
+    /// are written in a single pass.
+    OnPoll { min_interval_ms: u32 },
+    /// Never flush from the poll, the application flushes the config itself.
+    Manual,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy::OnPoll {
+            min_interval_ms: crate::config::FLUSH_INTERVAL_MS,
+        }
+    }
+}
+
+/// State of the deferred flush.
+pub(crate) struct Flush<C: Clock> {
+    policy: FlushPolicy,
+    /// When the config was first seen changed since the last flush.
+    dirty_since: Option<Instant<C>>,
+}
+
+impl<C: Clock> Flush<C> {
+    pub(crate) fn new(policy: FlushPolicy) -> Self {
+        Self {
+            policy,
+            dirty_since: None,
+        }
+    }
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Flush the node config when it is due by the [`FlushPolicy`].
+    pub(crate) fn poll_flush(&mut self, now: Instant<C>) {
+        let flush = &mut self.inner.flush;
+        if !self.inner.config.is_dirty() {
+            flush.dirty_since = None;
+            return;
+        }
+
+        let due = match flush.policy {
+            FlushPolicy::Immediate => true,
+            FlushPolicy::OnPoll { min_interval_ms } => {
+                let since = *flush.dirty_since.get_or_insert(now);
+                now >= since + millis::<C>(Milliseconds(min_interval_ms))
+            }
+            FlushPolicy::Manual => false,
+        };
+        if due {
+            self.inner.config.flush();
+            flush.dirty_since = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+    use vlcb_defs::Manufacturer;
+
+    use super::*;
+    use crate::test_utils::{config, interface, TestConfig, TestUi};
+    use crate::{ModuleVersion, Processor};
+
+    fn module(policy: FlushPolicy) -> Module<'static, TestUi, TestClock, TestConfig> {
+        Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .flush_policy(policy)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flush_interval_batches_changes() {
+        let clock = TestClock::new();
+        let mut module = module(FlushPolicy::OnPoll { min_interval_ms: 100 });
+
+        for (index, ms) in [(1, 0), (2, 40), (3, 40)] {
+            clock.advance(ms);
+            module.inner.config.set_nv(index, 0x10).unwrap();
+            module.poll_flush(clock.now());
+            assert!(module.inner.config.is_dirty(), "flush is deferred");
+        }
+
+        clock.advance(20);
+        module.poll_flush(clock.now());
+        assert!(!module.inner.config.is_dirty());
+    }
+
+    #[test]
+    fn test_default_policy_flushes_after_interval() {
+        let clock = TestClock::new();
+        let mut module = module(FlushPolicy::default());
+
+        module.inner.config.set_nv(1, 0x10).unwrap();
+        module.poll_flush(clock.now());
+        clock.advance(crate::config::FLUSH_INTERVAL_MS as u64 - 1);
+        module.poll_flush(clock.now());
+        assert!(module.inner.config.is_dirty(), "not flushed before the interval");
+
+        clock.advance(1);
+        module.poll_flush(clock.now());
+        assert!(!module.inner.config.is_dirty());
+
+        // a clean config starts a new interval once changed again
+        clock.advance(60_000);
+        module.inner.config.set_nv(2, 0x10).unwrap();
+        module.poll_flush(clock.now());
+        assert!(module.inner.config.is_dirty());
+    }
+
+    #[test]
+    fn test_flush_policies() {
+        let clock = TestClock::new();
+
+        let mut immediate = module(FlushPolicy::Immediate);
+        immediate.inner.config.set_nv(1, 0x10).unwrap();
+        immediate.poll_flush(clock.now());
+        assert!(!immediate.inner.config.is_dirty());
+
+        let mut manual = module(FlushPolicy::Manual);
+        manual.inner.config.set_nv(1, 0x10).unwrap();
+        clock.advance(60_000);
+        manual.poll_flush(clock.now());
+        assert!(manual.inner.config.is_dirty());
+    }
+}
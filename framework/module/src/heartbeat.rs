@@ -0,0 +1,153 @@
+//! Periodic heartbeat of the node.
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
+use vlcb_core::time::millis;
+use vlcb_defs::ModuleMode;
+use vlcb_network::data::packet::construct::{module_cfg, OutgoingPacket};
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::Module;
+
+/// State of the heartbeat timer.
+pub(crate) struct Heartbeat<C: Clock> {
+    interval_ms: u32,
+    /// When the next heartbeat is due, `None` while the heartbeat is not running.
+    next: Option<Instant<C>>,
+    sequence: u8,
+}
+
+impl<C: Clock> Heartbeat<C> {
+    pub(crate) fn new(interval_ms: u32) -> Self {
+        Self {
+            interval_ms,
+            next: None,
+            sequence: 0,
+        }
+    }
+
+    fn deadline(&self, now: Instant<C>) -> Instant<C> {
+        now + millis::<C>(Milliseconds(self.interval_ms))
+    }
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Produce the heartbeat (HEARTB) when it is due.
+    ///
+    /// The heartbeat runs while it is turned on in the node config and the node is in normal
+    /// mode. The first heartbeat is sent one interval after it started running, then every
+    /// interval, see [`crate::ModuleBuilder::heartbeat_interval`]. The heartbeat pauses while the
+    /// link to the bus is down, so it does not fill the transmit queue of a dead bus, and starts
+    /// over when the link is up again.
+    ///
+    /// Returns the HEARTB packet to transmit. [`Module::poll`] queues it to the module socket
+    /// itself, the application without one calls this on every poll.
+    pub fn poll_heartbeat(&mut self, now: Instant<C>) -> Option<OutgoingPacket> {
+        let config = &self.inner.config;
+        let heartbeat = &mut self.inner.heartbeat;
+        if !config.is_heartbeat_on() || config.mode() != ModuleMode::Normal || self.inner.link_down {
+            heartbeat.next = None;
+            return None;
+        }
+
+        let Some(next) = heartbeat.next else {
+            heartbeat.next = Some(heartbeat.deadline(now));
+            return None;
+        };
+        if now < next {
+            return None;
+        }
+
+        let sequence = heartbeat.sequence;
+        heartbeat.sequence = sequence.wrapping_add(1);
+        heartbeat.next = Some(heartbeat.deadline(now));
+
+        // 0 is the normal operation status
+        Some(module_cfg::ctrl::heartbeat(*config.node_number(), sequence, 0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::VlcbNodeNumber;
+    use vlcb_defs::{Manufacturer, OpCode};
+
+    use super::*;
+    use crate::config::HEARTBEAT_INTERVAL_MS;
+    use crate::test_utils::{config, interface, TestConfig, TestUi};
+    use crate::{ModuleVersion, Processor};
+
+    fn module() -> Module<'static, TestUi, TestClock, TestConfig> {
+        let mut module = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+
+        module.inner.config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        module.inner.config.set_heartbeat(true);
+        module
+    }
+
+    #[test]
+    fn test_heartbeat_interval() {
+        let clock = TestClock::new();
+        let mut module = module();
+
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64 - 1);
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+
+        clock.advance(1);
+        let heartbeat = module.poll_heartbeat(clock.now()).unwrap();
+        assert_eq!(
+            &heartbeat.payload[..],
+            &[OpCode::Heartbeat.into(), 0x01, 0x02, 0x00, 0x00, 0x00]
+        );
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64);
+        let heartbeat = module.poll_heartbeat(clock.now()).unwrap();
+        assert_eq!(heartbeat.payload[3], 0x01, "sequence is incremented");
+    }
+
+    #[test]
+    fn test_heartbeat_at_large_epoch() {
+        let clock = TestClock::starting_at(u32::MAX as u64 + 10_000);
+        let mut module = module();
+        assert_eq!(module.now(), None, "not polled yet");
+
+        // the first interval counts from the start of the heartbeat, not from the clock start
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64 - 1);
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+
+        clock.advance(1);
+        assert!(module.poll_heartbeat(clock.now()).is_some());
+    }
+
+    #[test]
+    fn test_heartbeat_off() {
+        let clock = TestClock::new();
+        let mut module = module();
+        module.inner.config.set_heartbeat(false);
+
+        module.poll_heartbeat(clock.now());
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64);
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+
+        module.inner.config.set_heartbeat(true);
+        module.inner.config.set_mode_uninitialized();
+        module.poll_heartbeat(clock.now());
+        clock.advance(HEARTBEAT_INTERVAL_MS as u64);
+        assert!(module.poll_heartbeat(clock.now()).is_none());
+    }
+}
@@ -1,33 +1,104 @@
+//! VLCB module runtime.
+//!
+//! Ties the network interface, persistent node configuration and user interface together
+//! and implements the default module behavior.
+//!
+//! # Testing
+//!
+//! Everything time related is generic over an [`embedded_time::Clock`]. Tests can drive
+//! timers deterministically with the `TestClock` from `vlcb-core` (behind its `test-clock`
+//! feature) and pass its instants to the module:
+//!
+//! ```
+//! use embedded_time::duration::Milliseconds;
+//! use vlcb_core::time::{millis, TestClock};
+//!
+//! let clock = TestClock::new();
+//! let started_at = clock.now();
+//!
+//! // hold the main switch for longer than the long press threshold
+//! let long_hold = millis::<TestClock>(vlcb_ui::config::SW_LONG_HOLD);
+//! clock.advance(long_hold.0 + 1);
+//!
+//! let held: Milliseconds<u64> = (clock.now() - started_at).try_into().unwrap();
+//! assert!(held > long_hold);
+//! ```
 #![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![deny(unsafe_code)]
 
-use cfg_if::cfg_if;
 use service_set::ServiceSet;
 use vlcb_persistence::node_config::NodeConfig;
 use vlcb_persistence::PersistentStorage;
 use embedded_time::{Clock, Instant};
+use vlcb_core::module::{ModuleParams, NodeFlags, CPU_MANUFACTURER_ID_SIZE};
+
+use vlcb_core::can::{VlcbCanId, CANID_MAX, CANID_MIN, CANID_SIZE};
+use vlcb_core::vlcb::NODENUM_SIZE;
 
 use vlcb_defs::{
-    ArmProcessor, BusType, Manufacturer, MergModuleType, MicrochipProcessor, ModuleParams as ModuleParam, ProcessorManufacturer
+    ArmProcessor, CommandError, Manufacturer, MicrochipProcessor, ModuleMode, ModuleParam,
+    OpCode, ProcessorManufacturer,
+};
+use vlcb_network::iface::{
+    Interface, InterfaceEvent, InterfaceStats, PollContext, SocketHandle, SocketSet,
 };
-use vlcb_network::iface::{Interface, SocketSet};
+use vlcb_network::data::packet::construct::{module_cfg, OutgoingPacket};
 use vlcb_network::phy::{Device};
+use vlcb_network::socket::module::Socket;
+use vlcb_network::wire::HardwareAddress;
 
-use vlcb_ui::VlcbUi;
+use vlcb_ui::{UserAction, VlcbUi};
 
-const MODULE_PARAMS_COUNT: usize = 20;
 
+pub mod builder;
+pub mod diagnostics;
+mod dispatch;
+pub mod events;
+mod flush;
+mod heartbeat;
 pub mod service_set;
+mod setup;
+pub mod status;
+
+pub use builder::{BuildError, ModuleBuilder};
+pub use diagnostics::Diagnostics;
+pub use events::{ConsumedEvent, DefaultEvent};
+pub use flush::FlushPolicy;
+pub use status::StatusSnapshot;
+pub use vlcb_core::module::ParamFlags;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+pub mod config {
+    /// How long the module waits for a node number from the configuration tool.
+    pub const SETUP_TIMEOUT_MS: u32 = 30_000;
+
+    /// Default interval between two heartbeats.
+    pub const HEARTBEAT_INTERVAL_MS: u32 = 5_000;
 
-pub type CpuId = [char; 4];
+    /// How long the node config has to stay changed before it is flushed by default,
+    /// see [`crate::FlushPolicy::OnPoll`].
+    pub const FLUSH_INTERVAL_MS: u32 = 1_000;
+
+    /// Number of devices whose last data is kept to answer the device data requests,
+    /// see [`crate::Module::send_device_data`].
+    pub const DEVICE_DATA_SLOTS: usize = 4;
+
+    /// Number of events consumed from the module socket that wait for the application,
+    /// see [`crate::Module::take_consumed_event`].
+    pub const CONSUMED_EVENT_SLOTS: usize = 8;
+}
+
+pub type CpuId = [char; CPU_MANUFACTURER_ID_SIZE];
 pub type CpuIdResolver = fn() -> CpuId;
 
 // pub enum ModuleType {
-//     Merg(CbusMergModuleTypes),
-//     Sprog(CbusSprogModuleTypes),
-//     RocRail(CbusRocRailModuleTypes),
-//     Spectrum(CbusSpectrumModuleTypes),
-//     SysPixie(CbusSysPixieModuleTypes),
+//     Merg(MergModuleType),
+//     Sprog(SprogModuleType),
+//     RocRail(RocRailModuleType),
+//     Spectrum(SpectrumModuleType),
+//     SysPixie(SysPixieModuleType),
 //     Generic(u8),
 // }
 
@@ -104,7 +175,8 @@ impl TryFrom<Processor> for MicrochipProcessor {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleVersion {
     major: u8,
     minor: char,
@@ -127,139 +199,174 @@ impl ModuleVersion {
     }
 }
 
-#[derive(Default, Debug)]
-struct ModuleParams([u8; MODULE_PARAMS_COUNT]);
-impl ModuleParams {
-    pub(crate) fn new(cpu: Processor, cpu_id_resolver: Option<CpuIdResolver>) -> Self {
-        let mut params = Self([0; MODULE_PARAMS_COUNT]);
-
-        cpu.emit(&mut params);
-
-        if let Some(r) = cpu_id_resolver {
-            let name: heapless::Vec<u8, 4> = r()
-                .as_slice()
-                .iter()
-                .map(|v| {
-                    debug_assert!(v.is_ascii(), "all characters need to be ASCII");
-                    *v as u8
-                })
-                .collect();
-            params.0[(ModuleParam::CpuManufacturerId as usize)..4].copy_from_slice(name.as_slice());
-        } else {
-            params.0[(ModuleParam::CpuManufacturerId as usize)..4].copy_from_slice([b'?'; 4].as_slice());
-        }
+/// Create the parameter block of a module running on the `cpu`.
+fn module_params(cpu: Processor, cpu_id_resolver: Option<CpuIdResolver>) -> ModuleParams {
+    let mut params = ModuleParams::new();
 
-        params
-    }
+    cpu.emit(&mut params);
 
-    pub(crate) fn get_param(&self, param: ModuleParam) -> u8 {
-        self.0[(param as usize) - 1]
-    }
+    let cpu_id = match cpu_id_resolver {
+        Some(r) => r().map(|v| {
+            debug_assert!(v.is_ascii(), "all characters need to be ASCII");
+            v as u8
+        }),
+        None => [b'?'; CPU_MANUFACTURER_ID_SIZE],
+    };
+    // note(discard): the CPU manufacturer id parameters are inside of the parameter block
+    let _ = params.set_params(ModuleParam::CpuManufacturerId, &cpu_id);
 
-    pub(crate) fn set_param(&mut self, param: ModuleParam, value: u8) {
-        self.0[(param as usize) - 1] = value
-    }
+    params
 }
 
-pub struct Module<UI: VlcbUi<C>, C: Clock, S: NodeConfig> {
+pub struct Module<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig> {
     name: &'static str,
     params: ModuleParams,
-    inner: ModuleInner<UI, C, S>,
+    default_events: &'static [DefaultEvent],
+    inner: ModuleInner<'a, UI, C, S>,
 }
 
-struct ModuleInner<UI: VlcbUi<C>, C: Clock, S: NodeConfig> {
-    now: Instant<C>,
+struct ModuleInner<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig> {
+    /// Time of the last poll, `None` until the first one.
+    now: Option<Instant<C>>,
+    /// Time of the first poll.
+    started: Option<Instant<C>>,
     config: S,
     ui: UI,
-    interface: Interface<C>,
+    services: ServiceSet<'a>,
+    /// Module socket the received packets are dispatched from.
+    socket: Option<SocketHandle>,
+    diagnostics: Diagnostics,
+    /// Counters of the polled interface, as of the last poll.
+    stats: InterfaceStats,
+    setup: setup::Setup<C>,
+    /// The interface reported the link to the bus down.
+    link_down: bool,
+    data: events::DataEvents,
+    /// Events consumed from the module socket, the oldest first.
+    consumed: heapless::Deque<ConsumedEvent, { config::CONSUMED_EVENT_SLOTS }>,
+    heartbeat: heartbeat::Heartbeat<C>,
+    flush: flush::Flush<C>,
 }
 
-impl<UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
-    Module<UI, C, S>
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
+    Module<'a, UI, C, S>
 {
+    /// Create a module.
+    ///
+    /// See [`ModuleBuilder`] for a more readable way of constructing a module.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &'static str,
         version: ModuleVersion,
         manufacturer: Manufacturer,
-        flags: u8,
+        flags: ParamFlags,
         ui: UI,
         config: S,
         cpu: Processor,
         cpu_id_resolver: Option<CpuIdResolver>,
-        interface: Interface<C>,
-        services: &ServiceSet
+        interface: &Interface<C>,
+        services: ServiceSet<'a>,
     ) -> Self {
-        let mut params = ModuleParams::new(cpu, cpu_id_resolver);
+        let mut builder = Self::builder()
+            .name(name)
+            .version(version)
+            .manufacturer(manufacturer)
+            .flags(flags)
+            .ui(ui)
+            .config(config)
+            .cpu(cpu)
+            .interface(interface)
+            .services(services);
+        if let Some(resolver) = cpu_id_resolver {
+            builder = builder.cpu_id_resolver(resolver);
+        }
 
-        params.set_param(ModuleParam::ModuleType, MergModuleType::VLCB.into());
-        params.set_param(ModuleParam::NodeFlags, flags);
+        match builder.build() {
+            Ok(module) => module,
+            Err(_) => unreachable!("all required module parts are provided"),
+        }
+    }
 
-        version.emit(&mut params);
+    /// Return a builder for constructing a module.
+    pub fn builder() -> ModuleBuilder<'a, UI, C, S> {
+        ModuleBuilder::new()
+    }
 
-        params.set_param(ModuleParam::ModuleManufacturer, manufacturer.into());
-        params.set_param(
-            ModuleParam::BusType,
-            BusType::from(interface.device_caps().medium).into(),
-        );
+    /// Return the module name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
 
-        params.set_param(ModuleParam::MaxEventCount, S::MAX_EVENTS);
-        params.set_param(ModuleParam::EventVariableCount, S::EVENT_VAR_COUNT);
-        params.set_param(ModuleParam::NodeVariableCount, S::NODE_VAR_COUNT);
-
-        Self {
-            name,
-            params,
-            inner: ModuleInner {
-                now: Instant::new(C::T::from(0)),
-                config,
-                ui,
-                interface,
-            },
-        }
+    /// Return the value of a module parameter.
+    ///
+    /// [`ModuleParam::ModuleParameterCount`] returns the amount of parameters.
+    pub fn param(&self, param: ModuleParam) -> u8 {
+        self.params.get_param(param)
+    }
+
+    /// Return the module parameter block.
+    pub fn params(&self) -> &ModuleParams {
+        &self.params
+    }
+
+    /// Return the module parameters as octets, starting with parameter 1.
+    ///
+    /// The parameter index in [`ModuleParam`] is the position in the slice plus one.
+    pub fn params_as_bytes(&self) -> &[u8] {
+        self.params.as_bytes()
+    }
+
+    /// Return the module diagnostics counters.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.inner.diagnostics
+    }
+
+    /// Return the node config.
+    pub fn config(&self) -> &S {
+        &self.inner.config
+    }
+
+    /// Return the node config, e.g. for the services writing node variables and events.
+    ///
+    /// The changes are flushed by the [`FlushPolicy`] of the module.
+    pub fn config_mut(&mut self) -> &mut S {
+        &mut self.inner.config
+    }
+
+    /// Return the services of the module.
+    pub fn services(&self) -> &ServiceSet<'a> {
+        &self.inner.services
+    }
+
+    /// Return the services of the module, e.g. for registering the state provider of the
+    /// event producer.
+    pub fn services_mut(&mut self) -> &mut ServiceSet<'a> {
+        &mut self.inner.services
+    }
+
+    /// Return the user interface of the module.
+    pub fn ui(&self) -> &UI {
+        &self.inner.ui
+    }
+
+    /// Return the time of the last poll, `None` until the module is polled.
+    pub fn now(&self) -> Option<Instant<C>> {
+        self.inner.now
     }
 
     /// Initialize the module instance
     ///
     /// Loads config data from memory, and restores the saved state from previous runs if supported.
-    /// Restores the interface addresses from memory.
+    /// The restored node number is applied to the interface on the next [`Module::poll`].
     pub fn init(mut self) -> Self {
-        todo!();
-        // self.inner.config.load();
-
-        // let addr: VlcbNodeNumber;
-        // #[cfg(feature = "medium-can")]
-        // let can_id: VlcbCanId;
-        // if self.inner.config.flim() {
-        //     addr = self.inner.config.node_number();
-
-        //     #[cfg(feature = "medium-can")]
-        //     {
-        //         can_id = self.inner.config.can_id();
-        //     }
-        // } else {
-        //     addr = VlcbNodeNumber::default();
-        //     can_id = VlcbCanId::default();
-        // }
-
-        // iface.set_addr(addr);
-        // #[cfg(feature = "medium-can")]
-        // {
-        //     if Medium::CAN == iface.device_caps().medium {
-        //         iface.set_hw_addr(HardwareAddress::CAN(can_id));
-        //     }
-        // }
-
-        // Module {
-        //     name: self.name,
-        //     params: self.params,
-        //     inner: self.inner,
-        // }
+        // note(discard): the config falls back to the defaults for whatever was not restored
+        let _ = self.inner.config.load();
+        self
     }
 }
 
-impl<UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
-    Module<UI, C, S>
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
+    Module<'a, UI, C, S>
 {
     /// Shutdown the module
     ///
@@ -273,70 +380,50 @@ impl<UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
         Module {
             name: self.name,
             params: self.params,
+            default_events: self.default_events,
             inner: self.inner,
         }
     }
 
+    /// Return the node to its factory state.
+    ///
+    /// The learned events are forgotten, the NVs are set to their defaults, and the node
+    /// becomes uninitialized with the default CAN ID. The reset flag is raised for the next
+    /// start, and the node config is flushed right away.
     pub fn reset_module(&mut self) {
-        // /// standard implementation of resetModule()
-        //
-        // bool bDone;
-        // unsigned long waittime;
-        //
-        // // start timeout timer
-        // waittime = millis();
-        // bDone = false;
-        //
-        // // DEBUG_SERIAL << F("> waiting for a further 5 sec button push, as a safety measure") << endl;
-        //
-        // pbSwitch.reset();
-        // ledGrn.blink();
-        // ledYlw.blink();
-        //
-        // // wait for a further (5 sec) button press -- as a 'safety' mechanism
-        // while (!bDone) {
-        //
-        //     // 30 sec timeout
-        //     if ((millis() - waittime) > 30000) {
-        //         // DEBUG_SERIAL << F("> timeout expired, reset not performed") << endl;
-        //         return;
-        //     }
-        //
-        //     pbSwitch.run();
-        //     ledGrn.run();
-        //     ledYlw.run();
-        //
-        //     // wait until switch held for a further 5 secs
-        //     if (pbSwitch.isPressed() && pbSwitch.getCurrentStateDuration() > 5000) {
-        //         bDone = true;
-        //     }
-        // }
-        //
-        // // do the reset
-        // // DEBUG_SERIAL << F("> performing module reset ...") <<  endl;
-        //
-        // ledGrn.off();
-        // ledYlw.off();
-        // ledGrn.run();
-        // ledYlw.run();
-        //
-        // resetModule(); -> this is basically continuation to the next lines reseting the data
-
-        // self.config.wipe();
-        // self.config.set_flim(false);
-        // self.config.set_can_id(CbusCanId::default());
-        // self.config.set_node_number(CbusNodeNumber::default());
-        // self.config.flag_for_reset();
-    }
-
-    pub fn poll<'a, D: Device>(
+        let config = &mut self.inner.config;
+        config.reset_to_factory();
+        config.set_mode_uninitialized();
+        config.set_can_id(VlcbCanId::default());
+        config.set_flags(NodeFlags::empty());
+        config.raise_reset_flag();
+        config.flush();
+
+        self.inner.setup = setup::Setup::Idle;
+        self.inner.ui.indicate_mode(ModuleMode::Uninitialized);
+    }
+
+    /// Run one iteration of the module.
+    ///
+    /// With a module socket set in the [`ModuleBuilder::socket`], the packets it received on
+    /// the previous poll are dispatched to the module and its services, and their responses
+    /// are queued to the socket for the `interface` to transmit. The consumed events are
+    /// acknowledged and kept for [`Module::take_consumed_event`], and the heartbeat is queued
+    /// when it is due. Without it the application dispatches the packets itself, e.g. with
+    /// [`Module::process`], [`Module::consume_event`] and [`Module::acknowledge_event`], and
+    /// transmits [`Module::poll_heartbeat`].
+    ///
+    /// The node number and the CAN ID of the node are applied to the `interface` before it
+    /// is polled.
+    pub fn poll<D: Device>(
         &mut self,
         now: Instant<C>,
-        interface: &'a mut Interface<C>,
-        device: &'a mut D,
-        sockets: &'a mut SocketSet<'a>,
+        interface: &mut Interface<C>,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
     ) {
-        self.inner.now = now;
+        self.inner.now = Some(now);
+        self.inner.started.get_or_insert(now);
 
         // TODO: module stuff like flim, can enumeration etc should be done using a socket
         // the socket impl should only forward packets we care about and then processing here should
@@ -345,65 +432,647 @@ impl<UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage>
 
         // self.process_mode_state(interface);
 
-        // TODO: instead of forcing the library users to adhere to this logic it should be rewriten to "on request"
-        // so that users can manipulate the button behaviors and things and maybe implement a default loop elsewhere
-        // also makes this more testable i guess
-        cfg_if! {
-            if #[cfg(feature = "user-interface")] {
-                self.inner.ui.poll(now)
+        self.inner.ui.poll(now);
+        if let Some(action) = self.inner.ui.take_requested_action() {
+            self.process_user_action(now, action, interface, device);
+        }
+
+        if let Some(socket) = self.inner.socket {
+            let socket = sockets.get_mut::<Socket>(socket);
+            self.process_socket(socket);
+            if let Some(heartbeat) = self.poll_heartbeat(now) {
+                // note(discard): a heartbeat that does not fit the socket is skipped, the
+                // next one follows an interval later
+                let _ = socket.send_packet(&heartbeat);
+            }
+        }
+        self.sync_interface(interface);
+
+        interface.poll(PollContext::new(now, device, sockets));
+        self.inner.stats = *interface.stats();
 
-                /*
+        self.process_interface_events(interface);
+        self.poll_setup(now);
+        self.poll_flush(now);
+    }
+
+    /// Apply the node number and the CAN ID of the node config to the polled interface.
+    ///
+    /// The interface has no node number unless the node is in normal mode.
+    fn sync_interface(&self, interface: &mut Interface<C>) {
+        interface.set_addr(self.node_number());
 
-          // use LEDs to indicate that the user can release the switch
-            if (_sw.isPressed() && _sw.getCurrentStateDuration() > SW_TR_HOLD) {
-                indicateMode(MODE_CHANGING);
+        let can_id = *self.inner.config.can_id();
+        match interface.hw_addr() {
+            HardwareAddress::CAN(current) if current != can_id => {
+                interface.set_hw_addr(HardwareAddress::CAN(can_id))
             }
+            _ => {}
+        }
+    }
 
-          //
-          /// handle switch state changes
-          //
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the response to transmit, if there is one.
+    pub fn process(&mut self, packet: &[u8]) -> Option<OutgoingPacket> {
+        let (&opcode, data) = packet.split_first()?;
 
-          if (_sw.stateChanged()) {
+        match OpCode::try_from(opcode).ok()? {
+            OpCode::SetNodeNumber => self.process_set_node_number(data),
+            OpCode::SetNodeCanId => self.process_set_can_id(data),
+            OpCode::QueryNodeInfo => self.process_query_node_info(),
+            OpCode::QueryNodeData => self.process_query_node_data(data),
+            OpCode::RequestDeviceDataShortMode => self.process_query_device_data(data),
+            _ => None,
+        }
+    }
+
+    /// Answer QNN with PNN while the node is in normal mode.
+    ///
+    /// The node flags parameter is reported with the FLiM bit set.
+    fn process_query_node_info(&self) -> Option<OutgoingPacket> {
+        if self.inner.config.mode() != ModuleMode::Normal {
+            return None;
+        }
 
-            // has switch been released ?
-            if (!_sw.isPressed()) {
+        let flags = ParamFlags::from_bits_retain(self.param(ModuleParam::NodeFlags)) | ParamFlags::FLiM;
+        Some(module_cfg::response::node_info(
+            *self.inner.config.node_number(),
+            self.param(ModuleParam::ModuleManufacturer),
+            self.param(ModuleParam::ModuleType),
+            flags.bits(),
+        ))
+    }
 
-              // how long was it pressed for ?
-              unsigned long press_time = _sw.getLastStateDuration();
+    /// Set the CAN ID requested by CANID addressed to this node.
+    ///
+    /// The CAN ID is stored in the node config and used by the interface from the next poll.
+    /// CAN IDs outside of the [`CANID_MIN`]..=[`CANID_MAX`] range are rejected with CMDERR.
+    fn process_set_can_id(&mut self, data: &[u8]) -> Option<OutgoingPacket> {
+        if self.inner.config.mode() != ModuleMode::Normal {
+            return None;
+        }
 
-              // long hold > 6 secs
-              if (press_time > SW_TR_HOLD) {
-                // initiate mode change
-                if (!module_config->FLiM) {
-                  initFLiM();
-                } else {
-                  revertSLiM();cbus::Packet
-                }
-              }
+        let node_num = *self.inner.config.node_number();
+        let (&can_id, nn) = data.get(..NODENUM_SIZE + CANID_SIZE)?.split_last()?;
+        if nn != node_num.as_bytes() {
+            return None;
+        }
 
-              // short 1-2 secs
-              if (press_time >= 1000 && press_time < 2000) {
-                renegotiate();
-              }
+        if !(CANID_MIN..=CANID_MAX).contains(&can_id) {
+            return Some(module_cfg::response::config_error(
+                node_num,
+                CommandError::InvalidEvent,
+            ));
+        }
 
-              // very short < 0.5 sec
-              if (press_time < 500 && module_config->FLiM) {
-                CANenumeration();
-              }
+        let can_id = VlcbCanId::from_bytes(&[can_id]);
+        self.inner.config.set_can_id(can_id);
+        self.inner.config.flush();
+        None
+    }
 
-            } else {
-              // do any switch release processing here
+    /// Serve an action requested on the user interface.
+    ///
+    /// The mode change starts the setup of an uninitialized node, and releases the node
+    /// number of a node in normal mode. The renegotiation and the CAN enumeration are only
+    /// available in normal mode.
+    fn process_user_action<D: Device>(
+        &mut self,
+        now: Instant<C>,
+        action: UserAction,
+        interface: &mut Interface<C>,
+        device: &mut D,
+    ) {
+        let normal = self.inner.config.mode() == ModuleMode::Normal;
+        // A packet which could not be sent is not retried, the user can repeat the request.
+        match action {
+            UserAction::ChangeMode if self.is_in_setup() => self.leave_setup(),
+            UserAction::ChangeMode if normal => {
+                let release = module_cfg::ctrl::release_node_number(*self.inner.config.node_number());
+                self.inner.config.set_mode_uninitialized();
+                self.inner.config.flush();
+                interface.set_addr(None);
+                self.inner.ui.indicate_mode(ModuleMode::Uninitialized);
+                let _ = interface.send_now(device, &release);
             }
-          }
+            UserAction::ChangeMode => {
+                let _ = self.request_node_number(now, interface, device);
+            }
+            UserAction::Renegotiate if normal => {
+                let _ = self.request_node_number(now, interface, device);
+            }
+            UserAction::StartCanEnumeration if normal => interface.start_can_enumeration(),
+            UserAction::Reset => {
+                self.reset_module();
+                interface.set_addr(None);
+                interface.set_hw_addr(HardwareAddress::CAN(VlcbCanId::default()));
+            }
+            _ => {}
         }
-        */
+    }
+
+    /// Drain the interface events into the diagnostics counters and indicate them on the UI.
+    ///
+    /// A CAN ID assigned by the enumeration is stored in the node config. The heartbeat pauses
+    /// while the link to the bus is down.
+    fn process_interface_events(&mut self, interface: &mut Interface<C>) {
+        if interface.take_events_lost() {
+            self.inner.diagnostics.record_lost_events();
+        }
+
+        for event in interface.poll_events() {
+            self.inner.diagnostics.record(&event);
+
+            match event {
+                InterfaceEvent::CanIdConflict { .. } | InterfaceEvent::CanEnumerationFailed => {
+                    self.inner.ui.indicate_error()
+                }
+                InterfaceEvent::CanEnumerationCompleted { new_id } => {
+                    self.inner.config.set_can_id(new_id);
+                    self.inner.ui.indicate_activity()
+                }
+                InterfaceEvent::LinkDown | InterfaceEvent::LinkUp => {
+                    self.inner.link_down = event == InterfaceEvent::LinkDown;
+                    self.inner.ui.indicate_link(!self.inner.link_down)
+                }
+                _ => {}
             }
         }
+    }
+}
 
-        // sockets.
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+    use vlcb_defs::{BusType, MergModuleType};
 
-        // let ctx: PollContext<'a, D, C> = PollContext::new(now, device, sockets);
-        todo!();
-        // interface.poll(ctx);
+    use super::*;
+    use vlcb_core::can::VlcbCanId;
+    use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
+    use vlcb_defs::OpCode;
+    use vlcb_network::iface::{InterfaceConfig, LinkSupervisionConfig, SocketStorage};
+    use vlcb_network::phy::loopback::Loopback;
+    use vlcb_network::wire::{CanFrame, HardwareAddress};
+    use vlcb_ui::NullUi;
+
+    use crate::test_utils::{config, interface, TestConfig, TestUi, MAX_EVENTS, NODE_VAR_COUNT};
+
+    #[test]
+    fn test_params_as_bytes() {
+        fn resolver() -> CpuId {
+            ['A', 'T', 'M', 'G']
+        }
+
+        let module: Module<TestUi, TestClock, TestConfig> = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(2, 'c', 7))
+            .manufacturer(Manufacturer::Development)
+            .flags(ParamFlags::Consumer | ParamFlags::FLiM | ParamFlags::Bootable)
+            .load_address(0x0000_0800)
+            .ui(TestUi::default())
+            .config(config())
+            .cpu(Processor::Atmel)
+            .cpu_id_resolver(resolver)
+            .interface(&interface())
+            .build()
+            .unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            Manufacturer::Development as u8, // manufacturer
+            b'c',                            // minor version
+            MergModuleType::VLCB as u8,      // module type
+            MAX_EVENTS as u8,                // events
+            2,                               // event variables
+            4,                               // node variables
+            2,                               // major version
+            0x0D,                            // flags
+            50,                              // cpu id
+            BusType::CAN as u8,              // bus type
+            0x00, 0x08, 0x00, 0x00,          // load address
+            b'A', b'T', b'M', b'G',          // cpu manufacturer id
+            ProcessorManufacturer::Atmel as u8,
+            7,                               // beta version
+        ];
+        assert_eq!(module.params_as_bytes(), &expected);
+
+        // the parameter count and every parameter but the cpu manufacturer id
+        let checksum = 20 + expected[..14].iter().chain(&expected[18..]).map(|&v| v as u16).sum::<u16>();
+        assert_eq!(module.params().checksum(), checksum);
+
+        assert_eq!(module.param(ModuleParam::ModuleParameterCount), 20);
+        assert_eq!(module.param(ModuleParam::ModuleManufacturer), expected[0]);
+        assert_eq!(module.param(ModuleParam::CpuManufacturerId), b'A');
+        assert_eq!(module.param(ModuleParam::BetaVersion), 7);
+    }
+
+    #[test]
+    fn test_cpu_manufacturer_id() {
+        fn resolver() -> CpuId {
+            ['A', 'T', 'M', 'G']
+        }
+
+        let params = module_params(Processor::Atmel, Some(resolver));
+        assert_eq!(&params.as_bytes()[14..18], b"ATMG");
+        assert_eq!(params.get_param(ModuleParam::CpuManufacturerId), b'A');
+        assert_eq!(params.get_param(ModuleParam::CpuManufacturer), ProcessorManufacturer::Atmel as u8);
+
+        let params = module_params(Processor::Atmel, None);
+        assert_eq!(&params.as_bytes()[14..18], b"????");
+        assert_eq!(params.get_param(ModuleParam::CpuId), 50);
+    }
+
+    #[test]
+    fn test_poll_sets_addr_in_normal_mode() {
+        let build = |config| -> Module<TestUi, TestClock, TestConfig> {
+            Module::builder()
+                .name("TEST")
+                .version(ModuleVersion::new(1, 'a', 0))
+                .manufacturer(Manufacturer::Development)
+                .cpu(Processor::Atmel)
+                .ui(TestUi::default())
+                .config(config)
+                .interface(&interface())
+                .build()
+                .unwrap()
+        };
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+        let mut iface = Interface::new(&device, Some(node_num), HardwareAddress::default());
+        let mut sockets = SocketSet::new(&mut [][..]);
+
+        let mut module = build(config()).init();
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(iface.addr(), None);
+
+        let mut stored = config();
+        stored.set_mode_normal(node_num);
+        stored.flush();
+        let mut module = build(stored).init();
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(iface.addr(), Some(node_num));
+    }
+
+    #[test]
+    fn test_poll_records_can_id_conflict() {
+        let mut module: Module<TestUi, TestClock, TestConfig> = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+        module.inner.config.set_can_id(VlcbCanId::from_bytes(&[1]));
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[1])),
+        );
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        // another node transmits with our CAN ID
+        let mut buffer = [0u8; 3];
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[1]));
+        frame.payload_mut()[0] = OpCode::QueryNodeInfo.into();
+        device.inject(&buffer).unwrap();
+
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.diagnostics().can_id_conflicts(), 1);
+        assert_eq!(module.inner.ui.errors, 1);
+
+        // the other node answers the enumeration request
+        device.inject(&buffer[..CanFrame::<&[u8]>::header_len()]).unwrap();
+
+        clock.advance(vlcb_network::config::CAN_RESERVE_DELAY_MS);
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.diagnostics().can_enumerations(), 1);
+        assert_eq!(module.diagnostics().lost_events(), 0);
+        assert_eq!(module.inner.ui.activity, 1);
+
+        let new_id = VlcbCanId::from_bytes(&[2]);
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(new_id));
+        assert_eq!(module.inner.config.can_id(), &new_id);
+    }
+
+    #[test]
+    fn test_set_can_id() {
+        let mut module: Module<TestUi, TestClock, TestConfig> = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+        module.inner.config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = normal_iface(&device);
+        let mut sockets = SocketSet::new(&mut [][..]);
+
+        let canid = |can_id: u8| [OpCode::SetNodeCanId.into(), 0x01, 0x02, can_id];
+        let cmderr = [
+            OpCode::NodeConfigurationError.into(),
+            0x01,
+            0x02,
+            CommandError::InvalidEvent.into(),
+        ];
+
+        assert!(module.process(&canid(42)).is_none());
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        let new_id = VlcbCanId::from_bytes(&[42]);
+        assert_eq!(module.inner.config.can_id(), &new_id);
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(new_id), "the polled interface transmits with it");
+        assert_eq!(module.can_id(), Some(new_id));
+
+        for invalid in [0, 100] {
+            let response = module.process(&canid(invalid)).unwrap();
+            assert_eq!(&response.payload[..], &cmderr);
+            module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+            assert_eq!(module.inner.config.can_id(), &new_id);
+            assert_eq!(iface.hw_addr(), HardwareAddress::CAN(new_id));
+        }
+
+        // addressed to another node
+        assert!(module
+            .process(&[OpCode::SetNodeCanId.into(), 0x09, 0x09, 0])
+            .is_none());
+    }
+
+    #[test]
+    fn test_query_node_info() {
+        let mut module: Module<TestUi, TestClock, TestConfig> = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .flags(ParamFlags::Consumer)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+
+        let qnn = [OpCode::QueryNodeInfo.into()];
+        assert!(module.process(&qnn).is_none(), "an uninitialized node has no node number");
+
+        module.inner.config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        let response = module.process(&qnn).unwrap();
+        assert_eq!(
+            &response.payload[..],
+            &[
+                OpCode::NodeInfo.into(),
+                0x01,
+                0x02,
+                Manufacturer::Development as u8,
+                MergModuleType::VLCB as u8,
+                0x05,
+            ]
+        );
+    }
+
+    /// A module in normal mode with a learned event and a changed NV.
+    fn normal_module() -> Module<'static, TestUi, TestClock, TestConfig> {
+        let mut module: Module<TestUi, TestClock, TestConfig> = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+
+        let config = &mut module.inner.config;
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.set_can_id(VlcbCanId::from_bytes(&[5]));
+        config.save_event(&EventId::short(1), &[0x0A, 0x0B]).unwrap();
+        config.set_nv(1, 0x42).unwrap();
+        module
+    }
+
+    fn normal_iface(device: &Loopback<8>) -> Interface<TestClock> {
+        Interface::new(
+            device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[5])),
+        )
+    }
+
+    /// Return the payload of the only frame transmitted to the `device`.
+    fn sent_payload(device: &mut Loopback<8>) -> std::vec::Vec<u8> {
+        use vlcb_network::phy::RxToken as _;
+
+        assert_eq!(device.len(), 1);
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| CanFrame::new_checked(&*buffer).unwrap().payload().to_vec())
+    }
+
+    #[test]
+    fn test_user_action_served_on_poll() {
+        let mut module = normal_module();
+        module.inner.config.set_mode_uninitialized();
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(&device, None, HardwareAddress::CAN(VlcbCanId::default()));
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        module.inner.ui.action = Some(UserAction::ChangeMode);
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.inner.ui.action, None, "the request is taken");
+        assert!(module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::InSetup));
+
+        // a second change cancels the setup
+        module.inner.ui.action = Some(UserAction::ChangeMode);
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert!(!module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Uninitialized));
+    }
+
+    #[test]
+    fn test_user_action_change_mode() {
+        let clock = TestClock::new();
+        let mut module = normal_module();
+        let mut device = Loopback::<8>::new();
+        let mut iface = normal_iface(&device);
+
+        module.process_user_action(clock.now(), UserAction::ChangeMode, &mut iface, &mut device);
+        assert_eq!(sent_payload(&mut device), [OpCode::NodeNumberReleased.into(), 0x01, 0x02]);
+        assert_eq!(module.inner.config.mode(), ModuleMode::Uninitialized);
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Uninitialized));
+        assert_eq!(iface.addr(), None);
+        assert!(module.inner.config.has_event(&EventId::short(1)), "the events are kept");
+
+        module.process_user_action(clock.now(), UserAction::ChangeMode, &mut iface, &mut device);
+        assert_eq!(sent_payload(&mut device), [OpCode::RequestNewNodeNumber.into(), 0x00, 0x00]);
+        assert!(module.is_in_setup());
+    }
+
+    #[test]
+    fn test_user_action_renegotiate() {
+        let clock = TestClock::new();
+        let mut module = normal_module();
+        let mut device = Loopback::<8>::new();
+        let mut iface = normal_iface(&device);
+
+        module.process_user_action(clock.now(), UserAction::Renegotiate, &mut iface, &mut device);
+        assert_eq!(sent_payload(&mut device), [OpCode::RequestNewNodeNumber.into(), 0x01, 0x02]);
+        assert!(module.is_in_setup());
+        assert_eq!(module.inner.config.mode(), ModuleMode::Normal);
+
+        // an uninitialized node has nothing to renegotiate
+        let mut module = normal_module();
+        module.inner.config.set_mode_uninitialized();
+        module.process_user_action(clock.now(), UserAction::Renegotiate, &mut iface, &mut device);
+        assert_eq!(device.len(), 0);
+        assert!(!module.is_in_setup());
+    }
+
+    #[test]
+    fn test_user_action_start_can_enumeration() {
+        let clock = TestClock::new();
+        let mut module = normal_module();
+        let mut device = Loopback::<8>::new();
+        let mut iface = normal_iface(&device);
+        let mut sockets = SocketSet::new(&mut [][..]);
+
+        module.process_user_action(
+            clock.now(),
+            UserAction::StartCanEnumeration,
+            &mut iface,
+            &mut device,
+        );
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(sent_payload(&mut device).is_empty(), "the enumeration request has no payload");
+
+        // the enumeration assigns the lowest vacant CAN ID
+        clock.advance(vlcb_network::config::CAN_RESERVE_DELAY_MS);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        module.process_interface_events(&mut iface);
+        assert_eq!(module.diagnostics().can_enumerations(), 1);
+        assert_eq!(module.inner.config.can_id(), &VlcbCanId::from_bytes(&[1]));
+    }
+
+    #[test]
+    fn test_user_action_reset() {
+        let clock = TestClock::new();
+        let mut module = normal_module();
+        let mut device = Loopback::<8>::new();
+        let mut iface = normal_iface(&device);
+        let config = &mut module.inner.config;
+        config.apply_nv_defaults(&[7; NODE_VAR_COUNT]).unwrap();
+        config.set_nv(1, 0x42).unwrap();
+        config.clear_reset_flag();
+        config.set_heartbeat(true);
+
+        module.process_user_action(clock.now(), UserAction::Reset, &mut iface, &mut device);
+        let config = &module.inner.config;
+        assert_eq!(config.mode(), ModuleMode::Uninitialized);
+        assert_eq!(config.stored_event_count(), 0);
+        assert_eq!(config.get_nv(1), Ok(7), "the NVs are back at their defaults");
+        assert_eq!(config.can_id(), &VlcbCanId::default());
+        assert!(!config.is_heartbeat_on());
+        assert!(config.was_reset());
+        assert!(!config.is_dirty(), "the reset is flushed");
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Uninitialized));
+        assert_eq!(iface.addr(), None);
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(VlcbCanId::default()));
+        assert_eq!(device.len(), 0);
+    }
+
+    #[test]
+    fn test_link_down_indicated() {
+        let mut module = normal_module();
+        module.inner.config.set_heartbeat(true);
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let iface_config = InterfaceConfig {
+            link_supervision: LinkSupervisionConfig {
+                silent_timeout_ms: Some(2000),
+                ..LinkSupervisionConfig::default()
+            },
+            ..InterfaceConfig::default()
+        };
+        let mut iface = Interface::with_config(&device, None, HardwareAddress::default(), iface_config);
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert!(module.poll_heartbeat(clock.now()).is_none(), "the first interval starts");
+        assert_eq!(module.inner.ui.link_up, None);
+
+        // the bus went silent
+        clock.advance(2000);
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.inner.ui.link_up, Some(false));
+        clock.advance(config::HEARTBEAT_INTERVAL_MS as u64);
+        assert!(module.poll_heartbeat(clock.now()).is_none(), "the heartbeat pauses");
+
+        let mut buffer = [0u8; 3];
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[9]));
+        frame.payload_mut()[0] = OpCode::QueryNodeInfo.into();
+        device.inject(&buffer).unwrap();
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.inner.ui.link_up, Some(true));
+
+        assert!(module.poll_heartbeat(clock.now()).is_none(), "the interval starts over");
+        clock.advance(config::HEARTBEAT_INTERVAL_MS as u64);
+        assert!(module.poll_heartbeat(clock.now()).is_some());
+    }
+
+    #[test]
+    fn test_headless_module() {
+        let mut module: Module<NullUi, TestClock, TestConfig> = Module::builder()
+            .name("GATEWAY")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(NullUi)
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap()
+            .init();
+
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(&device, None, HardwareAddress::CAN(VlcbCanId::default()));
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(module.config().mode(), ModuleMode::Uninitialized);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_module_version_serde_round_trip() {
+        let version = ModuleVersion::new(2, 'c', 7);
+        let json = serde_json::to_string(&version).unwrap();
+        assert_eq!(json, r#"{"major":2,"minor":"c","beta":7}"#);
+        assert_eq!(serde_json::from_str::<ModuleVersion>(&json).unwrap(), version);
+
+        let bytes = postcard::to_allocvec(&version).unwrap();
+        assert_eq!(postcard::from_bytes::<ModuleVersion>(&bytes).unwrap(), version);
     }
 }
@@ -1,22 +1,29 @@
 use core::fmt;
 use managed::ManagedSlice;
+use vlcb_defs::ServiceType;
 use vlcb_svc_all::{AnyService, Service};
 
 /// Opaque struct with space for one service.
 ///
 /// This is public, to allow using it for allocating space for storing
-/// sockets when creating a Module.
+/// services when creating a Module. A fixed-size backing slice should be
+/// filled with [`ServiceStorage::EMPTY`], with one slot per service:
+///
+/// ```ignore
+/// let mut storage = [ServiceStorage::EMPTY; 2];
+/// let mut services = ServiceSet::new(&mut storage[..]);
+/// ```
 #[derive(Default)]
-pub struct ServiceStorage {
-    inner: Option<Item>,
+pub struct ServiceStorage<'a> {
+    inner: Option<Item<'a>>,
 }
 
-impl<'a> ServiceStorage {
+impl<'a> ServiceStorage<'a> {
     pub const EMPTY: Self = Self { inner: None };
 }
 
-pub(crate) struct Item {
-    service: Service
+pub(crate) struct Item<'a> {
+    service: Service<'a>
 }
 
 /// An extensible set of services.
@@ -25,14 +32,14 @@ pub(crate) struct Item {
 /// owned buffers for your sockets (passed in as `Vec`s) you can use
 /// `ServiceSet<'static>`.
 pub struct ServiceSet<'a> {
-    services: ManagedSlice<'a, ServiceStorage>,
+    services: ManagedSlice<'a, ServiceStorage<'a>>,
 }
 
 impl<'a> ServiceSet<'a> {
     /// Create a service set using the provided storage.
     pub fn new<ServicesT>(sockets: ServicesT) -> ServiceSet<'a>
     where
-        ServicesT: Into<ManagedSlice<'a, ServiceStorage>>,
+        ServicesT: Into<ManagedSlice<'a, ServiceStorage<'a>>>,
     {
         let services = sockets.into();
         ServiceSet { services }
@@ -42,8 +49,8 @@ impl<'a> ServiceSet<'a> {
     ///
     /// # Panics
     /// This function panics if the storage is fixed-size (not a `Vec`) and is full.
-    pub fn add<T: AnyService>(&mut self, socket: T) {
-        fn put(slot: &mut ServiceStorage, service: Service) {
+    pub fn add<T: AnyService<'a>>(&mut self, socket: T) {
+        fn put<'a>(slot: &mut ServiceStorage<'a>, service: Service<'a>) {
             *slot = ServiceStorage {
                 inner: Some(Item { service }),
             };
@@ -68,23 +75,86 @@ impl<'a> ServiceSet<'a> {
         }
     }
 
+    /// Get a service of the type `T` from the set.
+    pub fn get<T: AnyService<'a>>(&self) -> Option<&T> {
+        self.items().find_map(|i| T::downcast(&i.service))
+    }
+
+    /// Get a service of the type `T` from the set, as mutable.
+    pub fn get_mut<T: AnyService<'a>>(&mut self) -> Option<&mut T> {
+        self.items_mut().find_map(|i| T::downcast_mut(&mut i.service))
+    }
+
+    /// Find the service with the service ID `id`.
+    pub fn find_by_id(&self, id: ServiceType) -> Option<&Service<'a>> {
+        self.iter().find(|s| s.service_id() == id)
+    }
+
     /// Get an iterator to the inner service items.
-    pub fn iter(&self) -> impl Iterator<Item = &Service> {
+    pub fn iter(&self) -> impl Iterator<Item = &Service<'a>> + Clone {
         self.items().map(|i| &i.service)
     }
 
     /// Get a mutable iterator to the inner service items.
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Service> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Service<'a>> {
         self.items_mut().map(|i| &mut i.service)
     }
 
     /// Iterate every service in this set.
-    pub(crate) fn items(&self) -> impl Iterator<Item = &Item> + '_ {
+    pub(crate) fn items(&self) -> impl Iterator<Item = &Item<'a>> + Clone + '_ {
         self.services.iter().filter_map(|x| x.inner.as_ref())
     }
 
     /// Iterate every service in this set.
-    pub(crate) fn items_mut(&mut self) -> impl Iterator<Item = &mut Item> + '_ {
+    pub(crate) fn items_mut(&mut self) -> impl Iterator<Item = &mut Item<'a>> + '_ {
         self.services.iter_mut().filter_map(|x| x.inner.as_mut())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let mut storage = [ServiceStorage::EMPTY; 1];
+        let mut services = ServiceSet::new(&mut storage[..]);
+        assert!(services.get::<vlcb_svc_mns::Service>().is_none());
+
+        services.add(vlcb_svc_mns::Service::new());
+
+        assert!(services.get::<vlcb_svc_mns::Service>().is_some());
+        assert!(services.get_mut::<vlcb_svc_mns::Service>().is_some());
+
+        let mns = services.find_by_id(ServiceType::MinimumNodeService).unwrap();
+        assert!(matches!(mns, Service::Mns(_)));
+        assert!(services.find_by_id(ServiceType::EventProducer).is_none());
+    }
+
+    #[test]
+    fn test_service_discovery() {
+        let mut storage = [ServiceStorage::EMPTY, ServiceStorage::EMPTY];
+        let mut services = ServiceSet::new(&mut storage[..]);
+        services.add(vlcb_svc_mns::Service::new());
+        services.add(vlcb_svc_nv::Service::new());
+
+        let node_num = vlcb_core::vlcb::VlcbNodeNumber::new(0x01, 0x02);
+        let rqsd = [vlcb_defs::OpCode::ServiceDiscoveryQuery.into(), 0x01, 0x02, 0];
+        let responses: Vec<_> = vlcb_svc_discovery::Service::new()
+            .process(Some(node_num), services.iter().map(Service::record), &rqsd)
+            .unwrap()
+            .map(|p| p.payload.to_vec())
+            .collect();
+
+        let mns = ServiceType::MinimumNodeService.into();
+        let nv = ServiceType::NodeVariable.into();
+        assert_eq!(
+            responses,
+            [
+                vec![vlcb_defs::OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 0, 0, 2],
+                vec![vlcb_defs::OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 1, mns, 1],
+                vec![vlcb_defs::OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 2, nv, 1],
+            ]
+        );
+    }
+}
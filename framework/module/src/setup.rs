@@ -0,0 +1,312 @@
+//! Node number negotiation.
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
+use vlcb_core::time::millis;
+use vlcb_core::vlcb::{VlcbNodeNumber, NODENUM_SIZE};
+use vlcb_defs::ModuleMode;
+use vlcb_network::data::packet::construct::{module_cfg, OutgoingPacket};
+use vlcb_network::iface::{Interface, SendNowError};
+use vlcb_network::phy::Device;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::config::SETUP_TIMEOUT_MS;
+use crate::Module;
+
+/// State of the node number negotiation.
+pub(crate) enum Setup<C: Clock> {
+    Idle,
+    /// RQNN was sent, waiting for the configuration tool to answer with SNN.
+    AwaitingSnn {
+        deadline: Instant<C>,
+        /// Node number to keep when the tool never answers, `None` for an uninitialized node.
+        previous: Option<VlcbNodeNumber>,
+    },
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Start the renegotiation of the node number.
+    ///
+    /// The node re-enters setup and requests a node number with its existing one, keeping
+    /// the learned events and node variables. The node stays in normal mode in the persistent
+    /// storage until the new node number arrives, so the previous node number is kept when
+    /// the configuration tool does not answer within [`SETUP_TIMEOUT_MS`].
+    ///
+    /// Returns the RQNN packet to transmit, or `None` if the node is not in normal mode.
+    pub fn start_renegotiation(&mut self, now: Instant<C>) -> Option<OutgoingPacket> {
+        if self.inner.config.mode() != ModuleMode::Normal {
+            return None;
+        }
+
+        let previous = *self.inner.config.node_number();
+        Some(self.enter_setup(now, Some(previous)))
+    }
+
+    /// Start the setup of an uninitialized node.
+    ///
+    /// The node requests its first node number. It stays uninitialized when the configuration
+    /// tool does not answer within [`SETUP_TIMEOUT_MS`], and its default events are moved
+    /// into the learned events when it does.
+    ///
+    /// Returns the RQNN packet to transmit, or `None` if the node is not uninitialized.
+    pub fn start_setup(&mut self, now: Instant<C>) -> Option<OutgoingPacket> {
+        if self.inner.config.mode() != ModuleMode::Uninitialized {
+            return None;
+        }
+
+        Some(self.enter_setup(now, None))
+    }
+
+    /// Start the setup, or the renegotiation of a node in normal mode, and transmit the RQNN
+    /// right away with [`Interface::send_now`].
+    ///
+    /// Nothing is sent while the node is already in setup.
+    pub fn request_node_number<D: Device>(
+        &mut self,
+        now: Instant<C>,
+        interface: &mut Interface<C>,
+        device: &mut D,
+    ) -> Result<(), SendNowError> {
+        if self.is_in_setup() {
+            return Ok(());
+        }
+
+        let request = match self.inner.config.mode() {
+            ModuleMode::Normal => self.start_renegotiation(now),
+            _ => self.start_setup(now),
+        };
+        match request {
+            Some(request) => interface.send_now(device, &request),
+            None => Ok(()),
+        }
+    }
+
+    fn enter_setup(&mut self, now: Instant<C>, previous: Option<VlcbNodeNumber>) -> OutgoingPacket {
+        let timeout = millis::<C>(Milliseconds(SETUP_TIMEOUT_MS));
+        self.inner.setup = Setup::AwaitingSnn {
+            deadline: now + timeout,
+            previous,
+        };
+        self.inner.ui.indicate_mode(ModuleMode::InSetup);
+
+        module_cfg::command::allocate_node_number(previous)
+    }
+
+    /// Check whether the module waits for a node number from the configuration tool.
+    pub fn is_in_setup(&self) -> bool {
+        matches!(self.inner.setup, Setup::AwaitingSnn { .. })
+    }
+
+    /// Accept the node number set by SNN while in setup.
+    ///
+    /// Returns the NNACK packet to transmit. The node number is applied to the interface
+    /// on the next [`Module::poll`].
+    pub(crate) fn process_set_node_number(&mut self, data: &[u8]) -> Option<OutgoingPacket> {
+        let Setup::AwaitingSnn { .. } = self.inner.setup else {
+            return None;
+        };
+        let node_num = VlcbNodeNumber::try_from_bytes(data.get(..NODENUM_SIZE)?).ok()?;
+
+        let first_setup = self.inner.config.mode() == ModuleMode::Uninitialized;
+        self.inner.config.set_mode_normal(node_num);
+        if first_setup {
+            self.migrate_default_events();
+        }
+        self.inner.config.flush();
+        self.inner.setup = Setup::Idle;
+        self.inner.ui.indicate_mode(ModuleMode::Normal);
+
+        Some(module_cfg::ctrl::ack_node_number(node_num))
+    }
+
+    /// Leave the setup when the configuration tool did not answer in time.
+    pub(crate) fn poll_setup(&mut self, now: Instant<C>) {
+        let Setup::AwaitingSnn { deadline, .. } = self.inner.setup else {
+            return;
+        };
+        if now < deadline {
+            return;
+        }
+        self.leave_setup();
+    }
+
+    /// Leave the setup without a new node number, e.g. when the user cancels it.
+    pub(crate) fn leave_setup(&mut self) {
+        let Setup::AwaitingSnn { previous, .. } = self.inner.setup else {
+            return;
+        };
+
+        // The persistent mode was never changed, only make sure the node number was not either.
+        let mode = match previous {
+            Some(node_num) => {
+                self.inner.config.set_mode_normal(node_num);
+                ModuleMode::Normal
+            }
+            None => ModuleMode::Uninitialized,
+        };
+        self.inner.setup = Setup::Idle;
+        self.inner.ui.indicate_mode(mode);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::EventId;
+    use vlcb_defs::{Manufacturer, OpCode};
+    use vlcb_network::iface::SocketSet;
+    use vlcb_network::phy::loopback::Loopback;
+    use vlcb_network::phy::RxToken as _;
+    use vlcb_network::wire::{CanFrame, HardwareAddress};
+    use vlcb_persistence::node_config::LearnedEvent;
+
+    use super::*;
+    use crate::test_utils::{config, interface, TestConfig, TestUi};
+    use crate::{ModuleVersion, Processor};
+
+    const NODE_NUM: VlcbNodeNumber = VlcbNodeNumber::new(0x01, 0x02);
+    const EVENT: EventId = EventId::long(NODE_NUM, 1);
+
+    fn module() -> Module<'static, TestUi, TestClock, TestConfig> {
+        let mut module = Module::builder()
+            .name("TEST")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .cpu(Processor::Atmel)
+            .ui(TestUi::default())
+            .config(config())
+            .interface(&interface())
+            .build()
+            .unwrap();
+
+        let config = &mut module.inner.config;
+        config.set_mode_normal(NODE_NUM);
+        config.save_event(&EVENT, &[0x0A, 0x0B]).unwrap();
+        config.set_nv(1, 0x42).unwrap();
+        module
+    }
+
+    fn assert_config_kept(module: &Module<'_, TestUi, TestClock, TestConfig>) {
+        let config = &module.inner.config;
+        assert_eq!(config.mode(), ModuleMode::Normal);
+        assert_eq!(config.get_event(&EVENT).map(|e| e.vars()), Some(&[0x0A, 0x0B][..]));
+        assert_eq!(config.get_nv(1), Ok(0x42));
+    }
+
+    #[test]
+    fn test_renegotiation() {
+        let clock = TestClock::new();
+        let mut module = module();
+
+        let request = module.start_renegotiation(clock.now()).unwrap();
+        assert_eq!(
+            &request.payload[..],
+            &[OpCode::RequestNewNodeNumber.into(), 0x01, 0x02]
+        );
+        assert!(module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::InSetup));
+        assert_config_kept(&module);
+
+        let ack = module
+            .process(&[OpCode::SetNodeNumber.into(), 0x03, 0x04])
+            .unwrap();
+        assert_eq!(&ack.payload[..], &[OpCode::NodeNumberAck.into(), 0x03, 0x04]);
+        assert!(!module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Normal));
+        assert_eq!(module.inner.config.node_number(), &VlcbNodeNumber::new(0x03, 0x04));
+        assert_config_kept(&module);
+
+        // SNN is ignored outside of setup
+        assert!(module.process(&[OpCode::SetNodeNumber.into(), 0x05, 0x06]).is_none());
+        assert_eq!(module.inner.config.node_number(), &VlcbNodeNumber::new(0x03, 0x04));
+    }
+
+    #[test]
+    fn test_snn_sets_interface_addr() {
+        let clock = TestClock::new();
+        let mut module = module();
+        module.inner.config.set_mode_uninitialized();
+        let mut device = Loopback::<4>::new();
+        let mut iface = Interface::new(&device, None, HardwareAddress::default());
+        let mut sockets = SocketSet::new(&mut [][..]);
+
+        module.start_setup(clock.now()).unwrap();
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(iface.addr(), None);
+
+        module.process(&[OpCode::SetNodeNumber.into(), 0x03, 0x04]).unwrap();
+        module.poll(clock.now(), &mut iface, &mut device, &mut sockets);
+        assert_eq!(iface.addr(), Some(VlcbNodeNumber::new(0x03, 0x04)));
+    }
+
+    #[test]
+    fn test_request_node_number_sent_now() {
+        let clock = TestClock::new();
+        let mut module = module();
+        let mut device = Loopback::<4>::new();
+        let mut iface = Interface::new(&device, Some(NODE_NUM), HardwareAddress::default());
+
+        module.request_node_number(clock.now(), &mut iface, &mut device).unwrap();
+        assert!(module.is_in_setup());
+        module.request_node_number(clock.now(), &mut iface, &mut device).unwrap();
+
+        assert_eq!(device.len(), 1, "sent once, without a socket");
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let frame = CanFrame::new_checked(&*buffer).unwrap();
+            assert_eq!(frame.payload(), &[OpCode::RequestNewNodeNumber.into(), 0x01, 0x02]);
+        });
+    }
+
+    #[test]
+    fn test_renegotiation_timeout() {
+        let clock = TestClock::new();
+        let mut module = module();
+        module.start_renegotiation(clock.now()).unwrap();
+
+        clock.advance(SETUP_TIMEOUT_MS as u64 - 1);
+        module.poll_setup(clock.now());
+        assert!(module.is_in_setup());
+
+        clock.advance(1);
+        module.poll_setup(clock.now());
+        assert!(!module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Normal));
+        assert_eq!(module.inner.config.node_number(), &NODE_NUM);
+        assert_config_kept(&module);
+
+        // the answer came too late
+        assert!(module.process(&[OpCode::SetNodeNumber.into(), 0x03, 0x04]).is_none());
+        assert_eq!(module.inner.config.node_number(), &NODE_NUM);
+    }
+
+    #[test]
+    fn test_setup_timeout_stays_uninitialized() {
+        let clock = TestClock::new();
+        let mut module = module();
+        module.inner.config.set_mode_uninitialized();
+
+        let request = module.start_setup(clock.now()).unwrap();
+        assert_eq!(&request.payload[..], &[OpCode::RequestNewNodeNumber.into(), 0x00, 0x00]);
+        assert!(module.start_renegotiation(clock.now()).is_none());
+
+        clock.advance(SETUP_TIMEOUT_MS as u64);
+        module.poll_setup(clock.now());
+        assert!(!module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, Some(ModuleMode::Uninitialized));
+        assert_eq!(module.inner.config.mode(), ModuleMode::Uninitialized);
+    }
+
+    #[test]
+    fn test_renegotiation_requires_normal_mode() {
+        let clock = TestClock::new();
+        let mut module = module();
+        module.inner.config.set_mode_uninitialized();
+
+        assert!(module.start_renegotiation(clock.now()).is_none());
+        assert!(!module.is_in_setup());
+        assert_eq!(module.inner.ui.mode, None);
+    }
+}
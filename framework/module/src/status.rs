@@ -0,0 +1,130 @@
+//! Read-only view of the module state for the application.
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::vlcb::VlcbNodeNumber;
+use vlcb_defs::{BusType, ModuleMode, ModuleParam};
+use vlcb_network::iface::InterfaceStats;
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::PersistentStorage;
+use vlcb_ui::VlcbUi;
+
+use crate::Module;
+
+/// The state of a module at a point in time, e.g. for a screen or a serial console of the
+/// application.
+///
+/// Taken with [`Module::status`], `T` is the integer type of the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusSnapshot<T> {
+    #[cfg_attr(feature = "serde", serde(with = "mode_serde"))]
+    pub mode: ModuleMode,
+    /// The node number in normal mode, `None` otherwise.
+    pub node_number: Option<VlcbNodeNumber>,
+    /// The CAN ID, `None` on other media.
+    pub can_id: Option<VlcbCanId>,
+    pub stored_event_count: u8,
+    /// The node config has changes not flushed to the persistent storage yet.
+    pub storage_dirty: bool,
+    pub stats: InterfaceStats,
+    /// Time since the first poll in milliseconds, `None` before it.
+    pub uptime_ms: Option<T>,
+}
+
+/// Serialize the mode as its octet, the modes are not serializable themselves.
+#[cfg(feature = "serde")]
+mod mode_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use vlcb_defs::ModuleMode;
+
+    pub fn serialize<S: Serializer>(mode: &ModuleMode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*mode).into())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ModuleMode, D::Error> {
+        u8::deserialize(deserializer).map(ModuleMode::from)
+    }
+}
+
+impl<'a, UI: VlcbUi<C>, C: Clock, S: NodeConfig + PersistentStorage> Module<'a, UI, C, S> {
+    /// Return the mode of the node, [`ModuleMode::InSetup`] while it waits for a node number.
+    pub fn mode(&self) -> ModuleMode {
+        match self.is_in_setup() {
+            true => ModuleMode::InSetup,
+            false => self.inner.config.mode(),
+        }
+    }
+
+    /// Return the node number in normal mode.
+    ///
+    /// A node renegotiating its node number keeps the previous one until the new one arrives.
+    pub fn node_number(&self) -> Option<VlcbNodeNumber> {
+        (self.inner.config.mode() == ModuleMode::Normal).then(|| *self.inner.config.node_number())
+    }
+
+    /// Return the CAN ID of the node, `None` when the medium is not CAN.
+    pub fn can_id(&self) -> Option<VlcbCanId> {
+        let can = self.param(ModuleParam::BusType) == BusType::CAN as u8;
+        can.then(|| *self.inner.config.can_id())
+    }
+
+    /// Return how many events the node learned.
+    pub fn stored_event_count(&self) -> u8 {
+        self.inner.config.stored_event_count()
+    }
+
+    /// Check whether the node config has changes not flushed to the persistent storage yet.
+    pub fn storage_dirty(&self) -> bool {
+        self.inner.config.is_dirty()
+    }
+
+    /// Return the counters of the interface passed to [`Module::poll`], as of the last poll.
+    pub fn stats(&self) -> &InterfaceStats {
+        &self.inner.stats
+    }
+
+    /// Return the time since the first poll, `None` before it.
+    pub fn uptime(&self, now: Instant<C>) -> Option<Milliseconds<C::T>> {
+        now.checked_duration_since(&self.inner.started?)?.try_into().ok()
+    }
+
+    /// Take a snapshot of the module state at `now`.
+    pub fn status(&self, now: Instant<C>) -> StatusSnapshot<C::T> {
+        StatusSnapshot {
+            mode: self.mode(),
+            node_number: self.node_number(),
+            can_id: self.can_id(),
+            stored_event_count: self.stored_event_count(),
+            storage_dirty: self.storage_dirty(),
+            stats: *self.stats(),
+            uptime_ms: self.uptime(now).map(|uptime| uptime.0),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let status = StatusSnapshot {
+            mode: ModuleMode::Normal,
+            node_number: Some(VlcbNodeNumber::new(0x01, 0x2C)),
+            can_id: Some(VlcbCanId::from_bytes(&[0x2A])),
+            stored_event_count: 3,
+            storage_dirty: true,
+            stats: InterfaceStats::default(),
+            uptime_ms: Some(1500u32),
+        };
+
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(serde_json::from_str::<StatusSnapshot<u32>>(&json).unwrap(), status);
+
+        let bytes = postcard::to_allocvec(&status).unwrap();
+        assert_eq!(postcard::from_bytes::<StatusSnapshot<u32>>(&bytes).unwrap(), status);
+    }
+}
@@ -0,0 +1,108 @@
+//! Fixtures shared by the module tests.
+
+use core::cell::RefCell;
+
+use embedded_storage::{ReadStorage, Storage};
+use embedded_time::Clock;
+use rclite::Rc;
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::time::TestClock;
+use vlcb_defs::ModuleMode;
+use vlcb_network::iface::Interface;
+use vlcb_network::phy::loopback::Loopback;
+use vlcb_network::wire::HardwareAddress;
+use vlcb_persistence::node_config_storage;
+use vlcb_ui::{UserAction, VlcbUi};
+
+/// A user interface without any hardware, counting the requested indications.
+#[derive(Default)]
+pub(crate) struct TestUi {
+    pub(crate) activity: usize,
+    pub(crate) errors: usize,
+    pub(crate) mode: Option<ModuleMode>,
+    /// The last indicated state of the link, `None` until it is indicated.
+    pub(crate) link_up: Option<bool>,
+    /// Returned by the next [`VlcbUi::take_requested_action`].
+    pub(crate) action: Option<UserAction>,
+}
+
+impl<C: Clock> VlcbUi<C> for TestUi {
+    fn poll(&mut self, _now: embedded_time::Instant<C>) {}
+
+    fn is_main_sw_pressed(&self) -> bool {
+        false
+    }
+
+    fn indicate_activity(&mut self) {
+        self.activity += 1;
+    }
+
+    fn indicate_error(&mut self) {
+        self.errors += 1;
+    }
+
+    fn indicate_mode(&mut self, mode: ModuleMode) {
+        self.mode = Some(mode);
+    }
+
+    fn indicate_link(&mut self, up: bool) {
+        self.link_up = Some(up);
+    }
+
+    fn take_requested_action(&mut self) -> Option<UserAction> {
+        self.action.take()
+    }
+}
+
+/// A RAM backed storage driver.
+pub(crate) struct RamStorage([u8; 256]);
+
+impl RamStorage {
+    pub(crate) fn new() -> Self {
+        Self([0xff; 256])
+    }
+}
+
+impl ReadStorage for RamStorage {
+    type Error = ();
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get(offset..offset + bytes.len()).ok_or(())?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Storage for RamStorage {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get_mut(offset..offset + bytes.len()).ok_or(())?;
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+pub(crate) const MAX_EVENTS: usize = 8;
+pub(crate) const EVENT_VAR_COUNT: usize = 2;
+pub(crate) const NODE_VAR_COUNT: usize = 4;
+
+pub(crate) type TestConfig =
+    node_config_storage!(RamStorage, 0, MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT);
+
+pub(crate) fn config() -> TestConfig {
+    TestConfig::new(Rc::new(RefCell::new(RamStorage::new())))
+}
+
+pub(crate) fn interface() -> Interface<TestClock> {
+    let device = Loopback::<4>::new();
+    Interface::new(
+        &device,
+        None,
+        HardwareAddress::CAN(VlcbCanId::default()),
+    )
+}
@@ -0,0 +1,231 @@
+//! Conformance scenarios of a module talking to a configuration tool.
+
+mod harness;
+
+use harness::{Bench, EVENT_VAR_COUNT, MAX_EVENTS, MODULE_CAN_ID, NODE_VAR_COUNT};
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::vlcb::VlcbNodeNumber;
+use vlcb_defs::{CommandError, Manufacturer, MergModuleType, ModuleMode, ModuleParam, OpCode};
+use vlcb_module::config::HEARTBEAT_INTERVAL_MS;
+use vlcb_persistence::node_config::NodeConfig;
+
+const NODE_NUM: VlcbNodeNumber = VlcbNodeNumber::new(0x01, 0x02);
+const NN: [u8; 2] = [0x01, 0x02];
+
+#[test]
+fn test_setup() {
+    let mut bench = Bench::new();
+    assert_eq!(bench.module.config().mode(), ModuleMode::Uninitialized);
+    bench.expect_silence();
+
+    // SNN is ignored unless the node asked for a node number
+    bench.inject(OpCode::SetNodeNumber, &NN);
+    bench.expect_silence();
+    assert_eq!(bench.node_number(), None);
+
+    bench.setup(NODE_NUM);
+    assert_eq!(bench.node_number(), Some(NODE_NUM));
+    assert_eq!(bench.module.ui().mode, Some(ModuleMode::Normal));
+    bench.expect_silence();
+}
+
+#[test]
+fn test_status_snapshot() {
+    let mut bench = Bench::new();
+    let status = bench.module.status(bench.clock.now());
+    assert_eq!(status.mode, ModuleMode::Uninitialized);
+    assert_eq!(status.uptime_ms, None, "not polled yet");
+
+    bench.enter_setup();
+    assert_eq!(bench.module.mode(), ModuleMode::InSetup);
+    bench.inject(OpCode::SetNodeNumber, &NN);
+    bench.expect(OpCode::NodeNumberAck, |data| data == NN);
+    bench.advance(1_000);
+
+    let status = bench.module.status(bench.clock.now());
+    assert_eq!(status.mode, ModuleMode::Normal);
+    assert_eq!(status.node_number, Some(NODE_NUM));
+    assert_eq!(status.can_id, Some(VlcbCanId::from_bytes(&[MODULE_CAN_ID])));
+    assert_eq!(status.stored_event_count, 0);
+    assert!(!status.storage_dirty, "the node number is flushed right away");
+    assert!(status.stats.rx_ok() > 0);
+    assert_eq!(status.uptime_ms, Some(1_000));
+}
+
+#[test]
+fn test_query_node_info() {
+    let mut bench = Bench::new();
+    bench.inject(OpCode::QueryNodeInfo, &[]);
+    bench.expect_silence();
+
+    bench.setup(NODE_NUM);
+    bench.inject(OpCode::QueryNodeInfo, &[]);
+    // consumer and VLCB flags of the bench module, with FLiM set in normal mode
+    let flags = 0b0100_0101;
+    bench.expect(OpCode::NodeInfo, |data| {
+        data == [
+            0x01,
+            0x02,
+            Manufacturer::Development as u8,
+            MergModuleType::VLCB as u8,
+            flags,
+        ]
+    });
+}
+
+#[test]
+fn test_parameter_readback() {
+    let mut bench = Bench::new();
+    // RQNP is only answered in setup
+    bench.inject(OpCode::QueryNodeParameters, &[]);
+    bench.expect_silence();
+
+    bench.enter_setup();
+    bench.inject(OpCode::QueryNodeParameters, &[]);
+    let params = bench.expect(OpCode::NodeParametersReport, |data| data.len() == 7);
+    assert_eq!(params, &bench.module.params_as_bytes()[..7]);
+    assert_eq!(params[3], MAX_EVENTS as u8);
+    assert_eq!(params[5], NODE_VAR_COUNT as u8);
+}
+
+#[test]
+fn test_parameter_by_index() {
+    let mut bench = Bench::with_node_number(NODE_NUM);
+
+    bench.inject(OpCode::QueryNodeParameterByIndex, &[0x01, 0x02, 0]);
+    bench.expect(OpCode::NodeParameterValue, |data| data == [0x01, 0x02, 0, 20]);
+
+    let index = ModuleParam::ModuleManufacturer as u8;
+    bench.inject(OpCode::QueryNodeParameterByIndex, &[0x01, 0x02, index]);
+    bench.expect(OpCode::NodeParameterValue, |data| {
+        data == [0x01, 0x02, index, Manufacturer::Development as u8]
+    });
+
+    bench.inject(OpCode::QueryNodeParameterByIndex, &[0x01, 0x02, 21]);
+    bench.expect(OpCode::NodeConfigurationError, |data| {
+        data == [0x01, 0x02, CommandError::InvalidParamIndex.into()]
+    });
+
+    // addressed to another node
+    bench.inject(OpCode::QueryNodeParameterByIndex, &[0x09, 0x09, 1]);
+    bench.expect_silence();
+}
+
+#[test]
+fn test_node_variables() {
+    let mut bench = Bench::with_node_number(NODE_NUM);
+
+    bench.inject(OpCode::LegacySetNodeVariable, &[0x01, 0x02, 3, 0x42]);
+    bench.expect(OpCode::WriteAck, |data| data == NN);
+    bench.expect(OpCode::GenericResponse, |data| data == [0x01, 0x02, 0x96, 0x02, 0x00]);
+    assert_eq!(bench.module.config().get_nv(3), Ok(0x42));
+
+    bench.inject(OpCode::QueryNodeVariable, &[0x01, 0x02, 3]);
+    bench.expect(OpCode::NodeVariableValue, |data| data == [0x01, 0x02, 3, 0x42]);
+
+    bench.inject(OpCode::SetNodeVariable, &[0x01, 0x02, 1, 0x07]);
+    bench.expect(OpCode::NodeVariableValue, |data| data == [0x01, 0x02, 1, 0x07]);
+
+    let invalid = NODE_VAR_COUNT as u8 + 1;
+    bench.inject(OpCode::QueryNodeVariable, &[0x01, 0x02, invalid]);
+    bench.expect(OpCode::NodeConfigurationError, |data| data[..2] == NN);
+    bench.expect(OpCode::GenericResponse, |data| data[..2] == NN);
+    bench.expect_silence();
+}
+
+#[test]
+fn test_event_teaching() {
+    let mut bench = Bench::with_node_number(NODE_NUM);
+
+    bench.inject(OpCode::QueryLearnedEventCount, &NN);
+    bench.expect(OpCode::LearnedEventCount, |data| data == [0x01, 0x02, 0]);
+    bench.inject(OpCode::QueryAvailableEventSlots, &NN);
+    bench.expect(OpCode::AvailableEventSlots, |data| {
+        data == [0x01, 0x02, MAX_EVENTS as u8]
+    });
+
+    // NNCLR is refused outside of learn mode
+    bench.inject(OpCode::ForgetAllLearnedEvents, &NN);
+    bench.expect(OpCode::NodeConfigurationError, |data| {
+        data == [0x01, 0x02, CommandError::NotInLearnMode.into()]
+    });
+    bench.expect(OpCode::GenericResponse, |data| data == [0x01, 0x02, 0x55, 0x07, 0x02]);
+
+    bench.inject(OpCode::PutNodeIntoLearnMode, &NN);
+    bench.expect_silence();
+    bench.inject(OpCode::ForgetAllLearnedEvents, &NN);
+    bench.expect(OpCode::WriteAck, |data| data == NN);
+    bench.expect(OpCode::GenericResponse, |data| data == [0x01, 0x02, 0x55, 0x07, 0x00]);
+
+    // each EV taught with EVLRN is acknowledged with WRACK and GRSP, the event is learned with the first one
+    const EVENT: [u8; 4] = [0x03, 0x04, 0x00, 0x05];
+    for (ev_index, value) in [(1, 0x11), (2, 0x22)] {
+        bench.inject(OpCode::TeachEvent, &[&EVENT[..], &[ev_index, value]].concat());
+        bench.expect(OpCode::WriteAck, |data| data == NN);
+        bench.expect(OpCode::GenericResponse, |data| data == [0x01, 0x02, 0xD2, 0x07, 0x00]);
+    }
+    bench.inject(OpCode::ReleaseNodeFromLearnMode, &NN);
+    bench.expect_silence();
+
+    // the learned event and its EVs are read back outside of learn mode
+    bench.inject(OpCode::QueryLearnedEventCount, &NN);
+    bench.expect(OpCode::LearnedEventCount, |data| data == [0x01, 0x02, 1]);
+    bench.inject(OpCode::QueryAllLearnedEvents, &NN);
+    let enrsp = bench.expect(OpCode::LearnedEventResponse, |data| {
+        data.len() == 7 && data[..2] == NN && data[2..6] == EVENT
+    });
+    bench.expect_silence();
+
+    let index = enrsp[6];
+    bench.inject(OpCode::QueryEventVariable, &[0x01, 0x02, index, 2]);
+    bench.expect(OpCode::EventVariableValue, |data| data == [0x01, 0x02, index, 2, 0x22]);
+    bench.inject(OpCode::QueryEventVariable, &[0x01, 0x02, index, 0]);
+    bench.expect(OpCode::EventVariableValue, |data| {
+        data == [0x01, 0x02, index, 0, EVENT_VAR_COUNT as u8]
+    });
+    bench.expect(OpCode::EventVariableValue, |data| data == [0x01, 0x02, index, 1, 0x11]);
+    bench.expect(OpCode::EventVariableValue, |data| data == [0x01, 0x02, index, 2, 0x22]);
+    bench.expect_silence();
+
+    bench.inject(OpCode::ForgetAllLearnedEvents, &NN);
+    bench.expect(OpCode::NodeConfigurationError, |_| true);
+    bench.expect(OpCode::GenericResponse, |_| true);
+}
+
+#[test]
+fn test_heartbeat() {
+    let mut bench = Bench::with_node_number(NODE_NUM);
+    bench.module.config_mut().set_heartbeat(true);
+
+    // the heartbeat starts running on the next poll
+    bench.advance(0);
+    bench.advance(HEARTBEAT_INTERVAL_MS as u64 - 1);
+    bench.expect_silence();
+
+    bench.advance(1);
+    bench.expect(OpCode::Heartbeat, |data| data == [0x01, 0x02, 0, 0, 0]);
+    bench.expect_silence();
+
+    bench.advance(HEARTBEAT_INTERVAL_MS as u64);
+    bench.expect(OpCode::Heartbeat, |data| data == [0x01, 0x02, 1, 0, 0]);
+}
+
+#[test]
+fn test_node_data_request() {
+    const DATA: [u8; 5] = [0xD1, 0xD2, 0xD3, 0xD4, 0xD5];
+    let mut bench = Bench::with_node_number(NODE_NUM);
+
+    // nothing to answer with before the node produced its data
+    bench.inject(OpCode::QueryNodeData, &NN);
+    bench.expect_silence();
+
+    let acdat = bench.module.send_node_data(&DATA).unwrap();
+    assert_eq!(acdat.opcode(), Some(OpCode::DataEventAccessory));
+    assert_eq!(&acdat.payload[1..3], &NN);
+    assert_eq!(&acdat.payload[3..], &DATA);
+
+    bench.inject(OpCode::QueryNodeData, &[0x01, 0x03]);
+    bench.expect_silence();
+    bench.inject(OpCode::QueryNodeData, &NN);
+    bench.expect(OpCode::NodeDataEventResponse, |data| data[..2] == NN && data[2..] == DATA);
+}
@@ -0,0 +1,317 @@
+//! Scripted conformance bench for a [`Module`].
+//!
+//! The bench plays the configuration tool on the other side of a CAN bus. It injects
+//! packets into a fully built module, runs the poll loop an application would run and
+//! checks the packets the module transmits. The time only moves when the script
+//! [advances](Bench::advance) it, so every scenario behaves the same on every run.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use embedded_storage::{ReadStorage, Storage};
+use embedded_time::Clock;
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::time::TestClock;
+use vlcb_core::vlcb::VlcbNodeNumber;
+use vlcb_defs::{Manufacturer, ModuleMode, OpCode};
+use vlcb_module::service_set::{ServiceSet, ServiceStorage};
+use vlcb_module::{Module, ModuleVersion, ParamFlags, Processor};
+use vlcb_network::iface::{Interface, SocketSet, SocketStorage};
+use vlcb_network::phy::{self, Device, DeviceCapabilities};
+use vlcb_network::socket::module::{Filter, PacketBuffer, PacketMetadata, Socket};
+use vlcb_network::wire::{CanFrame, HardwareAddress};
+use vlcb_persistence::node_config::NodeConfig;
+use vlcb_persistence::node_config_storage;
+use vlcb_ui::{UserAction, VlcbUi};
+
+/// CAN ID of the module under test.
+pub const MODULE_CAN_ID: u8 = 1;
+/// CAN ID the bench sends with, from the range used by configuration tools.
+pub const TOOL_CAN_ID: u8 = 0x7D;
+/// Polls [`Bench::expect`] waits for a packet before failing.
+pub const EXPECT_POLLS: usize = 10;
+
+pub const MAX_EVENTS: usize = 8;
+pub const EVENT_VAR_COUNT: usize = 2;
+pub const NODE_VAR_COUNT: usize = 4;
+
+pub type BenchConfig =
+    node_config_storage!(RamStorage, 0, MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT);
+pub type BenchModule = Module<'static, BenchUi, TestClock, BenchConfig>;
+
+/// A RAM backed storage driver, starting erased.
+pub struct RamStorage([u8; 256]);
+
+impl ReadStorage for RamStorage {
+    type Error = ();
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get(offset..offset + bytes.len()).ok_or(())?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Storage for RamStorage {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get_mut(offset..offset + bytes.len()).ok_or(())?;
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// A user interface without any hardware, remembering the indicated mode.
+#[derive(Default)]
+pub struct BenchUi {
+    pub mode: Option<ModuleMode>,
+}
+
+impl<C: Clock> VlcbUi<C> for BenchUi {
+    fn poll(&mut self, _now: embedded_time::Instant<C>) {}
+
+    fn is_main_sw_pressed(&self) -> bool {
+        false
+    }
+
+    fn indicate_activity(&mut self) {}
+
+    fn indicate_error(&mut self) {}
+
+    fn indicate_mode(&mut self, mode: ModuleMode) {
+        self.mode = Some(mode);
+    }
+
+    fn indicate_link(&mut self, _up: bool) {}
+
+    fn take_requested_action(&mut self) -> Option<UserAction> {
+        None
+    }
+}
+
+/// The bus as seen by the module.
+///
+/// Unlike the loopback device, the frames transmitted by the module are captured for the
+/// bench instead of being received back.
+#[derive(Default)]
+pub struct BenchDevice {
+    rx: VecDeque<Vec<u8>>,
+    tx: Rc<RefCell<VecDeque<Vec<u8>>>>,
+}
+
+impl Device for BenchDevice {
+    type RxToken<'a> = BenchRxToken;
+    type TxToken<'a> = BenchTxToken;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.rx.pop_front()?;
+        let tx = BenchTxToken {
+            tx: self.tx.clone(),
+        };
+        Some((BenchRxToken { frame }, tx))
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        Some(BenchTxToken {
+            tx: self.tx.clone(),
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::default()
+    }
+}
+
+pub struct BenchRxToken {
+    frame: Vec<u8>,
+}
+
+impl phy::RxToken for BenchRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.frame)
+    }
+}
+
+#[derive(Clone)]
+pub struct BenchTxToken {
+    tx: Rc<RefCell<VecDeque<Vec<u8>>>>,
+}
+
+impl phy::TxToken for BenchTxToken {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        self.tx.borrow_mut().push_back(frame);
+        result
+    }
+}
+
+/// A socket buffer living as long as the bench, the sockets borrow their storage.
+fn packet_buffer() -> PacketBuffer<'static> {
+    PacketBuffer::new(
+        Box::leak(Box::new([PacketMetadata::EMPTY; 8])) as &mut [_],
+        Box::leak(Box::new([0u8; 64])) as &mut [_],
+    )
+}
+
+/// A module wired up the way an application does it, driven by a script.
+pub struct Bench {
+    pub clock: TestClock,
+    pub module: BenchModule,
+    iface: Interface<TestClock>,
+    device: BenchDevice,
+    sockets: SocketSet<'static>,
+}
+
+impl Bench {
+    /// Create a bench with an uninitialized module.
+    ///
+    /// The module dispatches the packets of its socket to the minimum node, node variable
+    /// and event teaching services.
+    pub fn new() -> Self {
+        let device = BenchDevice::default();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[MODULE_CAN_ID]));
+        let config = BenchConfig::new(rclite::Rc::new(RefCell::new(RamStorage([0xFF; 256]))));
+        let iface = Interface::new(&device, None, hw_addr);
+
+        let mut socket = Socket::new(packet_buffer(), packet_buffer());
+        socket.bind(Filter::All).unwrap();
+        let storage = Box::leak(Box::new(<[SocketStorage; 1]>::default()));
+        let mut sockets = SocketSet::new(&mut storage[..]);
+        let socket = sockets.add(socket);
+
+        let storage = Box::leak(Box::new([ServiceStorage::EMPTY; 3]));
+        let mut services = ServiceSet::new(&mut storage[..]);
+        services.add(vlcb_svc_mns::Service::new());
+        services.add(vlcb_svc_nv::Service::new());
+        services.add(vlcb_svc_event_teaching::Service::new());
+
+        let mut module = Module::builder()
+            .name("BENCH")
+            .version(ModuleVersion::new(1, 'a', 0))
+            .manufacturer(Manufacturer::Development)
+            .flags(ParamFlags::Consumer | ParamFlags::Vlcb)
+            .cpu(Processor::Atmel)
+            .ui(BenchUi::default())
+            .config(config)
+            .interface(&iface)
+            .services(services)
+            .socket(socket)
+            .build()
+            .unwrap()
+            .init();
+        module.config_mut().set_can_id(VlcbCanId::from_bytes(&[MODULE_CAN_ID]));
+
+        Bench {
+            clock: TestClock::new(),
+            module,
+            iface,
+            device,
+            sockets,
+        }
+    }
+
+    /// Create a bench with a module set up with the `node_num`.
+    pub fn with_node_number(node_num: VlcbNodeNumber) -> Self {
+        let mut bench = Self::new();
+        bench.setup(node_num);
+        bench
+    }
+
+    /// Put the module into setup, as when its button is pressed, and expect its RQNN.
+    pub fn enter_setup(&mut self) {
+        let now = self.clock.now();
+        self.module
+            .request_node_number(now, &mut self.iface, &mut self.device)
+            .unwrap();
+        self.expect(OpCode::RequestNewNodeNumber, |_| true);
+    }
+
+    /// Take the module through the setup, answering its RQNN with SNN.
+    pub fn setup(&mut self, node_num: VlcbNodeNumber) {
+        self.enter_setup();
+        self.inject(OpCode::SetNodeNumber, node_num.as_bytes());
+        self.expect(OpCode::NodeNumberAck, |data| data == node_num.as_bytes());
+    }
+
+    /// Queue a packet from the configuration tool, received by the module on the next poll.
+    pub fn inject(&mut self, opcode: OpCode, payload: &[u8]) {
+        let mut frame = vec![0; CanFrame::<&[u8]>::buffer_len(1 + payload.len())];
+        let mut can = CanFrame::new_unchecked(&mut frame[..]);
+        can.set_src_addr(VlcbCanId::from_bytes(&[TOOL_CAN_ID]));
+        let packet = can.payload_mut();
+        packet[0] = opcode.into();
+        packet[1..].copy_from_slice(payload);
+        self.device.rx.push_back(frame);
+    }
+
+    /// Move the time by `ms` milliseconds and poll once.
+    pub fn advance(&mut self, ms: u64) {
+        self.clock.advance(ms);
+        self.poll();
+    }
+
+    /// Poll until the module transmits a packet, at most [`EXPECT_POLLS`] times.
+    ///
+    /// Panics unless the packet has the `opcode` and its data octets satisfy the `matcher`.
+    /// Returns the data octets.
+    pub fn expect(&mut self, opcode: OpCode, matcher: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+        for _ in 0..EXPECT_POLLS {
+            if let Some(packet) = self.take_transmitted() {
+                let (&received, data) = packet.split_first().unwrap();
+                assert_eq!(
+                    OpCode::try_from(received).ok(),
+                    Some(opcode),
+                    "expected {:?}, received {:02X?}",
+                    opcode,
+                    packet
+                );
+                assert!(matcher(data), "unexpected {:?} data {:02X?}", opcode, data);
+                return data.to_vec();
+            }
+            self.poll();
+        }
+        panic!("expected {:?}, nothing transmitted in {} polls", opcode, EXPECT_POLLS);
+    }
+
+    /// Poll [`EXPECT_POLLS`] times and panic if the module transmits anything.
+    pub fn expect_silence(&mut self) {
+        for _ in 0..EXPECT_POLLS {
+            self.poll();
+            if let Some(packet) = self.take_transmitted() {
+                panic!("expected silence, received {:02X?}", packet);
+            }
+        }
+    }
+
+    /// Return the node number while the module is in normal mode.
+    pub fn node_number(&self) -> Option<VlcbNodeNumber> {
+        self.module.node_number()
+    }
+
+    /// Run one iteration of the application loop.
+    fn poll(&mut self) {
+        let now = self.clock.now();
+        self.module
+            .poll(now, &mut self.iface, &mut self.device, &mut self.sockets);
+    }
+
+    /// Pop the oldest transmitted packet, the opcode followed by its data octets.
+    fn take_transmitted(&mut self) -> Option<Vec<u8>> {
+        let frame = self.device.tx.borrow_mut().pop_front()?;
+        let can = CanFrame::new_checked(&frame[..]).unwrap();
+        Some(can.payload().to_vec())
+    }
+}
@@ -0,0 +1,82 @@
+//! A bus monitor printing every frame the interface receives or transmits.
+//!
+//! The frames come from a loopback device standing in for a CAN peripheral,
+//! a host application wraps its own device into the tracer the same way.
+
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::time::TestClock;
+use vlcb_core::vlcb::VlcbNodeNumber;
+use vlcb_defs::OpCode;
+use vlcb_network::data::packet::construct::module_cfg;
+use vlcb_network::iface::{Interface, PollContext, SocketSet, SocketStorage};
+use vlcb_network::phy::loopback::Loopback;
+use vlcb_network::phy::tracer::{Tracer, TracerDirection};
+use vlcb_network::pretty::PrettyFrame;
+use vlcb_network::socket::module::{self, Filter, PacketBuffer, PacketMetadata};
+use vlcb_network::wire::{CanFrame, HardwareAddress};
+
+fn print_frame(direction: TracerDirection, buffer: &[u8]) {
+    match CanFrame::new_checked(buffer) {
+        Ok(frame) if frame.is_rtr() => {
+            println!("{:?} can_id={} RTR", direction, frame.src_addr())
+        }
+        Ok(frame) => println!(
+            "{:?} can_id={} {}",
+            direction,
+            frame.src_addr(),
+            PrettyFrame::new(frame.payload())
+        ),
+        Err(_) => println!("{:?} malformed CAN frame {:02X?}", direction, buffer),
+    }
+}
+
+/// Queue a frame on the loopback device as if another node sent it.
+fn inject(device: &mut Loopback<8>, can_id: u8, packet: &[u8]) {
+    let mut buffer = [0u8; 10];
+    let len = 2 + packet.len();
+    let mut frame = CanFrame::new_unchecked(&mut buffer[..len]);
+    frame.set_src_addr(VlcbCanId::from_bytes(&[can_id]));
+    frame.payload_mut().copy_from_slice(packet);
+    device.inject(&buffer[..len]).unwrap();
+}
+
+fn main() {
+    let mut loopback = Loopback::<8>::new();
+    inject(&mut loopback, 12, &[OpCode::QueryNodeInfo.into()]);
+    inject(&mut loopback, 12, &[OpCode::LongEventAccessoryOn.into(), 0x01, 0x2A, 0x00, 0x07]);
+    inject(&mut loopback, 12, &[OpCode::LegacySetNodeVariable.into(), 0x01, 0x2C, 0x04, 0x1F]);
+
+    let mut device = Tracer::new(loopback, print_frame);
+    let node_num = VlcbNodeNumber::new(0x01, 0x2C);
+    let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[5]));
+    let mut iface = Interface::<TestClock>::new(&device, Some(node_num), hw_addr);
+
+    let mut rx_metadata = [PacketMetadata::EMPTY; 4];
+    let mut rx_payload = [0; 32];
+    let mut tx_metadata = [PacketMetadata::EMPTY; 4];
+    let mut tx_payload = [0; 32];
+    let mut socket = module::Socket::new(
+        PacketBuffer::new(&mut rx_metadata[..], &mut rx_payload[..]),
+        PacketBuffer::new(&mut tx_metadata[..], &mut tx_payload[..]),
+    );
+    socket.bind(Filter::AddressedToNode).unwrap();
+
+    let mut storage = [SocketStorage::EMPTY; 1];
+    let mut sockets = SocketSet::new(&mut storage[..]);
+    let handle = sockets.add(socket);
+
+    let clock = TestClock::new();
+    iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+    // acknowledge the node variable write, the reply is traced as well
+    //
+    // The loopback device hands the reply back to the interface, which takes it
+    // for another node using our CAN ID and starts the CAN ID enumeration.
+    let socket: &mut module::Socket = sockets.get_mut(handle);
+    while socket.recv().is_ok() {
+        socket
+            .send_packet(&module_cfg::response::write_ack(node_num))
+            .unwrap();
+    }
+    iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+}
@@ -1,19 +1,19 @@
-use vlcb_core::fast_clock::{FastClockMonth, FastClockWeekday};
+use vlcb_core::fast_clock::{FastClock, FastClockError, FastClockMonth, FastClockWeekday};
 use vlcb_defs::OpCode;
 
-use super::{construct, PacketPayload};
+use super::{construct, OutgoingPacket};
 
 /// General Acknowledgement
 ///
 /// Positive response to query / request performed or report of availability on-line.
-pub fn ack() -> PacketPayload {
+pub fn ack() -> OutgoingPacket {
     construct::no_data(OpCode::GeneralAck)
 }
 
 /// General No Ack
 ///
 /// Negative response to query / request denied.
-pub fn nack() -> PacketPayload {
+pub fn nack() -> OutgoingPacket {
     construct::no_data(OpCode::GeneralNack)
 }
 
@@ -22,7 +22,7 @@ pub fn nack() -> PacketPayload {
 /// Commonly broadcasted to all nodes to indicate CBUS is not available and no
 /// further packets should be sent until a [`OpCode::BON`] or
 /// [`OpCode::ARST`] is received.
-pub fn bus_halt() -> PacketPayload {
+pub fn bus_halt() -> OutgoingPacket {
     construct::no_data(OpCode::BusHalt)
 }
 
@@ -30,7 +30,7 @@ pub fn bus_halt() -> PacketPayload {
 ///
 /// Commonly broadcasted to all nodes to indicate CBUS is available following a
 /// [`OpCode::HLT`].
-pub fn bus_resume() -> PacketPayload {
+pub fn bus_resume() -> OutgoingPacket {
     construct::no_data(OpCode::BusResume)
 }
 
@@ -47,6 +47,9 @@ pub fn bus_resume() -> PacketPayload {
 /// This method panics when `mins` is larger than 59
 /// This method panics when `hours` is larger than 23
 /// This method panics when `month_day` is outside of 1-31 range (inclusive)
+///
+/// See [`try_fast_clock`] for a non-panicking variant.
+#[track_caller]
 pub fn fast_clock(
     mins: u8,
     hours: u8,
@@ -55,11 +58,102 @@ pub fn fast_clock(
     month: FastClockMonth,
     month_day: u8,
     temperature: i8,
-) -> PacketPayload {
-    // TODO: add panics
-    let mut wdmon: u8 = week_day.into();
+) -> OutgoingPacket {
+    match try_fast_clock(mins, hours, accel_coefficient, week_day, month, month_day, temperature) {
+        Ok(packet) => packet,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Fast Clock
+///
+/// Same as [`fast_clock`], but returns an error instead of panicking
+/// when any of the arguments is out of its range.
+pub fn try_fast_clock(
+    mins: u8,
+    hours: u8,
+    accel_coefficient: u8,
+    week_day: FastClockWeekday,
+    month: FastClockMonth,
+    month_day: u8,
+    temperature: i8,
+) -> Result<OutgoingPacket, FastClockError> {
+    let clock = FastClock::new(mins, hours, accel_coefficient, week_day, month, month_day, temperature)?;
+
+    Ok(from_fast_clock(&clock))
+}
+
+/// Fast Clock
+///
+/// Broadcasts the state of a [`FastClock`].
+pub fn from_fast_clock(clock: &FastClock) -> OutgoingPacket {
+    let [mins, hours, wdmon, div, mday, temp] = clock.to_bytes();
+
+    construct::six_bytes(OpCode::FastClock, mins, hours, wdmon, div, mday, temp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fast_clock_round_trip() {
+        let packet = fast_clock(
+            45,
+            23,
+            4,
+            FastClockWeekday::Saturday,
+            FastClockMonth::December,
+            31,
+            -12,
+        );
+        assert_eq!(
+            &packet.payload[..],
+            &[OpCode::FastClock.into(), 45, 23, 0x67, 4, 31, (-12i8) as u8]
+        );
+
+        let clock = FastClock::parse(&packet.payload).unwrap();
+        assert_eq!((clock.hours(), clock.mins()), (23, 45));
+        assert_eq!(clock.week_day(), FastClockWeekday::Saturday);
+        assert_eq!(clock.month(), FastClockMonth::December);
+        assert_eq!(clock.month_day(), 31);
+        assert_eq!(clock.accel_coefficient(), 4);
+        assert_eq!(clock.temperature(), -12);
+
+        assert_eq!(from_fast_clock(&clock), packet);
+    }
+
+    #[test]
+    fn test_try_fast_clock_invalid() {
+        let try_new = |mins, hours, month_day| {
+            try_fast_clock(mins, hours, 1, FastClockWeekday::Monday, FastClockMonth::May, month_day, 0)
+        };
+
+        assert_eq!(try_new(60, 0, 1), Err(FastClockError::InvalidMinutes(60)));
+        assert_eq!(try_new(0, 24, 1), Err(FastClockError::InvalidHours(24)));
+        assert_eq!(try_new(0, 0, 0), Err(FastClockError::InvalidMonthDay(0)));
+        assert_eq!(try_new(0, 0, 32), Err(FastClockError::InvalidMonthDay(32)));
+
+        // the last valid values
+        assert!(try_new(59, 23, 1).is_ok());
+        assert!(try_new(0, 0, 31).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid minutes (60)")]
+    fn test_fast_clock_panics() {
+        fast_clock(60, 0, 1, FastClockWeekday::Monday, FastClockMonth::May, 1, 0);
+    }
 
-    wdmon |= (month as u8) << 3;
+    #[test]
+    #[should_panic(expected = "invalid hours (24)")]
+    fn test_fast_clock_panics_on_hours() {
+        fast_clock(0, 24, 1, FastClockWeekday::Monday, FastClockMonth::May, 1, 0);
+    }
 
-    construct::six_bytes(OpCode::FastClock, mins, hours, wdmon, accel_coefficient, month_day, temperature as u8)
+    #[test]
+    #[should_panic(expected = "invalid day of month (0)")]
+    fn test_fast_clock_panics_on_month_day() {
+        fast_clock(0, 0, 1, FastClockWeekday::Monday, FastClockMonth::May, 0, 0);
+    }
 }
@@ -1,12 +1,14 @@
-use crate::wire::VLCB_MAX_PAYLOAD;
+//! Opcode extensions
+//!
+//! These can be used in times when basic 32 opcodes are not enough.
+//! This extension supports additional 256 opcode values, these have no formal definition
+//! as of yet and support up to 6 bytes of data.
 
-/// Opcode extensions
-///
-/// These can be used in times when basic 32 opcodes are not enough.
-/// This extension supports additional 256 opcode values, these have no formal definition
-/// as of yet and support up to 6 bytes of data.
+// the extensions are kept warning free, test builds deny them
+#![cfg_attr(test, deny(warnings))]
 
-use super::{construct, PacketPayload};
+use crate::wire::VLCB_MAX_PAYLOAD;
+use super::{construct, OutgoingPacket};
 use vlcb_defs::OpCode;
 use heapless::Vec;
 
@@ -14,7 +16,7 @@ use heapless::Vec;
 ///
 /// # Panics
 /// This method panics if the payload is over 6 octets long
-pub fn from_bytes(opcode_ext: u8, payload: &[u8]) -> PacketPayload {
+pub fn from_bytes(opcode_ext: u8, payload: &[u8]) -> OutgoingPacket {
     let len = payload.len();
     if len > 6 {
         construct::len_mismatch_fail(len, 6);
@@ -31,15 +33,15 @@ pub fn from_bytes(opcode_ext: u8, payload: &[u8]) -> PacketPayload {
         _ => unreachable!(),
     };
 
-    // TODO: maybe use unchecked because we know the size
     let mut buf: Vec<u8, VLCB_MAX_PAYLOAD> = Vec::new();
-    buf.push(opc as u8);
-    buf.push(opcode_ext);
-    buf.extend_from_slice(payload);
-    construct::from_bytes(buf.as_slice())
+    // the length is checked above, so the buffer always fits
+    buf.push(opc.into()).unwrap();
+    buf.push(opcode_ext).unwrap();
+    buf.extend_from_slice(payload).unwrap();
+    construct::new(buf.as_slice())
 }
 
 /// Constructs a packet with extended opcode and no payload
-pub fn no_data(opcode_ext: u8) -> PacketPayload {
+pub fn no_data(opcode_ext: u8) -> OutgoingPacket {
     construct::one_byte(OpCode::ExtOpCode, opcode_ext)
 }
@@ -1,14 +1,44 @@
+use vlcb_core::vlcb::NODENUM_SIZE;
+use vlcb_defs::OpCode;
+
+use super::{construct, ConstructError, OutgoingPacket};
+
+/// Number of data octets of a node or device data event.
+pub const DATA_EVENT_SIZE: usize = 5;
+
+/// Build a data event, the node or device number octets followed by the data.
+fn data_event(opcode: OpCode, number: &[u8], data: [u8; DATA_EVENT_SIZE]) -> OutgoingPacket {
+    let [d0, d1, d2, d3, d4] = data;
+    construct::seven_bytes(opcode, number[0], number[1], d0, d1, d2, d3, d4)
+}
+
+/// Build a device data event, rejecting the device number zero.
+fn device_data_event(
+    opcode: OpCode,
+    device_number: u16,
+    data: [u8; DATA_EVENT_SIZE],
+) -> Result<OutgoingPacket, ConstructError> {
+    if device_number == 0 {
+        return Err(ConstructError::InvalidArgument);
+    }
+    let number: [u8; NODENUM_SIZE] = device_number.to_be_bytes();
+    Ok(data_event(opcode, &number, data))
+}
+
 pub mod produce {
     use heapless::Vec;
-    use vlcb_core::vlcb::{EventId, EventType};
+    use vlcb_core::vlcb::{EventId, EventType, VlcbNodeNumber, EVENT_SIZE};
     use vlcb_defs::OpCode;
 
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, try_new, ConstructError, OutgoingPacket};
+    use super::DATA_EVENT_SIZE;
+    use crate::wire::VLCB_MAX_PAYLOAD;
 
     /// Accessory event
     ///
     /// Indicates a event status using the full event number of 4 bytes.
     /// Produces different opcodes depending on the event type (long/short)
+    /// Short events are sent with the node number octets zeroed.
     ///
     /// Depending on the `event_type` the produced packet will either be of accessory on, accessory response on
     /// or the "off" counterparts.
@@ -21,18 +51,35 @@ pub mod produce {
     ///
     /// # Panics
     /// If payload has greater lenght than 3 and less than 1
+    /// If `event_type` is [`EventType::Unknown`]
+    ///
+    /// See [`try_accessory`] for a non-panicking variant.
     pub fn accessory(
         event_type: EventType,
         event: EventId,
         payload: Option<&[u8]>,
-    ) -> PacketPayload {
+    ) -> OutgoingPacket {
+        match try_accessory(event_type, event, payload) {
+            Ok(packet) => packet,
+            Err(ConstructError::InvalidPayloadLength(len)) => construct::len_range_fail(len, 1, 3),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Accessory event
+    ///
+    /// Same as [`accessory`], but returns `Err(ConstructError::InvalidPayloadLength)`
+    /// instead of panicking when the payload is empty or longer than 3 octets
+    /// and `Err(ConstructError::InvalidArgument)` for an unknown event type.
+    pub fn try_accessory(
+        event_type: EventType,
+        event: EventId,
+        payload: Option<&[u8]>,
+    ) -> Result<OutgoingPacket, ConstructError> {
         if let Some(payload) = payload {
             let l = payload.len();
-            if l < 1 || l > 3 {
-                panic!(
-                    "payload should not be bigger than 3 octets and smaller than 1, given ({})",
-                    l
-                );
+            if !(1..=3).contains(&l) {
+                return Err(ConstructError::InvalidPayloadLength(l));
             }
         }
 
@@ -49,10 +96,10 @@ pub mod produce {
             (EventType::AccessoryStatusOn, false, 1) => OpCode::LongEventAccessoryStateOn1,
             (EventType::AccessoryStatusOn, false, 2) => OpCode::LongEventAccessoryStateOn2,
             (EventType::AccessoryStatusOn, false, 3) => OpCode::LongEventAccessoryStateOn3,
-            (EventType::AccessoryStatusOn, true, 0) => OpCode::ShortEventAccessoryOn,
-            (EventType::AccessoryStatusOn, true, 1) => OpCode::ShortEventAccessoryOn1,
-            (EventType::AccessoryStatusOn, true, 2) => OpCode::ShortEventAccessoryOn2,
-            (EventType::AccessoryStatusOn, true, 3) => OpCode::ShortEventAccessoryOn3,
+            (EventType::AccessoryStatusOn, true, 0) => OpCode::ShortEventAccessoryStateOn,
+            (EventType::AccessoryStatusOn, true, 1) => OpCode::ShortEventAccessoryStateOn1,
+            (EventType::AccessoryStatusOn, true, 2) => OpCode::ShortEventAccessoryStateOn2,
+            (EventType::AccessoryStatusOn, true, 3) => OpCode::ShortEventAccessoryStateOn3,
 
             (EventType::AccessoryOff, false, 0) => OpCode::LongEventAccessoryOff,
             (EventType::AccessoryOff, false, 1) => OpCode::LongEventAccessoryOff1,
@@ -70,63 +117,66 @@ pub mod produce {
             (EventType::AccessoryStatusOff, true, 1) => OpCode::ShortEventAccessoryStateOff1,
             (EventType::AccessoryStatusOff, true, 2) => OpCode::ShortEventAccessoryStateOff2,
             (EventType::AccessoryStatusOff, true, 3) => OpCode::ShortEventAccessoryStateOff3,
-            _ => unreachable!(),
+            _ => return Err(ConstructError::InvalidArgument),
         };
 
-        //TODO: maybe use unchecked instead
-        let mut data: Vec<u8, 8> = Vec::new();
+        let mut data: Vec<u8, VLCB_MAX_PAYLOAD> = Vec::new();
         data.push(opc.into()).unwrap();
-        data.extend_from_slice(event.as_bytes()).unwrap();
-        construct::from_bytes(data.as_slice())
+        data.extend_from_slice(&construct::event_bytes(&event)).unwrap();
+        if let Some(payload) = payload {
+            data.extend_from_slice(payload)
+                .map_err(|_| ConstructError::PayloadTooLong(1 + EVENT_SIZE + payload.len()))?;
+        }
+        try_new(data.as_slice())
     }
 
-    pub fn accessory_data() -> PacketPayload {
-        todo!()
-        /*
-        Accessory node data event (ACDAT)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<F6><NN hi><NNlo>
-        <data1><data2><data3><data4><data5>
-        <Dat1> is the high byte of the node number
-        <Dat2> is the low byte of the node number
-        <Dat3> is the first node data byte
-        <Dat4> is the second node data byte
-        <Dat5> is the third node data byte
-        <Dat6> is the fourth node data byte
-        <Dat7> is the fifth node data byte
-        Indicates an event from this node with 5 bytes of data.
-        For example, this can be used to send the 40 bits of an RFID tag. There is no
-        event number in order to allow space for 5 bytes of data in the packet, so there
-        can only be one data event per node. */
-        /*
-        Device data event (short mode) (DDES)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<FA><DN hi><DN lo>
-        <data1><data2><data3><data4><data5>
-        <Dat1> is the high byte of the device number
-        <Dat2> is the low byte of the device number
-        <Dat3> is the first device data byte
-        <Dat4> is the second device data byte
-        <Dat5> is the third device data byte
-        <Dat6> is the fourth device data byte
-        <Dat7> is the fifth device data byte
-        Function is the same as F6 but uses device addressing so can relate data to a
-        device attached to a node. e.g. one of several RFID readers attached to a single
-        node. */
+    /// Accessory node data event (ACDAT)
+    ///
+    /// Indicates an event from the node `node_num` with 5 octets of data, e.g. the 40 bits
+    /// of an RFID tag. There is no event number, so a node has only one data event.
+    pub fn node_data(node_num: VlcbNodeNumber, data: [u8; DATA_EVENT_SIZE]) -> OutgoingPacket {
+        super::data_event(OpCode::DataEventAccessory, node_num.as_bytes(), data)
+    }
+
+    /// Device data event (short mode) (DDES)
+    ///
+    /// Same as [`node_data`], but relates the data to the device `device_number` attached
+    /// to the node, e.g. one of several RFID readers.
+    ///
+    /// # Panics
+    /// If `device_number` is zero
+    ///
+    /// See [`try_device_data`] for a non-panicking variant.
+    pub fn device_data(device_number: u16, data: [u8; DATA_EVENT_SIZE]) -> OutgoingPacket {
+        match try_device_data(device_number, data) {
+            Ok(packet) => packet,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Device data event (short mode) (DDES)
+    ///
+    /// Same as [`device_data`], but returns `Err(ConstructError::InvalidArgument)`
+    /// instead of panicking when `device_number` is zero.
+    pub fn try_device_data(
+        device_number: u16,
+        data: [u8; DATA_EVENT_SIZE],
+    ) -> Result<OutgoingPacket, ConstructError> {
+        super::device_data_event(OpCode::DeviceDataEventShortMode, device_number, data)
     }
 }
 pub mod command {
     use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
     use vlcb_defs::OpCode;
 
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
 
     /// Unlearn an event in learn mode
     ///
     /// Sent by a configuration tool to remove an event from a node.
     /// # Panics
     /// This method panics if the event is short
-    pub fn forget(event: EventId) -> PacketPayload {
+    pub fn forget(event: EventId) -> OutgoingPacket {
         if event.is_short() {
             panic!("The event must be long");
         }
@@ -140,7 +190,7 @@ pub mod command {
         )
     }
 
-    pub fn teach() -> PacketPayload {
+    pub fn teach() -> OutgoingPacket {
         /*
         Teach an event in learn mode (EVLRN)
         Format:
@@ -166,14 +216,14 @@ pub mod command {
     ///
     /// Sent by a configuration tool to clear all events from a specific node. Must be in
     /// learn mode first to safeguard against accidental erasure of all events.
-    pub fn forget_all(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn forget_all(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::ForgetAllLearnedEvents, bytes[0], bytes[1])
     }
 
 }
 pub mod query {
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
     use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
     use vlcb_defs::OpCode;
 
@@ -183,45 +233,34 @@ pub mod query {
     /// A request event is used to elicit a status response from a producer when it is required to
     /// know the ‘state’ of the producer without producing an ON or OFF event and to trigger an
     /// event from a ‘combi’ node.
-    pub fn accessory(event: EventId) -> PacketPayload {
+    ///
+    /// Short events are sent with the node number octets zeroed.
+    pub fn accessory(event: EventId) -> OutgoingPacket {
         let opc = match event.is_short() {
             true => OpCode::QueryShortEventAccessoryState,
             false => OpCode::QueryLongEventAccessoryState,
         };
 
-        let data = event.as_bytes();
-        //TODO: check if we need to set ms bytes of the event if it's short to 0
+        let data = construct::event_bytes(&event);
         construct::four_bytes(opc, data[0], data[1], data[2], data[3])
     }
 
-    /// Request for read of an event variable
-    pub fn event_variable() -> PacketPayload {
-        /**
-         * Request for read of an event variable (REVAL)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<9C><NN hi><NN lo><EN#><EV#>
-        This request differs from B2 (REQEV) as it doesn’t need to be in learn mode but does
-        require the knowledge of the event index to which the EV request is directed.
-        EN# is the event index. EV# is the event variable index. Response is B5 (NEVAL)
-         */
-
-        /**
-               * Read event variable in learn mode (REQEV)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<B2><NN hi><NN lo><EN hi>
-        <EN lo><EV# >
-        Allows a configuration tool to read stored event variables from a node. EV# is the
-        EV index. Reply is (EVANS)
-               */
-        todo!()
+    /// Request for read of an event variable (REVAL)
+    ///
+    /// Reads the event variable `ev_index` of the event stored at the event `index`, the node
+    /// doesn't need to be in learn mode. The `ev_index` 0 reads the number of event variables
+    /// followed by all of them.
+    /// Response is 0xB5 ([`OpCode::EventVariableValue`])
+    pub fn event_variable(node_num: VlcbNodeNumber, index: u8, ev_index: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::four_bytes(OpCode::QueryEventVariable, bytes[0], bytes[1], index, ev_index)
     }
 
-
     /// Read available event slots
     ///
     /// Sent by a configuration tool to read the number of available event slots in a node.
     /// Response is [`OpCode::EVLNF`] (0x70)
-    pub fn available_event_slots(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn available_event_slots(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::QueryAvailableEventSlots, bytes[0], bytes[1])
     }
@@ -229,7 +268,7 @@ pub mod query {
     /// Read all stored events
     ///
     /// Sent by a configuration tool to read all the stored events in a node. Response is [`OpCode::ENRSP`].
-    pub fn all_learned_events(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn all_learned_events(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::QueryAllLearnedEvents, bytes[0], bytes[1])
     }
@@ -238,7 +277,7 @@ pub mod query {
     ///
     /// Sent by a configuration tool to read the number of stored events in a node.
     /// Response is 0x74([`OpCode::NUMEV`]).
-    pub fn saved_events_amount(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn saved_events_amount(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::QueryLearnedEventCount, bytes[0], bytes[1])
     }
@@ -247,75 +286,250 @@ pub mod query {
     ///
     /// `index` is the index for the stored event requested.
     /// Response is 0xF2 ([`OpCode::ENRSP`])
-    pub fn event(node_num: VlcbNodeNumber, index: u8) -> PacketPayload {
+    pub fn event(node_num: VlcbNodeNumber, index: u8) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::QueryLearnedEventByIndex, bytes[0], bytes[1], index)
     }
 }
 pub mod response {
-    use super::super::{construct, PacketPayload};
+    use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
+    use vlcb_defs::OpCode;
 
-    /// Response to request for read of EV value
-    pub fn event_variable() -> PacketPayload {
-        // TODO: should probably be separate methods
-        /**
-        * Response to request for read of EV value (NEVAL)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<B5><NN hi><NN lo><EN#>
-        <EV#><EVval>
-        NN is the node replying. EN# is the index of the event in that node. EV# is the index of the
-        event variable. EVval is the value of that EV. This is response to 9C (REVAL)
-        */
-        todo!()
+    use super::super::{construct, ConstructError, OutgoingPacket};
+    use super::DATA_EVENT_SIZE;
 
-        /*
-         * Format:
-        [<MjPri><MinPri=3><CANID>]<D3><NN hi><NN lo><EN hi><EN lo>
-        <EV#><EV val>
-        A node response to a request from a configuration tool for the EVs associated
-        with an event (REQEV). For multiple EVs, there will be one response per request.
-         */
+    /// Event acknowledge (ENACK)
+    ///
+    /// Sent by the node `node_num` after consuming the `event` received with the `opcode`,
+    /// while the event acknowledgement is turned on.
+    pub fn event_ack(node_num: VlcbNodeNumber, opcode: OpCode, event: EventId) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        let [e0, e1, e2, e3] = construct::event_bytes(&event);
+        construct::seven_bytes(OpCode::EventAck, bytes[0], bytes[1], opcode.into(), e0, e1, e2, e3)
     }
 
-    pub fn event() -> PacketPayload {
-        /*
-        Response to request to read node events (ENRSP)
-        Format:
-        [<MjPri><MinPri=3><CANID>]<F2><NN hi><NN lo>
-        <EN3><EN2><EN1><EN0><EN#>
-        Where the NN is that of the sending node. EN3 to EN0 are the four bytes of the stored
-        event. EN# is the index of the event within the sending node. This is a response to either
-        57 (NERD) or 72 (NENRD) */
-        todo!()
+    /// Response to request for read of EV value (NEVAL)
+    ///
+    /// The `value` of the event variable `ev_index` of the event stored at the event `index`
+    /// in the node `node_num`. This is the response to 0x9C ([`OpCode::QueryEventVariable`]).
+    pub fn event_variable(
+        node_num: VlcbNodeNumber,
+        index: u8,
+        ev_index: u8,
+        value: u8,
+    ) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        let opcode = OpCode::EventVariableValue;
+        construct::five_bytes(opcode, bytes[0], bytes[1], index, ev_index, value)
     }
 
-    pub fn accessory_node_data() -> PacketPayload {
-        //     Accessory node data Response (ARDAT)
-        // Format:
-        // [<MjPri><MinPri=3><CANID>]<F7><NN hi><NN lo>
-        // <data1><data2><data3><data4><data5>
-        // <Dat1> is the high byte of the node number
-        // <Dat2> is the low byte of the node number
-        // <Dat3> is the first node data byte
-        // <Dat4> is the second node data byte
-        // <Dat5> is the third node data byte
-        // <Dat6> is the fourth node data byte
-        // <Dat7> is the fifth node data byte
-        // Indicates a node data response. A response event is a reply to a status request
-        // (RQDAT) without producing a new data event.
-
-        // Device data response (short mode) (DDRS)
-        // Format:
-        // [<MjPri><MinPri=3><CANID>]<FB><DN hi><DN lo>
-        // <data1><data2><data3><data4><data5>
-        // <Dat1> is the high byte of the device number
-        // <Dat2> is the low byte of the device number
-        // <Dat3> is the first device data byte
-        // <Dat4> is the second device data byte
-        // <Dat5> is the third device data byte
-        // <Dat6> is the fourth device data byte
-        // <Dat7> is the fifth device data byte
-        // The response to a request for data from a device. (0x5B)
-        todo!()
+    /// Response to request to read node events (ENRSP)
+    ///
+    /// The `event` stored at the event `index` in the node `node_num`. This is the response to
+    /// 0x57 ([`OpCode::QueryAllLearnedEvents`]) or 0x72 ([`OpCode::QueryLearnedEventByIndex`]).
+    pub fn event(node_num: VlcbNodeNumber, event: EventId, index: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        let [e0, e1, e2, e3] = construct::event_bytes(&event);
+        let opcode = OpCode::LearnedEventResponse;
+        construct::seven_bytes(opcode, bytes[0], bytes[1], e0, e1, e2, e3, index)
+    }
+
+    /// Accessory node data response (ARDAT)
+    ///
+    /// The reply of the node `node_num` to a data request (RQDAT), with the 5 octets of its
+    /// data event but without producing a new one.
+    pub fn node_data(node_num: VlcbNodeNumber, data: [u8; DATA_EVENT_SIZE]) -> OutgoingPacket {
+        super::data_event(OpCode::NodeDataEventResponse, node_num.as_bytes(), data)
+    }
+
+    /// Device data response (short mode) (DDRS)
+    ///
+    /// The reply to a request for the data of the device `device_number` (RQDDS).
+    ///
+    /// # Panics
+    /// If `device_number` is zero
+    ///
+    /// See [`try_device_data`] for a non-panicking variant.
+    pub fn device_data(device_number: u16, data: [u8; DATA_EVENT_SIZE]) -> OutgoingPacket {
+        match try_device_data(device_number, data) {
+            Ok(packet) => packet,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Device data response (short mode) (DDRS)
+    ///
+    /// Same as [`device_data`], but returns `Err(ConstructError::InvalidArgument)`
+    /// instead of panicking when `device_number` is zero.
+    pub fn try_device_data(
+        device_number: u16,
+        data: [u8; DATA_EVENT_SIZE],
+    ) -> Result<OutgoingPacket, ConstructError> {
+        super::device_data_event(OpCode::DeviceDataResponseShortMode, device_number, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::vlcb::{EventId, EventType, VlcbNodeNumber};
+    use vlcb_defs::OpCode;
+
+    use super::*;
+    use crate::data::packet::construct::ConstructError;
+
+    #[test]
+    fn test_produce_accessory_layout() {
+        use EventType::*;
+
+        #[rustfmt::skip]
+        let table = [
+            (AccessoryOn, false, 0, OpCode::LongEventAccessoryOn),
+            (AccessoryOn, false, 1, OpCode::LongEventAccessoryOn1),
+            (AccessoryOn, false, 2, OpCode::LongEventAccessoryOn2),
+            (AccessoryOn, false, 3, OpCode::LongEventAccessoryOn3),
+            (AccessoryOn, true, 0, OpCode::ShortEventAccessoryOn),
+            (AccessoryOn, true, 1, OpCode::ShortEventAccessoryOn1),
+            (AccessoryOn, true, 2, OpCode::ShortEventAccessoryOn2),
+            (AccessoryOn, true, 3, OpCode::ShortEventAccessoryOn3),
+            (AccessoryOff, false, 0, OpCode::LongEventAccessoryOff),
+            (AccessoryOff, false, 1, OpCode::LongEventAccessoryOff1),
+            (AccessoryOff, false, 2, OpCode::LongEventAccessoryOff2),
+            (AccessoryOff, false, 3, OpCode::LongEventAccessoryOff3),
+            (AccessoryOff, true, 0, OpCode::ShortEventAccessoryOff),
+            (AccessoryOff, true, 1, OpCode::ShortEventAccessoryOff1),
+            (AccessoryOff, true, 2, OpCode::ShortEventAccessoryOff2),
+            (AccessoryOff, true, 3, OpCode::ShortEventAccessoryOff3),
+            (AccessoryStatusOn, false, 0, OpCode::LongEventAccessoryStateOn),
+            (AccessoryStatusOn, false, 1, OpCode::LongEventAccessoryStateOn1),
+            (AccessoryStatusOn, false, 2, OpCode::LongEventAccessoryStateOn2),
+            (AccessoryStatusOn, false, 3, OpCode::LongEventAccessoryStateOn3),
+            (AccessoryStatusOn, true, 0, OpCode::ShortEventAccessoryStateOn),
+            (AccessoryStatusOn, true, 1, OpCode::ShortEventAccessoryStateOn1),
+            (AccessoryStatusOn, true, 2, OpCode::ShortEventAccessoryStateOn2),
+            (AccessoryStatusOn, true, 3, OpCode::ShortEventAccessoryStateOn3),
+            (AccessoryStatusOff, false, 0, OpCode::LongEventAccessoryStateOff),
+            (AccessoryStatusOff, false, 1, OpCode::LongEventAccessoryStateOff1),
+            (AccessoryStatusOff, false, 2, OpCode::LongEventAccessoryStateOff2),
+            (AccessoryStatusOff, false, 3, OpCode::LongEventAccessoryStateOff3),
+            (AccessoryStatusOff, true, 0, OpCode::ShortEventAccessoryStateOff),
+            (AccessoryStatusOff, true, 1, OpCode::ShortEventAccessoryStateOff1),
+            (AccessoryStatusOff, true, 2, OpCode::ShortEventAccessoryStateOff2),
+            (AccessoryStatusOff, true, 3, OpCode::ShortEventAccessoryStateOff3),
+        ];
+
+        let data = [0xA1, 0xA2, 0xA3];
+        for (event_type, short, len, opcode) in table {
+            let event = EventId::new(short, 0x01, 0x02, 0x03, 0x04);
+            let payload = (len > 0).then(|| &data[..len]);
+            let packet = produce::accessory(event_type, event, payload);
+
+            let nn: &[u8] = if short { &[0x00, 0x00] } else { &[0x01, 0x02] };
+            let expected: heapless::Vec<u8, 8> = [u8::from(opcode)]
+                .iter()
+                .chain(nn)
+                .chain(&[0x03, 0x04])
+                .chain(&data[..len])
+                .copied()
+                .collect();
+            assert_eq!(
+                packet.payload, expected,
+                "{:?} short: {} payload length: {}",
+                event_type, short, len
+            );
+        }
+    }
+
+    #[test]
+    fn test_produce_accessory_unknown_type() {
+        let event = EventId::new(false, 0x01, 0x02, 0x03, 0x04);
+        assert_eq!(
+            produce::try_accessory(EventType::Unknown, event, None),
+            Err(ConstructError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_event_ack() {
+        let event = EventId::long(VlcbNodeNumber::new(0x03, 0x04), 5);
+        let packet = response::event_ack(
+            VlcbNodeNumber::new(0x01, 0x02),
+            OpCode::LongEventAccessoryOn,
+            event,
+        );
+        assert_eq!(
+            &packet.payload[..],
+            &[
+                OpCode::EventAck.into(),
+                0x01,
+                0x02,
+                OpCode::LongEventAccessoryOn.into(),
+                0x03,
+                0x04,
+                0x00,
+                0x05
+            ]
+        );
+    }
+
+    #[test]
+    fn test_learned_event_layout() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = query::all_learned_events(node_num);
+        assert_eq!(&packet.payload[..], &[0x57, 0x01, 0x02]);
+        let event = EventId::long(VlcbNodeNumber::new(0x03, 0x04), 5);
+        let packet = response::event(node_num, event, 2);
+        assert_eq!(&packet.payload[..], &[0xF2, 0x01, 0x02, 0x03, 0x04, 0x00, 0x05, 0x02]);
+        let packet = response::event(node_num, EventId::short(5), 3);
+        assert_eq!(&packet.payload[..], &[0xF2, 0x01, 0x02, 0x00, 0x00, 0x00, 0x05, 0x03]);
+
+        let packet = query::event_variable(node_num, 2, 1);
+        assert_eq!(&packet.payload[..], &[0x9C, 0x01, 0x02, 0x02, 0x01]);
+        let packet = response::event_variable(node_num, 2, 1, 0x42);
+        assert_eq!(&packet.payload[..], &[0xB5, 0x01, 0x02, 0x02, 0x01, 0x42]);
+    }
+
+    #[test]
+    fn test_data_event_layout() {
+        const DATA: [u8; DATA_EVENT_SIZE] = [0xD1, 0xD2, 0xD3, 0xD4, 0xD5];
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packets = [
+            (produce::node_data(node_num, DATA), OpCode::DataEventAccessory),
+            (response::node_data(node_num, DATA), OpCode::NodeDataEventResponse),
+            (produce::device_data(0x0102, DATA), OpCode::DeviceDataEventShortMode),
+            (response::device_data(0x0102, DATA), OpCode::DeviceDataResponseShortMode),
+        ];
+        for (packet, opcode) in packets {
+            assert_eq!(
+                &packet.payload[..],
+                &[opcode.into(), 0x01, 0x02, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5],
+                "{:?}",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn test_device_data_number_zero() {
+        let data = [0; DATA_EVENT_SIZE];
+        assert_eq!(produce::try_device_data(0, data), Err(ConstructError::InvalidArgument));
+        assert_eq!(response::try_device_data(0, data), Err(ConstructError::InvalidArgument));
+        assert!(response::try_device_data(u16::MAX, data).is_ok());
+    }
+
+    #[test]
+    fn test_query_accessory_layout() {
+        let long = query::accessory(EventId::new(false, 0x01, 0x02, 0x03, 0x04));
+        assert_eq!(
+            &long.payload[..],
+            &[OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02, 0x03, 0x04]
+        );
+
+        let short = query::accessory(EventId::new(true, 0x01, 0x02, 0x03, 0x04));
+        assert_eq!(
+            &short.payload[..],
+            &[OpCode::QueryShortEventAccessoryState.into(), 0x00, 0x00, 0x03, 0x04]
+        );
     }
 }
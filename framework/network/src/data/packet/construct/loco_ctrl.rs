@@ -1,21 +1,21 @@
 pub mod command {
     use vlcb_core::dcc::{EngineFunctionRange, EngineState};
-    use vlcb_defs::{DccError, OpCode, DccThrottleMode};
+    use vlcb_defs::{OpCode, DccThrottleMode};
     use zerocopy::{ByteOrder, NetworkEndian};
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, try_new, ConstructError, OutgoingPacket};
     use heapless::Vec;
 
     /// Request track off
     ///
     /// Sent to request change of track power state to “off”.
-    pub fn track_power_off() -> PacketPayload {
+    pub fn track_power_off() -> OutgoingPacket {
         construct::no_data(OpCode::DccTrackPowerOff)
     }
 
     /// Request track on
     ///
     /// Sent to request change of track power state to “on”.
-    pub fn track_power_on() -> PacketPayload {
+    pub fn track_power_on() -> OutgoingPacket {
         construct::no_data(OpCode::DccTrackPowerOn)
     }
 
@@ -24,7 +24,7 @@ pub mod command {
     /// Sent to request an emergency stop to all trains.
     /// Does not affect accessory control.
     /// See section 9.1.8 of the CBUS Developer's guide
-    pub fn emergency_stop() -> PacketPayload {
+    pub fn emergency_stop() -> OutgoingPacket {
         construct::no_data(OpCode::DccEmergencyStop)
     }
 
@@ -33,7 +33,7 @@ pub mod command {
     ///
     /// Sent by a CAB to the Command Station. The engine with that Session
     /// number is removed from the active engine list.
-    pub fn release_session(session_id: u8) -> PacketPayload {
+    pub fn release_session(session_id: u8) -> OutgoingPacket {
         construct::one_byte(OpCode::DccReleaseSession, session_id)
     }
 
@@ -42,7 +42,7 @@ pub mod command {
     /// The cab sends a keep alive at regular intervals for the active session. The interval
     /// between keep alive messages must be less than the session timeout implemented by the
     /// command station.
-    pub fn session_keep_alive(session_id: u8) -> PacketPayload {
+    pub fn session_keep_alive(session_id: u8) -> OutgoingPacket {
         construct::one_byte(OpCode::DccSessionKeepAlive, session_id)
     }
 
@@ -55,14 +55,14 @@ pub mod command {
     /// [`OpCode::RLOC`] is exactly equivalent to [`OpCode::GLOC`] with
     /// all flag bits set to zero, but command stations must continue to support
     /// [`OpCode::RLOC`] for backwards compatibility.
-    pub fn allocate_loco_session(engine_addr: u16) -> PacketPayload {
+    pub fn allocate_loco_session(engine_addr: u16) -> OutgoingPacket {
         let mut payload = [0u8; 2];
         NetworkEndian::write_u16(&mut payload, engine_addr);
         construct::two_bytes(OpCode::DccRequestNewSession, payload[0], payload[1])
     }
 
     /// Allocate loco (used to allocate to a shuttle in cancmd)
-    pub fn allocate_loco_to_activity(session_id: u8, activity_id: u8) -> PacketPayload {
+    pub fn allocate_loco_to_activity(session_id: u8, activity_id: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccAllocateLocoToActivity, session_id, activity_id)
     }
 
@@ -72,7 +72,7 @@ pub mod command {
         throttle_mode: DccThrottleMode,
         service_mode: bool,
         sound_control_mode: bool,
-    ) -> PacketPayload {
+    ) -> OutgoingPacket {
         let mut throttle_mode: u8 = throttle_mode.into();
 
         if service_mode {
@@ -90,14 +90,14 @@ pub mod command {
     ///
     /// Adds a decoder to a consist.
     /// `consist` has the most significant bit set if consist direction is reversed.
-    pub fn add_loco_to_consist(session_id: u8, consist: u8) -> PacketPayload {
+    pub fn add_loco_to_consist(session_id: u8, consist: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccConsistAddLoco, session_id, consist)
     }
 
     /// Remove loco from consist
     ///
     /// Removes a loco from a consist.
-    pub fn remove_loco_from_consist(session_id: u8, consist: u8) -> PacketPayload {
+    pub fn remove_loco_from_consist(session_id: u8, consist: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccConsistRemoveLoco, session_id, consist)
     }
 
@@ -105,7 +105,7 @@ pub mod command {
     ///
     /// The speed is an unsigned 7 bit number
     /// Sent by a CAB or equivalent to request an engine speed/dir change.
-    pub fn set_loco_throttle(session_id: u8, speed: u8, is_reversed: bool) -> PacketPayload {
+    pub fn set_loco_throttle(session_id: u8, speed: u8, is_reversed: bool) -> OutgoingPacket {
         let mut data = speed & 0x7F;
 
         if is_reversed {
@@ -125,7 +125,7 @@ pub mod command {
         lights_on: bool,
         relative_direction: bool,
         state: EngineState,
-    ) -> PacketPayload {
+    ) -> OutgoingPacket {
         let mut data: u8 = throttle_mode.into();
 
         if lights_on {
@@ -148,7 +148,7 @@ pub mod command {
     ///
     /// Sent by a cab to turn on a specific loco function. This provides an alternative method to
     /// [`OpCode::DFUN`] for controlling loco functions. A command station must implement both methods.
-    pub fn loco_func_on(session_id: u8, func_num: u8) -> PacketPayload {
+    pub fn loco_func_on(session_id: u8, func_num: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccLocoFunctionOn, session_id, func_num & 0x7F)
     }
 
@@ -158,7 +158,7 @@ pub mod command {
     ///
     /// Sent by a cab to turn off a specific loco function. This provides an alternative method to
     /// [`OpCode::DFUN`] for controlling loco functions. A command station must implement both methods.
-    pub fn loco_func_off(session_id: u8, func_num: u8) -> PacketPayload {
+    pub fn loco_func_off(session_id: u8, func_num: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccLocoFunctionOff, session_id, func_num & 0x7F)
     }
 
@@ -169,7 +169,7 @@ pub mod command {
         session_id: u8,
         selection_range: EngineFunctionRange,
         data: u8,
-    ) -> PacketPayload {
+    ) -> OutgoingPacket {
         construct::three_bytes(OpCode::DccSetLocoFunctions, session_id, selection_range.into(), data)
     }
 
@@ -184,17 +184,31 @@ pub mod command {
     ///
     /// # Panics
     /// The function panics if `payload` is outside of exactly 3 to 6 octets long
-    pub fn send_dcc_packet(times: u8, payload: &[u8]) -> PacketPayload {
+    ///
+    /// See [`try_send_dcc_packet`] for a non-panicking variant.
+    pub fn send_dcc_packet(times: u8, payload: &[u8]) -> OutgoingPacket {
+        match try_send_dcc_packet(times, payload) {
+            Ok(packet) => packet,
+            Err(ConstructError::InvalidArgument) => {
+                panic!("repeat amount `times` must be greater or equal to 1")
+            }
+            Err(_) => construct::len_range_fail(payload.len(), 3, 6),
+        }
+    }
+
+    /// Request 3-byte DCC Packet
+    ///
+    /// Same as [`send_dcc_packet`], but returns `Err(ConstructError::InvalidArgument)` when `times`
+    /// is zero and `Err(ConstructError::InvalidPayloadLength)` when `payload` is outside of
+    /// 3 to 6 octets instead of panicking.
+    pub fn try_send_dcc_packet(times: u8, payload: &[u8]) -> Result<OutgoingPacket, ConstructError> {
         if times < 1 {
-            panic!("repeat amount `times` must be greater or equal to 1");
+            return Err(ConstructError::InvalidArgument);
         }
 
         let payload_len = payload.len();
-        if payload_len < 3 || payload_len > 6 {
-            panic!(
-                "payload slice length ({}) must be at least 3 bytes long and must not be larger than 6",
-                payload_len,
-            );
+        if !(3..=6).contains(&payload_len) {
+            return Err(ConstructError::InvalidPayloadLength(payload_len));
         }
 
         let opc = match payload_len {
@@ -210,10 +224,10 @@ pub mod command {
         data.push(opc.into()).unwrap();
         data.push(times).unwrap();
         data.extend_from_slice(payload).unwrap();
-        construct::from_bytes(data.as_slice())
+        try_new(data.as_slice())
     }
 
-    pub fn write_cv_data() -> PacketPayload {
+    pub fn write_cv_data() -> OutgoingPacket {
         todo!()
 
         // TODO: these should probably be separate functions
@@ -259,7 +273,7 @@ pub mod command {
         handle. */
     }
 
-    pub fn write_cv_flag() -> PacketPayload {
+    pub fn write_cv_flag() -> OutgoingPacket {
         todo!()
 
         /*
@@ -283,16 +297,15 @@ pub mod command {
     }
 
     pub mod query {
-    use vlcb_defs::{OpCode, DccError};
-    use zerocopy::{AsBytes, ByteOrder, NetworkEndian};
-    use super::super::{construct, PacketPayload};
+    use vlcb_defs::OpCode;
+    use super::super::{construct, OutgoingPacket};
     use vlcb_core::dcc::{LocoAddress, SessionQueryMode};
 
     /// Request Command Station Status
     ///
     /// Sent to query the status of the command station. See description of ([`OpCode::STAT`]) for the
     /// response from the command station.
-    pub fn command_station_status() -> PacketPayload {
+    pub fn command_station_status() -> OutgoingPacket {
         construct::no_data(OpCode::DccQueryCommandStationStatus)
     }
 
@@ -301,7 +314,7 @@ pub mod command {
     /// The command station responds with [`OpCode::PLOC`] if the session is assigned.
     /// Otherwise responds with ERR: [`DccError::LOCO_NOT_FOUND`]. See section 12.5. of the
     /// CBUS Developer's guide.
-    pub fn loco_status(session_id: u8) -> PacketPayload {
+    pub fn loco_status(session_id: u8) -> OutgoingPacket {
         construct::one_byte(OpCode::DccQueryLocoStatus, session_id)
     }
 
@@ -315,7 +328,7 @@ pub mod command {
     /// #Note
     /// A command station needs not support this opcode if it uses advanced consisting
     /// and has no way of reading back the CV currently containing the consist address in a loco.
-    pub fn consist(consist_addr: u8, engine_index: u8) -> PacketPayload {
+    pub fn consist(consist_addr: u8, engine_index: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccQueryConsist, consist_addr, engine_index)
     }
 
@@ -333,7 +346,7 @@ pub mod command {
     pub fn loco_session(
         loco_addr: LocoAddress,
         query_mode: SessionQueryMode,
-    ) -> PacketPayload {
+    ) -> OutgoingPacket {
         let addr = loco_addr.as_bytes_sanitized();
 
         let flags: u8 = query_mode.into();
@@ -341,7 +354,7 @@ pub mod command {
         construct::three_bytes(OpCode::DccQueryLocoSession, addr[0], addr[1], flags)
     }
 
-    pub fn cv_data() -> PacketPayload {
+    pub fn cv_data() -> OutgoingPacket {
         todo!()
 
         /*
@@ -359,7 +372,7 @@ pub mod command {
         */
     }
 
-    pub fn cv_report() -> PacketPayload {
+    pub fn cv_report() -> OutgoingPacket {
         todo!()
 
         /*
@@ -378,18 +391,18 @@ pub mod command {
 
 pub mod response {
     use vlcb_defs::{OpCode};
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
 
     /// Service mode status
     ///
     /// Status returned by command station/programmer at end of programming
     /// operation that does not return data.
-    pub fn service_mode_status(session_id: u8, status: u8) -> PacketPayload {
+    pub fn service_mode_status(session_id: u8, status: u8) -> OutgoingPacket {
         construct::two_bytes(OpCode::DccServiceModeStatus, session_id, status)
     }
 
 
-    pub fn loco_report() -> PacketPayload {
+    pub fn loco_report() -> OutgoingPacket {
         //                 Engine report (PLOC)
         // Format:
         // [<MjPri><MinPri=2><CANID>]<E1><Session><AddrH><AddrL>
@@ -410,7 +423,7 @@ pub mod response {
     todo!()
     }
 
-    pub fn command_station_report() -> PacketPayload {
+    pub fn command_station_report() -> OutgoingPacket {
         /*
         E3 Command Station status report (STAT)
         Format:
@@ -440,32 +453,32 @@ pub mod response {
     pub mod error {
         use vlcb_core::dcc::LocoAddress;
         use vlcb_defs::{DccError, OpCode};
-        use super::super::super::{construct, PacketPayload};
+        use super::super::super::{construct, OutgoingPacket};
 
         /// Loco stack full error
-        pub fn loco_stack_full(loco_addr: LocoAddress) -> PacketPayload {
+        pub fn loco_stack_full(loco_addr: LocoAddress) -> OutgoingPacket {
             let addr = loco_addr.as_bytes_sanitized();
             construct::three_bytes(OpCode::DccCommandStationError, addr[0], addr[1], DccError::LocoStackIsFull.into())
         }
 
         /// Loco address is already taken
-        pub fn loco_addr_taken(loco_addr: LocoAddress) -> PacketPayload {
+        pub fn loco_addr_taken(loco_addr: LocoAddress) -> OutgoingPacket {
             let addr = loco_addr.as_bytes_sanitized();
             construct::three_bytes(OpCode::DccCommandStationError, addr[0], addr[1], DccError::LocoAddressIsTaken.into())
         }
 
         /// Session is not present
-        pub fn session_not_found(session_id: u8) -> PacketPayload {
+        pub fn session_not_found(session_id: u8) -> OutgoingPacket {
             construct::three_bytes(OpCode::DccCommandStationError, session_id, 0, DccError::SessionIsNotPresent.into())
         }
 
         /// Consist is empty
-        pub fn consist_is_empty(session_id: u8) -> PacketPayload {
+        pub fn consist_is_empty(session_id: u8) -> OutgoingPacket {
             construct::three_bytes(OpCode::DccCommandStationError, session_id, 0, DccError::EmptyConsist.into())
         }
 
         /// Loco not found
-        pub fn loco_not_found(session_id: u8) -> PacketPayload {
+        pub fn loco_not_found(session_id: u8) -> OutgoingPacket {
             construct::three_bytes(OpCode::DccCommandStationError, session_id, 0, DccError::LocoWasNotFound.into())
         }
 
@@ -473,7 +486,7 @@ pub mod response {
         ///
         /// This would be sent out in the unlikely event that the command
         /// station buffers overflow.
-        pub fn rx_buffer_overflown() -> PacketPayload {
+        pub fn rx_buffer_overflown() -> OutgoingPacket {
             construct::three_bytes(OpCode::DccCommandStationError, 0, 0, DccError::RxBufferOverflow.into())
         }
 
@@ -481,7 +494,7 @@ pub mod response {
         ///
         /// Indicates an invalid or inconsistent request. For example, a GLOC
         /// request with both steal and share flags set.
-        pub fn invalid_request(loco_addr: LocoAddress) -> PacketPayload {
+        pub fn invalid_request(loco_addr: LocoAddress) -> OutgoingPacket {
             let addr = loco_addr.as_bytes_sanitized();
             construct::three_bytes(OpCode::DccCommandStationError, addr[0], addr[1], DccError::InvalidRequest.into())
         }
@@ -489,7 +502,7 @@ pub mod response {
         /// Session cancelled
         ///
         /// Sent to a cab to cancel the session when another cab is stealing that session.
-        pub fn session_cancelled(session_id: u8) -> PacketPayload {
+        pub fn session_cancelled(session_id: u8) -> OutgoingPacket {
             construct::three_bytes(OpCode::DccCommandStationError, session_id, 0, DccError::SessionWasCancelled.into())
         }
     }
@@ -497,20 +510,20 @@ pub mod response {
 
 pub mod ctrl {
     use vlcb_defs::OpCode;
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
 
     /// Track Off
     ///
     /// Commonly broadcasted to all nodes by a command station to indicate track
     /// power is off and no further command packets should be sent, except inquiries.
-    pub fn track_powered_off() -> PacketPayload {
+    pub fn track_powered_off() -> OutgoingPacket {
         construct::no_data(OpCode::DccTrackPoweredOff)
     }
 
     /// Track on
     ///
     /// Commonly broadcasted to all nodes by a command station to indicate track power is on.
-    pub fn track_powered_on() -> PacketPayload {
+    pub fn track_powered_on() -> OutgoingPacket {
         construct::no_data(OpCode::DccTrackPoweredOn)
     }
 
@@ -518,7 +531,7 @@ pub mod ctrl {
     ///
     /// Commonly broadcast to all nodes by a command station to indicate all
     /// engines have been emergency stopped.
-    pub fn emergency_stop_engaged() -> PacketPayload {
+    pub fn emergency_stop_engaged() -> OutgoingPacket {
         construct::no_data(OpCode::DccEmergencyStopEngaged)
     }
 }
@@ -1,28 +1,91 @@
-#![allow(warnings)] // TODO: remove this and fix warnings
-/**
- * This module holds useful helpers such as packet constructors
- * that define structure using higher level types
- * and map them to low level buffers.
- */
+//! This module holds useful helpers such as packet constructors
+//! that define structure using higher level types
+//! and map them to low level buffers.
 
 use heapless::Vec;
+use vlcb_defs::OpCode;
+
+use crate::wire::CanPriority;
 // TODO: tests
 // TODO: when implementations are finished, change names to more suitable and consistent formats
 
-// TODO: we should implement priority for CAN bus somewhere -> either by specifying it here or by match elsewhere -> preferably here
+/// A packet ready to be sent.
+///
+/// Holds the raw VLCB packet (the opcode followed by its data octets) and the CAN
+/// priority it should be sent with. The priority defaults to [`CanPriority::for_opcode`]
+/// and can be changed with [`with_priority`](OutgoingPacket::with_priority).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutgoingPacket {
+    pub payload: Vec<u8, 8>,
+    pub priority: CanPriority,
+}
+
+impl OutgoingPacket {
+    /// Return the opcode of the packet.
+    ///
+    /// Returns `None` for an empty packet or an opcode unknown to this library.
+    pub fn opcode(&self) -> Option<OpCode> {
+        self.payload
+            .first()
+            .and_then(|&opc| OpCode::try_from(opc).ok())
+    }
+
+    /// Override the default priority of the packet.
+    pub fn with_priority(mut self, priority: CanPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
 
-// TODO: since this sucker doesn't have much on it we should use some data type either from `wire` or `interface` module
-// so that we don't have to map data one more time
+#[deprecated(note = "renamed to `OutgoingPacket`, which also carries the packet priority")]
+pub type PacketPayload = OutgoingPacket;
 
-pub struct PacketPayload {
-    pub payload: Vec<u8, 8>,
+/// Error returned by the fallible packet constructors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConstructError {
+    /// The packet would not fit into the 8 octets of a VLCB packet.
+    PayloadTooLong(usize),
+    /// The length of a payload argument is outside of the range allowed by the packet.
+    InvalidPayloadLength(usize),
+    /// An argument is outside of its valid range.
+    InvalidArgument,
 }
 
+impl core::fmt::Display for ConstructError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ConstructError::PayloadTooLong(len) => write!(f, "packet too long ({} octets)", len),
+            ConstructError::InvalidPayloadLength(len) => {
+                write!(f, "invalid payload length ({} octets)", len)
+            }
+            ConstructError::InvalidArgument => write!(f, "invalid argument"),
+        }
+    }
+}
+
+/// Construct a packet from raw octets, the opcode followed by its data octets.
+///
+/// Returns `Err(ConstructError::PayloadTooLong)` if `data` is longer than 8 octets.
+pub fn try_new(data: &[u8]) -> Result<OutgoingPacket, ConstructError> {
+    let payload = Vec::from_slice(data).map_err(|_| ConstructError::PayloadTooLong(data.len()))?;
+    let priority = data
+        .first()
+        .and_then(|&opc| OpCode::try_from(opc).ok())
+        .map_or(CanPriority::default(), CanPriority::for_opcode);
+
+    Ok(OutgoingPacket { payload, priority })
+}
+
+#[allow(clippy::module_inception)]
 mod construct {
+    use vlcb_core::vlcb::{EventId, EVENT_SIZE};
     use vlcb_defs::OpCode;
-    use heapless::Vec;
 
-    use super::PacketPayload;
+    use super::{try_new, OutgoingPacket};
+    use crate::wire::VLCB_MAX_PAYLOAD;
 
     #[inline(never)]
     #[cold]
@@ -34,53 +97,82 @@ mod construct {
         );
     }
 
+    #[inline(never)]
+    #[cold]
+    #[track_caller]
+    pub(super) fn len_range_fail(payload_len: usize, gte: usize, lte: usize) -> ! {
+        panic!(
+            "payload slice length ({}) is outside of ({}..={})",
+            payload_len, gte, lte,
+        );
+    }
+
+    /// Return the event octets as sent on the bus.
+    ///
+    /// Short events carry only the device number, the node number octets are zeroed.
     #[inline]
-    pub(super) fn from_bytes(data: &[u8]) -> PacketPayload {
-        debug_assert!(data.len() < 9, "payload slice cannot be larger than 8 octets, given ({})", data.len());
+    pub(super) fn event_bytes(event: &EventId) -> [u8; EVENT_SIZE] {
+        let mut bytes = [0; EVENT_SIZE];
+        if event.is_short() {
+            bytes[2..].copy_from_slice(&event.as_bytes()[2..]);
+        } else {
+            bytes.copy_from_slice(event.as_bytes());
+        }
+        bytes
+    }
 
-        PacketPayload {
-            payload: Vec::from_slice(data).unwrap()
+    /// Construct a packet from raw octets.
+    ///
+    /// # Panics
+    /// This function panics if `data` is longer than 8 octets, in every build profile.
+    #[inline]
+    #[track_caller]
+    pub(super) fn new(data: &[u8]) -> OutgoingPacket {
+        match try_new(data) {
+            Ok(packet) => packet,
+            Err(_) => len_mismatch_fail(data.len(), VLCB_MAX_PAYLOAD),
         }
     }
 
     #[inline]
-    pub(super) fn no_data(opcode: OpCode) -> PacketPayload {
-        from_bytes(&[opcode.into()])
+    pub(super) fn no_data(opcode: OpCode) -> OutgoingPacket {
+        new(&[opcode.into()])
     }
 
     #[inline]
-    pub(super) fn one_byte(opcode: OpCode, a0: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0])
+    pub(super) fn one_byte(opcode: OpCode, a0: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0])
     }
 
     #[inline]
-    pub(super) fn two_bytes(opcode: OpCode, a0: u8, a1: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1])
+    pub(super) fn two_bytes(opcode: OpCode, a0: u8, a1: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1])
     }
 
     #[inline]
-    pub(super) fn three_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1, a2])
+    pub(super) fn three_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1, a2])
     }
 
     #[inline]
-    pub(super) fn four_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1, a2, a3])
+    pub(super) fn four_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1, a2, a3])
     }
 
     #[inline]
-    pub(super) fn five_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1, a2, a3, a4])
+    pub(super) fn five_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1, a2, a3, a4])
     }
 
     #[inline]
-    pub(super) fn six_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8, a5: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1, a2, a3, a4, a5])
+    pub(super) fn six_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8, a5: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1, a2, a3, a4, a5])
     }
 
     #[inline]
-    pub(super) fn seven_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8, a5: u8, a6: u8) -> PacketPayload {
-        from_bytes(&[opcode.into(), a0, a1, a2, a3, a4, a5, a6])
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn seven_bytes(opcode: OpCode, a0: u8, a1: u8, a2: u8, a3: u8, a4: u8, a5: u8, a6: u8) -> OutgoingPacket {
+        new(&[opcode.into(), a0, a1, a2, a3, a4, a5, a6])
     }
 }
 
@@ -94,13 +186,167 @@ pub mod ext;
 /// These should never be used in production builds!
 pub mod debug {
     use vlcb_defs::OpCode;
-    use super::{construct, PacketPayload};
+    use super::{construct, OutgoingPacket};
 
     /// Debug with one data byte
     ///
     /// The byte is a freeform status value for debugging during CBUS module development.
     /// Not used during normal operation
-    pub fn send_debug_data(data: u8) -> PacketPayload {
+    pub fn send_debug_data(data: u8) -> OutgoingPacket {
         construct::one_byte(OpCode::DebugMsg1, data)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::vlcb::{EventId, EventType, VlcbNodeNumber};
+
+    use super::*;
+
+    #[test]
+    fn test_default_priority() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+        let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+
+        assert_eq!(loco_ctrl::command::emergency_stop().priority, CanPriority::High);
+        assert_eq!(loco_ctrl::command::set_loco_throttle(1, 10, false).priority, CanPriority::High);
+        assert_eq!(loco_ctrl::ctrl::track_powered_off().priority, CanPriority::High);
+        assert_eq!(
+            layout_ctrl::produce::accessory(EventType::AccessoryOn, event, None).priority,
+            CanPriority::Normal
+        );
+        assert_eq!(layout_ctrl::query::accessory(event).priority, CanPriority::Normal);
+        assert_eq!(module_cfg::response::write_ack(node_num).priority, CanPriority::Low);
+        assert_eq!(module_cfg::command::restart(node_num).priority, CanPriority::Low);
+        assert_eq!(debug::send_debug_data(0x55).priority, CanPriority::Low);
+    }
+
+    #[test]
+    fn test_try_new_length() {
+        let data = [u8::from(OpCode::ExtOpCode6), 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        let packet = try_new(&data).unwrap();
+        assert_eq!(&packet.payload[..], &data);
+        assert_eq!(packet.opcode(), Some(OpCode::ExtOpCode6));
+
+        let data = [0u8; 9];
+        assert_eq!(try_new(&data), Err(ConstructError::PayloadTooLong(9)));
+    }
+
+    #[test]
+    fn test_try_accessory() {
+        let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+        assert!(layout_ctrl::produce::try_accessory(EventType::AccessoryOn, event, Some(&[0x01])).is_ok());
+        assert_eq!(
+            layout_ctrl::produce::try_accessory(EventType::AccessoryOn, event, Some(&[])),
+            Err(ConstructError::InvalidPayloadLength(0))
+        );
+        assert_eq!(
+            layout_ctrl::produce::try_accessory(EventType::AccessoryOn, event, Some(&[0; 4])),
+            Err(ConstructError::InvalidPayloadLength(4))
+        );
+    }
+
+    #[test]
+    fn test_try_send_dcc_packet() {
+        let packet = loco_ctrl::command::try_send_dcc_packet(2, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]).unwrap();
+        assert_eq!(
+            &packet.payload[..],
+            &[u8::from(OpCode::DccSendRawPacket6), 0x02, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]
+        );
+
+        assert_eq!(
+            loco_ctrl::command::try_send_dcc_packet(0, &[0x01, 0x02, 0x03]),
+            Err(ConstructError::InvalidArgument)
+        );
+        assert_eq!(
+            loco_ctrl::command::try_send_dcc_packet(1, &[0x01, 0x02]),
+            Err(ConstructError::InvalidPayloadLength(2))
+        );
+        assert_eq!(
+            loco_ctrl::command::try_send_dcc_packet(1, &[0; 7]),
+            Err(ConstructError::InvalidPayloadLength(7))
+        );
+    }
+
+    #[test]
+    fn test_new_boundary() {
+        let packet = construct::new(&[0u8; 8]);
+        assert_eq!(packet.payload.len(), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (9) is greater than (8)")]
+    fn test_new_too_long() {
+        construct::new(&[0u8; 9]);
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (4) is outside of (1..=3)")]
+    fn test_accessory_payload_too_long() {
+        let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+        layout_ctrl::produce::accessory(EventType::AccessoryOn, event, Some(&[0; 4]));
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (0) is outside of (1..=3)")]
+    fn test_accessory_payload_empty() {
+        let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+        layout_ctrl::produce::accessory(EventType::AccessoryOn, event, Some(&[]));
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (2) is outside of (3..=6)")]
+    fn test_send_dcc_packet_too_short() {
+        loco_ctrl::command::send_dcc_packet(1, &[0; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (7) is outside of (3..=6)")]
+    fn test_send_dcc_packet_too_long() {
+        loco_ctrl::command::send_dcc_packet(1, &[0; 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "repeat amount `times` must be greater or equal to 1")]
+    fn test_send_dcc_packet_zero_times() {
+        loco_ctrl::command::send_dcc_packet(0, &[0; 3]);
+    }
+
+    #[test]
+    fn test_ext() {
+        let packet = ext::from_bytes(0x10, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+        assert_eq!(
+            &packet.payload[..],
+            &[u8::from(OpCode::ExtOpCode6), 0x10, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]
+        );
+
+        let packet = ext::from_bytes(0x10, &[]);
+        assert_eq!(&packet.payload[..], &[u8::from(OpCode::ExtOpCode), 0x10]);
+        assert_eq!(packet, ext::no_data(0x10));
+    }
+
+    #[test]
+    #[should_panic(expected = "payload slice length (7) is greater than (6)")]
+    fn test_ext_too_long() {
+        ext::from_bytes(0x10, &[0; 7]);
+    }
+
+    #[test]
+    fn test_with_priority() {
+        let packet = bus_ctrl::ack().with_priority(CanPriority::High);
+        assert_eq!(packet.priority, CanPriority::High);
+        assert_eq!(packet.opcode(), Some(OpCode::GeneralAck));
+        assert_eq!(&packet.payload[..], &[u8::from(OpCode::GeneralAck)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let packet = bus_ctrl::ack().with_priority(CanPriority::High);
+        let json = serde_json::to_string(&packet).unwrap();
+        assert_eq!(serde_json::from_str::<OutgoingPacket>(&json).unwrap(), packet);
+
+        let bytes = postcard::to_allocvec(&packet).unwrap();
+        assert_eq!(postcard::from_bytes::<OutgoingPacket>(&bytes).unwrap(), packet);
+    }
+}
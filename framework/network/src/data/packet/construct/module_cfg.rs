@@ -1,13 +1,12 @@
 pub mod command {
     use vlcb_core::{can::VlcbCanId, vlcb::VlcbNodeNumber};
-    use vlcb_defs::{OpCode, CommandError};
-    use zerocopy::{ByteOrder, NetworkEndian};
-    use super::super::{construct, PacketPayload};
+    use vlcb_defs::OpCode;
+    use super::super::{construct, OutgoingPacket};
 
     /// System reset
     ///
     /// Commonly broadcasted to all nodes to indicate a full system reset.
-    pub fn restart_all_nodes() -> PacketPayload {
+    pub fn restart_all_nodes() -> OutgoingPacket {
         construct::no_data(OpCode::RestartAllNodes)
     }
 
@@ -15,7 +14,7 @@ pub mod command {
     ///
     /// Causes module to carry out a software reset to restart the firmware.
     /// No settings are affected.
-    pub fn restart(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn restart(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::RestartNode, bytes[0], bytes[1])
     }
@@ -23,7 +22,7 @@ pub mod command {
     /// Set Node Number
     ///
     /// Commonly broadcasted to all nodes to indicate a full system reset.
-    pub fn set_node_number(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn set_node_number(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::SetNodeNumber, bytes[0], bytes[1])
     }
@@ -35,7 +34,7 @@ pub mod command {
     /// What the manufacturers defaults are will be defined for each module, but should be
     /// equivalent to putting a new module into FLiM, with no events taught, only default events
     /// defined (if any) and all Nvs returned to their default values.
-    pub fn reset_to_factory(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn reset_to_factory(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::ResetModuleToFactory, bytes[0], bytes[1])
     }
@@ -46,7 +45,7 @@ pub mod command {
     /// number (NN). The node allocating node numbers responds with (SNN) which contains the
     /// newly assigned node number. The `node_num` is an the existing node number, if the
     /// node has one. If it does not yet have a node number, you should pass [`None`] into the argument.
-    pub fn allocate_node_number(node_num: Option<VlcbNodeNumber>) -> PacketPayload {
+    pub fn allocate_node_number(node_num: Option<VlcbNodeNumber>) -> OutgoingPacket {
             // If it does not yet have a node number, these bytes should be set to zero.
             let mut bytes = [0u8; 2];
 
@@ -61,7 +60,7 @@ pub mod command {
     ///
     /// Sent by a configuration tool to take node out of learn mode and revert to normal
     /// operation.
-    pub fn start_learn_mode(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn start_learn_mode(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::PutNodeIntoLearnMode, bytes[0], bytes[1])
     }
@@ -70,7 +69,7 @@ pub mod command {
     ///
     /// Sent by a configuration tool to take node out of learn mode and revert to normal
     /// operation.
-    pub fn end_learn_mode(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn end_learn_mode(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::ReleaseNodeFromLearnMode, bytes[0], bytes[1])
     }
@@ -80,7 +79,7 @@ pub mod command {
     /// For SliM nodes with no NN then the NN of the command is must be zero. For SLiM
     /// nodes with an NN, and all FLiM nodes the command must contain the NN of the target
     /// node. Sent by a configuration tool to prepare for loading a new program.
-    pub fn reboot_into_bootloader(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn reboot_into_bootloader(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::RebootIntoBootloader, bytes[0], bytes[1])
     }
@@ -91,7 +90,7 @@ pub mod command {
     /// for the specified node. A new CAN_ID will be allocated if needed. Following the [`OpCode::ENUM`]
     /// sequence, the node should issue a [`OpCode::NNACK`] to confirm completion and verify the new
     /// CAN_ID. If no CAN_ID values are available, an error message [`CommandError::INVALID_EVENT`] will be issued instead.
-    pub fn force_can_enumeration(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn force_can_enumeration(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::ForceCanEnumeration, bytes[0], bytes[1])
     }
@@ -101,20 +100,28 @@ pub mod command {
     /// Used to force a specified CAN_ID into a node. Value range is from 1 to 0x63 (99 decimal)
     /// This OPC must be used with care as duplicate CAN_IDs are not allowed.. Values outside
     /// the permitted range will produce an error 7 message.and the CAN_ID will not change.
-    pub fn set_can_id(node_num: VlcbNodeNumber, can_id: VlcbCanId) -> PacketPayload {
+    pub fn set_can_id(node_num: VlcbNodeNumber, can_id: VlcbCanId) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::SetNodeCanId, bytes[0], bytes[1], can_id.into())
     }
 
-    pub fn set_node_var(node_num: VlcbNodeNumber, nv_index: u8, value: u8) -> PacketPayload {
-        /*
-            Set a node variable (NVSET)
-            Format:
-            [<MjPri><MinPri=3><CANID>]<96><NN hi><NN lo><NV# ><NV val>
-            Sent by a configuration tool to set a node variable. NV# is the NV index
-            number.
-        */
-        todo!()
+    /// Set a node variable
+    ///
+    /// Sent by a configuration tool to set the node variable at `nv_index` (starting at 1)
+    /// to `value`. The node answers with [`super::response::write_ack`] when the write succeeded.
+    pub fn set_node_var(node_num: VlcbNodeNumber, nv_index: u8, value: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::four_bytes(OpCode::LegacySetNodeVariable, bytes[0], bytes[1], nv_index, value)
+    }
+
+    /// Clear all events
+    ///
+    /// Sent by a configuration tool to clear all events from a node in learn mode, the node
+    /// answers with [`super::response::write_ack`]. Outside of learn mode the node answers
+    /// with [`super::response::config_error`] and keeps its events.
+    pub fn clear_all_events(node_num: VlcbNodeNumber) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::two_bytes(OpCode::ForgetAllLearnedEvents, bytes[0], bytes[1])
     }
 }
 
@@ -122,13 +129,13 @@ pub mod query {
     use vlcb_core::vlcb::VlcbNodeNumber;
     use vlcb_defs::OpCode;
     use zerocopy::{ByteOrder, NetworkEndian};
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
 
     /// Query node number
     ///
     /// Sent by a node to elicit a PNN reply from each node on the bus that has a node number.
     /// See OpCode 0xB6
-    pub fn node_info() -> PacketPayload {
+    pub fn node_info() -> OutgoingPacket {
         construct::no_data(OpCode::QueryNodeInfo)
     }
 
@@ -136,7 +143,7 @@ pub mod query {
     ///
     /// Sent to a node while in ‘setup’ mode to read its parameter set. Used
     /// when initially configuring a node. See section 7.2.3 of the CBUS Developer's guide.
-    pub fn node_parameters() -> PacketPayload {
+    pub fn node_parameters() -> OutgoingPacket {
         construct::no_data(OpCode::QueryNodeParameters)
     }
 
@@ -144,7 +151,7 @@ pub mod query {
     ///
     /// Sent by a node to request the name of the type of module that is in setup mode. The
     /// module in setup mode will reply with opcode NAME. See OpCode 0xE2
-    pub fn module_name() -> PacketPayload {
+    pub fn module_name() -> OutgoingPacket {
         construct::no_data(OpCode::QueryModuleName)
     }
 
@@ -152,7 +159,7 @@ pub mod query {
     ///
     /// Sent by one node to read the data event from another node.(eg: RFID data).
     /// Response is 0xF7 ([`OpCode::ARDAT`]).
-    pub fn node_data(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn node_data(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::QueryNodeData, bytes[0], bytes[1])
     }
@@ -161,7 +168,7 @@ pub mod query {
     ///
     /// To request a ‘data set’ from a device using the short event method.
     /// Response is 0xFB ([`OpCode::DDRS`])
-    pub fn device_data(device_number: u16) -> PacketPayload {
+    pub fn device_data(device_number: u16) -> OutgoingPacket {
         // TODO: are we sure the `device_number` is not a `node_num` ?
         let mut bytes: [u8; 2] = [0u8; 2];
 
@@ -173,7 +180,7 @@ pub mod query {
     /// Request read of a node variable
     ///
     /// `index` is the index for the node variable value requested. Response is [`OpCode::NVANS`].
-    pub fn node_variable(node_num: VlcbNodeNumber, index: u8) -> PacketPayload {
+    pub fn node_variable(node_num: VlcbNodeNumber, index: u8) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::QueryNodeVariable, bytes[0], bytes[1], index)
     }
@@ -184,23 +191,50 @@ pub mod query {
     /// parameters.
     /// Response is 0x9B ([`OpCode::PARAN`]) See section 7.2.3 of the
     /// CBUS Developer's guide for details of the node parameters.
-    pub fn node_parameter(node_num: VlcbNodeNumber, index: u8) -> PacketPayload {
+    pub fn node_parameter(node_num: VlcbNodeNumber, index: u8) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::QueryNodeParameterByIndex, bytes[0], bytes[1], index)
     }
+
+    /// Request read of diagnostic data
+    ///
+    /// The service `index` 0 requests all diagnostics of all services, the diagnostic `code` 0
+    /// requests all diagnostics of the service at `index`.
+    /// Response is [`OpCode::DiagnosticData`].
+    pub fn diagnostics(node_num: VlcbNodeNumber, index: u8, code: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::four_bytes(OpCode::QueryDiagnosticData, bytes[0], bytes[1], index, code)
+    }
+
+    /// Read available event slots
+    ///
+    /// Response is [`super::response::available_event_slots`].
+    pub fn available_event_slots(node_num: VlcbNodeNumber) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::two_bytes(OpCode::QueryAvailableEventSlots, bytes[0], bytes[1])
+    }
+
+    /// Read number of stored events
+    ///
+    /// Response is [`super::response::saved_events_amount`].
+    pub fn learned_event_count(node_num: VlcbNodeNumber) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::two_bytes(OpCode::QueryLearnedEventCount, bytes[0], bytes[1])
+    }
 }
 
 pub mod response {
-    use vlcb_core::vlcb::VlcbNodeNumber;
-    use vlcb_defs::{CommandError, OpCode};
-    use super::super::{construct, PacketPayload};
+    use vlcb_core::module::PARAMS_REPORT_COUNT;
+    use vlcb_core::vlcb::{VlcbNodeNumber, VlcbResultCode};
+    use vlcb_defs::{CommandError, OpCode, ServiceType};
+    use super::super::{construct, OutgoingPacket};
 
     /// Write acknowledge
     ///
     /// Sent by a node to indicate the completion of a write to memory operation. All nodes must
     /// issue [`OpCode::WRACK`] when a write operation to node variables, events or event variables has
     /// completed. This allows for teaching nodes where the processing time may be slow.
-    pub fn write_ack(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn write_ack(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::WriteAck, bytes[0], bytes[1])
     }
@@ -209,15 +243,37 @@ pub mod response {
     ///
     /// Sent by node if there is an error when a configuration command is sent.
     /// See Section 12.4 of the CBUS developer's guide for details of the error codes.
-    pub fn config_error(node_num: VlcbNodeNumber, err: CommandError) -> PacketPayload {
+    pub fn config_error(node_num: VlcbNodeNumber, err: CommandError) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::NodeConfigurationError, bytes[0], bytes[1], err.into())
     }
 
+    /// Generic response
+    ///
+    /// Sent by a VLCB node in response to a command to report its outcome. The `opcode` is
+    /// the opcode of the command being responded to and `service` the service that
+    /// handled it. A `result` of [`VlcbResultCode::Ok`] means the command was carried out.
+    pub fn generic_response(
+        node_num: VlcbNodeNumber,
+        opcode: OpCode,
+        service: ServiceType,
+        result: VlcbResultCode,
+    ) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::five_bytes(
+            OpCode::GenericResponse,
+            bytes[0],
+            bytes[1],
+            opcode.into(),
+            service.into(),
+            result.into(),
+        )
+    }
+
     /// Event space left reply from node
     ///
     /// A one byte value giving the number of available events left in that node.
-    pub fn available_event_slots(node_num: VlcbNodeNumber, slots_available: u8) -> PacketPayload {
+    pub fn available_event_slots(node_num: VlcbNodeNumber, slots_available: u8) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::AvailableEventSlots, bytes[0], bytes[1], slots_available)
     }
@@ -225,56 +281,106 @@ pub mod response {
     /// Number of events stored in node
     ///
     /// Response to request 0x58 ([`OpCode::RQEVN`])
-    pub fn saved_events_amount(node_num: VlcbNodeNumber, saved_events: u8) -> PacketPayload {
+    pub fn saved_events_amount(node_num: VlcbNodeNumber, saved_events: u8) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::three_bytes(OpCode::LearnedEventCount, bytes[0], bytes[1], saved_events)
     }
 
     /// Response to a request for a node variable value
-    pub fn node_variable() -> PacketPayload {
-        /*
-            Response to a request for a node variable value (NVANS)
-            Format:
-            [<MjPri><MinPri=3><CANID>]<97><NN hi><NN lo><NV# ><NV val>
-            Sent by node in response to request. (NVRD)
-        */
-        todo!()
+    ///
+    /// Sent by a node in response to [`OpCode::QueryNodeVariable`] with the `value` of the
+    /// node variable at `index`.
+    pub fn node_variable(node_num: VlcbNodeNumber, index: u8, value: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::four_bytes(OpCode::NodeVariableValue, bytes[0], bytes[1], index, value)
     }
 
     /// Response to request for individual node parameter
-    pub fn node_parameter() -> PacketPayload {
-        todo!()
-        /*
-         * Response to request for individual node parameter (PARAN)
-            Format:
-            [<MjPri><MinPri=3><CANID>]<9B><NN hi><NN lo><Para#><Para val>
-            NN is the node number of the sending node. Para# is the index of the parameter and
-            Para val is the parameter value.
-        */
+    ///
+    /// `index` is the index of the parameter as requested by [`OpCode::QueryNodeParameterByIndex`]
+    /// and `value` the parameter value. Index 0 carries the number of parameters.
+    pub fn node_parameter(node_num: VlcbNodeNumber, index: u8, value: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::four_bytes(OpCode::NodeParameterValue, bytes[0], bytes[1], index, value)
     }
 
-    pub fn node_info() -> PacketPayload {
-    //             Response to Query Node (PNN)
-        // Format:
-        // [<MjPri><MinPri=3><CANID>]<B6><NN Hi><NN Lo><Manuf Id><Module Id><Flags>
-        // <NN Hi> is the high byte of the node number
-        // <NN Lo> is the low byte of the node number
-        // <Manuf Id> is the Manufacturer id as defined in the node parameters
-        // <Module Id> is the Module Type Id id as defined in the node parameters
-        // <Flags> is the node flags as defined in the node parameters, see Section 7.2.3.
-        // The Flags byte contains bit flags as follows:
-        // Bit 0: Set to 1 for consumer node
-        // Bit 1: Set to 1 for producer node
-        // Bit 2: Set to 1 for FLiM mode
-        // Bit 3: Set to 1 for Bootloader compatible
-        // If a module is both a producer and a consumer then it is referred to as a “combi” node and
-        // both flags will be set.
-        // Every node should send this message in response to a QNN message.
-        todo!()
+    /// Service discovery response with the number of services
+    ///
+    /// First reply to [`OpCode::ServiceDiscoveryQuery`] with the service index 0, followed by
+    /// a [`service_discovery`] response for each of the services.
+    pub fn service_count(node_num: VlcbNodeNumber, count: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::five_bytes(OpCode::ServiceDiscoveryResponse, bytes[0], bytes[1], 0, 0, count)
+    }
+
+    /// Service discovery response
+    ///
+    /// The `service` at the service `index` (starting at 1) is supported by the node
+    /// in the `version`.
+    pub fn service_discovery(
+        node_num: VlcbNodeNumber,
+        index: u8,
+        service: ServiceType,
+        version: u8,
+    ) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::five_bytes(
+            OpCode::ServiceDiscoveryResponse,
+            bytes[0],
+            bytes[1],
+            index,
+            service.into(),
+            version,
+        )
+    }
+
+    /// Extended service discovery response
+    ///
+    /// Reply to [`OpCode::ServiceDiscoveryQuery`] with a non-zero service `index`,
+    /// the `data` are service specific.
+    pub fn extended_service_discovery(
+        node_num: VlcbNodeNumber,
+        index: u8,
+        service: ServiceType,
+        data: [u8; 3],
+    ) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        let [d1, d2, d3] = data;
+        construct::seven_bytes(
+            OpCode::ExtendedServiceDiscoveryResponse,
+            bytes[0],
+            bytes[1],
+            index,
+            service.into(),
+            d1,
+            d2,
+            d3,
+        )
     }
 
-    pub fn node_name() -> PacketPayload {
-        /**
+    /// Diagnostic data response
+    ///
+    /// The `value` of the diagnostic `code` of the service at the service `index`
+    /// (starting at 1). Reply to [`OpCode::QueryDiagnosticData`].
+    pub fn diagnostic(node_num: VlcbNodeNumber, index: u8, code: u8, value: u16) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        let [hi, lo] = value.to_be_bytes();
+        construct::six_bytes(OpCode::DiagnosticData, bytes[0], bytes[1], index, code, hi, lo)
+    }
+
+    /// Response to Query Node (PNN)
+    ///
+    /// The `manufacturer` and `module_id` are the values of the node parameters, the `flags`
+    /// is the node flags parameter (consumer, producer, FLiM and bootloader bits).
+    /// Every node with a node number sends this message in response to
+    /// [`OpCode::QueryNodeInfo`].
+    pub fn node_info(node_num: VlcbNodeNumber, manufacturer: u8, module_id: u8, flags: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        construct::five_bytes(OpCode::NodeInfo, bytes[0], bytes[1], manufacturer, module_id, flags)
+    }
+
+    pub fn node_name() -> OutgoingPacket {
+        /*
          * Format:
             [<MjPri><MinPri=3><CANID>]<E2><char1><char2><char3><char4>
             <char5><char6><char7>
@@ -287,35 +393,27 @@ pub mod response {
         todo!()
     }
 
-    pub fn node_params() -> PacketPayload {
-    //             Response to request for node parameters (PARAMS)
-        // Format:
-        // [<MjPri><MinPri=3><CANID>]<EF><PARA 1><PARA 2><PARA 3>
-        // <PARA 4><PARA 5><PARA 6><PARA 7>
-        // A node response while in ‘setup’ mode for its parameter string. Reply to (RQNP)
-
-        // _msg.len = 8;
-        //           _msg.data[0] = OPC_PARAMS;    // opcode
-        //           _msg.data[1] = _mparams[1];     // manf code -- MERG
-        //           _msg.data[2] = _mparams[2];     // minor code ver
-        //           _msg.data[3] = _mparams[3]little;     // module ident
-        //           _msg.data[4] = _mparams[4];     // number of events
-        //           _msg.data[5] = _mparams[5];     // events vars per event
-        //           _msg.data[6] = _mparams[6];     // number of NVs
-        //           _msg.data[7] = _mparams[7];     // major code ver
-        todo!()
+    /// Response to request for node parameters
+    ///
+    /// A node response while in ‘setup’ mode for its parameter string. Reply to
+    /// [`OpCode::QueryNodeParameters`]. `params` are the first 7 node parameters: manufacturer,
+    /// minor version, module type, number of events, event variables per event,
+    /// number of node variables and major version.
+    pub fn node_params(params: [u8; PARAMS_REPORT_COUNT]) -> OutgoingPacket {
+        let [p1, p2, p3, p4, p5, p6, p7] = params;
+        construct::seven_bytes(OpCode::NodeParametersReport, p1, p2, p3, p4, p5, p6, p7)
     }
 }
 
 pub mod ctrl {
     use vlcb_core::vlcb::VlcbNodeNumber;
     use vlcb_defs::OpCode;
-    use super::super::{construct, PacketPayload};
+    use super::super::{construct, OutgoingPacket};
 
     /// Node number release
     ///
     /// Sent by node when taken out of service. e.g. when reverting to SLiM mode.
-    pub fn release_node_number(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn release_node_number(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::NodeNumberReleased, bytes[0], bytes[1])
     }
@@ -324,8 +422,155 @@ pub mod ctrl {
     ///
     /// Sent by a node to verify its presence and confirm its node id. This message is sent to
     /// acknowledge an [`OpCode::SNN`].
-    pub fn ack_node_number(node_num: VlcbNodeNumber) -> PacketPayload {
+    pub fn ack_node_number(node_num: VlcbNodeNumber) -> OutgoingPacket {
         let bytes = node_num.as_bytes();
         construct::two_bytes(OpCode::NodeNumberAck, bytes[0], bytes[1])
     }
+
+    /// Heartbeat
+    ///
+    /// Sent periodically by a node to confirm it is alive. The `sequence` counts the heartbeats
+    /// sent, wrapping around to zero, the `status` 0 represents normal operation.
+    pub fn heartbeat(node_num: VlcbNodeNumber, sequence: u8, status: u8) -> OutgoingPacket {
+        let bytes = node_num.as_bytes();
+        // the status bits are reserved and always 0
+        construct::five_bytes(OpCode::Heartbeat, bytes[0], bytes[1], sequence, status, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::vlcb::{VlcbNodeNumber, VlcbResultCode};
+    use vlcb_defs::{CommandError, OpCode, ServiceType};
+
+    use super::{command, ctrl, query, response};
+
+    #[test]
+    fn test_config_error() {
+        let packet = response::config_error(VlcbNodeNumber::new(0x01, 0x02), CommandError::InvalidNvIndex);
+        assert_eq!(&packet.payload[..], &[0x6F, 0x01, 0x02, 0x0A]);
+    }
+
+    #[test]
+    fn test_generic_response_ok() {
+        let packet = response::generic_response(
+            VlcbNodeNumber::new(0x01, 0x02),
+            OpCode::SetNodeVariable,
+            ServiceType::NodeVariable,
+            VlcbResultCode::Ok,
+        );
+        assert_eq!(&packet.payload[..], &[0xAF, 0x01, 0x02, 0x8E, 0x02, 0x00]);
+    }
+
+    #[test]
+    fn test_generic_response_errors() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = response::generic_response(
+            node_num,
+            OpCode::SetNodeVariable,
+            ServiceType::NodeVariable,
+            VlcbResultCode::InvalidNvIndex,
+        );
+        assert_eq!(&packet.payload[..], &[0xAF, 0x01, 0x02, 0x8E, 0x02, 0x0A]);
+
+        let packet = response::generic_response(
+            node_num,
+            OpCode::TeachEvent,
+            ServiceType::EventTeaching,
+            VlcbResultCode::NotInLearnMode,
+        );
+        assert_eq!(&packet.payload[..], &[0xAF, 0x01, 0x02, 0xD2, 0x07, 0x02]);
+
+        let packet = response::generic_response(
+            node_num,
+            OpCode::PutNodeIntoMode,
+            ServiceType::MinimumNodeService,
+            VlcbResultCode::InvalidMode,
+        );
+        assert_eq!(&packet.payload[..], &[0xAF, 0x01, 0x02, 0x76, 0x01, 0xFA]);
+    }
+
+    #[test]
+    fn test_node_variable() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = command::set_node_var(node_num, 3, 0x10);
+        assert_eq!(&packet.payload[..], &[0x96, 0x01, 0x02, 0x03, 0x10]);
+
+        let packet = response::node_variable(node_num, 3, 0x10);
+        assert_eq!(&packet.payload[..], &[0x97, 0x01, 0x02, 0x03, 0x10]);
+    }
+
+    #[test]
+    fn test_node_parameter() {
+        let packet = response::node_parameter(VlcbNodeNumber::new(0x01, 0x02), 0, 20);
+        assert_eq!(&packet.payload[..], &[0x9B, 0x01, 0x02, 0x00, 0x14]);
+    }
+
+    #[test]
+    fn test_service_discovery() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = response::service_count(node_num, 2);
+        assert_eq!(&packet.payload[..], &[0xAC, 0x01, 0x02, 0x00, 0x00, 0x02]);
+
+        let packet = response::service_discovery(node_num, 2, ServiceType::NodeVariable, 1);
+        assert_eq!(&packet.payload[..], &[0xAC, 0x01, 0x02, 0x02, 0x02, 0x01]);
+
+        let packet = response::extended_service_discovery(
+            node_num,
+            1,
+            ServiceType::MinimumNodeService,
+            [0x0A, 0x0B, 0x0C],
+        );
+        assert_eq!(&packet.payload[..], &[0xE7, 0x01, 0x02, 0x01, 0x01, 0x0A, 0x0B, 0x0C]);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = query::diagnostics(node_num, 1, 0);
+        assert_eq!(&packet.payload[..], &[0x87, 0x01, 0x02, 0x01, 0x00]);
+
+        let packet = response::diagnostic(node_num, 1, 2, 0x0304);
+        assert_eq!(&packet.payload[..], &[0xC7, 0x01, 0x02, 0x01, 0x02, 0x03, 0x04]);
+    }
+
+    #[test]
+    fn test_node_info() {
+        let packet = response::node_info(VlcbNodeNumber::new(0x01, 0x02), 0x0D, 0xFC, 0x44);
+        assert_eq!(&packet.payload[..], &[0xB6, 0x01, 0x02, 0x0D, 0xFC, 0x44]);
+    }
+
+    #[test]
+    fn test_event_counts() {
+        let node_num = VlcbNodeNumber::new(0x01, 0x02);
+
+        let packet = command::clear_all_events(node_num);
+        assert_eq!(&packet.payload[..], &[0x55, 0x01, 0x02]);
+
+        let packet = query::available_event_slots(node_num);
+        assert_eq!(&packet.payload[..], &[0x56, 0x01, 0x02]);
+        let packet = response::available_event_slots(node_num, 30);
+        assert_eq!(&packet.payload[..], &[0x70, 0x01, 0x02, 0x1E]);
+
+        let packet = query::learned_event_count(node_num);
+        assert_eq!(&packet.payload[..], &[0x58, 0x01, 0x02]);
+        let packet = response::saved_events_amount(node_num, 2);
+        assert_eq!(&packet.payload[..], &[0x74, 0x01, 0x02, 0x02]);
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let packet = ctrl::heartbeat(VlcbNodeNumber::new(0x01, 0x02), 7, 0);
+        assert_eq!(&packet.payload[..], &[0xAB, 0x01, 0x02, 0x07, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_node_params() {
+        let packet = response::node_params([0xA5, b'a', 0xFC, 8, 2, 4, 1]);
+        assert_eq!(&packet.payload[..], &[0xEF, 0xA5, b'a', 0xFC, 8, 2, 4, 1]);
+    }
 }
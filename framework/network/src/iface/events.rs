@@ -0,0 +1,109 @@
+use heapless::Deque;
+
+#[cfg(feature = "medium-can")]
+use vlcb_core::can::VlcbCanId;
+
+/// Maximum amount of interface events kept until they are drained.
+pub const INTERFACE_EVENT_QUEUE_SIZE: usize = 8;
+
+/// A notable change of the interface state.
+///
+/// Events are queued by the interface while it is polled and drained with
+/// [`Interface::poll_events`](super::Interface::poll_events).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum InterfaceEvent {
+    /// Another node transmitted a frame with our CAN ID.
+    ///
+    /// A CAN ID enumeration is started to find a free one.
+    #[cfg(feature = "medium-can")]
+    CanIdConflict { other_frame_opcode: u8 },
+
+    /// The CAN ID enumeration finished and the interface uses the new CAN ID.
+    #[cfg(feature = "medium-can")]
+    CanEnumerationCompleted { new_id: VlcbCanId },
+
+    /// The CAN ID enumeration finished without finding a free CAN ID.
+    ///
+    /// The interface keeps using the previous CAN ID.
+    #[cfg(feature = "medium-can")]
+    CanEnumerationFailed,
+
+    /// The link to the bus was lost, see [`Interface::link_state`](super::Interface::link_state).
+    LinkDown,
+
+    /// The link to the bus works again.
+    LinkUp,
+
+    /// A packet was dropped because the deferred queue of the interface was full, see
+    /// [`Context::enqueue_reply`](super::Context::enqueue_reply).
+    DeferredQueueFull,
+}
+
+/// A fixed capacity queue of interface events.
+///
+/// When the queue is full the oldest event is dropped to make room for the new one.
+#[derive(Debug)]
+pub(crate) struct EventQueue {
+    events: Deque<InterfaceEvent, INTERFACE_EVENT_QUEUE_SIZE>,
+    lost: bool,
+}
+
+impl EventQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            events: Deque::new(),
+            lost: false,
+        }
+    }
+
+    /// Queue an event, overwriting the oldest one when the queue is full.
+    pub(crate) fn push(&mut self, event: InterfaceEvent) {
+        if self.events.is_full() {
+            net_debug!("iface: event queue full, dropping the oldest event");
+            self.events.pop_front();
+            self.lost = true;
+        }
+        // note(discard): there is always room after the oldest event was dropped
+        let _ = self.events.push_back(event);
+    }
+
+    /// Dequeue the oldest event.
+    pub(crate) fn pop(&mut self) -> Option<InterfaceEvent> {
+        self.events.pop_front()
+    }
+
+    /// Return whether events were dropped since the last call, and reset the flag.
+    pub(crate) fn take_lost(&mut self) -> bool {
+        core::mem::take(&mut self.lost)
+    }
+}
+
+#[cfg(all(test, feature = "medium-can"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_queue_overwrites_oldest() {
+        let mut queue = EventQueue::new();
+        for opcode in 0..INTERFACE_EVENT_QUEUE_SIZE as u8 {
+            queue.push(InterfaceEvent::CanIdConflict { other_frame_opcode: opcode });
+        }
+        assert!(!queue.take_lost());
+
+        queue.push(InterfaceEvent::CanEnumerationFailed);
+        assert!(queue.take_lost());
+        assert!(!queue.take_lost(), "the flag is reset");
+
+        assert_eq!(
+            queue.pop(),
+            Some(InterfaceEvent::CanIdConflict { other_frame_opcode: 1 })
+        );
+        let rest: heapless::Vec<_, INTERFACE_EVENT_QUEUE_SIZE> =
+            core::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(rest.len(), INTERFACE_EVENT_QUEUE_SIZE - 1);
+        assert_eq!(rest.last(), Some(&InterfaceEvent::CanEnumerationFailed));
+        assert_eq!(queue.pop(), None);
+    }
+}
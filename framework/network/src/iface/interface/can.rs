@@ -1,172 +1,855 @@
 use super::DispatchError;
 use super::InterfaceInner;
 use super::{check, PollContext};
-use crate::iface::vlcb_packet::VlcbPacket;
+use crate::iface::events::InterfaceEvent;
+use crate::iface::vlcb_packet::{CanControl, InterfacePacket};
+use embedded_time::duration::Milliseconds;
 use embedded_time::{Clock, Instant};
+use heapless::{Deque, Vec};
+use vlcb_core::can::{VlcbCanId, CANID_MAX, CANID_MIN};
+use vlcb_core::time::millis;
 
+use crate::phy::can::FRAME_LEN;
 use crate::phy::{Device, TxToken};
 use crate::iface::socket_set::SocketSet;
-use crate::wire::{CanFrame, VlcbPacketWire};
+use crate::wire::{CanExtendedFrame, CanFrame, HardwareAddress, VlcbPacketWire};
 
-
-// TODO: when sending any packets we need to add priority to them!!!
-
-/*pub(super) enum CanControlEvent<C: Clock> {
-    Poll,
-    RequestEnumeration { now: Instant<C> },
-}
-
-#[derive(Debug, Copy, Clone, Default)]
-pub(super) enum CanControlState<C: Clock> {
-    #[default]
+/// State of the CAN ID self-enumeration.
+pub(super) enum Enumeration<C: Clock> {
     Idle,
-    StartingEnumeration {
-        started_at: Instant<C>,
-    },
-    Enumerating {
-        started_at: Instant<C>,
+    /// A CAN ID conflict was detected, the enumeration request is to be sent.
+    Required,
+    /// The enumeration request was sent, collecting the CAN IDs of the responding nodes.
+    InProgress {
+        deadline: Instant<C>,
         responses: u128,
     },
 }
 
-impl<C: Clock> CanControlState<C> {
-    pub(super) fn consume(self, evt: CanControlEvent<C>) -> Self {
-        match (self, evt) {
-            (Self::Idle, CanControlEvent::RequestEnumeration { now }) => {
-                Self::StartingEnumeration { started_at: now }
-            }
-            (Self::StartingEnumeration { started_at }, CanControlEvent::Poll) => {
-                Self::Enumerating {
-                    started_at,
-                    // CAN protocol should choose the lowest vacant value, but ID 0 is reserved
-                    // for SLiM mode consumer nodes so by default we need to start at 1.
-                    responses: 1,
-                }
-            }
-            (x, _) => x,
+/// Amount of transmitted frames whose echo is awaited, see [`OwnFrames`].
+const OWN_FRAMES_PENDING: usize = 8;
+
+/// The frames transmitted on a device that [reports own frames], to recognize their echoes.
+///
+/// An echo is told apart from a frame of another node with the same CAN ID by its content,
+/// so the CAN ID conflicts are still detected. The oldest frame is forgotten when its echo
+/// does not arrive in time.
+///
+/// [reports own frames]: crate::phy::DeviceCapabilities::reports_own_frames
+pub(super) struct OwnFrames {
+    pub(super) enabled: bool,
+    pending: Deque<Vec<u8, FRAME_LEN>, OWN_FRAMES_PENDING>,
+}
+
+impl Default for OwnFrames {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pending: Deque::new(),
+        }
+    }
+}
+
+impl OwnFrames {
+    fn remember(&mut self, frame: Vec<u8, FRAME_LEN>) {
+        if self.pending.is_full() {
+            self.pending.pop_front();
+        }
+        let _ = self.pending.push_back(frame);
+    }
+
+    /// Forget the transmitted `frame` and return `true` if the frame is its echo.
+    ///
+    /// The frames transmitted before it are forgotten as well, their echoes were lost.
+    fn take_echo(&mut self, frame: &[u8]) -> bool {
+        let Some(position) = self.pending.iter().position(|f| f[..] == *frame) else {
+            return false;
+        };
+        for _ in 0..=position {
+            self.pending.pop_front();
         }
+        true
     }
-}*/
+
+    pub(super) fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+impl<C: Clock> Enumeration<C> {
+    /// Return the lowest assignable CAN ID not taken by any of the responding nodes.
+    fn lowest_vacant(responses: u128) -> Option<VlcbCanId> {
+        (CANID_MIN..=CANID_MAX)
+            .find(|id| responses & (1 << id) == 0)
+            .map(|id| VlcbCanId::from_bytes(&[id]))
+    }
+}
 
 impl<C: Clock> InterfaceInner<C> {
     #[cfg(feature = "medium-can")]
     pub(super) fn process_can<'frame>(
         &mut self,
         sockets: &mut SocketSet<'_>,
-        frame: &[u8],
-    ) -> Option<VlcbPacket<'frame>> {
-        let can_frame = check!(CanFrame::new_checked(frame));
+        frame: &'frame [u8],
+    ) -> Option<InterfacePacket<'frame>> {
+        // Frames of other protocols on the bus, e.g. the CBUS bootloader, bypass the sockets.
+        if self.caps.accepts_extended {
+            if let Ok(extended) = CanExtendedFrame::new_checked(frame) {
+                match self.extended_frame_handler {
+                    Some(handler) => handler(extended),
+                    None => net_trace!("iface: no handler for the extended frame, skipping it"),
+                }
+                return None;
+            }
+        }
 
-        let vlcb_packet = check!(VlcbPacketWire::new_checked(can_frame.payload()));
+        let can_frame = check!(
+            self.stats.count_malformed(CanFrame::new_checked(frame)),
+            "iface: malformed CAN frame, {} octets",
+            frame.len()
+        );
+        let remote_id = can_frame.src_addr();
+
+        // The device hands our own frames back, they must not reach our sockets.
+        if self.ignores_own_frames() && !can_frame.is_rtr() && self.own_frames.take_echo(frame) {
+            net_trace!("iface: dropping the echo of our own frame");
+            self.stats.record_own_frame();
+            return None;
+        }
+
+        // Enumeration request from another node, announce our CAN ID.
+        if can_frame.is_rtr() {
+            net_trace!("iface: enumeration request from CAN ID {}", remote_id);
+            return Some(InterfacePacket::CanControl(CanControl::EnumerationResponse));
+        }
+
+        // Zero-length frames are the responses to an enumeration request.
+        if can_frame.payload().is_empty() {
+            if let Enumeration::InProgress { responses, .. } = &mut self.can_enumeration {
+                *responses |= 1 << remote_id.0[0];
+            }
+            return None;
+        }
+
+        // CAN ID 0 is never assigned by the enumeration, there is nothing to resolve until
+        // the interface has one.
+        let own_id = self.hw_addr.can_or_panic();
+        if remote_id == own_id && u8::from(own_id) != 0 {
+            net_debug!("iface: CAN ID {} conflict, enumeration required", own_id);
+            self.events.push(InterfaceEvent::CanIdConflict {
+                other_frame_opcode: can_frame.payload()[0],
+            });
+            if let Enumeration::Idle = self.can_enumeration {
+                self.can_enumeration = Enumeration::Required;
+            }
+        }
+
+        let vlcb_packet = check!(
+            self.stats.count_malformed(VlcbPacketWire::new_checked(can_frame.payload())),
+            "iface: malformed VLCB packet from CAN ID {}, opcode {}, {} octets",
+            remote_id,
+            can_frame.payload()[0],
+            can_frame.payload().len()
+        );
 
         /*
+          switch OPC from frame
+        case OPC_ENUM:
+          // received ENUM -- start CAN bus self-enumeration
+          // DEBUG_SERIAL << F("> ENUM message for nn = ") << nn << F(" from CANID = ") << remoteCANID << endl;
+          // DEBUG_SERIAL << F("> my nn = ") << module_config->nodeNum << endl;
+
+          if (nn == module_config->nodeNum && remoteCANID != module_config->CANID && !bCANenum) {
+            // DEBUG_SERIAL << F("> initiating enumeration") << endl;
+            CANenumeration();
+          }
 
-           //
-          /// set flag if we find a CANID conflict with the frame's producer
-          /// doesn't apply to RTR or zero-length frames, so as not to trigger an enumeration loop
-          //
+          break;
 
+        */
 
-          //
-          /// extract the CANID of the sending module
-          //
+        self.process_vlcb(sockets, HardwareAddress::CAN(remote_id), &vlcb_packet)
+            .map(InterfacePacket::Vlcb)
+    }
 
-          remoteCANID = getCANID(_msg.id);
+    /// Drive the CAN ID self-enumeration.
+    ///
+    /// Sends the enumeration request once a conflict was detected, and assigns the lowest
+    /// vacant CAN ID after the [reserve delay](super::InterfaceConfig::can_reserve_delay_ms) of
+    /// collecting the responses.
+    /// Returns the amount of transmitted frames.
+    #[cfg(feature = "medium-can")]
+    pub(super) fn poll_can_enumeration<D>(&mut self, now: Instant<C>, device: &mut D) -> usize
+    where
+        D: Device + ?Sized,
+    {
+        match self.can_enumeration {
+            Enumeration::Idle => 0,
+            Enumeration::Required => {
+                let request = InterfacePacket::CanControl(CanControl::EnumerationRequest);
+                if let Err(err) = self.transmit_now(device, request) {
+                    net_debug!("iface: failed to send CAN enumeration request: {:?}", err);
+                    return 0;
+                }
 
-        // start bus enumeration if required
-        if (enumeration_required) {
-          enumeration_required = false;
-          CANenumeration();
+                let delay = millis::<C>(Milliseconds(self.config.can_reserve_delay_ms as u32));
+                self.can_enumeration = Enumeration::InProgress {
+                    deadline: now + delay,
+                    responses: 0,
+                };
+                1
+            }
+            Enumeration::InProgress { deadline, responses } => {
+                if now < deadline {
+                    return 0;
+                }
+
+                let event = match Enumeration::<C>::lowest_vacant(responses) {
+                    Some(new_id) => {
+                        net_debug!("iface: CAN enumeration completed, new CAN ID {}", new_id);
+                        self.hw_addr = HardwareAddress::CAN(new_id);
+                        InterfaceEvent::CanEnumerationCompleted { new_id }
+                    }
+                    None => {
+                        net_debug!("iface: CAN enumeration failed, no vacant CAN ID");
+                        InterfaceEvent::CanEnumerationFailed
+                    }
+                };
+                self.events.push(event);
+                self.can_enumeration = Enumeration::Idle;
+                0
+            }
         }
+    }
 
-        // check CAN bus enumeration timer
-        checkCANenum();
+    /// Emit a header-only CAN frame.
+    #[cfg(feature = "medium-can")]
+    pub(super) fn dispatch_can_control<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        control: CanControl,
+    ) -> Result<(), DispatchError> {
+        self.dispatch_can(tx_token, 0, |mut frame| {
+            frame.set_rtr(control == CanControl::EnumerationRequest)
+        })
+    }
 
+    /// Allocate a CAN frame for a payload of `buffer_len` octets, fill in the header
+    /// and let `f` emit the rest of the frame.
+    ///
+    /// Payloads larger than the device MTU are not transmitted at all.
+    #[cfg(feature = "medium-can")]
+    pub(super) fn dispatch_can<Tx, F>(
+        &mut self,
+        tx_token: Tx,
+        buffer_len: usize,
+        f: F,
+    ) -> Result<(), DispatchError>
+    where
+        Tx: TxToken,
+        F: FnOnce(CanFrame<&mut [u8]>),
+    {
+        if buffer_len > self.caps.max_transmission_unit {
+            net_debug!("iface: {} octets do not fit into a CAN frame", buffer_len);
+            return self.stats.count_dispatch(Err(DispatchError::BufferTooSmall));
+        }
 
-          // is this a CANID enumeration request from another node (RTR set) ?
-          if (_msg.rtr) {
-            // DEBUG_SERIAL << F("> CANID enumeration RTR from CANID = ") << remoteCANID << endl;
-            // send an empty message to show our CANID
-            _msg.len = 0;
-            sendMessage(&_msg);
-            continue;
-          }
+        let tx_len = CanFrame::<&[u8]>::buffer_len(buffer_len);
+        let remember = self.ignores_own_frames();
+        let result = tx_token.try_consume(tx_len, |tx_buffer| {
+            if tx_buffer.len() < tx_len {
+                return Err(DispatchError::BufferTooSmall);
+            }
+            let mut frame = CanFrame::new_unchecked(&mut *tx_buffer);
 
-        if (remoteCANID == module_config->CANID && _msg.len > 0) {
-            // DEBUG_SERIAL << F("> CAN id clash, enumeration required") << endl;
-            enumeration_required = true;
-          }
+            frame.set_src_addr(self.hw_addr.can_or_panic());
+            frame.set_major_priority(self.config.can_default_priority >> 2);
 
-          // are we enumerating CANIDs ?
-          if (bCANenum && _msg.len == 0) {
+            f(frame);
 
-            // store this response in the responses array
-            if (remoteCANID > 0) {
-              // fix to correctly record the received CANID
-              bitWrite(enum_responses[(remoteCANID / 16)], remoteCANID % 8, 1);
-              // DEBUG_SERIAL << F("> stored CANID ") << remoteCANID << F(" at index = ") << (remoteCANID / 8) << F(", bit = ") << (remoteCANID % 8) << endl;
+            // A copy of the frame to recognize its echo
+            match remember {
+                true => Ok(Vec::from_slice(&tx_buffer[..tx_len]).ok()),
+                false => Ok(None),
             }
+        });
+        let echo = self.count_tx(result)?;
+        if let Some(frame) = echo {
+            self.own_frames.remember(frame);
+        }
+        Ok(())
+    }
 
-            continue;
-          }
+    /// Emit a CAN frame with an extended identifier, see [`Interface::send_extended_now`].
+    ///
+    /// [`Interface::send_extended_now`]: super::Interface::send_extended_now
+    #[cfg(feature = "medium-can")]
+    pub(super) fn dispatch_can_extended<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        id: u32,
+        data: &[u8],
+    ) -> Result<(), DispatchError> {
+        if data.len() > self.caps.max_transmission_unit {
+            net_debug!("iface: {} octets do not fit into a CAN frame", data.len());
+            return self.stats.count_dispatch(Err(DispatchError::BufferTooSmall));
+        }
 
-          switch OPC from frame
-          case OPC_CANID:
-              // CAN -- set CANID
-              // DEBUG_SERIAL << F("> CANID for nn = ") << nn << F(" with new CANID = ") << _msg.data[3] << endl;
-
-              if (nn == module_config->nodeNum) {
-                // DEBUG_SERIAL << F("> setting my CANID to ") << _msg.data[3] << endl;
-                if (_msg.data[3] < 1 || _msg.data[3] > 99) {
-                  sendCMDERR(7);
-                } else {
-                  module_config->setCANID(_msg.data[3]);
-                }
-              }
+        let tx_len = CanExtendedFrame::<&[u8]>::buffer_len(data.len());
+        let result = tx_token.try_consume(tx_len, |tx_buffer| {
+            if tx_buffer.len() < tx_len {
+                return Err(DispatchError::BufferTooSmall);
+            }
+            let mut frame = CanExtendedFrame::new_unchecked(&mut *tx_buffer);
+            frame.set_id(id);
+            frame.payload_mut().copy_from_slice(data);
+            Ok(())
+        });
+        self.count_tx(result)
+    }
 
-              break;
+    /// Return `true` when the echoes of our own frames are to be dropped.
+    fn ignores_own_frames(&self) -> bool {
+        self.caps.reports_own_frames && self.own_frames.enabled
+    }
+}
 
-        case OPC_ENUM:
-          // received ENUM -- start CAN bus self-enumeration
-          // DEBUG_SERIAL << F("> ENUM message for nn = ") << nn << F(" from CANID = ") << remoteCANID << endl;
-          // DEBUG_SERIAL << F("> my nn = ") << module_config->nodeNum << endl;
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+    use alloc::vec::Vec as StdVec;
+
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::VlcbNodeNumber;
+    use vlcb_defs::OpCode;
+
+    use super::*;
+    use crate::config::{CAN_DEFAULT_PRIORITY, CAN_RESERVE_DELAY_MS};
+    use crate::iface::{Interface, InterfaceConfig};
+    use crate::phy::loopback::Loopback;
+    use crate::phy::RxToken as _;
+    use crate::wire::CanPriority;
+
+    fn frame(can_id: u8, payload: &[u8]) -> StdVec<u8> {
+        let mut buffer = vec![0; CanFrame::<&[u8]>::buffer_len(payload.len())];
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[can_id]));
+        frame.set_major_priority(CAN_DEFAULT_PRIORITY >> 2);
+        frame.payload_mut().copy_from_slice(payload);
+        buffer
+    }
 
-          if (nn == module_config->nodeNum && remoteCANID != module_config->CANID && !bCANenum) {
-            // DEBUG_SERIAL << F("> initiating enumeration") << endl;
-            CANenumeration();
-          }
+    fn interface(device: &Loopback<8>, can_id: u8) -> Interface<TestClock> {
+        Interface::new(
+            device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[can_id])),
+        )
+    }
 
-          break;
+    #[test]
+    fn test_can_id_conflict_enumeration() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 2);
+        let mut sockets = SocketSet::new(vec![]);
+
+        device.inject(&frame(2, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.tx_emitted, 1, "enumeration request sent");
+
+        // the request is looped back and ignored, other nodes respond with their CAN IDs
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let request = CanFrame::new_checked(&*buffer).unwrap();
+            assert!(request.is_rtr());
+            assert!(request.payload().is_empty());
+        });
+        for can_id in [1, 2, 4] {
+            device.inject(&frame(can_id, &[])).unwrap();
+        }
 
-        */
+        clock.advance(CAN_RESERVE_DELAY_MS - 1);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(
+            iface.poll_events().next(),
+            Some(InterfaceEvent::CanIdConflict {
+                other_frame_opcode: OpCode::QueryNodeInfo.into()
+            })
+        );
+        assert_eq!(iface.poll_events().next(), None, "enumeration still in progress");
+
+        clock.advance(1);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        let new_id = VlcbCanId::from_bytes(&[3]);
+        assert_eq!(
+            iface.poll_events().collect::<StdVec<_>>(),
+            [InterfaceEvent::CanEnumerationCompleted { new_id }]
+        );
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(new_id));
+        assert!(!iface.take_events_lost());
+    }
+
+    #[test]
+    fn test_can_enumeration_failed() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 5);
+        let mut sockets = SocketSet::new(vec![]);
+
+        device.inject(&frame(5, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        // pretend every CAN ID is taken
+        if let Enumeration::InProgress { responses, .. } = &mut iface.inner.can_enumeration {
+            *responses = u128::MAX;
+        }
+        clock.advance(CAN_RESERVE_DELAY_MS);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        assert_eq!(
+            iface.poll_events().collect::<StdVec<_>>(),
+            [
+                InterfaceEvent::CanIdConflict {
+                    other_frame_opcode: OpCode::QueryNodeInfo.into()
+                },
+                InterfaceEvent::CanEnumerationFailed,
+            ]
+        );
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(VlcbCanId::from_bytes(&[5])));
+    }
+
+    #[test]
+    fn test_enumeration_at_large_epoch() {
+        let clock = TestClock::starting_at(u32::MAX as u64 + 10_000);
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 2);
+        let mut sockets = SocketSet::new(vec![]);
+        assert_eq!(iface.now(), None, "not polled yet");
+
+        device.inject(&frame(2, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(iface.now(), Some(clock.now()));
+        let _ = device.receive();
+
+        // the deadline counts from the poll, not from the start of the clock
+        clock.advance(CAN_RESERVE_DELAY_MS - 1);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::InProgress { .. }));
+
+        clock.advance(1);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::Idle));
+    }
+
+    #[test]
+    fn test_start_can_enumeration() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = Interface::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[2])),
+        );
+        let mut sockets = SocketSet::new(vec![]);
+
+        iface.start_can_enumeration();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::InProgress { .. }));
+
+        // a running enumeration is not restarted
+        iface.start_can_enumeration();
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::InProgress { .. }));
+    }
+
+    #[test]
+    fn test_custom_reserve_delay() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let config = InterfaceConfig {
+            can_reserve_delay_ms: 250,
+            ..InterfaceConfig::default()
+        };
+        let mut iface = Interface::with_config(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[2])),
+            config,
+        );
+        assert_eq!(iface.config(), &config);
+        let mut sockets = SocketSet::new(vec![]);
+
+        device.inject(&frame(2, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::InProgress { .. }));
+
+        // the default delay has passed, the enumeration keeps collecting the responses
+        clock.advance(CAN_RESERVE_DELAY_MS);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::InProgress { .. }));
+
+        clock.advance(250 - CAN_RESERVE_DELAY_MS);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(matches!(iface.inner.can_enumeration, Enumeration::Idle));
+        assert_eq!(iface.hw_addr(), HardwareAddress::CAN(VlcbCanId::from_bytes(&[1])));
+    }
+
+    #[cfg(feature = "socket-module")]
+    #[test]
+    fn test_own_frames_ignored() {
+        use crate::phy::{TxToken as _, VirtualBus};
+        use crate::socket::module::{self, Filter, PacketBuffer, PacketMetadata};
+
+        let clock = TestClock::new();
+        let bus = VirtualBus::new(&clock).with_self_reception();
+        let mut port = bus.port();
+        // another node using our CAN ID
+        let mut other = bus.port();
+        assert!(port.capabilities().reports_own_frames);
+
+        let mut iface = Interface::<TestClock>::new(
+            &port,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::CAN(VlcbCanId::from_bytes(&[5])),
+        );
+        let mut sockets = SocketSet::new(vec![]);
+        let mut socket = module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+        );
+        socket.bind(Filter::All).unwrap();
+        let handle = sockets.add(socket);
+
+        let acon = [OpCode::LongEventAccessoryOn.into(), 0x01, 0x02, 0x00, 0x01];
+        sockets.get_mut::<module::Socket>(handle).send_slice(&acon).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut port, &mut sockets));
+        iface.poll(PollContext::new(clock.now(), &mut port, &mut sockets));
+
+        assert!(sockets.get_mut::<module::Socket>(handle).recv().is_err(), "no self-consumption");
+        assert_eq!(iface.stats().rx_own_frames(), 1);
+        assert_eq!(iface.poll_events().next(), None, "the echo is no conflict");
+
+        // the same frame from another node is no echo
+        other
+            .transmit()
+            .unwrap()
+            .consume(7, |buffer| buffer.copy_from_slice(&frame(5, &acon)));
+        iface.poll(PollContext::new(clock.now(), &mut port, &mut sockets));
+
+        assert_eq!(sockets.get_mut::<module::Socket>(handle).recv(), Ok(&acon[..]));
+        assert_eq!(
+            iface.poll_events().next(),
+            Some(InterfaceEvent::CanIdConflict {
+                other_frame_opcode: OpCode::LongEventAccessoryOn.into()
+            })
+        );
+        assert_eq!(iface.stats().rx_own_frames(), 1);
+    }
+
+    #[test]
+    fn test_no_conflict_without_can_id() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 0);
+        let mut sockets = SocketSet::new(vec![]);
+
+        device.inject(&frame(0, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        device.inject(&frame(7, &[OpCode::QueryNodeInfo.into()])).unwrap();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        assert_eq!(result.tx_emitted, 0);
+        assert_eq!(iface.poll_events().next(), None);
+    }
+
+    #[test]
+    fn test_enumeration_request_answered() {
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 5);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let mut request = frame(7, &[]);
+        CanFrame::new_unchecked(&mut request[..]).set_rtr(true);
+        device.inject(&request).unwrap();
+        // the response is looped back, leave it in the device
+        assert_eq!(iface.ingress_packets(&mut device, &mut sockets, 1), 1);
+        assert_eq!(iface.stats().rx_malformed(), 0);
+
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let response = CanFrame::new_checked(&*buffer).unwrap();
+            assert_eq!(response.src_addr(), VlcbCanId::from_bytes(&[5]));
+            assert!(!response.is_rtr());
+            assert!(response.payload().is_empty());
+        });
+        assert!(device.is_empty());
+    }
+
+    #[test]
+    fn test_zero_length_frame_not_malformed() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<8>::new();
+        let mut iface = interface(&device, 5);
+        let mut sockets = SocketSet::new(vec![]);
+
+        device.inject(&frame(7, &[])).unwrap();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.rx_processed, 1);
+        assert_eq!(result.tx_emitted, 0);
+        assert_eq!(iface.stats().rx_malformed(), 0);
+        assert!(device.is_empty(), "zero-length frames are not answered");
+    }
+
+    #[test]
+    fn test_dispatch_loopback() {
+        let mut device = Loopback::<8>::new();
+        let iface = interface(&device, 5);
+        let mut inner = iface.inner;
+
+        let payload = [OpCode::QueryNodeVariable.into(), 0x01, 0x02, 0x03];
+        let tx_token = device.transmit().unwrap();
+        let result = inner.dispatch_can(tx_token, payload.len(), |mut frame| {
+            frame.set_priority(CanPriority::Low);
+            frame.payload_mut().copy_from_slice(&payload);
+        });
+        assert_eq!(result, Ok(()));
+
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let frame = CanFrame::new_checked(&*buffer).unwrap();
+            assert_eq!(frame.src_addr(), VlcbCanId::from_bytes(&[5]));
+            assert_eq!(frame.full_priority(), CAN_DEFAULT_PRIORITY);
+            assert!(!frame.is_rtr());
+            assert_eq!(frame.payload(), &payload);
+        });
+    }
+
+    #[test]
+    fn test_dispatch_too_large() {
+        let mut device = Loopback::<8>::new();
+        let iface = interface(&device, 5);
+        let mut inner = iface.inner;
+
+        let tx_token = device.transmit().unwrap();
+        let result = inner.dispatch_can(tx_token, 9, |_| unreachable!());
+        assert_eq!(result, Err(DispatchError::BufferTooSmall));
+        assert!(device.is_empty(), "nothing was transmitted");
+    }
+
+    #[cfg(feature = "socket-module")]
+    mod extended {
+        use alloc::collections::VecDeque;
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        use embedded_can::{ExtendedId, Frame as _, Id, StandardId};
+
+        use super::*;
+        use crate::iface::SendNowError;
+        use crate::phy::can::EmbeddedCan;
+        use crate::socket::module::{self, Filter, PacketBuffer, PacketMetadata};
+
+        struct MockFrame {
+            id: Id,
+            data: StdVec<u8>,
+        }
+
+        impl embedded_can::Frame for MockFrame {
+            fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+                Some(MockFrame {
+                    id: id.into(),
+                    data: data.to_vec(),
+                })
+            }
+
+            fn new_remote(_id: impl Into<Id>, _dlc: usize) -> Option<Self> {
+                None
+            }
+
+            fn is_extended(&self) -> bool {
+                matches!(self.id, Id::Extended(_))
+            }
+
+            fn is_remote_frame(&self) -> bool {
+                false
+            }
 
-        // self.process_cbus(sockets, &cbus_packet)
-        todo!()
-    }
-
-    // #[cfg(feature = "medium-can")]
-    // pub(super) fn dispatch_can<D, Tx, F>(
-    //     &mut self,
-    //     tx_token: Tx,
-    //     buffer_len: usize,
-    //     f: F,
-    // ) -> Result<(), DispatchError>
-    // where
-    //     D: Device,
-    //     Tx: TxToken,
-    //     F: FnOnce(CanFrame<&mut [u8]>),
-    // {
-    //     let tx_len = CanFrame::<&[u8]>::buffer_len(buffer_len);
-    //     tx_token.consume(tx_len, |tx_buffer| {
-    //         debug_assert!(tx_buffer.as_ref().len() == tx_len);
-    //         let mut frame = CanFrame::new_unchecked(tx_buffer);
-
-    //         let src_addr = self.hw_addr.can_or_panic();
-    //         frame.set_src_addr(src_addr);
-
-    //         f(frame);
-
-    //         Ok(())
-    //     })
-    // }
+            fn id(&self) -> Id {
+                self.id
+            }
+
+            fn dlc(&self) -> usize {
+                self.data.len()
+            }
+
+            fn data(&self) -> &[u8] {
+                &self.data
+            }
+        }
+
+        /// CAN driver with the received frames queued by the test, e.g. of a bootloader host.
+        #[derive(Default, Clone)]
+        struct MockCan {
+            rx: Rc<RefCell<VecDeque<MockFrame>>>,
+            tx: Rc<RefCell<StdVec<MockFrame>>>,
+        }
+
+        impl embedded_can::nb::Can for MockCan {
+            type Frame = MockFrame;
+            type Error = embedded_can::ErrorKind;
+
+            fn transmit(&mut self, frame: &MockFrame) -> nb::Result<Option<MockFrame>, Self::Error> {
+                self.tx.borrow_mut().push(MockFrame::new(frame.id, &frame.data).unwrap());
+                Ok(None)
+            }
+
+            fn receive(&mut self) -> nb::Result<MockFrame, Self::Error> {
+                self.rx.borrow_mut().pop_front().ok_or(nb::Error::WouldBlock)
+            }
+        }
+
+        const ACON: [u8; 5] = [0x90, 0x01, 0x02, 0x00, 0x01];
+
+        fn receive(can: &MockCan, id: impl Into<Id>, data: &[u8]) {
+            can.rx.borrow_mut().push_back(MockFrame::new(id, data).unwrap());
+        }
+
+        fn socket(sockets: &mut SocketSet<'static>) -> crate::iface::SocketHandle {
+            let mut socket = module::Socket::new(
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+            );
+            socket.bind(Filter::All).unwrap();
+            sockets.add(socket)
+        }
+
+        static HANDLED_ID: AtomicU32 = AtomicU32::new(0);
+
+        fn handler(frame: CanExtendedFrame<&[u8]>) {
+            assert_eq!(frame.payload(), &[0x01, 0x02, 0x03]);
+            HANDLED_ID.store(frame.id(), Ordering::Relaxed);
+        }
+
+        #[test]
+        fn test_extended_frames_to_handler() {
+            let clock = TestClock::new();
+            let can = MockCan::default();
+            let mut device = EmbeddedCan::new(can.clone());
+            device.set_accepts_extended(true);
+            let mut iface = Interface::<TestClock>::new(
+                &device,
+                Some(VlcbNodeNumber::new(0x01, 0x02)),
+                HardwareAddress::CAN(VlcbCanId::from_bytes(&[5])),
+            );
+            iface.set_extended_frame_handler(Some(handler));
+            let mut sockets = SocketSet::new(vec![]);
+            let handle = socket(&mut sockets);
+
+            receive(&can, ExtendedId::new(0x1F00FF00).unwrap(), &[0x01, 0x02, 0x03]);
+            receive(&can, StandardId::new(0x07).unwrap(), &ACON);
+            let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+            assert_eq!(result.rx_processed, 2);
+
+            assert_eq!(HANDLED_ID.load(Ordering::Relaxed), 0x1F00FF00);
+            let socket = sockets.get_mut::<module::Socket>(handle);
+            assert_eq!(socket.recv(), Ok(&ACON[..]), "only the standard frame reaches the socket");
+            assert!(socket.recv().is_err());
+            assert_eq!(iface.stats().rx_malformed(), 0);
+
+            assert_eq!(iface.send_extended_now(&mut device, 0x1F00FF01, &[0x04]), Ok(()));
+            assert_eq!(
+                iface.send_extended_now(&mut device, 0x2000_0000, &[]),
+                Err(SendNowError::Malformed)
+            );
+            assert_eq!(
+                iface.send_extended_now(&mut device, 0x1F00FF01, &[0; 9]),
+                Err(SendNowError::Truncated)
+            );
+            let tx = can.tx.borrow();
+            assert_eq!(tx.len(), 1);
+            assert_eq!(tx[0].id, Id::Extended(ExtendedId::new(0x1F00FF01).unwrap()));
+            assert_eq!(tx[0].data, [0x04]);
+        }
+
+        #[test]
+        fn test_extended_frames_skipped_by_default() {
+            let clock = TestClock::new();
+            let can = MockCan::default();
+            let mut device = EmbeddedCan::new(can.clone());
+            let mut iface = Interface::<TestClock>::new(
+                &device,
+                Some(VlcbNodeNumber::new(0x01, 0x02)),
+                HardwareAddress::CAN(VlcbCanId::from_bytes(&[5])),
+            );
+            iface.set_extended_frame_handler(Some(|_| unreachable!("the device skips extended frames")));
+            let mut sockets = SocketSet::new(vec![]);
+            let handle = socket(&mut sockets);
+
+            receive(&can, ExtendedId::new(0x1F00FF00).unwrap(), &[0x01, 0x02, 0x03]);
+            receive(&can, StandardId::new(0x07).unwrap(), &ACON);
+            iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+            iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+            assert_eq!(sockets.get_mut::<module::Socket>(handle).recv(), Ok(&ACON[..]));
+            assert_eq!(iface.stats().rx_ok(), 1);
+            assert_eq!(
+                iface.send_extended_now(&mut device, 0x1F00FF01, &[0x04]),
+                Err(SendNowError::Unsupported)
+            );
+            assert!(can.tx.borrow().is_empty());
+        }
+    }
+
+    #[cfg(all(feature = "log", not(feature = "defmt")))]
+    mod logging {
+        use std::string::{String, ToString};
+        use std::sync::Mutex;
+        use std::vec::Vec;
+
+        use super::*;
+
+        /// Logger capturing the messages so the tests can inspect them.
+        struct CaptureLogger(Mutex<Vec<String>>);
+
+        impl log::Log for CaptureLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+
+        /// Return whether a message containing `needle` was logged since the test started.
+        fn logged(needle: &str) -> bool {
+            LOGGER.0.lock().unwrap().iter().any(|m| m.contains(needle))
+        }
+
+        #[test]
+        fn test_malformed_frame_is_logged() {
+            // note(discard): another test may have installed the logger already
+            let _ = log::set_logger(&LOGGER);
+            log::set_max_level(log::LevelFilter::Trace);
+
+            let clock = TestClock::new();
+            let mut device = Loopback::<8>::new();
+            let mut iface = interface(&device, 2);
+            let mut sockets = SocketSet::new(vec![]);
+
+            // shorter than the CAN header
+            device.inject(&[0x00]).unwrap();
+            // the opcode announces three data octets, none follow
+            device.inject(&frame(3, &[0x60])).unwrap();
+            let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+            assert_eq!(result.rx_processed, 2);
+
+            assert!(logged("iface: malformed CAN frame, 1 octets"));
+            assert!(logged("iface: malformed VLCB packet from CAN ID 03, opcode 96, 1 octets"));
+        }
+    }
 }
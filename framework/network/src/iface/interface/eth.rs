@@ -0,0 +1,68 @@
+use super::DispatchError;
+use super::InterfaceInner;
+use super::check;
+use crate::iface::vlcb_packet::InterfacePacket;
+use embedded_time::Clock;
+
+use crate::phy::TxToken;
+use crate::iface::socket_set::SocketSet;
+use crate::wire::{EthernetFrame, HardwareAddress, VlcbPacketWire};
+
+impl<C: Clock> InterfaceInner<C> {
+    pub(super) fn process_eth<'frame>(
+        &mut self,
+        sockets: &mut SocketSet<'_>,
+        frame: &'frame [u8],
+    ) -> Option<InterfacePacket<'frame>> {
+        let eth_frame = check!(
+            self.stats.count_malformed(EthernetFrame::new_checked(frame)),
+            "iface: malformed Ethernet frame, {} octets",
+            frame.len()
+        );
+        let remote_addr = eth_frame.src_addr();
+
+        let vlcb_packet = check!(
+            self.stats.count_malformed(VlcbPacketWire::new_checked(eth_frame.payload())),
+            "iface: malformed VLCB packet from {}, {} octets",
+            remote_addr,
+            eth_frame.payload().len()
+        );
+
+        self.process_vlcb(sockets, HardwareAddress::ETH(remote_addr), &vlcb_packet)
+            .map(InterfacePacket::Vlcb)
+    }
+
+    /// Allocate an Ethernet frame for a payload of `buffer_len` octets, fill in the header
+    /// and let `f` emit the rest of the frame.
+    ///
+    /// Payloads larger than the device MTU are not transmitted at all.
+    pub(super) fn dispatch_eth<Tx, F>(
+        &mut self,
+        tx_token: Tx,
+        buffer_len: usize,
+        f: F,
+    ) -> Result<(), DispatchError>
+    where
+        Tx: TxToken,
+        F: FnOnce(EthernetFrame<&mut [u8]>),
+    {
+        if buffer_len > self.caps.max_transmission_unit {
+            net_debug!("iface: {} octets do not fit into an Ethernet frame", buffer_len);
+            return self.stats.count_dispatch(Err(DispatchError::BufferTooSmall));
+        }
+
+        let tx_len = EthernetFrame::<&[u8]>::buffer_len(buffer_len);
+        let result = tx_token.try_consume(tx_len, |tx_buffer| {
+            if tx_buffer.len() < tx_len {
+                return Err(DispatchError::BufferTooSmall);
+            }
+            let mut frame = EthernetFrame::new_unchecked(tx_buffer);
+            frame.set_src_addr(self.hw_addr.ethernet_or_panic());
+
+            f(frame);
+
+            Ok(())
+        });
+        self.count_tx(result)
+    }
+}
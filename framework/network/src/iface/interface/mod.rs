@@ -4,36 +4,52 @@
 #[cfg(feature = "medium-can")]
 mod can;
 
+#[cfg(feature = "medium-eth")]
+mod eth;
+
 #[cfg(feature = "socket-event")]
 mod event;
 
 mod vlcb;
 
+use super::events::{EventQueue, InterfaceEvent};
+use super::link::{LinkState, LinkSupervisionConfig, LinkSupervisor};
+use super::stats::InterfaceStats;
 use super::vlcb_packet::*;
 use core::convert::Infallible;
 use core::marker::PhantomData;
+#[cfg(feature = "async")]
+use core::task::Waker;
 
 use vlcb_core::vlcb::VlcbNodeNumber;
 use core::result::Result;
 use embedded_time::{Clock, Instant};
+use heapless::Deque;
 use nb::Error::WouldBlock;
 
-use crate::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use crate::data::packet::construct::OutgoingPacket;
+use crate::phy::{Device, DeviceCapabilities, Medium, RxToken, TxRefused, TxToken};
 
 use crate::iface::SocketSet;
 use crate::socket::Socket;
-use crate::wire::{VlcbPacketWire, HardwareAddress};
+#[cfg(feature = "async")]
+use crate::socket::WakerRegistration;
+use crate::wire::{VlcbPacketWire, VlcbRepr, HardwareAddress};
+#[cfg(feature = "medium-can")]
+use crate::wire::CanExtendedFrame;
 
+/// Unwrap the result of parsing a received frame, or log and drop the frame.
+///
+/// The log message can be given after the expression, otherwise the expression is logged.
 macro_rules! check {
     ($e:expr) => {
+        check!($e, "iface: malformed {}", stringify!($e))
+    };
+    ($e:expr, $($msg:expr),+) => {
         match $e {
             Ok(x) => x,
             Err(_) => {
-                // concat!/stringify! doesn't work with defmt macros
-                #[cfg(not(feature = "defmt"))]
-                net_trace!(concat!("iface: malformed ", stringify!($e)));
-                #[cfg(feature = "defmt")]
-                net_trace!("iface: malformed");
+                net_debug!($($msg),+);
                 return Default::default();
             }
         }
@@ -42,14 +58,14 @@ macro_rules! check {
 
 use check;
 
-pub struct PollContext<'a, D: Device + ?Sized, C: Clock> {
+pub struct PollContext<'a, 's, D: Device + ?Sized, C: Clock> {
     timestamp: Instant<C>,
     device: &'a mut D,
-    sockets: &'a mut SocketSet<'a>,
+    sockets: &'a mut SocketSet<'s>,
 }
 
-impl<'a, D: Device, C: Clock> PollContext<'a, D, C> {
-    pub fn new(timestamp: Instant<C>, device: &'a mut D, sockets: &'a mut SocketSet<'a>) -> Self {
+impl<'a, 's, D: Device, C: Clock> PollContext<'a, 's, D, C> {
+    pub fn new(timestamp: Instant<C>, device: &'a mut D, sockets: &'a mut SocketSet<'s>) -> Self {
         Self {
             timestamp,
             device,
@@ -58,11 +74,89 @@ impl<'a, D: Device, C: Clock> PollContext<'a, D, C> {
     }
 }
 
+/// Default maximum amount of packets received in a single [`Interface::poll`].
+pub const DEFAULT_MAX_INGRESS_PACKETS: usize = 64;
+
+/// Default maximum amount of packets transmitted in a single [`Interface::poll`].
+pub const DEFAULT_MAX_EGRESS_PACKETS: usize = 64;
+
+/// Maximum amount of packets queued with [`InterfaceInner::enqueue_reply`] until the next poll.
+pub const DEFERRED_QUEUE_SIZE: usize = 4;
+
+/// Function receiving the CAN frames with an extended identifier, see
+/// [`Interface::set_extended_frame_handler`].
+#[cfg(feature = "medium-can")]
+pub type ExtendedFrameHandler = fn(CanExtendedFrame<&[u8]>);
+
+/// Protocol timings and defaults of an [`Interface`].
+///
+/// The [`Default`] is taken from the [`crate::config`] constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceConfig {
+    /// Time the CAN ID self-enumeration collects the responses of the other nodes, in milliseconds.
+    ///
+    /// A busy bus may need more time for all the nodes to respond.
+    pub can_reserve_delay_ms: u64,
+    /// Priority of the transmitted CAN frames, the major priority in the upper two bits.
+    pub can_default_priority: u8,
+    /// Delay between the packets of a long message, in milliseconds.
+    pub long_message_default_delay: u16,
+    /// Time to wait for the next packet of a long message, in milliseconds.
+    pub long_message_receive_timeout: u16,
+    /// Thresholds of the supervision of the link to the bus.
+    pub link_supervision: LinkSupervisionConfig,
+    /// Amount of failed attempts to transmit a socket packet after which it is dropped.
+    ///
+    /// An attempt fails when the device has no free transmit buffer or refuses the frame,
+    /// the packet stays in the socket until the next poll then. See
+    /// [`InterfaceStats::tx_abandoned`].
+    pub tx_retry_limit: u8,
+}
+
+impl Default for InterfaceConfig {
+    fn default() -> Self {
+        Self {
+            can_reserve_delay_ms: crate::config::CAN_RESERVE_DELAY_MS,
+            can_default_priority: crate::config::CAN_DEFAULT_PRIORITY,
+            long_message_default_delay: crate::config::LONG_MESSAGE_DEFAULT_DELAY,
+            long_message_receive_timeout: crate::config::LONG_MESSAGE_RECEIVE_TIMEOUT,
+            link_supervision: LinkSupervisionConfig::default(),
+            tx_retry_limit: crate::config::TX_RETRY_LIMIT,
+        }
+    }
+}
+
+/// Outcome of a single [`Interface::poll`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PollResult {
+    /// Amount of packets received from the device.
+    pub rx_processed: usize,
+    /// Amount of packets transmitted to the device.
+    pub tx_emitted: usize,
+    /// The ingress or egress budget was used up, there may be more packets waiting
+    /// and the interface should be polled again soon.
+    pub budget_exhausted: bool,
+}
+
+impl PollResult {
+    /// Check whether any packets were processed or transmitted, thereby indicating
+    /// if the availability status of any socket could have been altered.
+    pub fn readiness_may_have_changed(&self) -> bool {
+        self.rx_processed > 0 || self.tx_emitted > 0
+    }
+}
+
 /// A Network Interface Entity.
 ///
 /// This entity is logically associated with multiple other data structures.
 pub struct Interface<C: Clock> {
     pub(crate) inner: InterfaceInner<C>,
+    max_ingress_packets: usize,
+    max_egress_packets: usize,
+    #[cfg(feature = "async")]
+    waker: WakerRegistration,
 }
 
 /// The hardware-agnostic component of a network interface.
@@ -74,14 +168,43 @@ pub struct Interface<C: Clock> {
 /// there is still the allowance to invoke methods on its `inner` field.
 pub struct InterfaceInner<C: Clock> {
     caps: DeviceCapabilities,
-    addr: VlcbNodeNumber,
+    config: InterfaceConfig,
+    addr: Option<VlcbNodeNumber>,
     hw_addr: HardwareAddress,
-    now: Instant<C>,
+    /// Time of the last poll, `None` until the first one.
+    now: Option<Instant<C>>,
+    events: EventQueue,
+    stats: InterfaceStats,
+    link: LinkSupervisor<C>,
+    #[cfg(feature = "medium-can")]
+    can_enumeration: can::Enumeration<C>,
+    #[cfg(feature = "medium-can")]
+    own_frames: can::OwnFrames,
+    #[cfg(feature = "medium-can")]
+    extended_frame_handler: Option<ExtendedFrameHandler>,
+    /// Packets to send ahead of the socket packets, oldest first, see
+    /// [`InterfaceInner::enqueue_reply`].
+    deferred: Deque<OutgoingPacket, DEFERRED_QUEUE_SIZE>,
 }
 
 impl<C: Clock> Interface<C> {
     /// Create a network interface.
-    pub fn new<D>(device: &D, addr: VlcbNodeNumber, hw_addr: HardwareAddress) -> Self
+    ///
+    /// The `addr` is the node number, or `None` when the node has not been allocated one yet.
+    pub fn new<D>(device: &D, addr: Option<VlcbNodeNumber>, hw_addr: HardwareAddress) -> Self
+    where
+        D: Device,
+    {
+        Self::with_config(device, addr, hw_addr, InterfaceConfig::default())
+    }
+
+    /// Create a network interface with custom protocol timings, see [`Interface::new`].
+    pub fn with_config<D>(
+        device: &D,
+        addr: Option<VlcbNodeNumber>,
+        hw_addr: HardwareAddress,
+        config: InterfaceConfig,
+    ) -> Self
     where
         D: Device,
     {
@@ -90,15 +213,57 @@ impl<C: Clock> Interface<C> {
         Interface {
             inner: InterfaceInner {
                 caps,
+                config,
                 addr,
                 hw_addr,
-                now: Instant::new(C::T::from(0)),
+                now: None,
+                events: EventQueue::new(),
+                stats: InterfaceStats::default(),
+                link: LinkSupervisor::new(),
+                #[cfg(feature = "medium-can")]
+                can_enumeration: can::Enumeration::Idle,
+                #[cfg(feature = "medium-can")]
+                own_frames: can::OwnFrames::default(),
+                #[cfg(feature = "medium-can")]
+                extended_frame_handler: None,
+                deferred: Deque::new(),
             },
+            max_ingress_packets: DEFAULT_MAX_INGRESS_PACKETS,
+            max_egress_packets: DEFAULT_MAX_EGRESS_PACKETS,
+            #[cfg(feature = "async")]
+            waker: WakerRegistration::new(),
         }
     }
 
+    /// Register the waker of the task polling the interface.
+    ///
+    /// The waker is woken when a poll used up its budget and the interface should be polled
+    /// again. Device drivers should wake the same task from their receive interrupt.
+    ///
+    /// Like the socket wakers, the waker is woken only once and has to be registered again.
+    #[cfg(feature = "async")]
+    pub fn register_waker(&mut self, waker: &Waker) {
+        self.waker.register(waker)
+    }
+
+    /// Set the maximum amount of packets received in a single poll.
+    ///
+    /// The budget is at least one packet.
+    pub fn set_max_ingress_packets(&mut self, max: usize) {
+        self.max_ingress_packets = max.max(1)
+    }
+
+    /// Set the maximum amount of packets transmitted in a single poll.
+    ///
+    /// The budget is at least one packet.
+    pub fn set_max_egress_packets(&mut self, max: usize) {
+        self.max_egress_packets = max.max(1)
+    }
+
     /// Set the interface's address
-    pub fn set_addr(&mut self, addr: VlcbNodeNumber) {
+    ///
+    /// Without an address, packets addressed to a node are not accepted by the sockets.
+    pub fn set_addr(&mut self, addr: Option<VlcbNodeNumber>) {
         self.inner.addr = addr
     }
 
@@ -107,8 +272,71 @@ impl<C: Clock> Interface<C> {
         self.inner.hw_addr = addr
     }
 
-    /// Get the interface's address
-    pub fn addr(&self) -> VlcbNodeNumber {
+    /// Set whether the echoes of our own frames are dropped, on by default.
+    ///
+    /// Only applies to a device that [reports own frames]. The echoes are dropped before
+    /// they reach the sockets, so e.g. our own ACON does not trigger our own consumer.
+    ///
+    /// [reports own frames]: DeviceCapabilities::reports_own_frames
+    #[cfg(feature = "medium-can")]
+    pub fn set_ignore_own_frames(&mut self, ignore: bool) {
+        self.inner.own_frames.enabled = ignore;
+        self.inner.own_frames.clear();
+    }
+
+    /// Set the function receiving the CAN frames with an extended identifier, e.g. of the
+    /// CBUS bootloader protocol.
+    ///
+    /// Only a device that [accepts extended frames] hands them over. They are passed to the
+    /// handler verbatim and never reach the sockets, without a handler they are skipped.
+    /// Replies are sent with [`send_extended_now`](Self::send_extended_now).
+    ///
+    /// [accepts extended frames]: DeviceCapabilities::accepts_extended
+    #[cfg(feature = "medium-can")]
+    pub fn set_extended_frame_handler(&mut self, handler: Option<ExtendedFrameHandler>) {
+        self.inner.extended_frame_handler = handler;
+    }
+
+    /// Transmit a CAN frame with the extended identifier `id` and the `data` right away.
+    ///
+    /// Fails with [`SendNowError::Unsupported`] when the device does not [accept extended
+    /// frames], with [`SendNowError::Malformed`] when `id` does not fit into 29 bits and with
+    /// [`SendNowError::Truncated`] when `data` does not fit into the frame.
+    ///
+    /// [accept extended frames]: DeviceCapabilities::accepts_extended
+    #[cfg(feature = "medium-can")]
+    pub fn send_extended_now<D>(&mut self, device: &mut D, id: u32, data: &[u8]) -> Result<(), SendNowError>
+    where
+        D: Device + ?Sized,
+    {
+        if self.inner.caps.medium != Medium::CAN || !self.inner.caps.accepts_extended {
+            return Err(SendNowError::Unsupported);
+        }
+        if embedded_can::ExtendedId::new(id).is_none() {
+            return Err(SendNowError::Malformed);
+        }
+        let Some(tx_token) = device.transmit() else {
+            self.inner.stats.record_tx_exhausted();
+            return Err(SendNowError::Exhausted);
+        };
+        self.inner.dispatch_can_extended(tx_token, id, data)?;
+        Ok(())
+    }
+
+    /// Start the CAN ID self-enumeration, e.g. on the request of the user.
+    ///
+    /// The enumeration request is sent on the next poll, nothing happens while an enumeration
+    /// is already running. The outcome is reported with [`InterfaceEvent::CanEnumerationCompleted`]
+    /// or [`InterfaceEvent::CanEnumerationFailed`].
+    #[cfg(feature = "medium-can")]
+    pub fn start_can_enumeration(&mut self) {
+        if let can::Enumeration::Idle = self.inner.can_enumeration {
+            self.inner.can_enumeration = can::Enumeration::Required;
+        }
+    }
+
+    /// Get the interface's address, `None` if the node has no node number.
+    pub fn addr(&self) -> Option<VlcbNodeNumber> {
         self.inner.addr
     }
 
@@ -122,6 +350,45 @@ impl<C: Clock> Interface<C> {
         &self.inner.caps
     }
 
+    /// Get the protocol timings and defaults of this interface
+    pub fn config(&self) -> &InterfaceConfig {
+        &self.inner.config
+    }
+
+    /// Drain the events queued by the interface since the last call, oldest first.
+    ///
+    /// At most [`INTERFACE_EVENT_QUEUE_SIZE`](super::INTERFACE_EVENT_QUEUE_SIZE) events are kept,
+    /// see [`take_events_lost`](Self::take_events_lost).
+    pub fn poll_events(&mut self) -> impl Iterator<Item = InterfaceEvent> + '_ {
+        core::iter::from_fn(move || self.inner.events.pop())
+    }
+
+    /// Return whether any events were dropped because they were not drained in time,
+    /// and reset the flag.
+    pub fn take_events_lost(&mut self) -> bool {
+        self.inner.events.take_lost()
+    }
+
+    /// Get the counters of the received and transmitted frames, e.g. for the diagnostics.
+    pub fn stats(&self) -> &InterfaceStats {
+        &self.inner.stats
+    }
+
+    /// Get the time of the last poll, `None` until the interface is polled.
+    pub fn now(&self) -> Option<Instant<C>> {
+        self.inner.now
+    }
+
+    /// Get the state of the link to the bus at `now`, as of the last poll.
+    ///
+    /// The link is supervised by the frames received and transmitted by the polls, with the
+    /// thresholds of the [`LinkSupervisionConfig`], and by the [`Device::link_hint`] of the
+    /// device. The changes are also reported with [`InterfaceEvent::LinkDown`] and
+    /// [`InterfaceEvent::LinkUp`].
+    pub fn link_state(&self, now: Instant<C>) -> LinkState<C::T> {
+        self.inner.link.state(now, &self.inner.config.link_supervision)
+    }
+
     /// Get the socket context.
     ///
     /// The context is needed for some socket methods.
@@ -129,80 +396,147 @@ impl<C: Clock> Interface<C> {
         &mut self.inner
     }
 
+    /// Transmit a single packet right away, without a socket.
+    ///
+    /// Meant for the time critical replies of the node, like the NNACK of the setup.
+    /// The packet bypasses the socket buffers, so it can overtake the packets queued in
+    /// the sockets. Its priority is still applied to the frame.
+    ///
+    /// Fails with [`SendNowError::Exhausted`] when the device has no free transmit buffer
+    /// or refuses the frame.
+    pub fn send_now<D>(&mut self, device: &mut D, packet: &OutgoingPacket) -> Result<(), SendNowError>
+    where
+        D: Device + ?Sized,
+    {
+        let packet = InterfaceInner::<C>::outgoing_packet(packet)?;
+        self.inner.transmit_now(device, InterfacePacket::Vlcb(packet))
+    }
+
     /// Process queued packets in the specified sockets for transmission and
     /// receive incoming packets queued in the device.
     ///
-    /// This function provides a boolean result indicating if any packets
-    /// were processed or transmitted, thereby indicating if
-    /// the availability status of any socket could have been altered.
+    /// At most [`set_max_ingress_packets`](Self::set_max_ingress_packets) packets are received
+    /// and [`set_max_egress_packets`](Self::set_max_egress_packets) transmitted, so a saturated
+    /// bus can not keep the caller from running. Transmission continues after the receive
+    /// budget is used up. The transmit budget is further limited by the device
+    /// [`max_burst_size`](DeviceCapabilities::max_burst_size).
     ///
     /// # Panics
     /// This method panics on debug builds when passed device in the `ctx` does not
     /// match the interface device capabilities
-    pub fn poll<D>(&mut self, ctx: PollContext<D, C>) -> bool
+    pub fn poll<D>(&mut self, ctx: PollContext<D, C>) -> PollResult
     where
         D: Device,
     {
-        self.inner.now = ctx.timestamp;
+        self.inner.now = Some(ctx.timestamp);
 
         debug_assert!(
             ctx.device.capabilities() == self.inner.caps,
             "Passed in device does not satisfy the device capabilities on this interface",
         );
 
-        let mut readiness_may_have_changed = false;
+        let mut result = PollResult::default();
+
+        let max_egress_packets = match self.inner.caps.max_burst_size {
+            Some(burst) => self.max_egress_packets.min(burst.max(1)),
+            None => self.max_egress_packets,
+        };
 
         loop {
-            let mut did_something = false;
+            let rx_budget = self.max_ingress_packets - result.rx_processed;
+            let rx = match rx_budget {
+                0 => 0,
+                _ => self.ingress_packets(ctx.device, ctx.sockets, rx_budget),
+            };
 
-            did_something |= self.ingress_packets(ctx.device, ctx.sockets);
-            did_something |= self.egress_packets(ctx.device, ctx.sockets);
+            let tx_budget = max_egress_packets - result.tx_emitted;
+            let tx = match tx_budget {
+                0 => 0,
+                _ => self.egress_packets(ctx.device, ctx.sockets, tx_budget),
+            };
 
-            if did_something {
-                readiness_may_have_changed = true;
-            } else {
+            result.rx_processed += rx;
+            result.tx_emitted += tx;
+
+            if rx == 0 && tx == 0 {
                 break;
             }
         }
 
-        readiness_may_have_changed
+        #[cfg(feature = "medium-can")]
+        if self.inner.caps.medium == Medium::CAN {
+            result.tx_emitted += self.inner.poll_can_enumeration(ctx.timestamp, ctx.device);
+        }
+
+        let inner = &mut self.inner;
+        let hint = ctx.device.link_hint();
+        if let Some(event) = inner.link.update(ctx.timestamp, &inner.stats, hint, &inner.config.link_supervision) {
+            net_debug!("iface: {:?}", event);
+            inner.events.push(event);
+        }
+
+        result.budget_exhausted = result.rx_processed >= self.max_ingress_packets
+            || result.tx_emitted >= max_egress_packets;
+
+        #[cfg(feature = "async")]
+        if result.budget_exhausted {
+            self.waker.wake();
+        }
+
+        result
     }
 
-    fn ingress_packets<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    fn ingress_packets<D>(
+        &mut self,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        budget: usize,
+    ) -> usize
     where
         D: Device + ?Sized,
     {
-        let mut processed_any = false;
+        let mut processed = 0;
+
+        while processed < budget {
+            let Some((rx_token, tx_token)) = device.receive() else {
+                break;
+            };
 
-        while let Some((rx_token, tx_token)) = device.receive() {
             rx_token.consume(|frame| {
-                match self.inner.caps.medium {
+                let dropped = self.inner.stats.rx_dropped();
+                let reply = match self.inner.caps.medium {
                     #[cfg(feature = "medium-can")]
-                    Medium::CAN => {
-                        if let Some(packet) = self.inner.process_can(
-                            sockets,
-                            frame,
-                        ) {
-                            if let Err(err) =
-                                self.inner.dispatch_vlcb(tx_token, packet)
-                            {
-                                net_debug!("Failed to send response: {:?}", err);
-                            }
-                        }
-                    }
+                    Medium::CAN => self.inner.process_can(sockets, frame),
+                    #[cfg(feature = "medium-eth")]
+                    Medium::ETH => self.inner.process_eth(sockets, frame),
+                };
+                if self.inner.stats.rx_dropped() == dropped {
+                    self.inner.stats.record_rx_ok();
+                }
+
+                // The paired transmit buffer is used by the oldest deferred packet when the
+                // frame needs no reply.
+                let result = match reply {
+                    Some(packet) => self.inner.dispatch(tx_token, packet),
+                    None => self.inner.dispatch_deferred(tx_token),
+                };
+                if let Err(err) = result {
+                    net_debug!("iface: failed to send response: {:?}", err);
                 }
-                processed_any = true;
             });
+            processed += 1;
         }
 
-        processed_any
+        processed
     }
 
+    /// Dispatch at most one packet from every socket, up to `budget` packets in total.
     fn egress_packets<D>(
         &mut self,
         device: &mut D,
         sockets: &mut SocketSet<'_>,
-    ) -> bool
+        budget: usize,
+    ) -> usize
     where
         D: Device + ?Sized,
     {
@@ -211,20 +545,27 @@ impl<C: Clock> Interface<C> {
             Dispatch(DispatchError),
         }
 
-        let mut emitted_any = false;
+        let mut emitted = self.inner.flush_deferred(device, budget);
         for item in sockets.items_mut() {
+            if emitted >= budget {
+                break;
+            }
+            let handle = item.meta.handle;
+
             let mut respond =
                 |inner: &mut InterfaceInner<C>, response: VlcbPacket| -> Result<(), EgressError> {
                     let t = device.transmit().ok_or_else(|| {
-                        net_debug!("failed to transmit CBUS: device exhausted");
+                        net_debug!("iface: failed to transmit from socket {}: device exhausted", handle);
+                        inner.stats.record_tx_exhausted();
                         EgressError::Exhausted
                     })?;
 
-                    inner
-                        .dispatch_vlcb(t, response)
-                        .map_err(EgressError::Dispatch)?;
+                    inner.dispatch_vlcb(t, response).map_err(|err| match err {
+                        DispatchError::Exhausted => EgressError::Exhausted,
+                        err => EgressError::Dispatch(err),
+                    })?;
 
-                    emitted_any = true;
+                    emitted += 1;
 
                     Ok(())
                 };
@@ -238,8 +579,11 @@ impl<C: Clock> Interface<C> {
                 }
                 #[cfg(feature = "socket-module")]
                 Socket::Module(socket) => {
-                    socket.dispatch(&mut self.inner, |inner, (cbus, payload)| {
-                        respond(inner, VlcbPacket::new(cbus, VlcbPayload::Module(payload)))
+                    socket.dispatch(&mut self.inner, |inner, (vlcb, priority, payload)| {
+                        respond(
+                            inner,
+                            VlcbPacket::new(vlcb, VlcbPayload::Module(payload)).with_priority(priority),
+                        )
                     })
                 },
             };
@@ -247,15 +591,733 @@ impl<C: Clock> Interface<C> {
             match result {
                 Err(EgressError::Exhausted) => break, // Device buffer full.
                 Err(EgressError::Dispatch(e)) => {
-                    net_debug!("dispatch error: {:?}", e)
+                    net_debug!("iface: socket {} dispatch error: {:?}", handle, e)
                 }
                 Ok(()) => {}
             }
         }
-        emitted_any
+        emitted
+    }
+}
+
+impl<C: Clock> InterfaceInner<C> {
+    /// Get the interface's address, `None` if the node has no node number.
+    pub fn addr(&self) -> Option<VlcbNodeNumber> {
+        self.addr
+    }
+
+    /// Get the device capabilities of the interface
+    pub fn caps(&self) -> &DeviceCapabilities {
+        &self.caps
+    }
+
+    /// Count a failed attempt to transmit a socket packet, the `attempts` one so far, and
+    /// return `true` when the packet is to be dropped.
+    pub(crate) fn give_up_tx(&mut self, attempts: u8) -> bool {
+        if attempts < self.config.tx_retry_limit {
+            return false;
+        }
+        net_debug!("iface: dropping a packet after {} failed attempts", attempts);
+        self.stats.record_tx_abandoned();
+        true
+    }
+
+    /// Queue a packet to be sent by the next poll, ahead of the socket packets.
+    ///
+    /// For the replies of code running without access to the device, e.g. while processing
+    /// a received packet, which may need more than one frame. The packets are sent in the
+    /// order they were queued. See also [`Interface::send_now`].
+    ///
+    /// At most [`DEFERRED_QUEUE_SIZE`] packets are kept, a packet not fitting is dropped with
+    /// [`SendNowError::Exhausted`], counted in the [`InterfaceStats::tx_deferred_dropped`]
+    /// and reported with [`InterfaceEvent::DeferredQueueFull`].
+    pub fn enqueue_reply(&mut self, packet: OutgoingPacket) -> Result<(), SendNowError> {
+        Self::outgoing_packet(&packet)?;
+        if self.deferred.push_back(packet).is_err() {
+            net_debug!("iface: deferred queue full, dropping the packet");
+            self.stats.record_deferred_dropped();
+            self.events.push(InterfaceEvent::DeferredQueueFull);
+            return Err(SendNowError::Exhausted);
+        }
+        Ok(())
+    }
+
+    /// Queue a copy of the packet, see [`enqueue_reply`](Self::enqueue_reply).
+    pub fn defer(&mut self, packet: &OutgoingPacket) -> Result<(), SendNowError> {
+        self.enqueue_reply(packet.clone())
+    }
+
+    /// Check a constructed packet and borrow it for the dispatch.
+    fn outgoing_packet(packet: &OutgoingPacket) -> Result<VlcbPacket<'_>, SendNowError> {
+        let wire = VlcbPacketWire::new_checked(&packet.payload[..]).map_err(|_| SendNowError::Malformed)?;
+        let vlcb_repr = VlcbRepr::parse(&wire).map_err(|_| SendNowError::Malformed)?;
+        Ok(VlcbPacket::new(vlcb_repr, VlcbPayload::Interface(wire.payload())).with_priority(packet.priority))
+    }
+
+    /// Transmit a packet of the interface itself with a new transmit buffer of the device.
+    fn transmit_now<D>(&mut self, device: &mut D, packet: InterfacePacket) -> Result<(), SendNowError>
+    where
+        D: Device + ?Sized,
+    {
+        let Some(tx_token) = device.transmit() else {
+            self.stats.record_tx_exhausted();
+            return Err(SendNowError::Exhausted);
+        };
+        self.dispatch(tx_token, packet)?;
+        Ok(())
+    }
+
+    /// Send the oldest deferred packet, if any, with the given transmit buffer.
+    ///
+    /// A packet refused by the device stays at the front of the queue.
+    fn dispatch_deferred<Tx: TxToken>(&mut self, tx_token: Tx) -> Result<(), DispatchError> {
+        let Some(deferred) = self.deferred.pop_front() else {
+            return Ok(());
+        };
+        // note(unwrap): checked by `enqueue_reply`
+        let packet = Self::outgoing_packet(&deferred).unwrap();
+        let result = self.dispatch_vlcb(tx_token, packet);
+        if result == Err(DispatchError::Exhausted) {
+            // note(discard): there is room for the packet just taken from the queue
+            let _ = self.deferred.push_front(deferred);
+        }
+        result
+    }
+
+    /// Count the outcome of [`TxToken::try_consume`], a frame refused by the device is
+    /// counted as exhausted rather than as a dispatch error.
+    fn count_tx<T>(
+        &mut self,
+        result: Result<Result<T, DispatchError>, TxRefused>,
+    ) -> Result<T, DispatchError> {
+        match result {
+            Ok(result) => self.stats.count_dispatch(result),
+            Err(TxRefused) => {
+                net_debug!("iface: the device refused the frame");
+                self.stats.record_tx_exhausted();
+                Err(DispatchError::Exhausted)
+            }
+        }
+    }
+
+    /// Transmit the deferred packets, up to `budget` frames. Returns the amount of transmitted
+    /// frames.
+    fn flush_deferred<D>(&mut self, device: &mut D, budget: usize) -> usize
+    where
+        D: Device + ?Sized,
+    {
+        let mut emitted = 0;
+        while emitted < budget && !self.deferred.is_empty() {
+            let Some(tx_token) = device.transmit() else {
+                self.stats.record_tx_exhausted();
+                break;
+            };
+            match self.dispatch_deferred(tx_token) {
+                Ok(()) => emitted += 1,
+                Err(DispatchError::Exhausted) => break,
+                Err(err) => net_debug!("iface: failed to send deferred packet: {:?}", err),
+            }
+        }
+        emitted
+    }
+}
+
+/// Error returned by [`Interface::send_now`] and [`InterfaceInner::defer`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendNowError {
+    /// The device has no free transmit buffer, or the deferred queue is full.
+    Exhausted,
+    /// The packet is empty, its opcode is unknown or it is shorter than the opcode requires.
+    Malformed,
+    /// The packet does not fit into a frame of the device.
+    Truncated,
+    /// The device does not support the frame, e.g. an extended CAN frame.
+    Unsupported,
+}
+
+impl core::fmt::Display for SendNowError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            SendNowError::Exhausted => write!(f, "exhausted"),
+            SendNowError::Malformed => write!(f, "malformed"),
+            SendNowError::Truncated => write!(f, "truncated"),
+            SendNowError::Unsupported => write!(f, "unsupported"),
+        }
+    }
+}
+
+impl From<DispatchError> for SendNowError {
+    fn from(value: DispatchError) -> Self {
+        match value {
+            DispatchError::BufferTooSmall => SendNowError::Truncated,
+            DispatchError::Exhausted => SendNowError::Exhausted,
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-enum DispatchError {}
+enum DispatchError {
+    /// The packet does not fit into the frame buffer provided by the device.
+    BufferTooSmall,
+    /// The device refused the frame, it may be sent again later.
+    Exhausted,
+}
+
+#[cfg(all(test, feature = "medium-can", feature = "socket-module"))]
+mod test {
+    use alloc::vec;
+    use core::cell::Cell;
+
+    use embedded_time::duration::Milliseconds;
+    use vlcb_core::time::TestClock;
+    use vlcb_defs::OpCode;
+
+    use super::*;
+    use crate::data::packet::construct::module_cfg;
+    use crate::iface::LinkSupervisionConfig;
+    use crate::phy::loopback::Loopback;
+    use crate::phy::LinkHint;
+    use crate::socket::module::{self, PacketBuffer, PacketMetadata};
+    use crate::wire::{CanFrame, CanPriority};
+    use vlcb_core::can::VlcbCanId;
+
+    /// A device receiving a QNN frame every time it is asked to.
+    #[derive(Default)]
+    struct Flood {
+        transmitted: Cell<usize>,
+        caps: DeviceCapabilities,
+    }
+
+    struct FloodRx;
+
+    impl RxToken for FloodRx {
+        fn consume<R, F>(self, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            f(&mut [0x05, 0x81, OpCode::QueryNodeInfo.into()])
+        }
+    }
+
+    #[derive(Clone)]
+    struct FloodTx<'a> {
+        transmitted: &'a Cell<usize>,
+    }
+
+    impl<'a> TxToken for FloodTx<'a> {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            self.transmitted.set(self.transmitted.get() + 1);
+            f(&mut [0; 10][..len])
+        }
+    }
+
+    impl Device for Flood {
+        type RxToken<'a> = FloodRx;
+        type TxToken<'a> = FloodTx<'a>;
+
+        fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+            Some((FloodRx, FloodTx { transmitted: &self.transmitted }))
+        }
+
+        fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+            Some(FloodTx { transmitted: &self.transmitted })
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            self.caps.clone()
+        }
+    }
+
+    /// A device refusing the first `refusals` frames, like a CAN controller with full
+    /// transmit mailboxes.
+    #[derive(Default)]
+    struct Refusing {
+        refusals: Cell<usize>,
+        transmitted: core::cell::RefCell<alloc::vec::Vec<alloc::vec::Vec<u8>>>,
+    }
+
+    #[derive(Clone)]
+    struct RefusingTx<'a>(&'a Refusing);
+
+    impl<'a> TxToken for RefusingTx<'a> {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            let mut buffer = [0; 10];
+            let result = f(&mut buffer[..len]);
+            self.0.transmitted.borrow_mut().push(buffer[..len].to_vec());
+            result
+        }
+
+        fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, TxRefused>
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            match self.0.refusals.get() {
+                0 => Ok(self.consume(len, f)),
+                refusals => {
+                    self.0.refusals.set(refusals - 1);
+                    Err(TxRefused)
+                }
+            }
+        }
+    }
+
+    impl Device for Refusing {
+        type RxToken<'a> = FloodRx;
+        type TxToken<'a> = RefusingTx<'a>;
+
+        fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+            None
+        }
+
+        fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+            Some(RefusingTx(self))
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities::default()
+        }
+    }
+
+    #[test]
+    fn test_poll_budget() {
+        let mut device = Flood::default();
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        iface.set_max_ingress_packets(8);
+        iface.set_max_egress_packets(4);
+
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 8], vec![0; 64]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 8], vec![0; 64]),
+        ));
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+        socket.send_slice(&[OpCode::GeneralNack.into()]).unwrap();
+
+        let clock = TestClock::new();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        // the receive budget is used up, but both queued packets still go out
+        assert_eq!(
+            result,
+            PollResult {
+                rx_processed: 8,
+                tx_emitted: 2,
+                budget_exhausted: true,
+            }
+        );
+        assert!(result.readiness_may_have_changed());
+        assert_eq!(device.transmitted.get(), 2);
+
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.rx_processed, 8);
+        assert_eq!(result.tx_emitted, 0);
+        assert!(result.budget_exhausted);
+    }
+
+    #[test]
+    fn test_poll_egress_budget() {
+        let mut device = Flood::default();
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        iface.set_max_ingress_packets(0);
+        iface.set_max_egress_packets(2);
+
+        let mut sockets = SocketSet::new(vec![]);
+        for _ in 0..3 {
+            let handle = sockets.add(module::Socket::new(
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            ));
+            let socket: &mut module::Socket = sockets.get_mut(handle);
+            socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+        }
+
+        let clock = TestClock::new();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.rx_processed, 1, "the budget is at least one packet");
+        assert_eq!(result.tx_emitted, 2);
+        assert!(result.budget_exhausted);
+
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.tx_emitted, 1);
+        assert_eq!(device.transmitted.get(), 3);
+    }
+
+    #[test]
+    fn test_poll_burst_size() {
+        let mut device = Flood {
+            caps: DeviceCapabilities {
+                max_burst_size: Some(1),
+                ..DeviceCapabilities::default()
+            },
+            ..Flood::default()
+        };
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        iface.set_max_ingress_packets(1);
+
+        let mut sockets = SocketSet::new(vec![]);
+        for _ in 0..2 {
+            let handle = sockets.add(module::Socket::new(
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+                PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            ));
+            let socket: &mut module::Socket = sockets.get_mut(handle);
+            socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+        }
+
+        let clock = TestClock::new();
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.tx_emitted, 1);
+        assert!(result.budget_exhausted);
+
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(result.tx_emitted, 1);
+        assert_eq!(device.transmitted.get(), 2);
+    }
+
+    #[test]
+    fn test_socket_send_respects_mtu() {
+        let mut device = Flood {
+            caps: DeviceCapabilities {
+                max_transmission_unit: 4,
+                ..DeviceCapabilities::default()
+            },
+            ..Flood::default()
+        };
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        iface.set_max_ingress_packets(1);
+
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+        ));
+
+        let clock = TestClock::new();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        assert_eq!(socket.max_transmission_unit(), 4);
+        assert_eq!(socket.send(5), Err(module::SendError::Truncated));
+        assert_eq!(socket.send_with(5, |_| 1), Err(module::SendError::Truncated));
+        assert!(socket.can_send(), "nothing was enqueued");
+        assert!(socket.send(4).is_ok());
+    }
+
+    fn loopback_iface(device: &Loopback<4>) -> Interface<TestClock> {
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        Interface::new(device, Some(VlcbNodeNumber::new(0x01, 0x02)), hw_addr)
+    }
+
+    fn transmitted(device: &mut Loopback<4>) -> (CanPriority, heapless::Vec<u8, 8>) {
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let frame = CanFrame::new_checked(&*buffer).unwrap();
+            (frame.priority(), heapless::Vec::from_slice(frame.payload()).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_send_now_without_socket() {
+        let mut device = Loopback::<4>::new();
+        let mut iface = loopback_iface(&device);
+
+        let nnack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02))
+            .with_priority(CanPriority::AboveNormal);
+        assert_eq!(iface.send_now(&mut device, &nnack), Ok(()));
+        assert_eq!(transmitted(&mut device), (CanPriority::AboveNormal, nnack.payload));
+
+        let empty = OutgoingPacket {
+            payload: heapless::Vec::new(),
+            priority: CanPriority::Low,
+        };
+        assert_eq!(iface.send_now(&mut device, &empty), Err(SendNowError::Malformed));
+        assert!(device.is_empty());
+    }
+
+    #[test]
+    fn test_deferred_sent_before_sockets() {
+        let mut device = Loopback::<4>::new();
+        let mut iface = loopback_iface(&device);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+        ));
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+
+        let nnack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02));
+        assert_eq!(iface.context().defer(&nnack), Ok(()));
+
+        assert_eq!(iface.egress_packets(&mut device, &mut sockets, usize::MAX), 2);
+        assert_eq!(transmitted(&mut device).1, nnack.payload);
+        assert_eq!(transmitted(&mut device).1, [u8::from(OpCode::GeneralAck)]);
+    }
+
+    #[test]
+    fn test_replies_sent_in_order() {
+        let mut device = Loopback::<4>::new();
+        let mut iface = loopback_iface(&device);
+        let mut sockets = SocketSet::new(vec![]);
+        let replies = [0x01, 0x02, 0x03]
+            .map(|nn| module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x00, nn)));
+
+        // the replies of a single received frame
+        device.inject(&[0x00, 0x07, OpCode::QueryNodeInfo.into()]).unwrap();
+        for reply in &replies {
+            iface.context().enqueue_reply(reply.clone()).unwrap();
+        }
+        assert_eq!(iface.ingress_packets(&mut device, &mut sockets, 1), 1);
+        // the first reply used the paired transmit buffer
+        assert_eq!(iface.egress_packets(&mut device, &mut sockets, usize::MAX), 2);
+        for reply in &replies {
+            assert_eq!(transmitted(&mut device).1, reply.payload);
+        }
+        assert!(device.is_empty());
+    }
+
+    #[test]
+    fn test_deferred_queue_full() {
+        let mut iface = loopback_iface(&Loopback::<4>::new());
+        let nnack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02));
+        for _ in 0..DEFERRED_QUEUE_SIZE {
+            iface.context().defer(&nnack).unwrap();
+        }
+
+        assert_eq!(iface.context().defer(&nnack), Err(SendNowError::Exhausted));
+        assert_eq!(iface.stats().tx_deferred_dropped(), 1);
+        assert_eq!(iface.poll_events().next(), Some(InterfaceEvent::DeferredQueueFull));
+    }
+
+    #[test]
+    fn test_deferred_uses_paired_tx_token() {
+        let mut device = Loopback::<4>::new();
+        let mut iface = loopback_iface(&device);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let nnack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02));
+        iface.context().defer(&nnack).unwrap();
+        device.inject(&[0x00, 0x07, OpCode::QueryNodeInfo.into()]).unwrap();
+
+        assert_eq!(iface.ingress_packets(&mut device, &mut sockets, 1), 1);
+        assert_eq!(transmitted(&mut device).1, nnack.payload);
+        assert!(device.is_empty());
+    }
+
+    #[test]
+    fn test_refused_frame_retried() {
+        let clock = TestClock::new();
+        let mut device = Refusing::default();
+        device.refusals.set(2);
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+        ));
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+
+        for tx_emitted in [0, 0, 1, 0] {
+            let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+            assert_eq!(result.tx_emitted, tx_emitted);
+        }
+
+        let transmitted = device.transmitted.borrow();
+        assert_eq!(transmitted.len(), 1, "sent exactly once");
+        assert_eq!(transmitted[0][2..], [u8::from(OpCode::GeneralAck)]);
+        assert_eq!(iface.stats().tx_exhausted(), 2);
+        assert_eq!(iface.stats().tx_ok(), 1);
+        assert_eq!(iface.stats().dispatch_errors(), 0);
+        assert_eq!(iface.stats().tx_abandoned(), 0);
+    }
+
+    #[test]
+    fn test_refused_frame_abandoned() {
+        let clock = TestClock::new();
+        let mut device = Refusing::default();
+        device.refusals.set(usize::MAX);
+        let config = InterfaceConfig {
+            tx_retry_limit: 3,
+            ..InterfaceConfig::default()
+        };
+        let mut iface = Interface::<TestClock>::with_config(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+            config,
+        );
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 16]),
+        ));
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+        socket.send_slice(&[OpCode::GeneralNack.into()]).unwrap();
+
+        for _ in 0..3 {
+            iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        }
+        assert_eq!(iface.stats().tx_abandoned(), 1);
+
+        // the next packet gets its own attempts
+        device.refusals.set(0);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        let transmitted = device.transmitted.borrow();
+        assert_eq!(transmitted.len(), 1);
+        assert_eq!(transmitted[0][2..], [u8::from(OpCode::GeneralNack)]);
+        assert_eq!(iface.stats().tx_abandoned(), 1);
+    }
+
+    #[test]
+    fn test_refused_send_now_and_deferred() {
+        let clock = TestClock::new();
+        let mut device = Refusing::default();
+        let mut iface = Interface::<TestClock>::new(
+            &device,
+            Some(VlcbNodeNumber::new(0x01, 0x02)),
+            HardwareAddress::default(),
+        );
+        let mut sockets = SocketSet::new(vec![]);
+        let nnack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02));
+
+        device.refusals.set(1);
+        assert_eq!(iface.send_now(&mut device, &nnack), Err(SendNowError::Exhausted));
+
+        // a refused deferred packet stays queued
+        device.refusals.set(1);
+        iface.context().defer(&nnack).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(device.transmitted.borrow().is_empty());
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        let transmitted = device.transmitted.borrow();
+        assert_eq!(transmitted.len(), 1);
+        assert_eq!(transmitted[0][2..], nnack.payload[..]);
+    }
+
+    #[test]
+    fn test_link_supervision() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<4>::new();
+        let config = InterfaceConfig {
+            link_supervision: LinkSupervisionConfig {
+                silent_timeout_ms: Some(1000),
+                ..LinkSupervisionConfig::default()
+            },
+            ..InterfaceConfig::default()
+        };
+        let mut iface = Interface::with_config(&device, None, HardwareAddress::default(), config);
+        let mut sockets = SocketSet::new(vec![]);
+        let mut poll = |iface: &mut Interface<TestClock>, device: &mut Loopback<4>| {
+            iface.poll(PollContext::new(clock.now(), device, &mut sockets));
+            iface.poll_events().collect::<heapless::Vec<_, 4>>()
+        };
+
+        assert!(poll(&mut iface, &mut device).is_empty());
+        clock.advance(999);
+        assert!(poll(&mut iface, &mut device).is_empty());
+        assert_eq!(iface.link_state(clock.now()), LinkState::Healthy);
+
+        // nothing received from the bus for a second
+        clock.advance(1);
+        assert_eq!(poll(&mut iface, &mut device), [InterfaceEvent::LinkDown]);
+        clock.advance(500);
+        assert_eq!(iface.link_state(clock.now()), LinkState::SilentFor(Milliseconds(1500)));
+        assert!(poll(&mut iface, &mut device).is_empty(), "reported once");
+
+        let mut frame = CanFrame::new_unchecked([0u8; 3]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x07]));
+        frame.payload_mut()[0] = OpCode::QueryNodeInfo.into();
+        device.inject(&frame.into_inner()).unwrap();
+        assert_eq!(poll(&mut iface, &mut device), [InterfaceEvent::LinkUp]);
+        assert_eq!(iface.link_state(clock.now()), LinkState::Healthy);
+
+        // the driver knows better
+        device.set_link_hint(Some(LinkHint::BusOff));
+        assert_eq!(poll(&mut iface, &mut device), [InterfaceEvent::LinkDown]);
+        assert_eq!(iface.link_state(clock.now()), LinkState::TxStalled);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut device = Loopback::<4>::new();
+        let mut iface = loopback_iface(&device);
+        let mut sockets = SocketSet::new(vec![]);
+
+        // too short to hold the CAN header
+        device.inject(&[0x00]).unwrap();
+        assert_eq!(iface.ingress_packets(&mut device, &mut sockets, 1), 1);
+        assert_eq!(iface.stats().rx_dropped(), 1);
+        assert_eq!(iface.stats().rx_malformed(), 1);
+        assert_eq!(iface.stats().rx_ok(), 0);
+
+        // an enumeration request is answered by the interface itself
+        let mut frame = CanFrame::new_unchecked([0u8; 2]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x07]));
+        frame.set_rtr(true);
+        device.inject(&frame.into_inner()).unwrap();
+        assert_eq!(iface.ingress_packets(&mut device, &mut sockets, 1), 1);
+        assert_eq!(iface.stats().rx_ok(), 1);
+        assert_eq!(iface.stats().rx_dropped(), 1);
+        assert_eq!(iface.stats().tx_ok(), 1);
+
+        let ack = module_cfg::ctrl::ack_node_number(VlcbNodeNumber::new(0x01, 0x02));
+        iface.send_now(&mut device, &ack).unwrap();
+        assert_eq!(iface.stats().tx_ok(), 2);
+        assert_eq!(iface.stats().dispatch_errors(), 0);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_poll_wakes_when_budget_exhausted() {
+        use crate::socket::CountingWaker;
+
+        let clock = TestClock::new();
+        let (counter, waker) = CountingWaker::new();
+        let mut sockets = SocketSet::new(vec![]);
+
+        let mut idle = Loopback::<4>::new();
+        let mut iface = Interface::<TestClock>::new(&idle, None, HardwareAddress::default());
+        iface.register_waker(&waker);
+        iface.poll(PollContext::new(clock.now(), &mut idle, &mut sockets));
+        assert_eq!(counter.count(), 0, "nothing is left to poll");
+
+        let mut device = Flood::default();
+        let mut iface = Interface::<TestClock>::new(&device, None, HardwareAddress::default());
+        iface.register_waker(&waker);
+        iface.set_max_ingress_packets(2);
+        let result = iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(result.budget_exhausted);
+        assert_eq!(counter.count(), 1);
+    }
+}
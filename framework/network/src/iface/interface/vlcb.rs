@@ -3,249 +3,399 @@ use super::*;
 use vlcb_defs::OpCode;
 
 use crate::{phy::{Medium, TxToken}, wire::VlcbRepr};
+use crate::wire::VlcbProtocol as Protocol;
+
+#[cfg(feature = "socket-module")]
+use crate::socket::{module, AnySocket};
 
 impl<C: Clock> InterfaceInner<C> {
-    pub(super) fn process_vlcb<'a, D>(
+    /// Route a received VLCB packet to the sockets by its protocol.
+    ///
+    /// Packets that no socket accepts are dropped and counted in the interface stats.
+    pub(super) fn process_vlcb<'frame>(
         &mut self,
         sockets: &mut SocketSet<'_>,
-        vlcb_packet: &VlcbPacketWire<&'a [u8]>,
-    ) -> Option<VlcbPacket>
-    where
-        D: Device + ?Sized,
-    {
-        let vlcb_repr = check!(VlcbRepr::parse(vlcb_packet));
+        src_hw_addr: HardwareAddress,
+        vlcb_packet: &VlcbPacketWire<&'frame [u8]>,
+    ) -> Option<VlcbPacket<'frame>> {
+        let vlcb_repr = check!(
+            self.stats.count_malformed(VlcbRepr::parse(vlcb_packet)),
+            "iface: unknown opcode {}, {} data octets",
+            vlcb_packet.opcode(),
+            vlcb_packet.payload_len()
+        );
         let vlcb_payload = vlcb_packet.payload();
-todo!()/*
-        match cbus_repr.next_header() {
-            #[cfg(feature = "socket-event")]
-            LcbProtocol::Event => {
-                let udp_repr = check!(UdpRepr::parse(
-                    &udp_packet,
-                    &ipv4_repr.src_addr.into(),
-                    &ipv4_repr.dst_addr.into(),
-                ));
-
-                self.process_event(sockets, meta, cbus_repr, udp_repr, udp_packet.payload())
-            }
 
+        match vlcb_repr.next_header() {
+            // Until there is a dedicated event socket, events are delivered to module
+            // sockets bound with the events filter.
             #[cfg(feature = "socket-module")]
-            LcbProtocol::Datagram => self.process_datagram(sockets, cbus_repr, cbus_payload),
+            Protocol::Module | Protocol::Event => {
+                self.process_module(sockets, &vlcb_repr, vlcb_payload)
+            }
 
+            // Stream packets are dropped until there is a long message socket.
+            // TODO perhaps transmit an error back?
             _ => {
-                // TODO perhaps transmit an error back?
-                /*
-                // Send back as much of the original payload as we can.
-                let payload_len =
-                    icmp_reply_payload_len(lcb_payload.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len());
-                let icmp_reply_repr = Icmpv4Repr::DstUnreachable {
-                    reason: Icmpv4DstUnreachable::ProtoUnreachable,
-                    header: ipv4_repr,
-                    data: &lcb_payload[0..payload_len],
-                };
-                self.icmpv4_reply(ipv4_repr, icmp_reply_repr)
-                 */
+                net_debug!(
+                    "iface: no socket for opcode {} from {}, dropping",
+                    vlcb_packet.opcode(),
+                    src_hw_addr
+                );
+                self.stats.record_unrouted();
                 None
             }
         }
-        */
+    }
+
+    /// Enqueue the packet into every module socket whose filter accepts it.
+    ///
+    /// The packet is counted as unrouted when no socket accepts it.
+    #[cfg(feature = "socket-module")]
+    pub(super) fn process_module<'frame>(
+        &mut self,
+        sockets: &mut SocketSet<'_>,
+        vlcb_repr: &VlcbRepr,
+        payload: &'frame [u8],
+    ) -> Option<VlcbPacket<'frame>> {
+        let mut handled = false;
+        for module_socket in sockets
+            .items_mut()
+            .filter_map(|i| module::Socket::downcast_mut(&mut i.socket))
+        {
+            if module_socket.accepts(self, vlcb_repr, payload) {
+                module_socket.process(self, vlcb_repr, payload);
+                handled = true;
+            }
+        }
+
+        if !handled {
+            self.stats.record_unrouted();
+        }
+        None
+    }
+
+    /// Emit a packet transmitted by the interface itself.
+    pub(super) fn dispatch<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        packet: InterfacePacket,
+    ) -> Result<(), DispatchError> {
+        match packet {
+            InterfacePacket::Vlcb(packet) => self.dispatch_vlcb(tx_token, packet),
+            #[cfg(feature = "medium-can")]
+            InterfacePacket::CanControl(control) => self.dispatch_can_control(tx_token, control),
+        }
     }
 
     pub(super) fn dispatch_vlcb<Tx: TxToken>(
         &mut self,
-        mut tx_token: Tx,
+        tx_token: Tx,
         packet: VlcbPacket,
     ) -> Result<(), DispatchError> {
-        Ok(())
-        /*
-        let mut ip_repr = packet.ip_repr();
-        assert!(!ip_repr.dst_addr().is_unspecified());
-
-        // Dispatch IEEE802.15.4:
-
-        #[cfg(feature = "medium-ieee802154")]
-        if matches!(self.caps.medium, Medium::Ieee802154) {
-            let (addr, tx_token) = self.lookup_hardware_addr(
-                tx_token,
-                &ip_repr.src_addr(),
-                &ip_repr.dst_addr(),
-                frag,
-            )?;
-            let addr = addr.ieee802154_or_panic();
-
-            self.dispatch_ieee802154(addr, tx_token, meta, packet, frag);
-            return Ok(());
+        let vlcb_repr = packet.vlcb_repr();
+        let vlcb_len = vlcb_repr.header_len() + vlcb_repr.data_len as usize;
+
+        match self.caps.medium {
+            #[cfg(feature = "medium-can")]
+            Medium::CAN => self.dispatch_can(tx_token, vlcb_len, |mut frame| {
+                frame.set_priority(packet.priority());
+                packet.emit_payload(&vlcb_repr, frame.payload_mut());
+            }),
+            #[cfg(feature = "medium-eth")]
+            Medium::ETH => self.dispatch_eth(tx_token, vlcb_len, |mut frame| {
+                packet.emit_payload(&vlcb_repr, frame.payload_mut());
+            }),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "medium-can"))]
+mod test {
+    use alloc::vec;
+    use alloc::vec::Vec as StdVec;
+
+    use super::*;
+    use crate::data::packet::construct::{layout_ctrl, loco_ctrl, module_cfg};
+    use crate::phy::{loopback::Loopback, Device, RxToken as _};
+    use crate::socket::module::{Filter, PacketBuffer, PacketMetadata};
+    use crate::wire::{CanFrame, CanPriority};
+    use vlcb_core::can::VlcbCanId;
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::{EventId, EventType};
+
+    fn interface_inner() -> InterfaceInner<TestClock> {
+        InterfaceInner {
+            caps: DeviceCapabilities::default(),
+            config: InterfaceConfig::default(),
+            addr: Some(VlcbNodeNumber::new(0x01, 0x02)),
+            hw_addr: HardwareAddress::default(),
+            now: None,
+            events: EventQueue::new(),
+            stats: InterfaceStats::default(),
+            link: crate::iface::link::LinkSupervisor::new(),
+            can_enumeration: can::Enumeration::Idle,
+            own_frames: can::OwnFrames::default(),
+            extended_frame_handler: None,
+            deferred: heapless::Deque::new(),
+        }
+    }
+
+    fn module_socket<'a>(filter: Option<Filter<'a>>) -> module::Socket<'a> {
+        let mut socket = module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 8], vec![0; 64]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 8], vec![0; 64]),
+        );
+        if let Some(filter) = filter {
+            socket.bind(filter).unwrap();
+        }
+        socket
+    }
+
+    fn received(socket: &mut module::Socket) -> StdVec<u8> {
+        let mut opcodes = StdVec::new();
+        while let Ok(packet) = socket.recv() {
+            opcodes.push(packet[0]);
         }
+        opcodes
+    }
 
-        // Dispatch IP/Ethernet:
+    #[test]
+    fn test_process_module_filters() {
+        let mut inner = interface_inner();
+        let mut sockets = SocketSet::new(vec![]);
+
+        let all = sockets.add(module_socket(Some(Filter::All)));
+        let addressed = sockets.add(module_socket(Some(Filter::AddressedToNode)));
+        let opcodes = sockets.add(module_socket(Some(Filter::Opcodes(&[
+            OpCode::QueryNodeInfo,
+        ]))));
+        let events = sockets.add(module_socket(Some(Filter::Events)));
+        let unbound = sockets.add(module_socket(None));
+
+        let packets: [(OpCode, &[u8]); 4] = [
+            // addressed to us
+            (OpCode::SetNodeVariable, &[0x01, 0x02, 0x01, 0x05]),
+            // addressed to another node
+            (OpCode::SetNodeVariable, &[0x09, 0x09, 0x01, 0x05]),
+            // broadcast
+            (OpCode::QueryNodeInfo, &[]),
+            // event produced by another node
+            (OpCode::LongEventAccessoryOn, &[0x0A, 0x0B, 0x00, 0x01]),
+        ];
+
+        for (opcode, payload) in packets {
+            let repr = VlcbRepr::new(opcode, payload.len() as u8, Protocol::Module);
+            assert_eq!(inner.process_module(&mut sockets, &repr, payload), None);
+        }
 
-        let caps = self.caps.clone();
+        let all_received = received(sockets.get_mut(all));
+        assert_eq!(
+            all_received,
+            [
+                u8::from(OpCode::SetNodeVariable),
+                OpCode::SetNodeVariable.into(),
+                OpCode::QueryNodeInfo.into(),
+                OpCode::LongEventAccessoryOn.into(),
+            ]
+        );
+        assert_eq!(
+            received(sockets.get_mut(addressed)),
+            [u8::from(OpCode::SetNodeVariable)]
+        );
+        assert_eq!(
+            received(sockets.get_mut(opcodes)),
+            [u8::from(OpCode::QueryNodeInfo)]
+        );
+        assert_eq!(
+            received(sockets.get_mut(events)),
+            [u8::from(OpCode::LongEventAccessoryOn)]
+        );
+        assert!(received(sockets.get_mut(unbound)).is_empty());
+    }
 
-        #[cfg(feature = "proto-ipv4-fragmentation")]
-        let ipv4_id = self.get_ipv4_ident();
+    #[test]
+    fn test_process_module_keeps_payload() {
+        let mut inner = interface_inner();
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module_socket(Some(Filter::All)));
+
+        let payload = [0x01, 0x02, 0x01, 0x05];
+        let repr = VlcbRepr::new(OpCode::SetNodeVariable, payload.len() as u8, Protocol::Module);
+        inner.process_module(&mut sockets, &repr, &payload);
+
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        assert_eq!(
+            socket.recv(),
+            Ok(&[u8::from(OpCode::SetNodeVariable), 0x01, 0x02, 0x01, 0x05][..])
+        );
+    }
 
-        // First we calculate the total length that we will have to emit.
-        let mut total_len = ip_repr.buffer_len();
+    fn transmitted_priority(device: &mut Loopback<4>) -> (CanPriority, u8, StdVec<u8>) {
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let frame = CanFrame::new_checked(&*buffer).unwrap();
+            (frame.priority(), frame.full_priority(), frame.payload().to_vec())
+        })
+    }
 
-        // Add the size of the Ethernet header if the medium is Ethernet.
-        #[cfg(feature = "medium-ethernet")]
-        if matches!(self.caps.medium, Medium::Ethernet) {
-            total_len = EthernetFrame::<&[u8]>::buffer_len(total_len);
+    #[test]
+    fn test_dispatch_uses_packet_priority() {
+        let mut device = Loopback::<4>::new();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let mut iface = Interface::<TestClock>::new(&device, Some(VlcbNodeNumber::new(0x01, 0x02)), hw_addr);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module_socket(None));
+
+        let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+        let packets = [
+            loco_ctrl::command::emergency_stop(),
+            layout_ctrl::produce::accessory(EventType::AccessoryOn, event, None),
+            module_cfg::response::write_ack(VlcbNodeNumber::new(0x01, 0x02)),
+            module_cfg::response::write_ack(VlcbNodeNumber::new(0x01, 0x02))
+                .with_priority(CanPriority::AboveNormal),
+        ];
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        for packet in &packets {
+            socket.send_packet(packet).unwrap();
         }
 
-        // If the medium is Ethernet, then we need to retrieve the destination hardware address.
-        #[cfg(feature = "medium-ethernet")]
-        let (dst_hardware_addr, mut tx_token) = match self.caps.medium {
-            Medium::Ethernet => {
-                match self.lookup_hardware_addr(
-                    tx_token,
-                    &ip_repr.src_addr(),
-                    &ip_repr.dst_addr(),
-                    frag,
-                )? {
-                    (HardwareAddress::Ethernet(addr), tx_token) => (addr, tx_token),
-                    (_, _) => unreachable!(),
-                }
-            }
-            _ => (EthernetAddress([0; 6]), tx_token),
-        };
-
-        // Emit function for the Ethernet header.
-        #[cfg(feature = "medium-ethernet")]
-        let emit_ethernet = |repr: &IpRepr, tx_buffer: &mut [u8]| {
-            let mut frame = EthernetFrame::new_unchecked(tx_buffer);
-
-            let src_addr = self.hardware_addr.ethernet_or_panic();
-            frame.set_src_addr(src_addr);
-            frame.set_dst_addr(dst_hardware_addr);
-
-            match repr.version() {
-                #[cfg(feature = "proto-ipv4")]
-                IpVersion::Ipv4 => frame.set_ethertype(EthernetProtocol::Ipv4),
-                #[cfg(feature = "proto-ipv6")]
-                IpVersion::Ipv6 => frame.set_ethertype(EthernetProtocol::Ipv6),
-            }
+        // every poll dispatches a single packet per socket
+        while iface.egress_packets(&mut device, &mut sockets, usize::MAX) > 0 {}
+        assert_eq!(device.len(), packets.len());
+
+        for (packet, expected) in packets.iter().zip([
+            (CanPriority::High, 0x8),
+            (CanPriority::Normal, 0xA),
+            (CanPriority::Low, 0xB),
+            (CanPriority::AboveNormal, 0x9),
+        ]) {
+            let (priority, full_priority, payload) = transmitted_priority(&mut device);
+            assert_eq!((priority, full_priority), expected);
+            assert_eq!(payload, &packet.payload[..]);
+        }
+    }
 
-            Ok(())
-        };
-
-        // Emit function for the IP header and payload.
-        let emit_ip = |repr: &IpRepr, mut tx_buffer: &mut [u8]| {
-            repr.emit(&mut tx_buffer, &self.caps.checksum);
-
-            let payload = &mut tx_buffer[repr.header_len()..];
-            packet.emit_payload(repr, payload, &caps)
-        };
-
-        let total_ip_len = ip_repr.buffer_len();
-
-        match &mut ip_repr {
-            #[cfg(feature = "proto-ipv4")]
-            IpRepr::Ipv4(repr) => {
-                // If we have an IPv4 packet, then we need to check if we need to fragment it.
-                if total_ip_len > self.caps.max_transmission_unit {
-                    #[cfg(feature = "proto-ipv4-fragmentation")]
-                    {
-                        net_debug!("start fragmentation");
-
-                        // Calculate how much we will send now (including the Ethernet header).
-                        let tx_len = self.caps.max_transmission_unit;
-
-                        let ip_header_len = repr.buffer_len();
-                        let first_frag_ip_len = self.caps.ip_mtu();
-
-                        if frag.buffer.len() < total_ip_len {
-                            net_debug!(
-                                "Fragmentation buffer is too small, at least {} needed. Dropping",
-                                total_ip_len
-                            );
-                            return Ok(());
-                        }
-
-                        #[cfg(feature = "medium-ethernet")]
-                        {
-                            frag.ipv4.dst_hardware_addr = dst_hardware_addr;
-                        }
-
-                        // Save the total packet len (without the Ethernet header, but with the first
-                        // IP header).
-                        frag.packet_len = total_ip_len;
-
-                        // Save the IP header for other fragments.
-                        frag.ipv4.repr = *repr;
-
-                        // Save how much bytes we will send now.
-                        frag.sent_bytes = first_frag_ip_len;
-
-                        // Modify the IP header
-                        repr.payload_len = first_frag_ip_len - repr.buffer_len();
-
-                        // Emit the IP header to the buffer.
-                        emit_ip(&ip_repr, &mut frag.buffer);
-
-                        let mut ipv4_packet = Ipv4PacketWire::new_unchecked(&mut frag.buffer[..]);
-                        frag.ipv4.ident = ipv4_id;
-                        ipv4_packet.set_ident(ipv4_id);
-                        ipv4_packet.set_more_frags(true);
-                        ipv4_packet.set_dont_frag(false);
-                        ipv4_packet.set_frag_offset(0);
-
-                        if caps.checksum.ipv4.tx() {
-                            ipv4_packet.fill_checksum();
-                        }
-
-                        // Transmit the first packet.
-                        tx_token.consume(tx_len, |mut tx_buffer| {
-                            #[cfg(feature = "medium-ethernet")]
-                            if matches!(self.caps.medium, Medium::Ethernet) {
-                                emit_ethernet(&ip_repr, tx_buffer)?;
-                                tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
-                            }
-
-                            // Change the offset for the next packet.
-                            frag.ipv4.frag_offset = (first_frag_ip_len - ip_header_len) as u16;
-
-                            // Copy the IP header and the payload.
-                            tx_buffer[..first_frag_ip_len]
-                                .copy_from_slice(&frag.buffer[..first_frag_ip_len]);
-
-                            Ok(())
-                        })
-                    }
-
-                    #[cfg(not(feature = "proto-ipv4-fragmentation"))]
-                    {
-                        net_debug!("Enable the `proto-ipv4-fragmentation` feature for fragmentation support.");
-                        Ok(())
-                    }
-                } else {
-                    tx_token.set_meta(meta);
-
-                    // No fragmentation is required.
-                    tx_token.consume(total_len, |mut tx_buffer| {
-                        #[cfg(feature = "medium-ethernet")]
-                        if matches!(self.caps.medium, Medium::Ethernet) {
-                            emit_ethernet(&ip_repr, tx_buffer)?;
-                            tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
-                        }
-
-                        emit_ip(&ip_repr, tx_buffer);
-                        Ok(())
-                    })
-                }
-            }
-            // We don't support IPv6 fragmentation yet.
-            #[cfg(feature = "proto-ipv6")]
-            IpRepr::Ipv6(_) => tx_token.consume(total_len, |mut tx_buffer| {
-                #[cfg(feature = "medium-ethernet")]
-                if matches!(self.caps.medium, Medium::Ethernet) {
-                    emit_ethernet(&ip_repr, tx_buffer)?;
-                    tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
-                }
-
-                emit_ip(&ip_repr, tx_buffer);
-                Ok(())
-            }),
+    #[test]
+    fn test_dispatch_default_priority_for_raw_send() {
+        let mut device = Loopback::<4>::new();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let mut iface = Interface::<TestClock>::new(&device, Some(VlcbNodeNumber::new(0x01, 0x02)), hw_addr);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module_socket(None));
+
+        let socket: &mut module::Socket = sockets.get_mut(handle);
+        socket
+            .send_slice(&[OpCode::DccSetLocoThrottle.into(), 0x01, 0x80])
+            .unwrap();
+
+        assert_eq!(iface.egress_packets(&mut device, &mut sockets, usize::MAX), 1);
+
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| {
+            let frame = CanFrame::new_checked(&*buffer).unwrap();
+            assert_eq!(frame.priority(), CanPriority::High);
+            assert_eq!(frame.src_addr(), VlcbCanId::from_bytes(&[0x05]));
+            assert_eq!(frame.payload(), &[OpCode::DccSetLocoThrottle.into(), 0x01, 0x80]);
+        });
+    }
+
+    fn inject(device: &mut Loopback<4>, src: u8, payload: &[u8]) {
+        let mut buffer = [0u8; 10];
+        let len = 2 + payload.len();
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..len]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[src]));
+        frame.payload_mut().copy_from_slice(payload);
+        device.inject(&buffer[..len]).unwrap();
+    }
+
+    #[test]
+    fn test_ingress_routes_by_protocol() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<4>::new();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let mut iface = Interface::<TestClock>::new(&device, Some(VlcbNodeNumber::new(0x01, 0x02)), hw_addr);
+        let mut sockets = SocketSet::new(vec![]);
+        let events = sockets.add(module_socket(Some(Filter::Events)));
+        let opcodes = sockets.add(module_socket(Some(Filter::Opcodes(&[
+            OpCode::QueryNodeInfo,
+        ]))));
+        let all = sockets.add(module_socket(Some(Filter::All)));
+
+        let frames: [&[u8]; 3] = [
+            &[OpCode::StreamPacket.into(), 0x01, 0x00, 0x00, 0x41, 0x42, 0x43, 0x44],
+            &[OpCode::LongEventAccessoryOn.into(), 0x0A, 0x0B, 0x00, 0x01],
+            &[OpCode::QueryNodeInfo.into()],
+        ];
+        for payload in frames {
+            inject(&mut device, 0x07, payload);
+            iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
         }
-        */
+
+        assert_eq!(
+            received(sockets.get_mut(events)),
+            [u8::from(OpCode::LongEventAccessoryOn)]
+        );
+        assert_eq!(
+            received(sockets.get_mut(opcodes)),
+            [u8::from(OpCode::QueryNodeInfo)]
+        );
+        assert_eq!(
+            received(sockets.get_mut(all)),
+            [
+                u8::from(OpCode::LongEventAccessoryOn),
+                u8::from(OpCode::QueryNodeInfo)
+            ],
+            "stream packets are not delivered to module sockets"
+        );
+        assert_eq!(iface.stats().rx_unrouted(), 1);
+        assert_eq!(iface.stats().rx_malformed(), 0);
+    }
+
+    #[test]
+    fn test_ingress_counts_malformed() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<4>::new();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let mut iface = Interface::<TestClock>::new(&device, Some(VlcbNodeNumber::new(0x01, 0x02)), hw_addr);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module_socket(Some(Filter::All)));
+
+        // ACON is missing its last data octet
+        inject(&mut device, 0x07, &[OpCode::LongEventAccessoryOn.into(), 0x0A, 0x0B, 0x00]);
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+
+        assert!(received(sockets.get_mut(handle)).is_empty());
+        assert_eq!(iface.stats().rx_malformed(), 1);
+    }
+
+    #[test]
+    fn test_addressed_packets_need_addr() {
+        let clock = TestClock::new();
+        let mut device = Loopback::<4>::new();
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let mut iface = Interface::<TestClock>::new(&device, None, hw_addr);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(module_socket(Some(Filter::AddressedToNode)));
+
+        // NVRD addressed to node number 0
+        let mut buffer = [0u8; 6];
+        let mut frame = CanFrame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x07]));
+        frame
+            .payload_mut()
+            .copy_from_slice(&[OpCode::QueryNodeVariable.into(), 0x00, 0x00, 0x01]);
+
+        device.inject(&buffer).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert!(received(sockets.get_mut(handle)).is_empty());
+
+        iface.set_addr(Some(VlcbNodeNumber::new(0x00, 0x00)));
+        device.inject(&buffer).unwrap();
+        iface.poll(PollContext::new(clock.now(), &mut device, &mut sockets));
+        assert_eq!(
+            received(sockets.get_mut(handle)),
+            [u8::from(OpCode::QueryNodeVariable)]
+        );
     }
 }
@@ -0,0 +1,215 @@
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant, TimeInt};
+use vlcb_core::time::millis;
+
+use super::events::InterfaceEvent;
+use super::stats::InterfaceStats;
+use crate::phy::LinkHint;
+
+/// Thresholds of the link supervision, see [`Interface::link_state`](super::Interface::link_state).
+///
+/// A check is turned off with `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkSupervisionConfig {
+    /// Time without a received frame after which the bus is considered silent, in milliseconds.
+    ///
+    /// Off by default, a quiet bus is not necessarily broken. Suits a bus with periodic
+    /// traffic, e.g. the heartbeats of the other nodes.
+    pub silent_timeout_ms: Option<u32>,
+    /// Time the device may refuse the transmit buffers before the transmission is considered
+    /// stalled, in milliseconds.
+    pub tx_stall_timeout_ms: Option<u32>,
+}
+
+impl Default for LinkSupervisionConfig {
+    fn default() -> Self {
+        Self {
+            silent_timeout_ms: None,
+            tx_stall_timeout_ms: Some(crate::config::LINK_TX_STALL_TIMEOUT_MS),
+        }
+    }
+}
+
+/// State of the link to the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState<T: TimeInt> {
+    /// Frames are received and transmitted.
+    Healthy,
+    /// No frame was received for longer than the silent timeout.
+    SilentFor(Milliseconds<T>),
+    /// The device refused the transmit buffers for longer than the stall timeout,
+    /// or it reported the loss of the bus.
+    TxStalled,
+}
+
+impl<T: TimeInt> LinkState<T> {
+    /// Check whether the link works.
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, LinkState::Healthy)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: TimeInt + defmt::Format> defmt::Format for LinkState<T> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            LinkState::Healthy => defmt::write!(f, "Healthy"),
+            LinkState::SilentFor(silent) => defmt::write!(f, "SilentFor({} ms)", silent.0),
+            LinkState::TxStalled => defmt::write!(f, "TxStalled"),
+        }
+    }
+}
+
+/// Supervision of the link by the counters of the received and transmitted frames.
+///
+/// The counters are compared on every poll, so the timestamps have the resolution of the polls
+/// and include the frames sent without one, e.g. by [`Interface::send_now`](super::Interface::send_now).
+#[derive(Debug)]
+pub(crate) struct LinkSupervisor<C: Clock> {
+    rx_seen: u32,
+    tx_ok_seen: u32,
+    tx_exhausted_seen: u32,
+    /// Time of the last received frame, or of the first poll.
+    last_rx: Option<Instant<C>>,
+    /// Time of the first refused transmit buffer since the last transmitted frame.
+    tx_blocked_since: Option<Instant<C>>,
+    hint: Option<LinkHint>,
+    down: bool,
+}
+
+impl<C: Clock> LinkSupervisor<C> {
+    pub(crate) fn new() -> Self {
+        Self {
+            rx_seen: 0,
+            tx_ok_seen: 0,
+            tx_exhausted_seen: 0,
+            last_rx: None,
+            tx_blocked_since: None,
+            hint: None,
+            down: false,
+        }
+    }
+
+    /// Take the counters and the device hint of a poll.
+    ///
+    /// Returns the event to queue when the link went down or up.
+    pub(crate) fn update(
+        &mut self,
+        now: Instant<C>,
+        stats: &InterfaceStats,
+        hint: Option<LinkHint>,
+        config: &LinkSupervisionConfig,
+    ) -> Option<InterfaceEvent> {
+        // the echoes of our own frames come from the controller, not the bus
+        let rx = stats.rx_ok().saturating_add(stats.rx_dropped().saturating_sub(stats.rx_own_frames()));
+        if rx != self.rx_seen || self.last_rx.is_none() {
+            self.rx_seen = rx;
+            self.last_rx = Some(now);
+        }
+
+        if stats.tx_ok() != self.tx_ok_seen {
+            self.tx_ok_seen = stats.tx_ok();
+            self.tx_blocked_since = None;
+        } else if stats.tx_exhausted() != self.tx_exhausted_seen && self.tx_blocked_since.is_none() {
+            self.tx_blocked_since = Some(now);
+        }
+        self.tx_exhausted_seen = stats.tx_exhausted();
+        self.hint = hint;
+
+        let down = !self.state(now, config).is_healthy();
+        if down == self.down {
+            return None;
+        }
+        self.down = down;
+        Some(match down {
+            true => InterfaceEvent::LinkDown,
+            false => InterfaceEvent::LinkUp,
+        })
+    }
+
+    /// Return the state of the link at `now`, as of the last update.
+    pub(crate) fn state(&self, now: Instant<C>, config: &LinkSupervisionConfig) -> LinkState<C::T> {
+        if self.hint == Some(LinkHint::BusOff) {
+            return LinkState::TxStalled;
+        }
+
+        let elapsed = |since: Option<Instant<C>>| -> Option<Milliseconds<C::T>> {
+            now.checked_duration_since(&since?)?.try_into().ok()
+        };
+        let timeout = |ms: u32| millis::<C>(Milliseconds(ms));
+
+        if let (Some(blocked), Some(limit)) = (elapsed(self.tx_blocked_since), config.tx_stall_timeout_ms) {
+            if blocked >= timeout(limit) {
+                return LinkState::TxStalled;
+            }
+        }
+        if let (Some(silent), Some(limit)) = (elapsed(self.last_rx), config.silent_timeout_ms) {
+            if silent >= timeout(limit) {
+                return LinkState::SilentFor(silent);
+            }
+        }
+        LinkState::Healthy
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vlcb_core::time::TestClock;
+
+    const CONFIG: LinkSupervisionConfig = LinkSupervisionConfig {
+        silent_timeout_ms: Some(1000),
+        tx_stall_timeout_ms: Some(500),
+    };
+
+    #[test]
+    fn test_tx_stall() {
+        let clock = TestClock::new();
+        let mut link = LinkSupervisor::new();
+        let mut stats = InterfaceStats::default();
+        assert_eq!(link.update(clock.now(), &stats, None, &CONFIG), None);
+
+        stats.record_tx_exhausted();
+        assert_eq!(link.update(clock.now(), &stats, None, &CONFIG), None);
+        clock.advance(500);
+        assert_eq!(link.update(clock.now(), &stats, None, &CONFIG), Some(InterfaceEvent::LinkDown));
+        assert_eq!(link.state(clock.now(), &CONFIG), LinkState::TxStalled);
+
+        // a transmitted frame clears the stall
+        let _ = stats.count_dispatch(Ok::<(), ()>(()));
+        assert_eq!(link.update(clock.now(), &stats, None, &CONFIG), Some(InterfaceEvent::LinkUp));
+        assert_eq!(link.state(clock.now(), &CONFIG), LinkState::Healthy);
+    }
+
+    #[test]
+    fn test_bus_off_hint() {
+        let clock = TestClock::new();
+        let mut link = LinkSupervisor::new();
+        let stats = InterfaceStats::default();
+
+        let event = link.update(clock.now(), &stats, Some(LinkHint::BusOff), &CONFIG);
+        assert_eq!(event, Some(InterfaceEvent::LinkDown));
+        assert_eq!(link.state(clock.now(), &CONFIG), LinkState::TxStalled);
+
+        let event = link.update(clock.now(), &stats, Some(LinkHint::Up), &CONFIG);
+        assert_eq!(event, Some(InterfaceEvent::LinkUp));
+    }
+
+    #[test]
+    fn test_checks_off() {
+        let clock = TestClock::new();
+        let mut link = LinkSupervisor::new();
+        let mut stats = InterfaceStats::default();
+        let config = LinkSupervisionConfig {
+            silent_timeout_ms: None,
+            tx_stall_timeout_ms: None,
+        };
+
+        link.update(clock.now(), &stats, None, &config);
+        stats.record_tx_exhausted();
+        clock.advance(60_000);
+        assert_eq!(link.update(clock.now(), &stats, None, &config), None);
+        assert_eq!(link.state(clock.now(), &config), LinkState::Healthy);
+    }
+}
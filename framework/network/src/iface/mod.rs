@@ -1,9 +1,24 @@
+mod events;
 mod interface;
+mod link;
 
 pub mod vlcb_packet;
 mod socket_meta;
 mod socket_set;
+mod stats;
 
-pub use self::interface::{Interface, InterfaceInner as Context, PollContext};
+pub use self::interface::{
+    Interface, InterfaceConfig, InterfaceInner as Context, PollContext, PollResult, SendNowError,
+    DEFAULT_MAX_EGRESS_PACKETS, DEFAULT_MAX_INGRESS_PACKETS, DEFERRED_QUEUE_SIZE,
+};
+
+#[cfg(feature = "medium-can")]
+pub use self::interface::ExtendedFrameHandler;
+
+pub use self::events::{InterfaceEvent, INTERFACE_EVENT_QUEUE_SIZE};
+
+pub use self::link::{LinkState, LinkSupervisionConfig};
 
 pub use self::socket_set::{SocketHandle, SocketSet, SocketStorage};
+
+pub use self::stats::InterfaceStats;
@@ -92,7 +92,7 @@ impl<'a> SocketSet<'a> {
         }
     }
 
-    /// Get a socket from the set by its handle, as mutable.
+    /// Get a socket from the set by its handle.
     ///
     /// # Panics
     /// This function may panic if the handle does not belong to this socket set
@@ -106,7 +106,7 @@ impl<'a> SocketSet<'a> {
         }
     }
 
-    /// Get a mutable socket from the set by its handle, as mutable.
+    /// Get a socket from the set by its handle, as mutable.
     ///
     /// # Panics
     /// This function may panic if the handle does not belong to this socket set
@@ -151,3 +151,64 @@ impl<'a> SocketSet<'a> {
         self.sockets.iter_mut().filter_map(|x| x.inner.as_mut())
     }
 }
+
+#[cfg(all(test, feature = "socket-module"))]
+mod test {
+    use super::*;
+    use crate::socket::module::{self, Filter, PacketBuffer, PacketMetadata};
+
+    fn socket<'a>(
+        rx: (&'a mut [PacketMetadata], &'a mut [u8]),
+        tx: (&'a mut [PacketMetadata], &'a mut [u8]),
+    ) -> module::Socket<'a> {
+        module::Socket::new(PacketBuffer::new(rx.0, rx.1), PacketBuffer::new(tx.0, tx.1))
+    }
+
+    #[test]
+    fn test_get_by_handle() {
+        let (mut rx_meta_a, mut rx_a) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut tx_meta_a, mut tx_a) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut rx_meta_b, mut rx_b) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut tx_meta_b, mut tx_b) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+
+        let mut storage: [SocketStorage; 2] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        let mut bound = socket((&mut rx_meta_a, &mut rx_a), (&mut tx_meta_a, &mut tx_a));
+        bound.bind(Filter::All).unwrap();
+        let bound = sockets.add(bound);
+        let unbound = sockets.add(socket((&mut rx_meta_b, &mut rx_b), (&mut tx_meta_b, &mut tx_b)));
+        assert_ne!(bound, unbound);
+
+        assert!(sockets.get::<module::Socket>(bound).is_bound());
+        assert!(!sockets.get::<module::Socket>(unbound).is_bound());
+
+        sockets.get_mut::<module::Socket>(unbound).bind(Filter::Events).unwrap();
+        assert!(sockets.get::<module::Socket>(unbound).is_bound());
+
+        let mut handles = sockets.iter().map(|(handle, _)| handle);
+        assert_eq!(handles.next(), Some(bound));
+        assert_eq!(handles.next(), Some(unbound));
+        assert_eq!(handles.next(), None);
+    }
+
+    #[test]
+    fn test_remove_frees_slot() {
+        let (mut rx_meta_a, mut rx_a) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut tx_meta_a, mut tx_a) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut rx_meta_b, mut rx_b) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+        let (mut tx_meta_b, mut tx_b) = ([PacketMetadata::EMPTY; 2], [0u8; 16]);
+
+        let mut storage: [SocketStorage; 1] = Default::default();
+        let mut sockets = SocketSet::new(&mut storage[..]);
+
+        let first = sockets.add(socket((&mut rx_meta_a, &mut rx_a), (&mut tx_meta_a, &mut tx_a)));
+        let Socket::Module(removed) = sockets.remove(first);
+        assert!(!removed.is_bound());
+        assert_eq!(sockets.iter().count(), 0);
+
+        let second = sockets.add(socket((&mut rx_meta_b, &mut rx_b), (&mut tx_meta_b, &mut tx_b)));
+        assert_eq!(first, second, "the slot is reused");
+        assert!(!sockets.get::<module::Socket>(second).is_bound());
+    }
+}
@@ -0,0 +1,140 @@
+/// Counters of the frames received and transmitted by the interface.
+///
+/// All counters saturate instead of wrapping around.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceStats {
+    rx_ok: u32,
+    rx_dropped: u32,
+    rx_malformed: u32,
+    rx_unrouted: u32,
+    rx_own_frames: u32,
+    tx_ok: u32,
+    tx_exhausted: u32,
+    tx_deferred_dropped: u32,
+    tx_abandoned: u32,
+    dispatch_errors: u32,
+}
+
+impl InterfaceStats {
+    /// Return how many received frames were processed without being dropped.
+    pub fn rx_ok(&self) -> u32 {
+        self.rx_ok
+    }
+
+    /// Return how many received frames were dropped, because they were
+    /// [malformed](Self::rx_malformed), [unrouted](Self::rx_unrouted) or
+    /// [our own](Self::rx_own_frames).
+    pub fn rx_dropped(&self) -> u32 {
+        self.rx_dropped
+    }
+
+    /// Return how many received frames were malformed or carried an opcode
+    /// unknown to the library.
+    pub fn rx_malformed(&self) -> u32 {
+        self.rx_malformed
+    }
+
+    /// Return how many received packets were not accepted by any socket.
+    pub fn rx_unrouted(&self) -> u32 {
+        self.rx_unrouted
+    }
+
+    /// Return how many echoes of our own frames were dropped, see
+    /// [`Interface::set_ignore_own_frames`](crate::iface::Interface::set_ignore_own_frames).
+    pub fn rx_own_frames(&self) -> u32 {
+        self.rx_own_frames
+    }
+
+    /// Return how many frames were handed over to the device for transmission.
+    pub fn tx_ok(&self) -> u32 {
+        self.tx_ok
+    }
+
+    /// Return how many times a packet was held back because the device had no free
+    /// transmit buffer or refused the frame.
+    pub fn tx_exhausted(&self) -> u32 {
+        self.tx_exhausted
+    }
+
+    /// Return how many packets were dropped because the deferred queue of the interface was full.
+    pub fn tx_deferred_dropped(&self) -> u32 {
+        self.tx_deferred_dropped
+    }
+
+    /// Return how many socket packets were dropped after
+    /// [too many failed attempts](crate::iface::InterfaceConfig::tx_retry_limit) to transmit them.
+    pub fn tx_abandoned(&self) -> u32 {
+        self.tx_abandoned
+    }
+
+    /// Return how many packets could not be emitted into the transmit buffer of the device,
+    /// e.g. because they do not fit into a frame.
+    pub fn dispatch_errors(&self) -> u32 {
+        self.dispatch_errors
+    }
+
+    /// Count the frame as malformed if parsing it failed, passing the `result` through.
+    pub(crate) fn count_malformed<T, E>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.rx_malformed = self.rx_malformed.saturating_add(1);
+            self.rx_dropped = self.rx_dropped.saturating_add(1);
+        }
+        result
+    }
+
+    pub(crate) fn record_unrouted(&mut self) {
+        self.rx_unrouted = self.rx_unrouted.saturating_add(1);
+        self.rx_dropped = self.rx_dropped.saturating_add(1);
+    }
+
+    pub(crate) fn record_own_frame(&mut self) {
+        self.rx_own_frames = self.rx_own_frames.saturating_add(1);
+        self.rx_dropped = self.rx_dropped.saturating_add(1);
+    }
+
+    pub(crate) fn record_rx_ok(&mut self) {
+        self.rx_ok = self.rx_ok.saturating_add(1);
+    }
+
+    /// Count a transmitted frame or a dispatch error, passing the `result` through.
+    pub(crate) fn count_dispatch<T, E>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        match result {
+            Ok(_) => self.tx_ok = self.tx_ok.saturating_add(1),
+            Err(_) => self.dispatch_errors = self.dispatch_errors.saturating_add(1),
+        }
+        result
+    }
+
+    pub(crate) fn record_tx_exhausted(&mut self) {
+        self.tx_exhausted = self.tx_exhausted.saturating_add(1);
+    }
+
+    pub(crate) fn record_deferred_dropped(&mut self) {
+        self.tx_deferred_dropped = self.tx_deferred_dropped.saturating_add(1);
+    }
+
+    pub(crate) fn record_tx_abandoned(&mut self) {
+        self.tx_abandoned = self.tx_abandoned.saturating_add(1);
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut stats = InterfaceStats::default();
+        stats.record_rx_ok();
+        stats.record_unrouted();
+        stats.record_tx_exhausted();
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<InterfaceStats>(&json).unwrap(), stats);
+
+        let bytes = postcard::to_allocvec(&stats).unwrap();
+        assert_eq!(postcard::from_bytes::<InterfaceStats>(&bytes).unwrap(), stats);
+    }
+}
@@ -4,17 +4,32 @@ use crate::wire::*;
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct VlcbPacket<'p> {
     header: VlcbRepr,
+    priority: CanPriority,
     payload: VlcbPayload<'p>,
 }
 
 impl<'p> VlcbPacket<'p> {
+    /// Create a packet sent with the default priority of its opcode.
     pub fn new(vlcb_repr: VlcbRepr, payload: VlcbPayload<'p>) -> Self {
         Self {
             header: vlcb_repr,
+            priority: CanPriority::for_opcode(vlcb_repr.opcode),
             payload,
         }
     }
 
+    /// Override the priority the packet is sent with.
+    pub fn with_priority(mut self, priority: CanPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Return the priority, only the CAN header carries it.
+    #[cfg_attr(not(feature = "medium-can"), allow(dead_code))]
+    pub(crate) fn priority(&self) -> CanPriority {
+        self.priority
+    }
+
     pub(crate) fn vlcb_repr(&self) -> VlcbRepr {
         self.header
     }
@@ -24,13 +39,18 @@ impl<'p> VlcbPacket<'p> {
     }
 
     pub(crate) fn emit_payload(&self, vlcb_repr: &VlcbRepr, payload: &mut [u8]) {
-        match self.payload() {
+        let inner_payload = match self.payload() {
             #[cfg(feature = "socket-module")]
-            VlcbPayload::Module(inner_payload) => vlcb_repr
-                .emit(&mut VlcbPacketWire::new_unchecked(payload), |buf| {
-                    buf.copy_from_slice(inner_payload)
-                }),
-        }
+            VlcbPayload::Module(inner_payload) => inner_payload,
+            VlcbPayload::Interface(inner_payload) => inner_payload,
+        };
+
+        let mut packet = VlcbPacketWire::new_unchecked(&mut *payload);
+        packet.set_opcode(vlcb_repr.opcode.into());
+        packet.set_payload_len(vlcb_repr.data_len);
+
+        let header_len = vlcb_repr.header_len();
+        payload[header_len..header_len + inner_payload.len()].copy_from_slice(inner_payload)
     }
 }
 
@@ -39,4 +59,33 @@ impl<'p> VlcbPacket<'p> {
 pub enum VlcbPayload<'p> {
     #[cfg(feature = "socket-module")]
     Module(&'p [u8]),
+    /// Data octets of a packet sent by the interface itself, without a socket.
+    Interface(&'p [u8]),
+}
+
+/// A packet transmitted by the interface itself.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InterfacePacket<'p> {
+    Vlcb(VlcbPacket<'p>),
+    /// A header-only frame without any VLCB payload.
+    #[cfg(feature = "medium-can")]
+    CanControl(CanControl),
+}
+
+impl<'p> From<VlcbPacket<'p>> for InterfacePacket<'p> {
+    fn from(packet: VlcbPacket<'p>) -> Self {
+        InterfacePacket::Vlcb(packet)
+    }
+}
+
+/// Header-only CAN frames used by the CAN ID self-enumeration.
+#[cfg(feature = "medium-can")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CanControl {
+    /// Remote frame asking every node on the bus to announce its CAN ID.
+    EnumerationRequest,
+    /// Zero-length data frame announcing our CAN ID.
+    EnumerationResponse,
 }
@@ -7,13 +7,15 @@ extern crate alloc;
 #[macro_use]
 mod macros;
 
+/// Defaults of the [`iface::InterfaceConfig`].
 pub mod config {
-    // TODO: make this configurable
     #![allow(unused)]
     pub const CAN_RESERVE_DELAY_MS: u64 = 100;
     pub const CAN_DEFAULT_PRIORITY: u8 = 0xB;
     pub const LONG_MESSAGE_DEFAULT_DELAY: u16 = 20;
     pub const LONG_MESSAGE_RECEIVE_TIMEOUT: u16 = 5000;
+    pub const LINK_TX_STALL_TIMEOUT_MS: u32 = 1000;
+    pub const TX_RETRY_LIMIT: u8 = 16;
 }
 
 pub mod phy;
@@ -25,4 +27,7 @@ pub mod socket;
 
 pub mod storage;
 
-pub mod data;
\ No newline at end of file
+pub mod data;
+
+#[cfg(feature = "pretty")]
+pub mod pretty;
\ No newline at end of file
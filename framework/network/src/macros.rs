@@ -1,19 +1,12 @@
 // Credit: authors of https://github.com/smoltcp-rs/smoltcp
 
-#[cfg(not(test))]
-#[cfg(feature = "log")]
+// defmt takes precedence when both backends are enabled
+#[cfg(all(feature = "log", not(feature = "defmt")))]
 macro_rules! net_log {
     (trace, $($arg:expr),*) => { log::trace!($($arg),*) };
     (debug, $($arg:expr),*) => { log::debug!($($arg),*) };
 }
 
-#[cfg(test)]
-#[cfg(feature = "log")]
-macro_rules! net_log {
-    (trace, $($arg:expr),*) => { println!($($arg),*) };
-    (debug, $($arg:expr),*) => { println!($($arg),*) };
-}
-
 #[cfg(feature = "defmt")]
 macro_rules! net_log {
     (trace, $($arg:expr),*) => { defmt::trace!($($arg),*) };
@@ -25,10 +18,17 @@ macro_rules! net_log {
     ($level:ident, $($arg:expr),*) => {{ $( let _ = $arg; )* }}
 }
 
+/// Per-frame tracing, compiled in only with the `verbose` feature.
+#[cfg(feature = "verbose")]
 macro_rules! net_trace {
     ($($arg:expr),*) => (net_log!(trace, $($arg),*));
 }
 
+#[cfg(not(feature = "verbose"))]
+macro_rules! net_trace {
+    ($($arg:expr),*) => {{ $( let _ = $arg; )* }};
+}
+
 macro_rules! net_debug {
     ($($arg:expr),*) => (net_log!(debug, $($arg),*));
 }
@@ -4,26 +4,29 @@ use core::cell::RefCell;
 use core::fmt::Debug;
 
 use byteorder::{ByteOrder, NetworkEndian};
-use embedded_can::{Error, Id, StandardId};
+use embedded_can::{Error, ExtendedId, Id, StandardId};
 use heapless::Vec;
 use rclite::Rc;
 
 use crate::phy;
-use crate::wire::can::{HEADER_RTR_MASK};
+use crate::wire::can::{ExtendedFrame, EXTENDED_HEADER_LEN, HEADER_EXTENDED_MASK, HEADER_RTR_MASK};
 
-use super::{Device, DeviceCapabilities, Medium};
+use super::{Device, DeviceCapabilities, Medium, TxRefused};
 
 // 11 bits the least significant bits for the ID value
 // 1 most significant bit for RTR flag
 // RTR frames are always sent with DLC of 0
-const HEADER_LEN: usize = 2;
-const MTU: usize = 8;
-const FRAME_LEN: usize = HEADER_LEN + MTU;
+pub(super) const HEADER_LEN: usize = 2;
+pub(super) const MTU: usize = 8;
+pub(crate) const FRAME_LEN: usize = HEADER_LEN + MTU;
+// extended frames are only passed through, see `DeviceCapabilities::accepts_extended`
+pub(crate) const EXTENDED_FRAME_LEN: usize = EXTENDED_HEADER_LEN + MTU;
 
 /// An embedded-can device driver wrapper
 #[derive(Debug)]
 pub struct EmbeddedCan<D: embedded_can::nb::Can> {
     lower: Rc<RefCell<D>>,
+    accepts_extended: bool,
 }
 
 impl<D: embedded_can::nb::Can> EmbeddedCan<D> {
@@ -31,8 +34,18 @@ impl<D: embedded_can::nb::Can> EmbeddedCan<D> {
     pub fn new(device: D) -> Self {
         EmbeddedCan {
             lower: Rc::new(RefCell::new(device)),
+            accepts_extended: false,
         }
     }
+
+    /// Set whether the frames with an extended identifier are handed over instead of skipped,
+    /// off by default. See [`DeviceCapabilities::accepts_extended`].
+    ///
+    /// The capabilities are taken by the interface when it is created, so this is to be set
+    /// before.
+    pub fn set_accepts_extended(&mut self, accepts: bool) {
+        self.accepts_extended = accepts
+    }
 }
 
 impl<D: embedded_can::nb::Can> Device for EmbeddedCan<D> {
@@ -47,7 +60,7 @@ impl<D: embedded_can::nb::Can> Device for EmbeddedCan<D> {
         let mut lower = self.lower.borrow_mut();
         match lower.receive() {
             Ok(frame) => {
-                if let Some(buffer) = from_can_frame::<D::Frame>(frame) {
+                if let Some(buffer) = from_can_frame::<D::Frame>(frame, self.accepts_extended) {
                     let rx = RxToken { buffer };
                     let tx = TxToken {
                         lower: self.lower.clone(),
@@ -70,6 +83,8 @@ impl<D: embedded_can::nb::Can> Device for EmbeddedCan<D> {
     fn capabilities(&self) -> DeviceCapabilities {
         DeviceCapabilities {
             medium: Medium::CAN,
+            max_transmission_unit: MTU,
+            accepts_extended: self.accepts_extended,
             ..DeviceCapabilities::default()
         }
     }
@@ -77,7 +92,7 @@ impl<D: embedded_can::nb::Can> Device for EmbeddedCan<D> {
 
 #[doc(hidden)]
 pub struct RxToken {
-    buffer: Vec<u8, FRAME_LEN>,
+    buffer: Vec<u8, EXTENDED_FRAME_LEN>,
 }
 
 impl phy::RxToken for RxToken {
@@ -104,25 +119,181 @@ impl<D: embedded_can::nb::Can> Clone for TxToken<D> {
 
 impl<D: embedded_can::nb::Can> phy::TxToken for TxToken<D> {
     fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.transmit(len, f).0
+    }
+
+    /// Transmit the frame, reporting the driver's `WouldBlock` as [`TxRefused`].
+    fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, TxRefused>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (result, transmitted) = self.transmit(len, f);
+        transmitted.map(|_| result)
+    }
+}
+
+impl<D: embedded_can::nb::Can> TxToken<D> {
+    fn transmit<R, F>(self, len: usize, f: F) -> (R, Result<(), TxRefused>)
     where
         F: FnOnce(&mut [u8]) -> R,
     {
         let mut lower = self.lower.borrow_mut();
-        let mut buffer: Vec<u8, FRAME_LEN> = Vec::new();
+        let mut buffer = [0u8; EXTENDED_FRAME_LEN];
         let result = f(&mut buffer[..len]);
         match lower.transmit(&into_can_frame::<D::Frame>(&buffer[..len])) {
-            Ok(_) => {}
+            Ok(_) => (result, Ok(())),
             Err(nb::Error::WouldBlock) => {
-                net_debug!("phy: tx failed due to WouldBlock")
+                net_debug!("phy: tx failed due to WouldBlock");
+                (result, Err(TxRefused))
             }
             Err(nb::Error::Other(err)) => panic!("{}", err.kind()),
         }
-        result
+    }
+}
+
+/// An embedded-can blocking device driver wrapper
+///
+/// The blocking driver waits for a frame on receive, so the wrapper asks `rx_pending`
+/// whether a frame is waiting first, and receives only then. It is usually a check of
+/// the receive FIFO level of the peripheral.
+///
+/// ```ignore
+/// let device = EmbeddedBlockingCan::new(can, |can| can.rx_fifo_level() > 0);
+/// ```
+#[derive(Debug)]
+pub struct EmbeddedBlockingCan<D: embedded_can::blocking::Can> {
+    lower: Rc<RefCell<D>>,
+    rx_pending: fn(&mut D) -> bool,
+    accepts_extended: bool,
+}
+
+impl<D: embedded_can::blocking::Can> EmbeddedBlockingCan<D> {
+    /// Creates an embedded-can device, bound to the given blocking device driver
+    pub fn new(device: D, rx_pending: fn(&mut D) -> bool) -> Self {
+        EmbeddedBlockingCan {
+            lower: Rc::new(RefCell::new(device)),
+            rx_pending,
+            accepts_extended: false,
+        }
+    }
+
+    /// Set whether the frames with an extended identifier are handed over instead of skipped,
+    /// see [`EmbeddedCan::set_accepts_extended`].
+    pub fn set_accepts_extended(&mut self, accepts: bool) {
+        self.accepts_extended = accepts
+    }
+}
+
+impl<D: embedded_can::blocking::Can> Device for EmbeddedBlockingCan<D> {
+    type RxToken<'a> = RxToken
+        where
+            Self: 'a;
+    type TxToken<'a> = BlockingTxToken<D>
+        where
+            Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let mut lower = self.lower.borrow_mut();
+        if !(self.rx_pending)(&mut lower) {
+            return None;
+        }
+
+        match lower.receive() {
+            Ok(frame) => {
+                let buffer = from_can_frame::<D::Frame>(frame, self.accepts_extended)?;
+                let rx = RxToken { buffer };
+                let tx = BlockingTxToken {
+                    lower: self.lower.clone(),
+                };
+                Some((rx, tx))
+            }
+            Err(_) => {
+                net_debug!("phy: rx failed due to a CAN error");
+                None
+            }
+        }
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        Some(BlockingTxToken {
+            lower: self.lower.clone(),
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::CAN,
+            max_transmission_unit: MTU,
+            accepts_extended: self.accepts_extended,
+            ..DeviceCapabilities::default()
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct BlockingTxToken<D: embedded_can::blocking::Can> {
+    lower: Rc<RefCell<D>>,
+}
+
+impl<D: embedded_can::blocking::Can> Clone for BlockingTxToken<D> {
+    fn clone(&self) -> Self {
+        Self {
+            lower: Rc::clone(&self.lower),
+        }
+    }
+}
+
+impl<D: embedded_can::blocking::Can> phy::TxToken for BlockingTxToken<D> {
+    /// Transmit the frame, blocking until there is space in the transmit buffer.
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.transmit(len, f).0
+    }
+
+    /// Transmit the frame, reporting a CAN error of the driver as [`TxRefused`].
+    fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, TxRefused>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (result, transmitted) = self.transmit(len, f);
+        transmitted.map(|_| result)
+    }
+}
+
+impl<D: embedded_can::blocking::Can> BlockingTxToken<D> {
+    fn transmit<R, F>(self, len: usize, f: F) -> (R, Result<(), TxRefused>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut lower = self.lower.borrow_mut();
+        let mut buffer = [0u8; EXTENDED_FRAME_LEN];
+        let result = f(&mut buffer[..len]);
+        match lower.transmit(&into_can_frame::<D::Frame>(&buffer[..len])) {
+            Ok(()) => (result, Ok(())),
+            Err(_) => {
+                net_debug!("phy: tx failed due to a CAN error");
+                (result, Err(TxRefused))
+            }
+        }
     }
 }
 
 fn into_can_frame<T: embedded_can::Frame>(buffer: &[u8]) -> T {
     let header = NetworkEndian::read_u16(buffer);
+    if (header & HEADER_EXTENDED_MASK) != 0 {
+        let frame = ExtendedFrame::new_unchecked(buffer);
+        let id = Id::Extended(ExtendedId::new(frame.id()).unwrap());
+        return match frame.is_rtr() {
+            true => T::new_remote(id, 0).unwrap(),
+            false => T::new(id, frame.payload()).unwrap(),
+        };
+    }
+
     let id = Id::Standard(StandardId::new(header & !HEADER_RTR_MASK).unwrap());
     if (header & HEADER_RTR_MASK) != 0 {
         T::new_remote(id, 0).unwrap()
@@ -131,12 +302,19 @@ fn into_can_frame<T: embedded_can::Frame>(buffer: &[u8]) -> T {
     }
 }
 
-fn from_can_frame<T: embedded_can::Frame>(value: T) -> Option<Vec<u8, FRAME_LEN>> {
+/// Convert a received frame into the buffer layout of the interface.
+///
+/// Frames with an extended identifier are skipped unless `accepts_extended`, then they are
+/// converted into the [`ExtendedFrame`] layout.
+fn from_can_frame<T: embedded_can::Frame>(
+    value: T,
+    accepts_extended: bool,
+) -> Option<Vec<u8, EXTENDED_FRAME_LEN>> {
     match value.id() {
         // Nodes should operate properly even if network carries extended frames
         // If such frames are encountered simply ignore them
         Id::Standard(id) => {
-            let mut data = Vec::<u8, FRAME_LEN>::new();
+            let mut data = Vec::<u8, EXTENDED_FRAME_LEN>::new();
 
             // Safety: set the length of the vector to 2 to avoid copying from slices
             unsafe {
@@ -154,6 +332,18 @@ fn from_can_frame<T: embedded_can::Frame>(value: T) -> Option<Vec<u8, FRAME_LEN>
             }
             Some(data)
         }
+        Id::Extended(id) if accepts_extended => {
+            let mut data = Vec::<u8, EXTENDED_FRAME_LEN>::new();
+            data.resize_default(EXTENDED_HEADER_LEN).unwrap();
+
+            let mut frame = ExtendedFrame::new_unchecked(&mut data[..]);
+            frame.set_id(id.as_raw());
+            frame.set_rtr(value.is_remote_frame());
+            if value.is_data_frame() {
+                data.extend_from_slice(value.data()).unwrap();
+            }
+            Some(data)
+        }
         Id::Extended(_) => None,
     }
 }
@@ -236,17 +426,17 @@ mod test {
         let frame = TestFrame {
             id: Id::Standard(StandardId::new(0x00FF).unwrap()),
             remote: false,
-            data: Vec::from_slice(&buffer[4..]).unwrap(),
+            data: Vec::from_slice(&buffer[2..]).unwrap(),
         };
 
-        assert_eq!(from_can_frame::<TestFrame>(frame).unwrap(), buffer);
+        assert_eq!(from_can_frame::<TestFrame>(frame, false).unwrap(), buffer);
     }
 
     #[test]
     fn test_from_can_frame_remote_frame() {
-        let buffer: [u8; FRAME_LEN] = [
-            0x00, 0xFF, // id
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // a remote frame carries no data
+        let buffer = [
+            0x80, 0xFF, // id with the RTR bit
         ];
 
         let frame = TestFrame {
@@ -255,7 +445,74 @@ mod test {
             data: Vec::new(),
         };
 
-        assert_eq!(from_can_frame::<TestFrame>(frame).unwrap(), buffer);
+        assert_eq!(from_can_frame::<TestFrame>(frame, false).unwrap(), buffer);
+    }
+
+    #[derive(Debug)]
+    struct TestError;
+
+    impl Error for TestError {
+        fn kind(&self) -> embedded_can::ErrorKind {
+            embedded_can::ErrorKind::Other
+        }
+    }
+
+    /// Blocking CAN driver with queued received frames, panicking instead of blocking.
+    #[derive(Default)]
+    struct BlockingCan {
+        rx: alloc::collections::VecDeque<TestFrame>,
+        tx: alloc::vec::Vec<TestFrame>,
+    }
+
+    impl embedded_can::blocking::Can for BlockingCan {
+        type Frame = TestFrame;
+        type Error = TestError;
+
+        fn transmit(&mut self, frame: &Self::Frame) -> Result<(), Self::Error> {
+            self.tx.push(TestFrame::new(frame.id, frame.data()).unwrap());
+            Ok(())
+        }
+
+        fn receive(&mut self) -> Result<Self::Frame, Self::Error> {
+            Ok(self.rx.pop_front().expect("receive would block"))
+        }
+    }
+
+    #[test]
+    fn test_blocking_receive_when_pending() {
+        use crate::phy::RxToken as _;
+
+        let mut device = EmbeddedBlockingCan::new(BlockingCan::default(), |can| !can.rx.is_empty());
+        assert!(device.receive().is_none(), "does not block without a pending frame");
+
+        let id = StandardId::new(0x05).unwrap();
+        device.lower.borrow_mut().rx.push_back(TestFrame::new(id, &[0x0D]).unwrap());
+        let (rx, _) = device.receive().unwrap();
+        assert_eq!(rx.consume(|buffer| buffer.to_vec()), [0x00, 0x05, 0x0D]);
+        assert!(device.receive().is_none());
+
+        // extended frames are skipped
+        let id = ExtendedId::new(0x1F00FF00).unwrap();
+        device.lower.borrow_mut().rx.push_back(TestFrame::new(id, &[]).unwrap());
+        assert!(device.receive().is_none());
+        assert!(device.lower.borrow().rx.is_empty());
+    }
+
+    #[test]
+    fn test_blocking_transmit() {
+        use crate::phy::TxToken as _;
+
+        let mut device = EmbeddedBlockingCan::new(BlockingCan::default(), |_| false);
+        let result = device.transmit().unwrap().consume(3, |buffer| {
+            buffer.copy_from_slice(&[0x00, 0x05, 0x0D]);
+            42
+        });
+        assert_eq!(result, 42);
+
+        let tx = &device.lower.borrow().tx;
+        assert_eq!(tx.len(), 1);
+        assert_eq!(tx[0].id(), Id::Standard(StandardId::new(0x05).unwrap()));
+        assert_eq!(tx[0].data(), &[0x0D]);
     }
 
     #[test]
@@ -266,6 +523,88 @@ mod test {
             data: Vec::new(),
         };
 
-        assert_eq!(from_can_frame::<TestFrame>(frame), None);
+        assert_eq!(from_can_frame::<TestFrame>(frame, false), None);
+    }
+
+    #[test]
+    fn test_extended_frame_accepted() {
+        let id = ExtendedId::new(0x1F00FF00).unwrap();
+        let frame = TestFrame::new(id, &[0x0D, 0x0E]).unwrap();
+
+        let buffer = from_can_frame::<TestFrame>(frame, true).unwrap();
+        assert_eq!(buffer, [0x5F, 0x00, 0xFF, 0x00, 0x0D, 0x0E]);
+        let extended = ExtendedFrame::new_checked(&buffer[..]).unwrap();
+        assert_eq!(extended.id(), 0x1F00FF00);
+        assert_eq!(extended.payload(), &[0x0D, 0x0E]);
+
+        let frame = into_can_frame::<TestFrame>(&buffer);
+        assert_eq!(frame.id(), Id::Extended(id));
+        assert_eq!(frame.data(), &[0x0D, 0x0E]);
+    }
+
+    #[test]
+    fn test_blocking_receive_extended() {
+        use crate::phy::RxToken as _;
+
+        let mut device = EmbeddedBlockingCan::new(BlockingCan::default(), |can| !can.rx.is_empty());
+        assert!(!device.capabilities().accepts_extended);
+        device.set_accepts_extended(true);
+        assert!(device.capabilities().accepts_extended);
+
+        let id = ExtendedId::new(0x1F00FF00).unwrap();
+        device.lower.borrow_mut().rx.push_back(TestFrame::new(id, &[0x01; 8]).unwrap());
+        let (rx, _) = device.receive().unwrap();
+        let buffer = rx.consume(|buffer| buffer.to_vec());
+        assert_eq!(buffer.len(), EXTENDED_FRAME_LEN);
+        assert_eq!(ExtendedFrame::new_checked(&buffer[..]).unwrap().id(), 0x1F00FF00);
+
+        // standard frames are unchanged
+        let id = StandardId::new(0x05).unwrap();
+        device.lower.borrow_mut().rx.push_back(TestFrame::new(id, &[0x0D]).unwrap());
+        let (rx, _) = device.receive().unwrap();
+        assert_eq!(rx.consume(|buffer| buffer.to_vec()), [0x00, 0x05, 0x0D]);
+    }
+
+    /// Non-blocking CAN driver with a single transmit mailbox, full while `busy`.
+    #[derive(Default)]
+    struct MailboxCan {
+        busy: bool,
+        tx: alloc::vec::Vec<TestFrame>,
+    }
+
+    impl embedded_can::nb::Can for MailboxCan {
+        type Frame = TestFrame;
+        type Error = TestError;
+
+        fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+            if self.busy {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.tx.push(TestFrame::new(frame.id, frame.data()).unwrap());
+            Ok(None)
+        }
+
+        fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    #[test]
+    fn test_transmit_would_block_reported() {
+        use crate::phy::TxToken as _;
+
+        let mut device = EmbeddedCan::new(MailboxCan {
+            busy: true,
+            ..MailboxCan::default()
+        });
+        let frame = [0x00, 0x05, 0x0D];
+        let result = device.transmit().unwrap().try_consume(3, |buffer| buffer.copy_from_slice(&frame));
+        assert_eq!(result, Err(TxRefused));
+        assert!(device.lower.borrow().tx.is_empty());
+
+        device.lower.borrow_mut().busy = false;
+        let result = device.transmit().unwrap().try_consume(3, |buffer| buffer.copy_from_slice(&frame));
+        assert_eq!(result, Ok(()));
+        assert_eq!(device.lower.borrow().tx[0].data(), &[0x0D]);
     }
 }
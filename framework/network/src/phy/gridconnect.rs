@@ -0,0 +1,421 @@
+//! GridConnect serial device.
+//!
+//! USB-serial CAN adapters (CANUSB4, CANRPI, CAN-CAP, ...) talk the ASCII GridConnect protocol
+//! where every CAN frame is encoded as a single line such as `:SB020N9001020304;`.
+//!
+//! The header part of the line holds the SIDH/SIDL register pair of the frame, which is the
+//! 11-bit standard identifier shifted left by 5 bits. `N` marks a data frame, `R` a remote
+//! (RTR) frame, and it's followed by up to 8 data octets in hexadecimal form.
+
+use core::cell::RefCell;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use embedded_io::{Read, ReadReady, Write};
+use heapless::Vec;
+use rclite::Rc;
+
+use crate::phy;
+use crate::wire::can::HEADER_RTR_MASK;
+
+use super::can::{FRAME_LEN, HEADER_LEN, MTU};
+use super::{Device, DeviceCapabilities, Medium};
+
+const LINE_START: u8 = b':';
+const LINE_END: u8 = b';';
+const STANDARD_FRAME: u8 = b'S';
+const DATA_FRAME: u8 = b'N';
+const REMOTE_FRAME: u8 = b'R';
+const SID_SHIFT: u16 = 5;
+const STANDARD_ID_MASK: u16 = 0x07FF;
+
+/// Maximum length of a GridConnect line carrying a standard frame, delimiters included.
+pub const MAX_LINE_LEN: usize = 1 + 1 + 4 + 1 + MTU * 2 + 1;
+
+/// Encode an internal CAN buffer (2 octets of header followed by the payload) into
+/// a GridConnect line.
+///
+/// Returns `None` if the buffer is not a valid internal CAN buffer.
+pub fn encode(buffer: &[u8]) -> Option<Vec<u8, MAX_LINE_LEN>> {
+    if buffer.len() < HEADER_LEN || buffer.len() > FRAME_LEN {
+        return None;
+    }
+
+    let header = NetworkEndian::read_u16(&buffer[..HEADER_LEN]);
+    let sid = (header & STANDARD_ID_MASK) << SID_SHIFT;
+    let is_rtr = header & HEADER_RTR_MASK != 0;
+
+    let mut line = Vec::new();
+    // the capacity of the line is sized for the largest frame so pushes can't fail
+    let _ = line.push(LINE_START);
+    let _ = line.push(STANDARD_FRAME);
+    push_hex(&mut line, (sid >> 8) as u8);
+    push_hex(&mut line, sid as u8);
+
+    if is_rtr {
+        let _ = line.push(REMOTE_FRAME);
+    } else {
+        let _ = line.push(DATA_FRAME);
+        for byte in &buffer[HEADER_LEN..] {
+            push_hex(&mut line, *byte);
+        }
+    }
+    let _ = line.push(LINE_END);
+
+    Some(line)
+}
+
+/// Decode the body of a GridConnect line into an internal CAN buffer.
+///
+/// The `line` can be passed either with or without the `:` and `;` delimiters.
+/// Extended frames are not used by VLCB, so they are ignored the same way
+/// [EmbeddedCan](super::can::EmbeddedCan) ignores them and `None` is returned.
+pub fn decode(line: &[u8]) -> Option<Vec<u8, FRAME_LEN>> {
+    let line = line.strip_prefix(&[LINE_START]).unwrap_or(line);
+    let line = line.strip_suffix(&[LINE_END]).unwrap_or(line);
+
+    let (&kind, rest) = line.split_first()?;
+    if kind != STANDARD_FRAME || rest.len() < 5 {
+        return None;
+    }
+
+    let sid = (u16::from(hex_byte(&rest[0..2])?) << 8) | u16::from(hex_byte(&rest[2..4])?);
+    let mut header = (sid >> SID_SHIFT) & STANDARD_ID_MASK;
+
+    let data = &rest[5..];
+    let mut buffer = Vec::new();
+    match rest[4] {
+        DATA_FRAME => {
+            if data.len() % 2 != 0 || data.len() / 2 > MTU {
+                return None;
+            }
+            buffer.resize_default(HEADER_LEN).ok()?;
+            for pair in data.chunks(2) {
+                buffer.push(hex_byte(pair)?).ok()?;
+            }
+        }
+        REMOTE_FRAME => {
+            header |= HEADER_RTR_MASK;
+            buffer.resize_default(HEADER_LEN).ok()?;
+        }
+        _ => return None,
+    }
+    NetworkEndian::write_u16(&mut buffer[..HEADER_LEN], header);
+
+    Some(buffer)
+}
+
+fn push_hex(line: &mut Vec<u8, MAX_LINE_LEN>, byte: u8) {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let _ = line.push(DIGITS[(byte >> 4) as usize]);
+    let _ = line.push(DIGITS[(byte & 0x0F) as usize]);
+}
+
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    fn nibble(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            _ => None,
+        }
+    }
+
+    Some((nibble(pair[0])? << 4) | nibble(pair[1])?)
+}
+
+/// A GridConnect serial port device driver wrapper
+///
+/// Incoming bytes are accumulated between polls, so a line split over multiple
+/// reads is reassembled before being handed over to the interface.
+#[derive(Debug)]
+pub struct GridConnect<P: Read + ReadReady + Write> {
+    lower: Rc<RefCell<P>>,
+    line: Vec<u8, MAX_LINE_LEN>,
+    in_line: bool,
+}
+
+impl<P: Read + ReadReady + Write> GridConnect<P> {
+    /// Creates a GridConnect device, bound to the given serial port
+    pub fn new(port: P) -> Self {
+        GridConnect {
+            lower: Rc::new(RefCell::new(port)),
+            line: Vec::new(),
+            in_line: false,
+        }
+    }
+
+    /// Feed a single received byte into the line assembler.
+    ///
+    /// Returns the decoded frame once a complete line was received.
+    fn feed(&mut self, byte: u8) -> Option<Vec<u8, FRAME_LEN>> {
+        match byte {
+            LINE_START => {
+                self.line.clear();
+                self.in_line = true;
+                None
+            }
+            LINE_END if self.in_line => {
+                self.in_line = false;
+                let frame = decode(&self.line);
+                if frame.is_none() {
+                    net_debug!("phy: dropped malformed GridConnect line");
+                }
+                frame
+            }
+            _ if self.in_line => {
+                if self.line.push(byte).is_err() {
+                    net_debug!("phy: GridConnect line too long");
+                    self.in_line = false;
+                }
+                None
+            }
+            // line noise or line terminators between frames
+            _ => None,
+        }
+    }
+}
+
+impl<P: Read + ReadReady + Write> Device for GridConnect<P> {
+    type RxToken<'a> = RxToken
+        where
+            Self: 'a;
+    type TxToken<'a> = TxToken<P>
+        where
+            Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        loop {
+            let mut byte = [0u8; 1];
+            {
+                let mut lower = self.lower.borrow_mut();
+                match lower.read_ready() {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(_) => {
+                        net_debug!("phy: serial port read failed");
+                        return None;
+                    }
+                }
+                match lower.read(&mut byte) {
+                    Ok(1) => {}
+                    Ok(_) => return None,
+                    Err(_) => {
+                        net_debug!("phy: serial port read failed");
+                        return None;
+                    }
+                }
+            }
+
+            if let Some(buffer) = self.feed(byte[0]) {
+                let rx = RxToken { buffer };
+                let tx = TxToken {
+                    lower: self.lower.clone(),
+                };
+                return Some((rx, tx));
+            }
+        }
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            lower: self.lower.clone(),
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::CAN,
+            max_transmission_unit: MTU,
+            ..DeviceCapabilities::default()
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8, FRAME_LEN>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.buffer[..])
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<P: Read + ReadReady + Write> {
+    lower: Rc<RefCell<P>>,
+}
+
+impl<P: Read + ReadReady + Write> Clone for TxToken<P> {
+    fn clone(&self) -> Self {
+        Self {
+            lower: Rc::clone(&self.lower),
+        }
+    }
+}
+
+impl<P: Read + ReadReady + Write> phy::TxToken for TxToken<P> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = [0u8; FRAME_LEN];
+        let result = f(&mut buffer[..len]);
+
+        match encode(&buffer[..len]) {
+            Some(line) => {
+                let mut lower = self.lower.borrow_mut();
+                if lower.write_all(&line).is_err() {
+                    net_debug!("phy: serial port write failed");
+                }
+            }
+            None => net_debug!("phy: tx failed due to invalid frame length"),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec as StdVec;
+    use core::convert::Infallible;
+
+    use super::*;
+    use crate::phy::{RxToken as _, TxToken as _};
+
+    /// In-memory serial port which only makes the bytes queued by [Port::deliver] readable.
+    #[derive(Default)]
+    struct Port {
+        rx: VecDeque<u8>,
+        tx: StdVec<u8>,
+    }
+
+    impl embedded_io::ErrorType for Port {
+        type Error = Infallible;
+    }
+
+    impl Read for Port {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let mut n = 0;
+            while n < buf.len() {
+                match self.rx.pop_front() {
+                    Some(b) => {
+                        buf[n] = b;
+                        n += 1;
+                    }
+                    None => break,
+                }
+            }
+            Ok(n)
+        }
+    }
+
+    impl ReadReady for Port {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl Write for Port {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn deliver(device: &GridConnect<Port>, bytes: &[u8]) {
+        device.lower.borrow_mut().rx.extend(bytes.iter().copied());
+    }
+
+    fn receive_frame(device: &mut GridConnect<Port>) -> Option<StdVec<u8>> {
+        device
+            .receive()
+            .map(|(rx, _)| rx.consume(|buffer| buffer.to_vec()))
+    }
+
+    #[test]
+    fn test_decode_data_frame() {
+        let frame = decode(b":SB020N9001020304;").unwrap();
+        assert_eq!(&frame[..], &[0x05, 0x81, 0x90, 0x01, 0x02, 0x03, 0x04]);
+    }
+
+    #[test]
+    fn test_decode_remote_frame() {
+        let frame = decode(b":SB020R;").unwrap();
+        assert_eq!(&frame[..], &[0x85, 0x81]);
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        assert_eq!(decode(b":SB020N9;"), None);
+        assert_eq!(decode(b":SB0N90;"), None);
+        assert_eq!(decode(b":SZZ20N90;"), None);
+        assert_eq!(decode(b":SB020N000102030405060708;"), None);
+        assert_eq!(decode(b":X0000B020N90;"), None);
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let buffer = [0x05, 0x81, 0x90, 0x01, 0x02, 0x03, 0x04];
+        let line = encode(&buffer).unwrap();
+        assert_eq!(&line[..], b":SB020N9001020304;");
+        assert_eq!(&decode(&line).unwrap()[..], &buffer);
+
+        let line = encode(&[0x85, 0x81]).unwrap();
+        assert_eq!(&line[..], b":SB020R;");
+    }
+
+    #[test]
+    fn test_receive_partial_reads() {
+        let mut device = GridConnect::new(Port::default());
+
+        deliver(&device, b"\r\n:SB0");
+        assert_eq!(receive_frame(&mut device), None);
+
+        deliver(&device, b"20N90010203");
+        assert_eq!(receive_frame(&mut device), None);
+
+        deliver(&device, b"04;\r\n:SB020R;");
+        assert_eq!(
+            receive_frame(&mut device).unwrap(),
+            [0x05, 0x81, 0x90, 0x01, 0x02, 0x03, 0x04]
+        );
+        assert_eq!(receive_frame(&mut device).unwrap(), [0x85, 0x81]);
+        assert_eq!(receive_frame(&mut device), None);
+    }
+
+    #[test]
+    fn test_receive_skips_malformed_line() {
+        let mut device = GridConnect::new(Port::default());
+
+        deliver(&device, b":SB0GGN00;:SB020N90;");
+        assert_eq!(receive_frame(&mut device).unwrap(), [0x05, 0x81, 0x90]);
+    }
+
+    #[test]
+    fn test_transmit() {
+        let mut device = GridConnect::new(Port::default());
+
+        let tx = device.transmit().unwrap();
+        tx.consume(4, |buffer| {
+            buffer.copy_from_slice(&[0x05, 0x81, 0x90, 0x01]);
+        });
+
+        assert_eq!(&device.lower.borrow().tx[..], b":SB020N9001;");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let device = GridConnect::new(Port::default());
+        assert_eq!(device.capabilities().medium, Medium::CAN);
+    }
+}
@@ -0,0 +1,188 @@
+use core::cell::RefCell;
+
+use heapless::{Deque, Vec};
+use rclite::Rc;
+
+use crate::phy;
+
+use super::can::{FRAME_LEN, MTU};
+use super::{Device, DeviceCapabilities, LinkHint, Medium};
+
+type Queue<const N: usize> = Deque<Vec<u8, FRAME_LEN>, N>;
+
+/// A loopback device.
+///
+/// Every transmitted frame is queued and handed back by the next [`receive`](Device::receive).
+/// Frames are stored in the same buffer format [`EmbeddedCan`](super::can::EmbeddedCan) uses,
+/// so the device can stand in for a real CAN peripheral in tests. When the queue of
+/// `N` frames is full, further transmitted frames are dropped.
+#[derive(Debug)]
+pub struct Loopback<const N: usize> {
+    queue: Rc<RefCell<Queue<N>>>,
+    link_hint: Option<LinkHint>,
+}
+
+impl<const N: usize> Loopback<N> {
+    /// Creates a loopback device.
+    pub fn new() -> Self {
+        Loopback {
+            queue: Rc::new(RefCell::new(Deque::new())),
+            link_hint: None,
+        }
+    }
+
+    /// Set the link state reported by [`Device::link_hint`], e.g. to simulate a bus-off.
+    pub fn set_link_hint(&mut self, hint: Option<LinkHint>) {
+        self.link_hint = hint;
+    }
+
+    /// Queue a frame to be received as if it came from the bus.
+    ///
+    /// Returns the frame back if the queue is full or the frame is too long.
+    pub fn inject<'a>(&mut self, frame: &'a [u8]) -> Result<(), &'a [u8]> {
+        let buffer = Vec::from_slice(frame).map_err(|_| frame)?;
+        self.queue.borrow_mut().push_back(buffer).map_err(|_| frame)
+    }
+
+    /// Return the amount of frames waiting to be received.
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    /// Check whether there are no frames waiting to be received.
+    pub fn is_empty(&self) -> bool {
+        self.queue.borrow().is_empty()
+    }
+}
+
+impl<const N: usize> Default for Loopback<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Device for Loopback<N> {
+    type RxToken<'a> = RxToken
+        where
+            Self: 'a;
+    type TxToken<'a> = TxToken<N>
+        where
+            Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buffer = self.queue.borrow_mut().pop_front()?;
+        let rx = RxToken { buffer };
+        let tx = TxToken {
+            queue: self.queue.clone(),
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            queue: self.queue.clone(),
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::CAN,
+            max_transmission_unit: MTU,
+            // frames transmitted past the queue size would be dropped
+            max_burst_size: Some(N),
+            ..DeviceCapabilities::default()
+        }
+    }
+
+    fn link_hint(&self) -> Option<LinkHint> {
+        self.link_hint
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8, FRAME_LEN>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.buffer[..])
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<const N: usize> {
+    queue: Rc<RefCell<Queue<N>>>,
+}
+
+impl<const N: usize> Clone for TxToken<N> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: Rc::clone(&self.queue),
+        }
+    }
+}
+
+impl<const N: usize> phy::TxToken for TxToken<N> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = Vec::<u8, FRAME_LEN>::new();
+        if buffer.resize_default(len).is_err() {
+            net_debug!("phy: loopback frame too long, truncating");
+            buffer.resize_default(FRAME_LEN).ok();
+        }
+        let result = f(&mut buffer[..]);
+        if self.queue.borrow_mut().push_back(buffer).is_err() {
+            net_debug!("phy: loopback queue full, dropping frame");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::phy::{RxToken as _, TxToken as _};
+
+    #[test]
+    fn test_transmit_receive() {
+        let mut device = Loopback::<4>::new();
+        assert!(device.receive().is_none());
+
+        device.transmit().unwrap().consume(4, |buffer| {
+            buffer.copy_from_slice(&[0x05, 0x81, 0x0D, 0x00]);
+        });
+        assert_eq!(device.len(), 1);
+
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| assert_eq!(buffer, &[0x05, 0x81, 0x0D, 0x00]));
+        assert!(device.is_empty());
+    }
+
+    #[test]
+    fn test_reply_with_paired_token() {
+        let mut device = Loopback::<4>::new();
+        device.inject(&[0x85, 0x81]).unwrap();
+
+        let (rx, tx) = device.receive().unwrap();
+        rx.consume(|buffer| assert_eq!(buffer, &[0x85, 0x81]));
+        tx.consume(2, |buffer| buffer.copy_from_slice(&[0x05, 0x81]));
+
+        let (rx, _) = device.receive().unwrap();
+        rx.consume(|buffer| assert_eq!(buffer, &[0x05, 0x81]));
+    }
+
+    #[test]
+    fn test_full_queue() {
+        let mut device = Loopback::<1>::new();
+        assert_eq!(device.inject(&[0x05, 0x81]), Ok(()));
+        assert_eq!(device.inject(&[0x05, 0x82]), Err(&[0x05, 0x82][..]));
+        assert_eq!(device.capabilities().medium, Medium::CAN);
+        assert_eq!(device.capabilities().max_burst_size, Some(1));
+    }
+}
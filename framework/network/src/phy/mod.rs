@@ -4,10 +4,27 @@ use cfg_if::cfg_if;
 #[cfg(feature = "medium-can")]
 pub mod can;
 
+#[cfg(feature = "phy-gridconnect")]
+pub mod gridconnect;
+
+#[cfg(all(feature = "medium-can", any(test, feature = "phy-loopback")))]
+pub mod loopback;
+
+pub mod tracer;
+
+#[cfg(feature = "medium-can")]
+pub mod shared;
+
+#[cfg(all(feature = "medium-can", any(test, feature = "alloc")))]
+pub mod virtual_bus;
+
+#[cfg(all(feature = "medium-can", any(test, feature = "alloc")))]
+pub use self::virtual_bus::{VirtualBus, VirtualBusPort};
+
 /// A description of device capabilities.
 ///
 /// Higher-level protocols may use this information to determine how to behave.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[non_exhaustive]
 pub struct DeviceCapabilities {
@@ -16,6 +33,46 @@ pub struct DeviceCapabilities {
     /// This indicates what kind of packet the sent/received bytes are, and determines
     /// some behaviors of Interface.
     pub medium: Medium,
+
+    /// Maximum size of a VLCB packet (the opcode followed by its data octets)
+    /// the device is able to transmit.
+    ///
+    /// This is 8 octets for classic CAN.
+    pub max_transmission_unit: usize,
+
+    /// Maximum amount of frames the device accepts for transmission in a single poll.
+    ///
+    /// `None` means the amount is not limited by the device, e.g. it has a deep enough FIFO.
+    pub max_burst_size: Option<usize>,
+
+    /// The device receives the frames it transmitted itself, e.g. a CAN controller with
+    /// self-reception enabled.
+    ///
+    /// The interface then recognizes these echoes of its own frames and drops them, see
+    /// [`Interface::set_ignore_own_frames`](crate::iface::Interface::set_ignore_own_frames).
+    pub reports_own_frames: bool,
+
+    /// The device hands the CAN frames with an extended (29-bit) identifier over in the
+    /// [`CanExtendedFrame`](crate::wire::CanExtendedFrame) layout, instead of skipping them.
+    ///
+    /// VLCB never uses these frames, they belong to other protocols on the bus, e.g. the CBUS
+    /// bootloader. The interface passes them to its
+    /// [extended frame handler](crate::iface::Interface::set_extended_frame_handler) and they
+    /// never reach the sockets.
+    pub accepts_extended: bool,
+}
+
+impl Default for DeviceCapabilities {
+    fn default() -> Self {
+        let medium = Medium::default();
+        Self {
+            medium,
+            max_transmission_unit: medium.default_mtu(),
+            max_burst_size: None,
+            reports_own_frames: false,
+            accepts_extended: false,
+        }
+    }
 }
 
 /// Type of medium of a device.
@@ -25,6 +82,10 @@ pub enum Medium {
     /// CAN medium. Devices of this type send and receive CAN frames.
     #[cfg(feature = "medium-can")]
     CAN,
+    /// Ethernet medium. Devices of this type send and receive VLCB over Ethernet frames,
+    /// see [`EthernetFrame`](crate::wire::EthernetFrame).
+    #[cfg(feature = "medium-eth")]
+    ETH,
 }
 
 impl Default for Medium {
@@ -33,6 +94,9 @@ impl Default for Medium {
             if #[cfg(feature = "medium-can")] {
                 Medium::CAN
             }
+            else if #[cfg(feature = "medium-eth")] {
+                Medium::ETH
+            }
             else {
                 compile_error!("No medium feature enabled");
             }
@@ -40,10 +104,26 @@ impl Default for Medium {
     }
 }
 
+impl Medium {
+    /// Return the maximum payload of a frame of the medium, used when the device does not
+    /// report its own.
+    pub const fn default_mtu(self) -> usize {
+        match self {
+            #[cfg(feature = "medium-can")]
+            Medium::CAN => can::MTU,
+            #[cfg(feature = "medium-eth")]
+            Medium::ETH => crate::wire::VLCB_MAX_PAYLOAD,
+        }
+    }
+}
+
 impl From<Medium> for BusType {
     fn from(value: Medium) -> Self {
         match value {
+            #[cfg(feature = "medium-can")]
             Medium::CAN => Self::CAN,
+            #[cfg(feature = "medium-eth")]
+            Medium::ETH => Self::Ethernet,
         }
     }
 }
@@ -74,6 +154,24 @@ pub trait Device {
 
     /// Get a description of device capabilities.
     fn capabilities(&self) -> DeviceCapabilities;
+
+    /// Get the state of the link as known to the driver, e.g. the bus-off of a CAN controller.
+    ///
+    /// `None` when the driver does not know, the interface then supervises the link by the
+    /// received and transmitted frames alone.
+    fn link_hint(&self) -> Option<LinkHint> {
+        None
+    }
+}
+
+/// The state of the link reported by a device driver, see [`Device::link_hint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkHint {
+    /// The device is connected to the bus.
+    Up,
+    /// The device lost the bus and can not transmit, e.g. a CAN controller in bus-off.
+    BusOff,
 }
 
 /// A token to receive a single network packet.
@@ -98,4 +196,44 @@ pub trait TxToken: Clone {
     fn consume<R, F>(self, len: usize, f: F) -> R
     where
         F: FnOnce(&mut [u8]) -> R;
+
+    /// Like [`consume`](Self::consume), but report whether the device took the frame.
+    ///
+    /// A device whose transmit buffers may fill up after handing out the token, e.g. a CAN
+    /// controller with a few transmit mailboxes, returns `Err(TxRefused)` so the caller keeps
+    /// the packet for a retry. The default assumes every frame is taken.
+    fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, TxRefused>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        Ok(self.consume(len, f))
+    }
+}
+
+/// The device did not take the frame for transmission, see [`TxToken::try_consume`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxRefused;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_medium_bus_type() {
+        #[cfg(feature = "medium-can")]
+        assert_eq!(BusType::from(Medium::CAN), BusType::CAN);
+        #[cfg(feature = "medium-eth")]
+        assert_eq!(BusType::from(Medium::ETH), BusType::Ethernet);
+    }
+
+    #[test]
+    fn test_default_mtu() {
+        let caps = DeviceCapabilities::default();
+        assert_eq!(caps.max_transmission_unit, caps.medium.default_mtu());
+        #[cfg(feature = "medium-can")]
+        assert_eq!(Medium::CAN.default_mtu(), 8);
+        #[cfg(feature = "medium-eth")]
+        assert_eq!(Medium::ETH.default_mtu(), crate::wire::VLCB_MAX_PAYLOAD);
+    }
 }
@@ -0,0 +1,440 @@
+use core::cell::RefCell;
+
+use heapless::{Deque, Vec};
+use rclite::Rc;
+
+use crate::phy;
+
+use super::can::FRAME_LEN;
+use super::{Device, DeviceCapabilities, LinkHint, TxRefused};
+
+type Queue<const N: usize> = Deque<Vec<u8, FRAME_LEN>, N>;
+
+/// A device shared by several interfaces, e.g. two logical nodes on one CAN adapter.
+///
+/// The broker hands out `PORTS` [`SharedPort`]s, each of them a [`Device`] for its own
+/// [`Interface`](crate::iface::Interface). Every frame received from the device is handed
+/// to all the ports, a frame transmitted by a port goes to the device and, once the device
+/// took it, to the other ports, just like the frames of another node on the bus. A frame
+/// longer than a CAN frame is dropped.
+///
+/// Every port has a queue of `N` frames. A frame is dropped for the port whose queue is
+/// full, see [`SharedPort::rx_dropped`].
+pub struct Shared<D: Device, const PORTS: usize, const N: usize> {
+    inner: Rc<RefCell<SharedInner<D, PORTS, N>>>,
+}
+
+struct SharedInner<D: Device, const PORTS: usize, const N: usize> {
+    device: D,
+    queues: [Queue<N>; PORTS],
+    rx_dropped: [usize; PORTS],
+    taken: [bool; PORTS],
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> Shared<D, PORTS, N> {
+    /// Create a broker of the device.
+    pub fn new(device: D) -> Self {
+        Shared {
+            inner: Rc::new(RefCell::new(SharedInner {
+                device,
+                queues: core::array::from_fn(|_| Deque::new()),
+                rx_dropped: [0; PORTS],
+                taken: [false; PORTS],
+            })),
+        }
+    }
+
+    /// Hand out the port with the given index.
+    ///
+    /// Returns `None` if the index is out of range or the port was handed out already.
+    pub fn port(&self, index: usize) -> Option<SharedPort<D, PORTS, N>> {
+        let mut inner = self.inner.borrow_mut();
+        let taken = inner.taken.get_mut(index)?;
+        if *taken {
+            return None;
+        }
+        *taken = true;
+
+        Some(SharedPort {
+            inner: self.inner.clone(),
+            index,
+        })
+    }
+
+    /// Run `f` with the underlying device.
+    ///
+    /// It is inadvisable to receive from the device directly, the frames would bypass the ports.
+    pub fn with_device<R>(&self, f: impl FnOnce(&mut D) -> R) -> R {
+        f(&mut self.inner.borrow_mut().device)
+    }
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> SharedInner<D, PORTS, N> {
+    /// Queue a copy of the frame for every handed out port except `from`.
+    fn deliver(&mut self, from: usize, frame: &Vec<u8, FRAME_LEN>) {
+        for index in (0..PORTS).filter(|&i| i != from && self.taken[i]) {
+            if self.queues[index].push_back(frame.clone()).is_err() {
+                net_debug!("phy: shared port {} queue full, dropping frame", index);
+                self.rx_dropped[index] += 1;
+            }
+        }
+    }
+
+    /// Receive a frame from the device for the port `index`, the other ports get a copy.
+    fn pull(&mut self, index: usize) -> Option<Vec<u8, FRAME_LEN>> {
+        let (rx_token, _) = self.device.receive()?;
+        let Some(frame) = phy::RxToken::consume(rx_token, |buffer| Vec::from_slice(buffer).ok()) else {
+            net_debug!("phy: shared device frame too long, dropping");
+            return None;
+        };
+        self.deliver(index, &frame);
+        Some(frame)
+    }
+}
+
+/// A port of a [`Shared`] device.
+pub struct SharedPort<D: Device, const PORTS: usize, const N: usize> {
+    inner: Rc<RefCell<SharedInner<D, PORTS, N>>>,
+    index: usize,
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> SharedPort<D, PORTS, N> {
+    /// Return the amount of frames dropped because the queue of the port was full.
+    pub fn rx_dropped(&self) -> usize {
+        self.inner.borrow().rx_dropped[self.index]
+    }
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> Device for SharedPort<D, PORTS, N> {
+    type RxToken<'a> = RxToken
+    where
+        Self: 'a;
+    type TxToken<'a> = TxToken<D, PORTS, N>
+    where
+        Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let mut inner = self.inner.borrow_mut();
+        let buffer = match inner.queues[self.index].pop_front() {
+            Some(buffer) => buffer,
+            None => inner.pull(self.index)?,
+        };
+
+        let rx = RxToken { buffer };
+        let tx = TxToken {
+            inner: self.inner.clone(),
+            index: self.index,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        // only check the device has room, the frame is transmitted when the token is consumed
+        self.inner.borrow_mut().device.transmit()?;
+        Some(TxToken {
+            inner: self.inner.clone(),
+            index: self.index,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.inner.borrow().device.capabilities()
+    }
+
+    fn link_hint(&self) -> Option<LinkHint> {
+        self.inner.borrow().device.link_hint()
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8, FRAME_LEN>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.buffer[..])
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<D: Device, const PORTS: usize, const N: usize> {
+    inner: Rc<RefCell<SharedInner<D, PORTS, N>>>,
+    index: usize,
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> Clone for TxToken<D, PORTS, N> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            index: self.index,
+        }
+    }
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> phy::TxToken for TxToken<D, PORTS, N> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.transmit(len, f).0
+    }
+
+    /// Transmit the frame, reporting a frame the device did not take as [`TxRefused`].
+    fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, TxRefused>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let (result, transmitted) = self.transmit(len, f);
+        transmitted.map(|_| result)
+    }
+}
+
+impl<D: Device, const PORTS: usize, const N: usize> TxToken<D, PORTS, N> {
+    /// Transmit the frame to the device, the other ports get a copy once the device took it.
+    fn transmit<R, F>(self, len: usize, f: F) -> (R, Result<(), TxRefused>)
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = Vec::<u8, FRAME_LEN>::new();
+        if buffer.resize_default(len).is_err() {
+            net_debug!("phy: shared port frame too long, {} octets, dropping", len);
+            // the frame is emitted into a scratch buffer and goes nowhere
+            let mut scratch = [0u8; FRAME_LEN];
+            return (f(&mut scratch[..]), Ok(()));
+        }
+        let result = f(&mut buffer[..]);
+
+        // transmissions are serialised by the borrow of the broker
+        let mut inner = self.inner.borrow_mut();
+        let Some(tx_token) = inner.device.transmit() else {
+            net_debug!("phy: shared device exhausted");
+            return (result, Err(TxRefused));
+        };
+        let transmitted = phy::TxToken::try_consume(tx_token, buffer.len(), |tx_buffer| {
+            tx_buffer.copy_from_slice(&buffer)
+        });
+        if transmitted.is_ok() {
+            inner.deliver(self.index, &buffer);
+        }
+        (result, transmitted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::RefCell;
+
+    use alloc::vec;
+    use vlcb_core::can::VlcbCanId;
+    use vlcb_core::time::TestClock;
+    use vlcb_core::vlcb::VlcbNodeNumber;
+    use vlcb_defs::OpCode;
+
+    use super::*;
+    use crate::iface::{Interface, PollContext, SocketHandle, SocketSet};
+    use crate::phy::loopback::Loopback;
+    use crate::socket::module::{self, Filter, PacketBuffer, PacketMetadata};
+    use crate::wire::{CanFrame, HardwareAddress};
+
+    std::thread_local! {
+        static WIRE: RefCell<std::vec::Vec<std::vec::Vec<u8>>> =
+            const { RefCell::new(std::vec::Vec::new()) };
+    }
+
+    /// A CAN adapter receiving the injected frames, unlike the loopback it does not receive
+    /// its own frames. The transmitted frames are recorded in `WIRE`.
+    #[derive(Default)]
+    struct Bus {
+        rx: Loopback<8>,
+    }
+
+    impl Device for Bus {
+        type RxToken<'a> = <Loopback<8> as Device>::RxToken<'a>;
+        type TxToken<'a> = WireTxToken;
+
+        fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+            self.rx.receive().map(|(rx, _)| (rx, WireTxToken))
+        }
+
+        fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+            Some(WireTxToken)
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            self.rx.capabilities()
+        }
+    }
+
+    #[derive(Clone)]
+    struct WireTxToken;
+
+    impl phy::TxToken for WireTxToken {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            let mut buffer = std::vec![0; len];
+            let result = f(&mut buffer);
+            WIRE.with(|w| w.borrow_mut().push(buffer));
+            result
+        }
+    }
+
+    fn node<'a>(
+        port: &SharedPort<Bus, 2, 4>,
+        node_num: VlcbNodeNumber,
+        can_id: u8,
+    ) -> (Interface<TestClock>, SocketSet<'a>, SocketHandle) {
+        let hw_addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[can_id]));
+        let iface = Interface::new(port, Some(node_num), hw_addr);
+        let mut socket = module::Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+        );
+        socket.bind(Filter::Opcodes(&[OpCode::QueryNodeInfo])).unwrap();
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(socket);
+        (iface, sockets, handle)
+    }
+
+    /// Answer the received QNNs with a PNN of the node.
+    fn answer(socket: &mut module::Socket, node_num: VlcbNodeNumber) -> usize {
+        let nn = node_num.as_bytes();
+        let mut answered = 0;
+        while socket.recv().is_ok() {
+            socket.send_slice(&[OpCode::NodeInfo.into(), nn[0], nn[1], 0x0D, 0x01, 0x07]).unwrap();
+            answered += 1;
+        }
+        answered
+    }
+
+    #[test]
+    fn test_two_nodes_on_one_device() {
+        let clock = TestClock::new();
+        let shared = Shared::<Bus, 2, 4>::new(Bus::default());
+        let mut ports = [shared.port(0).unwrap(), shared.port(1).unwrap()];
+        assert!(shared.port(1).is_none(), "every port is handed out once");
+        assert!(shared.port(2).is_none());
+
+        let node_nums = [VlcbNodeNumber::new(0x01, 0x00), VlcbNodeNumber::new(0x02, 0x00)];
+        let mut nodes = [
+            node(&ports[0], node_nums[0], 1),
+            node(&ports[1], node_nums[1], 2),
+        ];
+
+        // a configuration tool broadcasts QNN
+        let qnn = [0x00, 0x7F, OpCode::QueryNodeInfo.into()];
+        shared.with_device(|bus| bus.rx.inject(&qnn)).unwrap();
+
+        for ((iface, sockets, handle), (port, node_num)) in
+            nodes.iter_mut().zip(ports.iter_mut().zip(node_nums))
+        {
+            iface.poll(PollContext::new(clock.now(), port, sockets));
+            assert_eq!(answer(sockets.get_mut(*handle), node_num), 1, "QNN seen by both nodes");
+        }
+        for ((iface, sockets, _), port) in nodes.iter_mut().zip(ports.iter_mut()) {
+            iface.poll(PollContext::new(clock.now(), port, sockets));
+        }
+
+        let wire = WIRE.with(|w| w.borrow().clone());
+        let pnns: std::vec::Vec<_> = wire
+            .iter()
+            .map(|frame| CanFrame::new_checked(&frame[..]).unwrap())
+            .filter(|frame| frame.payload().first() == Some(&OpCode::NodeInfo.into()))
+            .map(|frame| (u8::from(frame.src_addr()), frame.payload()[1]))
+            .collect();
+        assert_eq!(pnns, [(1, 0x01), (2, 0x02)], "both PNNs reach the wire");
+        assert_eq!(ports[0].rx_dropped(), 0);
+        assert_eq!(ports[1].rx_dropped(), 0);
+    }
+
+    /// A CAN adapter whose transmit buffers are full by the time a frame is handed over.
+    struct Refusing;
+
+    impl Device for Refusing {
+        type RxToken<'a> = RxToken;
+        type TxToken<'a> = RefusingTxToken;
+
+        fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+            None
+        }
+
+        fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+            Some(RefusingTxToken)
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities::default()
+        }
+    }
+
+    #[derive(Clone)]
+    struct RefusingTxToken;
+
+    impl phy::TxToken for RefusingTxToken {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            f(&mut [0; FRAME_LEN][..len])
+        }
+
+        fn try_consume<R, F>(self, _len: usize, _f: F) -> Result<R, TxRefused>
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            Err(TxRefused)
+        }
+    }
+
+    #[test]
+    fn test_refused_frame_not_delivered() {
+        let shared = Shared::<Refusing, 2, 4>::new(Refusing);
+        let mut first = shared.port(0).unwrap();
+        let mut second = shared.port(1).unwrap();
+
+        let tx = first.transmit().unwrap();
+        let result = phy::TxToken::try_consume(tx, 3, |buffer| buffer.copy_from_slice(&[1, 2, 3]));
+        assert_eq!(result, Err(TxRefused));
+        assert!(second.receive().is_none(), "the other port never sees a refused frame");
+    }
+
+    #[test]
+    fn test_oversized_frame_dropped() {
+        let shared = Shared::<Loopback<8>, 2, 4>::new(Loopback::new());
+        let mut first = shared.port(0).unwrap();
+        let mut second = shared.port(1).unwrap();
+
+        let tx = first.transmit().unwrap();
+        let len = phy::TxToken::consume(tx, FRAME_LEN + 1, |buffer| buffer.len());
+        assert_eq!(len, FRAME_LEN, "the frame is emitted into a scratch buffer");
+        assert!(second.receive().is_none());
+        assert!(shared.with_device(|device| device.receive().is_none()), "nothing transmitted");
+
+        let tx = first.transmit().unwrap();
+        phy::TxToken::consume(tx, 3, |buffer| buffer.copy_from_slice(&[1, 2, 3]));
+        let (rx, _) = second.receive().unwrap();
+        assert_eq!(phy::RxToken::consume(rx, |buffer| buffer.to_vec()), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_queue_full_counts_drops() {
+        let shared = Shared::<Loopback<8>, 2, 1>::new(Loopback::new());
+        let mut first = shared.port(0).unwrap();
+        let second = shared.port(1).unwrap();
+
+        let qnn = [0x00, 0x7F, OpCode::QueryNodeInfo.into()];
+        shared.with_device(|bus| {
+            bus.inject(&qnn).unwrap();
+            bus.inject(&qnn).unwrap();
+        });
+        while first.receive().is_some() {}
+
+        assert_eq!(first.rx_dropped(), 0);
+        assert_eq!(second.rx_dropped(), 1);
+    }
+}
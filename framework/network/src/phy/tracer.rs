@@ -0,0 +1,173 @@
+use crate::phy::{self, Device, DeviceCapabilities, LinkHint};
+
+// Credit: authors of https://github.com/smoltcp-rs/smoltcp
+
+/// Direction of a traced frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TracerDirection {
+    RX,
+    TX,
+}
+
+/// A tracer device.
+///
+/// A tracer is a device that passes every received and transmitted frame
+/// to a writer function, e.g. to print it.
+#[derive(Debug)]
+pub struct Tracer<D: Device> {
+    inner: D,
+    writer: fn(TracerDirection, &[u8]),
+}
+
+impl<D: Device> Tracer<D> {
+    /// Create a tracer device.
+    pub fn new(inner: D, writer: fn(TracerDirection, &[u8])) -> Tracer<D> {
+        Tracer { inner, writer }
+    }
+
+    /// Get a reference to the underlying device.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly operate the underlying device as doing so will
+    /// bypass the tracer.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Return the underlying device, consuming the tracer.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+}
+
+impl<D: Device> Device for Tracer<D> {
+    type RxToken<'a> = RxToken<D::RxToken<'a>>
+    where
+        Self: 'a;
+    type TxToken<'a> = TxToken<D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let writer = self.writer;
+        self.inner.receive().map(|(rx_token, tx_token)| {
+            let rx = RxToken {
+                token: rx_token,
+                writer,
+            };
+            let tx = TxToken {
+                token: tx_token,
+                writer,
+            };
+            (rx, tx)
+        })
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        let writer = self.writer;
+        self.inner
+            .transmit()
+            .map(|token| TxToken { token, writer })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn link_hint(&self) -> Option<LinkHint> {
+        self.inner.link_hint()
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken<Rx: phy::RxToken> {
+    token: Rx,
+    writer: fn(TracerDirection, &[u8]),
+}
+
+impl<Rx: phy::RxToken> phy::RxToken for RxToken<Rx> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.token.consume(|buffer| {
+            (self.writer)(TracerDirection::RX, buffer);
+            f(buffer)
+        })
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone)]
+pub struct TxToken<Tx: phy::TxToken> {
+    token: Tx,
+    writer: fn(TracerDirection, &[u8]),
+}
+
+impl<Tx: phy::TxToken> phy::TxToken for TxToken<Tx> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.token.consume(len, |buffer| {
+            let result = f(buffer);
+            (self.writer)(TracerDirection::TX, buffer);
+            result
+        })
+    }
+    fn try_consume<R, F>(self, len: usize, f: F) -> Result<R, phy::TxRefused>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.token.try_consume(len, |buffer| {
+            let result = f(buffer);
+            (self.writer)(TracerDirection::TX, buffer);
+            result
+        })
+    }
+}
+
+#[cfg(all(test, feature = "medium-can"))]
+mod test {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::phy::loopback::Loopback;
+    use crate::phy::{RxToken as _, TxToken as _};
+
+    std::thread_local! {
+        static TRACED: RefCell<std::vec::Vec<(TracerDirection, std::vec::Vec<u8>)>> =
+            const { RefCell::new(std::vec::Vec::new()) };
+    }
+
+    fn record(direction: TracerDirection, frame: &[u8]) {
+        TRACED.with(|t| t.borrow_mut().push((direction, frame.to_vec())));
+    }
+
+    #[test]
+    fn test_traces_both_directions() {
+        let mut device = Tracer::new(Loopback::<4>::new(), record);
+
+        device
+            .transmit()
+            .unwrap()
+            .consume(3, |buffer| buffer.copy_from_slice(&[0x01, 0x02, 0x0D]));
+        let frame = device.receive().unwrap().0.consume(|buffer| buffer.to_vec());
+        assert_eq!(frame, [0x01, 0x02, 0x0D]);
+
+        TRACED.with(|t| {
+            assert_eq!(
+                *t.borrow(),
+                [
+                    (TracerDirection::TX, std::vec![0x01, 0x02, 0x0D]),
+                    (TracerDirection::RX, std::vec![0x01, 0x02, 0x0D]),
+                ]
+            )
+        });
+    }
+}
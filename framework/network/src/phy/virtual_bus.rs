@@ -0,0 +1,333 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use embedded_time::duration::Milliseconds;
+use embedded_time::{Clock, Instant};
+use rclite::Rc;
+use vlcb_core::time::millis;
+
+use crate::phy;
+
+use super::can::{FRAME_LEN, MTU};
+use super::{Device, DeviceCapabilities, Medium};
+
+/// An in-process CAN bus connecting simulated nodes, e.g. for a virtual layout.
+///
+/// Every [`VirtualBusPort`] handed out by [`port`](VirtualBus::port) is a [`Device`] of its
+/// own node. A frame transmitted by a port is received by all the other ports, after the
+/// [latency](VirtualBus::with_latency) of the bus. Frames can be lost on their way to a port
+/// with a [drop probability](VirtualBus::with_drop_probability) to test the robustness of
+/// the nodes.
+///
+/// The time is read from the `clock` and the losses come from a seeded generator, there are
+/// no threads nor system time involved. A simulation driven by a manual clock thus behaves
+/// the same on every run, on the host as well as on `wasm32-unknown-unknown`.
+pub struct VirtualBus<'c, C: Clock> {
+    inner: Rc<RefCell<BusInner<'c, C>>>,
+}
+
+struct BusInner<'c, C: Clock> {
+    clock: &'c C,
+    latency_ms: u32,
+    drop_percent: u8,
+    self_reception: bool,
+    rng: u32,
+    queues: Vec<VecDeque<InFlight<C>>>,
+    dropped: usize,
+}
+
+/// A frame travelling to a port.
+struct InFlight<C: Clock> {
+    due: Instant<C>,
+    frame: Vec<u8>,
+}
+
+impl<'c, C: Clock> VirtualBus<'c, C> {
+    /// Create a bus without latency nor losses.
+    pub fn new(clock: &'c C) -> Self {
+        VirtualBus {
+            inner: Rc::new(RefCell::new(BusInner {
+                clock,
+                latency_ms: 0,
+                drop_percent: 0,
+                self_reception: false,
+                rng: 1,
+                queues: Vec::new(),
+                dropped: 0,
+            })),
+        }
+    }
+
+    /// Deliver the frames after the `latency`.
+    pub fn with_latency(self, latency: Milliseconds<u32>) -> Self {
+        self.inner.borrow_mut().latency_ms = latency.0;
+        self
+    }
+
+    /// Lose `percent` of the frames, on the way to every port separately.
+    ///
+    /// The lost frames are picked by a generator started from the `seed`, the same seed
+    /// loses the same frames.
+    pub fn with_drop_probability(self, percent: u8, seed: u32) -> Self {
+        {
+            let mut inner = self.inner.borrow_mut();
+            inner.drop_percent = percent.min(100);
+            // the generator would be stuck at zero
+            inner.rng = seed.max(1);
+        }
+        self
+    }
+
+    /// Deliver the frames to the transmitting port as well, like a CAN controller with
+    /// self-reception enabled.
+    ///
+    /// The ports then report [`reports_own_frames`](DeviceCapabilities::reports_own_frames).
+    pub fn with_self_reception(self) -> Self {
+        self.inner.borrow_mut().self_reception = true;
+        self
+    }
+
+    /// Connect a new port to the bus.
+    ///
+    /// The port receives the frames transmitted from now on.
+    pub fn port(&self) -> VirtualBusPort<'c, C> {
+        let mut inner = self.inner.borrow_mut();
+        inner.queues.push(VecDeque::new());
+        VirtualBusPort {
+            inner: self.inner.clone(),
+            index: inner.queues.len() - 1,
+        }
+    }
+
+    /// Return the amount of frames lost because of the drop probability.
+    pub fn dropped(&self) -> usize {
+        self.inner.borrow().dropped
+    }
+}
+
+impl<C: Clock> BusInner<'_, C> {
+    /// Queue the frame for every port except `from`, unless the bus has self-reception.
+    fn broadcast(&mut self, from: usize, frame: Vec<u8>) {
+        let Ok(now) = self.clock.try_now() else {
+            net_debug!("phy: virtual bus clock failed, dropping frame");
+            return;
+        };
+        let due = now + millis::<C>(Milliseconds(self.latency_ms));
+
+        let self_reception = self.self_reception;
+        for index in (0..self.queues.len()).filter(|&i| self_reception || i != from) {
+            if self.lose_frame() {
+                self.dropped += 1;
+                continue;
+            }
+            self.queues[index].push_back(InFlight {
+                due,
+                frame: frame.clone(),
+            });
+        }
+    }
+
+    /// Decide whether the next delivered frame is lost.
+    fn lose_frame(&mut self) -> bool {
+        if self.drop_percent == 0 {
+            return false;
+        }
+        // xorshift32, good enough to pick the lost frames
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x % 100 < self.drop_percent as u32
+    }
+}
+
+/// A port of a [`VirtualBus`].
+pub struct VirtualBusPort<'c, C: Clock> {
+    inner: Rc<RefCell<BusInner<'c, C>>>,
+    index: usize,
+}
+
+impl<'c, C: Clock> Device for VirtualBusPort<'c, C> {
+    type RxToken<'a> = RxToken
+    where
+        Self: 'a;
+    type TxToken<'a> = TxToken<'c, C>
+    where
+        Self: 'a;
+
+    fn receive(&mut self) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let mut inner = self.inner.borrow_mut();
+        let now = inner.clock.try_now().ok()?;
+        let queue = &mut inner.queues[self.index];
+        if queue.front()?.due > now {
+            return None;
+        }
+        let frame = queue.pop_front()?.frame;
+
+        let rx = RxToken { frame };
+        let tx = TxToken {
+            inner: self.inner.clone(),
+            index: self.index,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            inner: self.inner.clone(),
+            index: self.index,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::CAN,
+            max_transmission_unit: MTU,
+            max_burst_size: None,
+            reports_own_frames: self.inner.borrow().self_reception,
+            accepts_extended: false,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    frame: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.frame[..])
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'c, C: Clock> {
+    inner: Rc<RefCell<BusInner<'c, C>>>,
+    index: usize,
+}
+
+impl<C: Clock> Clone for TxToken<'_, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            index: self.index,
+        }
+    }
+}
+
+impl<C: Clock> phy::TxToken for TxToken<'_, C> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        if len > FRAME_LEN {
+            net_debug!("phy: virtual bus frame too long, truncating");
+        }
+        let mut frame = alloc::vec![0; len.min(FRAME_LEN)];
+        let result = f(&mut frame[..]);
+        self.inner.borrow_mut().broadcast(self.index, frame);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::time::TestClock;
+
+    use super::*;
+    use crate::phy::{RxToken as _, TxToken as _};
+
+    fn send<C: Clock>(port: &mut VirtualBusPort<C>, frame: &[u8]) {
+        port.transmit()
+            .unwrap()
+            .consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+    }
+
+    fn recv<C: Clock>(port: &mut VirtualBusPort<C>) -> Option<Vec<u8>> {
+        port.receive().map(|(rx, _)| rx.consume(|buffer| buffer.to_vec()))
+    }
+
+    #[test]
+    fn test_broadcast_delivery() {
+        let clock = TestClock::new();
+        let bus = VirtualBus::new(&clock);
+        let mut ports = [bus.port(), bus.port(), bus.port()];
+
+        send(&mut ports[0], &[0x00, 0x01, 0x0D]);
+        assert_eq!(recv(&mut ports[0]), None, "the sender does not receive its own frame");
+        for port in &mut ports[1..] {
+            assert_eq!(recv(port).as_deref(), Some(&[0x00, 0x01, 0x0D][..]));
+            assert_eq!(recv(port), None);
+        }
+
+        // a port connected later receives only the frames transmitted from then on
+        let mut late = bus.port();
+        assert_eq!(recv(&mut late), None);
+        send(&mut ports[1], &[0x00, 0x02]);
+        assert_eq!(recv(&mut late).as_deref(), Some(&[0x00, 0x02][..]));
+        assert_eq!(recv(&mut ports[0]).as_deref(), Some(&[0x00, 0x02][..]));
+    }
+
+    #[test]
+    fn test_self_reception() {
+        let clock = TestClock::new();
+        let bus = VirtualBus::new(&clock).with_self_reception();
+        let mut ports = [bus.port(), bus.port()];
+        assert!(ports[0].capabilities().reports_own_frames);
+
+        send(&mut ports[0], &[0x00, 0x01, 0x0D]);
+        for port in &mut ports {
+            assert_eq!(recv(port).as_deref(), Some(&[0x00, 0x01, 0x0D][..]));
+        }
+    }
+
+    #[test]
+    fn test_latency_ordering() {
+        let clock = TestClock::new();
+        let bus = VirtualBus::new(&clock).with_latency(Milliseconds(10));
+        let mut sender = bus.port();
+        let mut other = bus.port();
+        let mut receiver = bus.port();
+
+        send(&mut sender, &[0x00, 0x01]);
+        clock.advance(5);
+        send(&mut other, &[0x00, 0x02]);
+        assert_eq!(recv(&mut receiver), None, "nothing arrives before the latency");
+
+        clock.advance(5);
+        assert_eq!(recv(&mut receiver).as_deref(), Some(&[0x00, 0x01][..]));
+        assert_eq!(recv(&mut receiver), None);
+
+        clock.advance(5);
+        assert_eq!(recv(&mut receiver).as_deref(), Some(&[0x00, 0x02][..]));
+    }
+
+    #[test]
+    fn test_seeded_drops_deterministic() {
+        fn simulate(seed: u32) -> (Vec<u8>, usize) {
+            let clock = TestClock::new();
+            let bus = VirtualBus::new(&clock).with_drop_probability(30, seed);
+            let mut sender = bus.port();
+            let mut receiver = bus.port();
+
+            for n in 0..100 {
+                send(&mut sender, &[0x00, n]);
+            }
+            let received = core::iter::from_fn(|| recv(&mut receiver)).map(|frame| frame[1]).collect();
+            (received, bus.dropped())
+        }
+
+        let (received, dropped) = simulate(42);
+        assert_eq!(received.len() + dropped, 100);
+        assert!((10..50).contains(&dropped), "about 30 % is lost, lost {}", dropped);
+        assert!(received.windows(2).all(|w| w[0] < w[1]), "the order is kept");
+        assert_eq!(simulate(42), (received.clone(), dropped), "the same seed loses the same frames");
+        assert_ne!(simulate(7).0, received);
+    }
+}
@@ -0,0 +1,336 @@
+/*! Human-readable rendering of VLCB packets.
+
+The `pretty` module formats packets the way a bus monitor shows them, the mnemonic
+of the opcode followed by its decoded fields:
+
+```text
+ACON nn=298 en=7 [no data]
+NVSET nn=300 nv#=4 val=0x1F
+```
+
+Packets with an opcode unknown to the library, or with a length not matching
+their opcode, are rendered as a hexdump.
+ */
+
+use core::fmt::{self, Write};
+
+use heapless::String;
+use vlcb_core::opcode::opcode_info;
+use vlcb_defs::OpCode;
+
+use crate::wire::{is_event_opcode, VlcbPacketWire, VlcbRepr};
+
+/// Maximum length of a line produced by [`print_frame`].
+pub const MAX_LINE_LEN: usize = 64;
+
+/// A VLCB packet (the opcode followed by its data octets) displayed in a human-readable form.
+#[derive(Debug, Clone, Copy)]
+pub struct PrettyFrame<'a>(&'a [u8]);
+
+impl<'a> PrettyFrame<'a> {
+    /// Wrap the raw octets of a VLCB packet.
+    pub const fn new(packet: &'a [u8]) -> Self {
+        Self(packet)
+    }
+}
+
+impl<'a> From<VlcbPacketWire<&'a [u8]>> for PrettyFrame<'a> {
+    fn from(packet: VlcbPacketWire<&'a [u8]>) -> Self {
+        Self(packet.into_inner())
+    }
+}
+
+/// Render a VLCB packet into a line, for logging without the `std` formatting machinery.
+///
+/// Lines longer than [`MAX_LINE_LEN`] are truncated.
+pub fn print_frame(packet: &[u8]) -> String<MAX_LINE_LEN> {
+    let mut line = String::new();
+    // the line is only truncated when it runs out of space
+    let _ = write!(Truncating(&mut line), "{}", PrettyFrame(packet));
+    line
+}
+
+/// Writer filling the string up to its capacity and dropping the rest.
+struct Truncating<'a, const N: usize>(&'a mut String<N>);
+
+impl<'a, const N: usize> Write for Truncating<'a, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.0.push(c).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Layout of the data octets of an opcode.
+enum Layout {
+    /// Node number, event number and event data.
+    LongEvent,
+    /// Node number, device number and event data.
+    ShortEvent,
+    /// Node number, node variable index and its value.
+    NodeVariable,
+    /// Node number and node variable index.
+    NodeVariableIndex,
+    /// Node number, parameter index and its value.
+    Parameter,
+    /// Node number and parameter index.
+    ParameterIndex,
+    /// Node number and the CMDERR error code.
+    CommandError,
+    /// Node number followed by opcode specific data.
+    Node,
+    /// DCC session followed by opcode specific data.
+    Session,
+    /// Opcode specific data.
+    Data,
+}
+
+fn layout(opcode: OpCode) -> Layout {
+    match opcode {
+        OpCode::ShortEventAccessoryOn
+        | OpCode::ShortEventAccessoryOff
+        | OpCode::QueryShortEventAccessoryState
+        | OpCode::ShortEventAccessoryStateOn
+        | OpCode::ShortEventAccessoryStateOff
+        | OpCode::ShortEventAccessoryOn1
+        | OpCode::ShortEventAccessoryOff1
+        | OpCode::ShortEventAccessoryStateOn1
+        | OpCode::ShortEventAccessoryStateOff1
+        | OpCode::ShortEventAccessoryOn2
+        | OpCode::ShortEventAccessoryOff2
+        | OpCode::ShortEventAccessoryStateOn2
+        | OpCode::ShortEventAccessoryStateOff2
+        | OpCode::ShortEventAccessoryOn3
+        | OpCode::ShortEventAccessoryOff3
+        | OpCode::ShortEventAccessoryStateOn3
+        | OpCode::ShortEventAccessoryStateOff3 => Layout::ShortEvent,
+        opcode if is_event_opcode(opcode) => Layout::LongEvent,
+
+        OpCode::LegacySetNodeVariable | OpCode::SetNodeVariable | OpCode::NodeVariableValue => {
+            Layout::NodeVariable
+        }
+        OpCode::QueryNodeVariable => Layout::NodeVariableIndex,
+        OpCode::NodeParameterValue => Layout::Parameter,
+        OpCode::QueryNodeParameterByIndex => Layout::ParameterIndex,
+        OpCode::NodeConfigurationError => Layout::CommandError,
+
+        OpCode::SetNodeNumber
+        | OpCode::ResetModuleToFactory
+        | OpCode::RequestNewNodeNumber
+        | OpCode::NodeNumberReleased
+        | OpCode::NodeNumberAck
+        | OpCode::PutNodeIntoLearnMode
+        | OpCode::ReleaseNodeFromLearnMode
+        | OpCode::ForgetAllLearnedEvents
+        | OpCode::QueryAvailableEventSlots
+        | OpCode::QueryAllLearnedEvents
+        | OpCode::QueryLearnedEventCount
+        | OpCode::WriteAck
+        | OpCode::QueryNodeData
+        | OpCode::RebootIntoBootloader
+        | OpCode::ForceCanEnumeration
+        | OpCode::RestartNode
+        | OpCode::AvailableEventSlots
+        | OpCode::QueryLearnedEventByIndex
+        | OpCode::LearnedEventCount
+        | OpCode::SetNodeCanId
+        | OpCode::PutNodeIntoMode
+        | OpCode::ServiceDiscoveryQuery
+        | OpCode::QueryDiagnosticData
+        | OpCode::QueryEventVariable
+        | OpCode::Heartbeat
+        | OpCode::ServiceDiscoveryResponse
+        | OpCode::GenericResponse
+        | OpCode::EventVariableValue
+        | OpCode::NodeInfo
+        | OpCode::DiagnosticData
+        | OpCode::EventAck
+        | OpCode::ExtendedServiceDiscoveryResponse
+        | OpCode::LearnedEventResponse
+        | OpCode::DataEventAccessory
+        | OpCode::NodeDataEventResponse => Layout::Node,
+
+        OpCode::DccReleaseSession
+        | OpCode::DccQueryLocoStatus
+        | OpCode::DccSessionKeepAlive
+        | OpCode::DccAllocateLocoToActivity
+        | OpCode::DccSetThrottleMode
+        | OpCode::DccConsistAddLoco
+        | OpCode::DccConsistRemoveLoco
+        | OpCode::DccSetLocoThrottle
+        | OpCode::DccSetLocoFlags
+        | OpCode::DccLocoFunctionOn
+        | OpCode::DccLocoFunctionOff
+        | OpCode::DccSetLocoFunctions
+        | OpCode::DccLocoReport => Layout::Session,
+
+        _ => Layout::Data,
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter, octets: &[u8]) -> fmt::Result {
+    octets.iter().try_for_each(|octet| write!(f, " {:02X}", octet))
+}
+
+fn write_data(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    if data.is_empty() {
+        return Ok(());
+    }
+    write!(f, " data=")?;
+    for (i, octet) in data.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{:#04X}", octet)?;
+    }
+    Ok(())
+}
+
+fn write_event_data(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    match data {
+        [] => write!(f, " [no data]"),
+        _ => write_data(f, data),
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+impl<'a> fmt::Display for PrettyFrame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(&opcode) = self.0.first() else {
+            return write!(f, "MALFORMED");
+        };
+
+        let packet = VlcbPacketWire::new_unchecked(self.0);
+        let repr = match VlcbPacketWire::new_checked(self.0).and_then(|p| VlcbRepr::parse(&p)) {
+            Ok(repr) if self.0.len() == packet.total_len() as usize => repr,
+            _ => {
+                let name = match OpCode::try_from(opcode) {
+                    Ok(_) => "MALFORMED",
+                    Err(_) => "UNKNOWN",
+                };
+                write!(f, "{}", name)?;
+                return write_hex(f, self.0);
+            }
+        };
+
+        let data = packet.payload();
+        write!(f, "{}", opcode_info(opcode).name)?;
+        match layout(repr.opcode) {
+            Layout::LongEvent => {
+                write!(f, " nn={} en={}", u16_at(data, 0), u16_at(data, 2))?;
+                write_event_data(f, &data[4..])
+            }
+            Layout::ShortEvent => {
+                write!(f, " nn={} dn={}", u16_at(data, 0), u16_at(data, 2))?;
+                write_event_data(f, &data[4..])
+            }
+            Layout::NodeVariable => {
+                write!(f, " nn={} nv#={} val={:#04X}", u16_at(data, 0), data[2], data[3])
+            }
+            Layout::NodeVariableIndex => write!(f, " nn={} nv#={}", u16_at(data, 0), data[2]),
+            Layout::Parameter => {
+                write!(f, " nn={} param#={} val={:#04X}", u16_at(data, 0), data[2], data[3])
+            }
+            Layout::ParameterIndex => write!(f, " nn={} param#={}", u16_at(data, 0), data[2]),
+            Layout::CommandError => write!(f, " nn={} err={}", u16_at(data, 0), data[2]),
+            Layout::Node => {
+                write!(f, " nn={}", u16_at(data, 0))?;
+                write_data(f, &data[2..])
+            }
+            Layout::Session => {
+                write!(f, " session={}", data[0])?;
+                write_data(f, &data[1..])
+            }
+            Layout::Data => write_data(f, data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn pretty(packet: &[u8]) -> alloc::string::String {
+        PrettyFrame::new(packet).to_string()
+    }
+
+    #[test]
+    fn test_events() {
+        let acon = [OpCode::LongEventAccessoryOn.into(), 0x01, 0x2A, 0x00, 0x07];
+        assert_eq!(pretty(&acon), "ACON nn=298 en=7 [no data]");
+
+        let asof = [OpCode::ShortEventAccessoryOff.into(), 0x00, 0x00, 0x01, 0x00];
+        assert_eq!(pretty(&asof), "ASOF nn=0 dn=256 [no data]");
+
+        let acon2 = [OpCode::LongEventAccessoryOn2.into(), 0x00, 0x01, 0x00, 0x02, 0x0A, 0xFF];
+        assert_eq!(pretty(&acon2), "ACON2 nn=1 en=2 data=0x0A 0xFF");
+    }
+
+    #[test]
+    fn test_node_config() {
+        let nvset = [OpCode::LegacySetNodeVariable.into(), 0x01, 0x2C, 0x04, 0x1F];
+        assert_eq!(pretty(&nvset), "NVSET nn=300 nv#=4 val=0x1F");
+
+        let nvrd = [OpCode::QueryNodeVariable.into(), 0x01, 0x2C, 0x04];
+        assert_eq!(pretty(&nvrd), "NVRD nn=300 nv#=4");
+
+        let rqnpn = [OpCode::QueryNodeParameterByIndex.into(), 0x00, 0x05, 0x01];
+        assert_eq!(pretty(&rqnpn), "RQNPN nn=5 param#=1");
+
+        let paran = [OpCode::NodeParameterValue.into(), 0x00, 0x05, 0x01, 0xA5];
+        assert_eq!(pretty(&paran), "PARAN nn=5 param#=1 val=0xA5");
+
+        let cmderr = [OpCode::NodeConfigurationError.into(), 0x00, 0x05, 0x0A];
+        assert_eq!(pretty(&cmderr), "CMDERR nn=5 err=10");
+
+        let wrack = [OpCode::WriteAck.into(), 0x00, 0x05];
+        assert_eq!(pretty(&wrack), "WRACK nn=5");
+
+        let heartb = [OpCode::Heartbeat.into(), 0x00, 0x05, 0x01, 0x00, 0x00];
+        assert_eq!(pretty(&heartb), "HEARTB nn=5 data=0x01 0x00 0x00");
+
+        assert_eq!(pretty(&[OpCode::QueryNodeInfo.into()]), "QNN");
+    }
+
+    #[test]
+    fn test_loco_control() {
+        let dspd = [OpCode::DccSetLocoThrottle.into(), 0x03, 0x80];
+        assert_eq!(pretty(&dspd), "DSPD session=3 data=0x80");
+
+        let rloc = [OpCode::DccRequestNewSession.into(), 0xC0, 0x64];
+        assert_eq!(pretty(&rloc), "RLOC data=0xC0 0x64");
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(pretty(&[0x0B, 0x01]), "UNKNOWN 0B 01");
+        assert_eq!(
+            pretty(&[OpCode::LongEventAccessoryOn.into(), 0x01, 0x2A, 0x00]),
+            "MALFORMED 90 01 2A 00"
+        );
+        assert_eq!(
+            pretty(&[OpCode::QueryNodeInfo.into(), 0x01]),
+            "MALFORMED 0D 01",
+            "trailing octets"
+        );
+        assert_eq!(pretty(&[]), "MALFORMED");
+    }
+
+    #[test]
+    fn test_print_frame() {
+        let nvset = [OpCode::LegacySetNodeVariable.into(), 0x01, 0x2C, 0x04, 0x1F];
+        assert_eq!(print_frame(&nvset).as_str(), "NVSET nn=300 nv#=4 val=0x1F");
+
+        let acon3 = [OpCode::LongEventAccessoryOn3.into(), 0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3];
+        assert_eq!(
+            print_frame(&acon3).as_str(),
+            "ACON3 nn=65535 en=65535 data=0x01 0x02 0x03"
+        );
+    }
+}
@@ -12,6 +12,15 @@ use embedded_time::{Clock, Instant};
 #[cfg(feature = "socket-module")]
 pub mod module;
 
+#[cfg(feature = "async")]
+mod waker;
+
+#[cfg(feature = "async")]
+pub(crate) use self::waker::WakerRegistration;
+
+#[cfg(all(test, feature = "async"))]
+pub(crate) use self::waker::test::CountingWaker;
+
 /// Gives an indication on the next time the socket should be polled.
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
 pub(crate) enum PollAt<C: Clock> {
@@ -32,7 +41,7 @@ pub(crate) enum PollAt<C: Clock> {
 /// However, it's commonly more efficient to employ [SocketSet::get].
 ///
 /// [AnySocket]: trait.AnySocket.html
-/// [SocketSet::get]: struct.SocketSet.html#method.get
+/// [SocketSet::get]: crate::iface::SocketSet::get
 #[derive(Debug)]
 pub enum Socket<'a> {
     #[cfg(feature = "socket-module")]
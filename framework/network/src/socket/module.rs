@@ -1,11 +1,18 @@
 use core::cmp::min;
+#[cfg(feature = "async")]
+use core::task::{Poll, Waker};
 use embedded_time::Clock;
+use vlcb_core::vlcb::{VlcbNodeNumber, NODENUM_SIZE};
+use vlcb_defs::OpCode;
 
 use crate::iface::Context;
 use crate::socket::PollAt;
+#[cfg(feature = "async")]
+use crate::socket::WakerRegistration;
 
+use crate::data::packet::construct::OutgoingPacket;
 use crate::storage::Empty;
-use crate::wire::VlcbRepr;
+use crate::wire::{is_event_opcode, CanPriority, VlcbPacketWire, VlcbProtocol, VlcbRepr, VLCB_MAX_PAYLOAD};
 
 /// Error returned by [`Socket::bind`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -24,17 +31,71 @@ impl core::fmt::Display for BindError {
     }
 }
 
+/// Packet filter of a module socket.
+///
+/// Decides which of the received packets get enqueued into the socket's
+/// reception buffer, see [`Socket::bind`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Filter<'a> {
+    /// Promiscuous mode, every packet is received.
+    ///
+    /// Useful for gateways and bus monitors.
+    All,
+    /// Packets whose first two payload octets equal to the node number of the interface.
+    ///
+    /// Nothing is accepted while the interface has no node number.
+    AddressedToNode,
+    /// Packets with one of the listed opcodes.
+    Opcodes(&'a [OpCode]),
+    /// Accessory event packets.
+    Events,
+    /// Node data events and responses (ACDAT, ARDAT) of the node with the node number.
+    NodeData(VlcbNodeNumber),
+    /// Device data events and responses (DDES, DDRS) of the device with the device number.
+    ///
+    /// The device number zero is not addressable.
+    DeviceData(u16),
+}
+
+impl<'a> Filter<'a> {
+    /// Check whether a packet passes the filter.
+    pub fn accepts(&self, addr: Option<VlcbNodeNumber>, vlcb_repr: &VlcbRepr, payload: &[u8]) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::AddressedToNode => match addr {
+                Some(addr) => payload.get(..NODENUM_SIZE) == Some(addr.as_bytes()),
+                None => false,
+            },
+            Filter::Opcodes(opcodes) => opcodes.contains(&vlcb_repr.opcode),
+            Filter::Events => is_event_opcode(vlcb_repr.opcode),
+            Filter::NodeData(node_num) => {
+                matches!(vlcb_repr.opcode, OpCode::DataEventAccessory | OpCode::NodeDataEventResponse)
+                    && payload.get(..NODENUM_SIZE) == Some(node_num.as_bytes())
+            }
+            Filter::DeviceData(device_number) => {
+                matches!(
+                    vlcb_repr.opcode,
+                    OpCode::DeviceDataEventShortMode | OpCode::DeviceDataResponseShortMode
+                ) && payload.get(..2) == Some(&device_number.to_be_bytes()[..])
+            }
+        }
+    }
+}
+
 /// Error returned by [`Socket::send`]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SendError {
     BufferFull,
+    Truncated,
 }
 
 impl core::fmt::Display for SendError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             SendError::BufferFull => write!(f, "buffer full"),
+            SendError::Truncated => write!(f, "truncated"),
         }
     }
 }
@@ -45,6 +106,8 @@ impl core::fmt::Display for SendError {
 pub enum RecvError {
     Exhausted,
     Truncated,
+    /// The packet does not parse as a VLCB packet, see [`Socket::recv_with`].
+    Malformed,
 }
 
 impl core::fmt::Display for RecvError {
@@ -52,24 +115,37 @@ impl core::fmt::Display for RecvError {
         match self {
             RecvError::Exhausted => write!(f, "exhausted"),
             RecvError::Truncated => write!(f, "truncated"),
+            RecvError::Malformed => write!(f, "malformed"),
         }
     }
 }
 
 /// A Module packet metadata.
-pub type PacketMetadata = crate::storage::PacketMetadata<()>;
+///
+/// The header holds the priority the packet is sent with. Packets without
+/// one are sent with the default priority of their opcode.
+pub type PacketMetadata = crate::storage::PacketMetadata<Option<CanPriority>>;
 
 /// A Module packet ring buffer.
-pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, ()>;
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, Option<CanPriority>>;
 
 /// A Module CBUS socket.
 ///
 /// This socket type is essentially filtered raw CBUS protocol
 /// to be used by module implementations.
+///
+/// Received packets are stored as raw VLCB packets, that is the opcode
+/// followed by its data octets.
 #[derive(Debug)]
 pub struct Socket<'a> {
     rx_buffer: PacketBuffer<'a>,
     tx_buffer: PacketBuffer<'a>,
+    filter: Option<Filter<'a>>,
+    mtu: usize,
+    #[cfg(feature = "async")]
+    rx_waker: WakerRegistration,
+    #[cfg(feature = "async")]
+    tx_waker: WakerRegistration,
 }
 
 impl<'a> Socket<'a> {
@@ -78,9 +154,94 @@ impl<'a> Socket<'a> {
         Socket {
             rx_buffer,
             tx_buffer,
+            filter: None,
+            mtu: VLCB_MAX_PAYLOAD,
+            #[cfg(feature = "async")]
+            rx_waker: WakerRegistration::new(),
+            #[cfg(feature = "async")]
+            tx_waker: WakerRegistration::new(),
         }
     }
 
+    /// Register a waker for receive operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `recv` method calls, such as receiving data, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `recv` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_recv_waker(&mut self, waker: &Waker) {
+        self.rx_waker.register(waker)
+    }
+
+    /// Register a waker for send operations.
+    ///
+    /// The waker is woken on state changes that might affect the return value
+    /// of `send` method calls, such as space becoming available in the transmit
+    /// buffer, or the socket closing.
+    ///
+    /// Notes:
+    ///
+    /// - Only one waker can be registered at a time. If another waker was previously registered,
+    ///   it is overwritten and will no longer be woken.
+    /// - The Waker is woken only once. Once woken, you must register it again to receive more wakes.
+    /// - "Spurious wakes" are allowed: a wake doesn't guarantee the result of `send` has
+    ///   necessarily changed.
+    #[cfg(feature = "async")]
+    pub fn register_send_waker(&mut self, waker: &Waker) {
+        self.tx_waker.register(waker)
+    }
+
+    /// Bind the socket to the given packet filter.
+    ///
+    /// An unbound socket does not receive any packets, though it can still be used for sending.
+    ///
+    /// This function returns `Err(BindError::InvalidState)` if the socket is already bound,
+    /// and `Err(BindError::Unaddressable)` if the filter can never match any packet.
+    pub fn bind(&mut self, filter: Filter<'a>) -> Result<(), BindError> {
+        if self.is_bound() {
+            return Err(BindError::InvalidState);
+        }
+
+        match filter {
+            Filter::Opcodes([]) | Filter::DeviceData(0) => return Err(BindError::Unaddressable),
+            _ => {}
+        }
+
+        self.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Unbind the socket and drop any packets not yet received.
+    pub fn close(&mut self) {
+        self.filter = None;
+        self.rx_buffer.reset();
+
+        #[cfg(feature = "async")]
+        {
+            self.rx_waker.wake();
+            self.tx_waker.wake();
+        }
+    }
+
+    /// Check whether the socket is bound.
+    #[inline]
+    pub fn is_bound(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Return the packet filter the socket is bound to.
+    #[inline]
+    pub fn filter(&self) -> Option<Filter<'a>> {
+        self.filter
+    }
+
     /// Check whether the transmit buffer is full.
     #[inline]
     pub fn can_send(&self) -> bool {
@@ -117,15 +278,27 @@ impl<'a> Socket<'a> {
         self.tx_buffer.payload_capacity()
     }
 
+    /// Return the maximum size of a packet the socket accepts for sending.
+    ///
+    /// This is the MTU of the interface the socket was last polled with,
+    /// or the maximum VLCB packet size before the first poll.
+    pub fn max_transmission_unit(&self) -> usize {
+        self.mtu
+    }
+
     /// Enqueue a packet to send, and return a pointer to its payload.
     ///
-    /// This function returns `Err(Error::Exhausted)` if the transmit buffer is full,
-    /// and `Err(Error::Truncated)` if there is not enough transmit buffer capacity
-    /// to ever send this packet.
+    /// This function returns `Err(SendError::BufferFull)` if the transmit buffer is full,
+    /// and `Err(SendError::Truncated)` if the packet is larger than the interface
+    /// [MTU](Self::max_transmission_unit).
     pub fn send(&mut self, size: usize) -> Result<&mut [u8], SendError> {
+        if size > self.mtu {
+            return Err(SendError::Truncated);
+        }
+
         let packet_buf = self
             .tx_buffer
-            .enqueue(size, ())
+            .enqueue(size, None)
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!("module: buffer to send {} octets", packet_buf.len());
@@ -140,9 +313,13 @@ impl<'a> Socket<'a> {
     where
         F: FnOnce(&mut [u8]) -> usize,
     {
+        if max_size > self.mtu {
+            return Err(SendError::Truncated);
+        }
+
         let size = self
             .tx_buffer
-            .enqueue_with_infallible(max_size, (), f)
+            .enqueue_with_infallible(max_size, None, f)
             .map_err(|_| SendError::BufferFull)?;
 
         net_trace!("module: buffer to send {} octets", size);
@@ -158,6 +335,24 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Enqueue a constructed packet to send with its priority.
+    ///
+    /// See also [send](#method.send).
+    pub fn send_packet(&mut self, packet: &OutgoingPacket) -> Result<(), SendError> {
+        if packet.payload.len() > self.mtu {
+            return Err(SendError::Truncated);
+        }
+
+        let packet_buf = self
+            .tx_buffer
+            .enqueue(packet.payload.len(), Some(packet.priority))
+            .map_err(|_| SendError::BufferFull)?;
+        packet_buf.copy_from_slice(&packet.payload);
+
+        net_trace!("module: buffer to send {} octets", packet_buf.len());
+        Ok(())
+    }
+
     /// Dequeue a packet, and return a pointer to the payload.
     ///
     /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
@@ -165,7 +360,7 @@ impl<'a> Socket<'a> {
     /// **Note:** The IP header is parsed and re-serialized, and may not match
     /// the header actually received bit for bit.
     pub fn recv(&mut self) -> Result<&[u8], RecvError> {
-        let ((), packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+        let (_, packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!("module: receive {} buffered octets", packet_buf.len());
         Ok(packet_buf)
@@ -194,7 +389,7 @@ impl<'a> Socket<'a> {
     ///
     /// It returns `Err(Error::Exhausted)` if the receive buffer is empty.
     pub fn peek(&mut self) -> Result<&[u8], RecvError> {
-        let ((), packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
+        let (_, packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
 
         net_trace!("module: receive {} buffered octets", packet_buf.len());
 
@@ -220,101 +415,173 @@ impl<'a> Socket<'a> {
         Ok(length)
     }
 
-    pub(crate) fn process<C>(&mut self, cx: &mut Context<C>, vlcb_repr: &VlcbRepr, payload: &[u8])
+    /// Dequeue a packet, parse its header and pass it to the closure along with the data
+    /// octets, returning the result of the closure.
+    ///
+    /// The packet is released once the closure returns, nothing is copied. A packet that
+    /// does not parse is dropped and `RecvError::Malformed` is returned.
+    ///
+    /// See also [recv](#method.recv).
+    pub fn recv_with<R, F>(&mut self, f: F) -> Result<R, RecvError>
     where
-        C: Clock,
+        F: FnOnce(&VlcbRepr, &[u8]) -> R,
+    {
+        let (_, packet_buf) = self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!("module: receive {} buffered octets", packet_buf.len());
+        let (vlcb_repr, payload) = parse_packet(packet_buf)?;
+        Ok(f(&vlcb_repr, payload))
+    }
+
+    /// Peek at a packet in the receive buffer, parse its header and pass it to the closure
+    /// without removing the packet from the receive buffer.
+    /// This function otherwise behaves identically to [recv_with](#method.recv_with).
+    ///
+    /// **Note**: a packet that does not parse is kept in the buffer, use
+    /// [recv](#method.recv) to drop it.
+    pub fn peek_with<R, F>(&mut self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&VlcbRepr, &[u8]) -> R,
     {
-        todo!(); /*
-                 let header_len = cbus_repr.header_len();
-                 let total_len = header_len + payload.len();
+        let (_, packet_buf) = self.rx_buffer.peek().map_err(|_| RecvError::Exhausted)?;
 
-                 net_trace!("raw: receiving {} octets", total_len);
+        let (vlcb_repr, payload) = parse_packet(packet_buf)?;
+        Ok(f(&vlcb_repr, payload))
+    }
+
+    /// Dequeue all the packets in the receive buffer, passing every one of them to the closure
+    /// as [recv_with](#method.recv_with) does, and return how many were passed.
+    ///
+    /// Packets that do not parse are dropped.
+    pub fn recv_each<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&VlcbRepr, &[u8]),
+    {
+        let mut received = 0;
+        loop {
+            match self.recv_with(&mut f) {
+                Ok(()) => received += 1,
+                Err(RecvError::Malformed) => net_debug!("module: malformed packet in queue, dropping"),
+                Err(_) => return received,
+            }
+        }
+    }
 
-                 match self.rx_buffer.enqueue(total_len, ()) {
-                     Ok(buf) => {
-                         cbus_repr.emit(&mut buf[..header_len]);
-                         buf[header_len..].copy_from_slice(payload);
-                     }
-                     Err(_) => net_trace!("raw: buffer full, dropped incoming packet"),
-                 }*/
+    /// Wait for a packet, dequeue it and copy the payload into the given slice.
+    ///
+    /// See also [recv_slice](#method.recv_slice).
+    #[cfg(feature = "async")]
+    pub async fn recv_async(&mut self, data: &mut [u8]) -> Result<usize, RecvError> {
+        core::future::poll_fn(|cx| match self.recv_slice(data) {
+            Err(RecvError::Exhausted) => {
+                self.register_recv_waker(cx.waker());
+                Poll::Pending
+            }
+            res => Poll::Ready(res),
+        })
+        .await
+    }
+
+    /// Wait for space in the transmit buffer and enqueue a constructed packet to send.
+    ///
+    /// See also [send_packet](#method.send_packet).
+    #[cfg(feature = "async")]
+    pub async fn send_async(&mut self, packet: &OutgoingPacket) -> Result<(), SendError> {
+        core::future::poll_fn(|cx| match self.send_packet(packet) {
+            Err(SendError::BufferFull) => {
+                self.register_send_waker(cx.waker());
+                Poll::Pending
+            }
+            res => Poll::Ready(res),
+        })
+        .await
+    }
+
+    /// Check whether the packet should be processed by this socket.
+    pub(crate) fn accepts<C>(&self, cx: &mut Context<C>, vlcb_repr: &VlcbRepr, payload: &[u8]) -> bool
+    where
+        C: Clock,
+    {
+        match &self.filter {
+            Some(filter) => filter.accepts(cx.addr(), vlcb_repr, payload),
+            None => false,
+        }
+    }
+
+    pub(crate) fn process<C>(&mut self, _cx: &mut Context<C>, vlcb_repr: &VlcbRepr, payload: &[u8])
+    where
+        C: Clock,
+    {
+        let header_len = vlcb_repr.header_len();
+        let total_len = header_len + payload.len();
+
+        net_trace!("module: receiving {} octets", total_len);
+
+        match self.rx_buffer.enqueue(total_len, None) {
+            Ok(buf) => {
+                buf[0] = vlcb_repr.opcode.into();
+                buf[header_len..].copy_from_slice(payload);
+
+                #[cfg(feature = "async")]
+                self.rx_waker.wake();
+            }
+            Err(_) => net_debug!(
+                "module: buffer full, dropped incoming opcode {}, {} octets",
+                u8::from(vlcb_repr.opcode),
+                total_len
+            ),
+        }
     }
 
     pub(crate) fn dispatch<F, E, C>(&mut self, cx: &mut Context<C>, emit: F) -> Result<(), E>
     where
-        F: FnOnce(&mut Context<C>, (VlcbRepr, &[u8])) -> Result<(), E>,
+        F: FnOnce(&mut Context<C>, (VlcbRepr, CanPriority, &[u8])) -> Result<(), E>,
         C: Clock,
     {
-        todo!();
-        // let res = self.tx_buffer.dequeue_with(|&mut (), buffer| {
-        //     match IpVersion::of_packet(buffer) {
-        //         #[cfg(feature = "proto-ipv4")]
-        //         Ok(IpVersion::Ipv4) => {
-        //             let mut packet = match Ipv4Packet::new_checked(buffer) {
-        //                 Ok(x) => x,
-        //                 Err(_) => {
-        //                     net_trace!("raw: malformed ipv6 packet in queue, dropping.");
-        //                     return Ok(());
-        //                 }
-        //             };
-        //             if packet.next_header() != ip_protocol {
-        //                 net_trace!("raw: sent packet with wrong ip protocol, dropping.");
-        //                 return Ok(());
-        //             }
-        //             if _checksum_caps.ipv4.tx() {
-        //                 packet.fill_checksum();
-        //             } else {
-        //                 // make sure we get a consistently zeroed checksum,
-        //                 // since implementations might rely on it
-        //                 packet.set_checksum(0);
-        //             }
-        //
-        //             let packet = Ipv4Packet::new_unchecked(&*packet.into_inner());
-        //             let ipv4_repr = match Ipv4Repr::parse(&packet, _checksum_caps) {
-        //                 Ok(x) => x,
-        //                 Err(_) => {
-        //                     net_trace!("raw: malformed ipv4 packet in queue, dropping.");
-        //                     return Ok(());
-        //                 }
-        //             };
-        //             net_trace!("raw:{}:{}: sending", ip_version, ip_protocol);
-        //             emit(cx, (IpRepr::Ipv4(ipv4_repr), packet.payload()))
-        //         }
-        //         #[cfg(feature = "proto-ipv6")]
-        //         Ok(IpVersion::Ipv6) => {
-        //             let packet = match Ipv6Packet::new_checked(buffer) {
-        //                 Ok(x) => x,
-        //                 Err(_) => {
-        //                     net_trace!("raw: malformed ipv6 packet in queue, dropping.");
-        //                     return Ok(());
-        //                 }
-        //             };
-        //             if packet.next_header() != ip_protocol {
-        //                 net_trace!("raw: sent ipv6 packet with wrong ip protocol, dropping.");
-        //                 return Ok(());
-        //             }
-        //             let packet = Ipv6Packet::new_unchecked(&*packet.into_inner());
-        //             let ipv6_repr = match Ipv6Repr::parse(&packet) {
-        //                 Ok(x) => x,
-        //                 Err(_) => {
-        //                     net_trace!("raw: malformed ipv6 packet in queue, dropping.");
-        //                     return Ok(());
-        //                 }
-        //             };
-        //
-        //             net_trace!("raw:{}:{}: sending", ip_version, ip_protocol);
-        //             emit(cx, (IpRepr::Ipv6(ipv6_repr), packet.payload()))
-        //         }
-        //         Err(_) => {
-        //             net_trace!("raw: sent packet with invalid IP version, dropping.");
-        //             Ok(())
-        //         }
-        //     }
-        // });
-        // match res {
-        //     Err(Empty) => Ok(()),
-        //     Ok(Err(e)) => Err(e),
-        //     Ok(Ok(())) => Ok(()),
-        // }
+        self.mtu = cx.caps().max_transmission_unit;
+
+        let res = self.tx_buffer.dequeue_with(|priority, buffer| {
+            let Some((&opcode, data)) = buffer.split_first() else {
+                net_debug!("module: empty packet in queue, dropping");
+                return Ok(());
+            };
+            let Ok(opcode) = OpCode::try_from(opcode) else {
+                net_debug!("module: unknown opcode {} in queue, dropping", opcode);
+                return Ok(());
+            };
+            if data.len() >= VLCB_MAX_PAYLOAD {
+                net_debug!(
+                    "module: oversized packet in queue, opcode {}, {} octets, dropping",
+                    u8::from(opcode),
+                    buffer.len()
+                );
+                return Ok(());
+            }
+
+            let vlcb_repr = VlcbRepr::new(opcode, data.len() as u8, VlcbProtocol::Module);
+            let priority = priority.unwrap_or_else(|| CanPriority::for_opcode(opcode));
+
+            net_trace!("module: sending {} octets", buffer.len());
+            emit(cx, (vlcb_repr, priority, data))
+        });
+        match res {
+            Err(Empty) => Ok(()),
+            Ok(Err(e)) => {
+                // the packet stays queued for the next poll, unless it failed too often
+                let attempts = self.tx_buffer.record_failed_attempt().unwrap_or_default();
+                if cx.give_up_tx(attempts) {
+                    let _ = self.tx_buffer.dequeue();
+                    #[cfg(feature = "async")]
+                    self.tx_waker.wake();
+                }
+                Err(e)
+            }
+            Ok(Ok(())) => {
+                #[cfg(feature = "async")]
+                self.tx_waker.wake();
+                Ok(())
+            }
+        }
     }
 
     pub(crate) fn poll_at<C>(&self, _cx: &mut Context<C>) -> PollAt<C>
@@ -328,3 +595,246 @@ impl<'a> Socket<'a> {
         }
     }
 }
+
+/// Parse a buffered packet into its header and data octets.
+fn parse_packet(buffer: &[u8]) -> Result<(VlcbRepr, &[u8]), RecvError> {
+    let packet = VlcbPacketWire::new_checked(buffer).map_err(|_| RecvError::Malformed)?;
+    let vlcb_repr = VlcbRepr::parse(&packet).map_err(|_| RecvError::Malformed)?;
+    Ok((vlcb_repr, packet.payload()))
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+    use crate::wire::VlcbProtocol;
+
+    fn socket() -> Socket<'static> {
+        Socket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 32]),
+        )
+    }
+
+    fn repr(opcode: OpCode) -> VlcbRepr {
+        VlcbRepr::new(opcode, 0, VlcbProtocol::Module)
+    }
+
+    #[test]
+    fn test_send_truncated() {
+        let mut socket = socket();
+        assert_eq!(socket.max_transmission_unit(), VLCB_MAX_PAYLOAD);
+        assert_eq!(socket.send_slice(&[0; 9]), Err(SendError::Truncated));
+        assert_eq!(socket.send_slice(&[0; 8]), Ok(()));
+        assert_eq!(socket.packet_send_capacity(), 4);
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut socket = socket();
+        assert!(!socket.is_bound());
+        assert_eq!(socket.bind(Filter::Events), Ok(()));
+        assert_eq!(socket.filter(), Some(Filter::Events));
+        assert_eq!(socket.bind(Filter::All), Err(BindError::InvalidState));
+
+        socket.close();
+        assert!(!socket.is_bound());
+        assert_eq!(socket.bind(Filter::All), Ok(()));
+    }
+
+    #[test]
+    fn test_bind_empty_opcodes() {
+        let mut socket = socket();
+        assert_eq!(socket.bind(Filter::Opcodes(&[])), Err(BindError::Unaddressable));
+        assert!(!socket.is_bound());
+    }
+
+    #[test]
+    fn test_filter_accepts() {
+        let addr = Some(VlcbNodeNumber::new(0x01, 0x02));
+        let nvset = repr(OpCode::SetNodeVariable);
+        let acon = repr(OpCode::LongEventAccessoryOn);
+        let asof = repr(OpCode::ShortEventAccessoryOff);
+
+        assert!(Filter::All.accepts(addr, &nvset, &[]));
+
+        assert!(Filter::AddressedToNode.accepts(addr, &nvset, &[0x01, 0x02, 0x01]));
+        assert!(!Filter::AddressedToNode.accepts(addr, &nvset, &[0x01, 0x03, 0x01]));
+        assert!(!Filter::AddressedToNode.accepts(addr, &nvset, &[0x01]));
+        assert!(!Filter::AddressedToNode.accepts(None, &nvset, &[0x00, 0x00, 0x01]));
+
+        let opcodes = Filter::Opcodes(&[OpCode::SetNodeVariable]);
+        assert!(opcodes.accepts(addr, &nvset, &[]));
+        assert!(!opcodes.accepts(addr, &acon, &[]));
+
+        assert!(Filter::Events.accepts(addr, &acon, &[]));
+        assert!(Filter::Events.accepts(addr, &asof, &[]));
+        assert!(!Filter::Events.accepts(addr, &nvset, &[]));
+    }
+
+    #[test]
+    fn test_filter_data_events() {
+        let acdat = repr(OpCode::DataEventAccessory);
+        let ardat = repr(OpCode::NodeDataEventResponse);
+        let ddes = repr(OpCode::DeviceDataEventShortMode);
+        let ddrs = repr(OpCode::DeviceDataResponseShortMode);
+        let data = [0x01, 0x02, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5];
+
+        let node = Filter::NodeData(VlcbNodeNumber::new(0x01, 0x02));
+        assert!(node.accepts(None, &acdat, &data));
+        assert!(node.accepts(None, &ardat, &data));
+        assert!(!node.accepts(None, &ddes, &data), "a device data event");
+        assert!(!Filter::NodeData(VlcbNodeNumber::new(0x01, 0x03)).accepts(None, &acdat, &data));
+
+        let device = Filter::DeviceData(0x0102);
+        assert!(device.accepts(None, &ddes, &data));
+        assert!(device.accepts(None, &ddrs, &data));
+        assert!(!device.accepts(None, &acdat, &data), "a node data event");
+        assert!(!Filter::DeviceData(0x0103).accepts(None, &ddes, &data));
+        assert!(!device.accepts(None, &ddes, &data[..1]));
+
+        let mut socket = socket();
+        assert_eq!(socket.bind(Filter::DeviceData(0)), Err(BindError::Unaddressable));
+        assert_eq!(socket.bind(device), Ok(()));
+    }
+
+    fn received(payload: &[u8]) -> Socket<'static> {
+        let mut socket = socket();
+        let buf = socket.rx_buffer.enqueue(payload.len(), None).unwrap();
+        buf.copy_from_slice(payload);
+        socket
+    }
+
+    #[test]
+    fn test_recv_with() {
+        let nvset = [OpCode::SetNodeVariable.into(), 0x01, 0x02, 0x01, 0x05];
+        let mut socket = received(&nvset);
+
+        let peeked = socket.peek_with(|repr, payload| (repr.opcode, payload.len()));
+        assert_eq!(peeked, Ok((OpCode::SetNodeVariable, 4)));
+        assert!(socket.can_recv(), "peeking keeps the packet");
+
+        let mut calls = 0;
+        let result = socket.recv_with(|repr, payload| {
+            calls += 1;
+            assert_eq!(repr.data_len, 4);
+            payload.to_vec()
+        });
+        assert_eq!(result, Ok(vec![0x01, 0x02, 0x01, 0x05]));
+        assert_eq!(calls, 1);
+        assert_eq!(socket.recv_with(|_, _| calls += 1), Err(RecvError::Exhausted));
+        assert_eq!(calls, 1, "the packet is consumed once");
+    }
+
+    #[test]
+    fn test_recv_with_malformed() {
+        // NVSET with a data octet missing
+        let mut socket = received(&[OpCode::SetNodeVariable.into(), 0x01, 0x02, 0x01]);
+
+        assert_eq!(socket.peek_with(|_, _| ()), Err(RecvError::Malformed));
+        assert!(socket.can_recv());
+        assert_eq!(socket.recv_with(|_, _| panic!("not called")), Err(RecvError::Malformed));
+        assert!(!socket.can_recv(), "the malformed packet is dropped");
+    }
+
+    #[test]
+    fn test_recv_each() {
+        let mut socket = socket();
+        for packet in [
+            &[OpCode::QueryNodeInfo.into()][..],
+            &[OpCode::SetNodeVariable.into(), 0x01][..],
+            &[OpCode::SetNodeNumber.into(), 0x01, 0x02][..],
+        ] {
+            socket.rx_buffer.enqueue(packet.len(), None).unwrap().copy_from_slice(packet);
+        }
+
+        let mut opcodes = vec![];
+        assert_eq!(socket.recv_each(|repr, _| opcodes.push(repr.opcode)), 2);
+        assert_eq!(opcodes, [OpCode::QueryNodeInfo, OpCode::SetNodeNumber]);
+        assert!(!socket.can_recv());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_recv_waker() {
+        use crate::iface::Interface;
+        use crate::phy::loopback::Loopback;
+        use crate::socket::CountingWaker;
+        use crate::wire::HardwareAddress;
+        use vlcb_core::time::TestClock;
+
+        let device = Loopback::<4>::new();
+        let mut iface = Interface::<TestClock>::new(&device, None, HardwareAddress::default());
+        let (counter, waker) = CountingWaker::new();
+        let mut socket = socket();
+        socket.register_recv_waker(&waker);
+
+        socket.dispatch(iface.context(), |_, _| Ok::<_, ()>(())).unwrap();
+        assert_eq!(counter.count(), 0, "no spurious wake");
+
+        socket.process(iface.context(), &repr(OpCode::QueryNodeInfo), &[]);
+        assert_eq!(counter.count(), 1);
+        socket.process(iface.context(), &repr(OpCode::QueryNodeInfo), &[]);
+        assert_eq!(counter.count(), 1, "woken once per registration");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_send_waker() {
+        use crate::iface::Interface;
+        use crate::phy::loopback::Loopback;
+        use crate::socket::CountingWaker;
+        use crate::wire::HardwareAddress;
+        use vlcb_core::time::TestClock;
+
+        let device = Loopback::<4>::new();
+        let mut iface = Interface::<TestClock>::new(&device, None, HardwareAddress::default());
+        let (counter, waker) = CountingWaker::new();
+        let mut socket = socket();
+        while socket.can_send() {
+            socket.send_slice(&[OpCode::GeneralAck.into()]).unwrap();
+        }
+        socket.register_send_waker(&waker);
+
+        socket.process(iface.context(), &repr(OpCode::QueryNodeInfo), &[]);
+        assert_eq!(counter.count(), 0, "no spurious wake");
+
+        socket.dispatch(iface.context(), |_, _| Ok::<_, ()>(())).unwrap();
+        assert_eq!(counter.count(), 1);
+        assert!(socket.can_send());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_recv_async() {
+        use core::future::Future;
+        use core::task::Context;
+
+        use crate::iface::Interface;
+        use crate::phy::loopback::Loopback;
+        use crate::socket::CountingWaker;
+        use crate::wire::HardwareAddress;
+        use vlcb_core::time::TestClock;
+
+        let device = Loopback::<4>::new();
+        let mut iface = Interface::<TestClock>::new(&device, None, HardwareAddress::default());
+        let (counter, waker) = CountingWaker::new();
+        let mut cx = Context::from_waker(&waker);
+        let mut socket = socket();
+        let mut data = [0; 8];
+
+        {
+            let mut recv = core::pin::pin!(socket.recv_async(&mut data));
+            assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+        }
+        assert_eq!(counter.count(), 0);
+
+        socket.process(iface.context(), &repr(OpCode::QueryNodeInfo), &[]);
+        assert_eq!(counter.count(), 1);
+
+        let mut recv = core::pin::pin!(socket.recv_async(&mut data));
+        assert_eq!(recv.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+    }
+}
+
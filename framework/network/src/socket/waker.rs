@@ -0,0 +1,97 @@
+use core::task::Waker;
+
+// Credit: authors of https://github.com/smoltcp-rs/smoltcp
+
+/// Utility struct to register and wake a waker.
+#[derive(Debug, Default)]
+pub struct WakerRegistration {
+    waker: Option<Waker>,
+}
+
+impl WakerRegistration {
+    pub const fn new() -> Self {
+        Self { waker: None }
+    }
+
+    /// Register a waker. Overwrites the previous waker, if any.
+    pub fn register(&mut self, w: &Waker) {
+        match self.waker {
+            // Both wakers wake the same task, keep the old one and skip the clone.
+            Some(ref w2) if w2.will_wake(w) => {}
+            _ => {
+                // Wake the task of the replaced waker, so it can register itself again
+                // if it is still interested.
+                if let Some(old_waker) = self.waker.replace(w.clone()) {
+                    old_waker.wake()
+                }
+            }
+        }
+    }
+
+    /// Wake the registered waker, if any.
+    pub fn wake(&mut self) {
+        if let Some(w) = self.waker.take() {
+            w.wake()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use alloc::sync::Arc;
+    use alloc::task::Wake;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A waker counting how many times it was woken.
+    #[derive(Default)]
+    pub(crate) struct CountingWaker(AtomicUsize);
+
+    impl CountingWaker {
+        pub(crate) fn new() -> (Arc<Self>, Waker) {
+            let counter = Arc::new(Self::default());
+            let waker = Waker::from(counter.clone());
+            (counter, waker)
+        }
+
+        pub(crate) fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_wake_once() {
+        let (counter, waker) = CountingWaker::new();
+        let mut registration = WakerRegistration::new();
+
+        registration.wake();
+        registration.register(&waker);
+        assert_eq!(counter.count(), 0);
+
+        registration.wake();
+        registration.wake();
+        assert_eq!(counter.count(), 1, "the waker is woken only once per registration");
+    }
+
+    #[test]
+    fn test_replace_wakes_previous() {
+        let (first, first_waker) = CountingWaker::new();
+        let (second, second_waker) = CountingWaker::new();
+        let mut registration = WakerRegistration::new();
+
+        registration.register(&first_waker);
+        registration.register(&first_waker);
+        assert_eq!(first.count(), 0);
+
+        registration.register(&second_waker);
+        assert_eq!(first.count(), 1);
+        assert_eq!(second.count(), 0);
+    }
+}
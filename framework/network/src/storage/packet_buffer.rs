@@ -1,5 +1,7 @@
 // Credit: authors of https://github.com/smoltcp-rs/smoltcp
 
+use core::convert::Infallible;
+
 use managed::ManagedSlice;
 
 use crate::storage::{Full, RingBuffer};
@@ -12,6 +14,8 @@ use super::Empty;
 pub struct PacketMetadata<H> {
     size: usize,
     header: Option<H>,
+    /// Failed attempts to send the packet, see [`PacketBuffer::record_failed_attempt`].
+    attempts: u8,
 }
 
 impl<H> PacketMetadata<H> {
@@ -19,16 +23,22 @@ impl<H> PacketMetadata<H> {
     pub const EMPTY: PacketMetadata<H> = PacketMetadata {
         size: 0,
         header: None,
+        attempts: 0,
     };
 
     fn padding(size: usize) -> PacketMetadata<H> {
-        PacketMetadata { size, header: None }
+        PacketMetadata {
+            size,
+            header: None,
+            attempts: 0,
+        }
     }
 
     fn packet(size: usize, header: H) -> PacketMetadata<H> {
         PacketMetadata {
             size,
             header: Some(header),
+            attempts: 0,
         }
     }
 
@@ -73,10 +83,12 @@ impl<'a, H> PacketBuffer<'a, H> {
     // There is currently no enqueue_with() because of the complexity of managing padding
     // in case of failure.
 
-    /// Enqueue a single packet with the given header into the buffer, and
-    /// return a reference to its payload, or return `Err(Full)`
-    /// if the buffer is full.
-    pub fn enqueue(&mut self, size: usize, header: H) -> Result<&mut [u8], Full> {
+    /// Make room for a packet of `size` bytes.
+    ///
+    /// Ensures a free metadata slot and a contiguous payload window of `size` bytes. When the
+    /// contiguous window at the end of the payload ring is too small, padding is inserted so the
+    /// packet starts at the beginning of the ring. Nothing is changed if there is no room.
+    fn reserve(&mut self, size: usize) -> Result<(), Full> {
         if self.payload_ring.capacity() < size || self.metadata_ring.is_full() {
             return Err(Full);
         }
@@ -99,9 +111,13 @@ impl<'a, H> PacketBuffer<'a, H> {
                 // the padding necessary to circle around to the beginning of the
                 // ring buffer.
                 return Err(Full);
+            } else if self.metadata_ring.window() < 2 {
+                // The padding would take the last metadata slot.
+                return Err(Full);
             } else {
                 // Add padding to the end of the ring buffer so that the
                 // contiguous window is at the beginning of the ring buffer.
+                net_trace!("storage: padding {} octets to wrap a {} octet packet", contig_window, size);
                 *self.metadata_ring.enqueue_one()? = PacketMetadata::padding(contig_window);
                 // note(discard): function does not write to the result
                 // enqueued padding buffer location
@@ -109,6 +125,15 @@ impl<'a, H> PacketBuffer<'a, H> {
             }
         }
 
+        Ok(())
+    }
+
+    /// Enqueue a single packet with the given header into the buffer, and
+    /// return a reference to its payload, or return `Err(Full)`
+    /// if the buffer is full.
+    pub fn enqueue(&mut self, size: usize, header: H) -> Result<&mut [u8], Full> {
+        self.reserve(size)?;
+
         *self.metadata_ring.enqueue_one()? = PacketMetadata::packet(size, header);
 
         let payload_buf = self.payload_ring.enqueue_many(size);
@@ -118,6 +143,9 @@ impl<'a, H> PacketBuffer<'a, H> {
 
     /// Call `f` with a packet from the buffer large enough to fit `max_size` bytes. The packet
     /// is shrunk to the size returned from `f` and enqueued into the buffer.
+    ///
+    /// # Panics
+    /// This method panics on debug builds when `f` returns a size larger than `max_size`.
     pub fn enqueue_with_infallible<'b, F>(
         &'b mut self,
         max_size: usize,
@@ -127,35 +155,13 @@ impl<'a, H> PacketBuffer<'a, H> {
     where
         F: FnOnce(&'b mut [u8]) -> usize,
     {
-        if self.payload_ring.capacity() < max_size || self.metadata_ring.is_full() {
-            return Err(Full);
-        }
-
-        let window = self.payload_ring.window();
-        let contig_window = self.payload_ring.contiguous_window();
-
-        if window < max_size {
-            return Err(Full);
-        } else if contig_window < max_size {
-            if window - contig_window < max_size {
-                // The buffer length is larger than the current contiguous window
-                // and is larger than the contiguous window will be after adding
-                // the padding necessary to circle around to the beginning of the
-                // ring buffer.
-                return Err(Full);
-            } else {
-                // Add padding to the end of the ring buffer so that the
-                // contiguous window is at the beginning of the ring buffer.
-                *self.metadata_ring.enqueue_one()? = PacketMetadata::padding(contig_window);
-                // note(discard): function does not write to the result
-                // enqueued padding buffer location
-                let _buf_enqueued = self.payload_ring.enqueue_many(contig_window);
-            }
-        }
+        self.reserve(max_size)?;
 
-        let (size, _) = self
-            .payload_ring
-            .enqueue_many_with(|data| (f(&mut data[..max_size]), ()));
+        let (size, _) = self.payload_ring.enqueue_many_with(|data| {
+            let size = f(&mut data[..max_size]);
+            debug_assert!(size <= max_size, "packet grew past the reserved size");
+            (size.min(max_size), ())
+        });
 
         *self.metadata_ring.enqueue_one()? = PacketMetadata::packet(size, header);
 
@@ -228,6 +234,19 @@ impl<'a, H> PacketBuffer<'a, H> {
         }
     }
 
+    /// Count a failed attempt to send the packet at the front of the buffer, which stays
+    /// in the buffer. Return the amount of failed attempts so far, or `Err(Empty)` if the
+    /// buffer is empty.
+    pub fn record_failed_attempt(&mut self) -> Result<u8, Empty> {
+        self.dequeue_padding();
+
+        let attempts = self.metadata_ring.dequeue_one_with(|metadata| {
+            metadata.attempts = metadata.attempts.saturating_add(1);
+            Err::<Infallible, _>(metadata.attempts) // don't dequeue metadata
+        })?;
+        Ok(attempts.unwrap_err())
+    }
+
     /// Return the maximum number packets that can be stored.
     pub fn packet_capacity(&self) -> usize {
         self.metadata_ring.capacity()
@@ -399,4 +418,168 @@ mod test {
         buffer.reset();
         assert!(buffer.is_empty());
     }
+
+    #[test]
+    fn test_enqueue_with_infallible_shrinks() {
+        let mut buffer = buffer();
+        let size = buffer
+            .enqueue_with_infallible(8, (), |buf| {
+                assert_eq!(buf.len(), 8);
+                buf[..3].copy_from_slice(b"abc");
+                3
+            })
+            .unwrap();
+        assert_eq!(size, 3);
+
+        // the unused part of the allocation is available again
+        assert!(buffer.enqueue(13, ()).is_ok());
+        assert_eq!(buffer.enqueue(1, ()), Err(Full));
+
+        assert_eq!(buffer.dequeue().unwrap().1, &b"abc"[..]);
+        assert_eq!(buffer.dequeue().unwrap().1.len(), 13);
+    }
+
+    #[test]
+    fn test_enqueue_with_infallible_full() {
+        let mut buffer = buffer();
+        assert_eq!(buffer.enqueue_with_infallible(17, (), |_| 0), Err(Full));
+
+        assert!(buffer.enqueue(10, ()).is_ok());
+        assert_eq!(buffer.enqueue_with_infallible(8, (), |_| 0), Err(Full));
+        assert_eq!(buffer.metadata_ring.len(), 1);
+    }
+
+    #[test]
+    fn test_enqueue_with_infallible_wraps() {
+        let mut buffer = buffer();
+        assert!(buffer.enqueue(10, ()).is_ok());
+        assert!(buffer.enqueue(2, ()).is_ok());
+        assert!(buffer.dequeue().is_ok());
+
+        // 4 bytes left at the end, 10 at the start
+        let size = buffer
+            .enqueue_with_infallible(8, (), |buf| {
+                buf[..5].copy_from_slice(b"abcde");
+                5
+            })
+            .unwrap();
+        assert_eq!(size, 5);
+        assert_eq!(buffer.metadata_ring.len(), 3, "padding was inserted");
+
+        assert_eq!(buffer.dequeue().unwrap().1.len(), 2);
+        assert_eq!(buffer.peek().unwrap().1, &b"abcde"[..]);
+        assert_eq!(buffer.dequeue().unwrap().1, &b"abcde"[..]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_padding_needs_metadata_slot() {
+        let mut buffer: PacketBuffer<'static, ()> =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0u8; 16]);
+        assert!(buffer.enqueue(10, ()).is_ok());
+        assert!(buffer.enqueue(2, ()).is_ok());
+        assert!(buffer.dequeue().is_ok());
+
+        // the packet would have to wrap, but there is only one free metadata slot
+        assert_eq!(buffer.enqueue(8, ()), Err(Full));
+        assert_eq!(buffer.metadata_ring.len(), 1);
+        assert_eq!(buffer.payload_ring.len(), 2);
+
+        // the packet fits into the contiguous window at the end
+        assert!(buffer.enqueue(4, ()).is_ok());
+    }
+
+    #[test]
+    fn test_zero_length_packets() {
+        let mut buffer = buffer();
+        assert!(buffer.enqueue(0, ()).unwrap().is_empty());
+        assert_eq!(buffer.enqueue_with_infallible(4, (), |_| 0), Ok(0));
+        assert!(buffer.enqueue(16, ()).is_ok());
+        assert!(buffer.enqueue(0, ()).is_ok());
+        assert!(buffer.is_full());
+
+        assert_eq!(buffer.dequeue().unwrap().1, &b""[..]);
+        assert_eq!(buffer.dequeue().unwrap().1, &b""[..]);
+        assert_eq!(buffer.dequeue().unwrap().1.len(), 16);
+        assert!(buffer
+            .dequeue_with(|&mut (), payload| {
+                assert!(payload.is_empty());
+                Result::<(), ()>::Ok(())
+            })
+            .is_ok());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_fill_drain_cycles() {
+        use alloc::collections::VecDeque;
+        use alloc::vec::Vec;
+
+        let mut buffer: PacketBuffer<'static, u32> =
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; 5], vec![0u8; 23]);
+        let mut expected: VecDeque<(u32, Vec<u8>)> = VecDeque::new();
+
+        // small linear congruential generator for deterministic sizes
+        let mut seed = 0x2545_f491_u32;
+        let mut next = |max: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % max
+        };
+
+        for id in 0..2000 {
+            let size = next(12) as usize;
+            let payload: Vec<u8> = (0..size).map(|i| (id as usize + i) as u8).collect();
+
+            let enqueued = match next(3) {
+                0 => buffer
+                    .enqueue_with_infallible(size + 4, id, |buf| {
+                        buf[..size].copy_from_slice(&payload);
+                        size
+                    })
+                    .is_ok(),
+                _ => buffer
+                    .enqueue(size, id)
+                    .map(|buf| buf.copy_from_slice(&payload))
+                    .is_ok(),
+            };
+            if enqueued {
+                expected.push_back((id, payload));
+            }
+
+            // drain a bit slower than filling, so the buffer keeps running full
+            if next(4) != 0 || !enqueued {
+                match expected.pop_front() {
+                    Some((id, payload)) => {
+                        let (header, buf) = buffer.dequeue().unwrap();
+                        assert_eq!(header, id);
+                        assert_eq!(buf, &payload[..]);
+                    }
+                    None => assert_eq!(buffer.dequeue(), Err(Empty)),
+                }
+            }
+        }
+
+        while let Some((id, payload)) = expected.pop_front() {
+            assert_eq!(buffer.peek(), Ok((&id, &payload[..])));
+            assert_eq!(buffer.dequeue(), Ok((id, &mut payload.clone()[..])));
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_failed_attempts() {
+        let mut buffer = buffer();
+        assert_eq!(buffer.record_failed_attempt(), Err(Empty));
+
+        buffer.enqueue(2, ()).unwrap().copy_from_slice(b"ab");
+        buffer.enqueue(2, ()).unwrap().copy_from_slice(b"cd");
+        assert_eq!(buffer.record_failed_attempt(), Ok(1));
+        assert_eq!(buffer.record_failed_attempt(), Ok(2));
+        assert_eq!(buffer.peek(), Ok((&(), &b"ab"[..])), "the packet stays in the buffer");
+
+        // the next packet starts over
+        buffer.dequeue().unwrap();
+        assert_eq!(buffer.record_failed_attempt(), Ok(1));
+        assert_eq!(buffer.dequeue().unwrap().1, &b"cd"[..]);
+    }
 }
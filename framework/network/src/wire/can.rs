@@ -2,40 +2,11 @@ use byteorder::{ByteOrder, NetworkEndian};
 use vlcb_core::can::{VlcbCanId, CANID_MASK};
 use core::{borrow::BorrowMut, fmt::Debug};
 use core::fmt;
-use num_enum::{FromPrimitive, IntoPrimitive};
+use num_enum::FromPrimitive;
 
+use super::vlcb::Priority;
 use super::{Error, Result};
 
-/// VLCB CAN frame minor priority.
-///
-/// Static priority based on message and node type.
-/// bits 7 - 8 of the CAN header.
-///
-/// VLCB unlike CBUS does not support priority ratcheting in case
-/// of transport failure
-#[derive(Debug, Eq, PartialEq, Copy, Clone, IntoPrimitive, FromPrimitive, Default)]
-#[repr(u8)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum Priority {
-    High = 0x00,
-    AboveNormal = 0x01,
-    Normal = 0x02,
-    #[default]
-    Low = 0x03,
-}
-
-impl Priority {
-    pub const MASK: u8 = 0x03;
-    pub const MIN: Self = Self::Low;
-    pub const MAX: Self = Self::High;
-}
-
-impl fmt::Display for Priority {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Debug::fmt(&self, f)
-    }
-}
-
 /// A read/write wrapper around an CAN frame buffer.
 ///
 /// this buffer is not 1:1 representation of the frame, but
@@ -54,10 +25,18 @@ pub struct Frame<T: AsRef<[u8]>> {
 
 pub(crate) const HEADER_RTR_MASK: u16 = 0x8000;
 
+/// Marks the [`ExtendedFrame`] layout in the first octets of a frame buffer.
+///
+/// Never set in the header of a standard [`Frame`], the 11-bit CAN ID does not reach it.
+pub(crate) const HEADER_EXTENDED_MASK: u16 = 0x4000;
+
 mod field {
     use crate::wire::field::*;
 
     pub(crate) const ID_PRIORITY_MASK: u16 = 0x0780;
+    pub(crate) const ID_MAJOR_PRIORITY_MASK: u16 = 0x0600;
+    pub(crate) const ID_MINOR_PRIORITY_MASK: u16 = 0x0180;
+    pub(crate) const ID_PRIORITY_SHIFT: u16 = 7;
 
     // VLCB uses standard CAN frame with 11-bit identifiers only.
     pub const ID: Field = 0..2;
@@ -117,13 +96,20 @@ impl<T: AsRef<[u8]>> Frame<T> {
         VlcbCanId::from_bytes(&[self.buffer.as_ref()[field::ID_CANID]])
     }
 
-    /// Return the frame priority.
+    /// Return the frame (minor) priority.
     pub fn priority(&self) -> Priority {
-        let prio = (NetworkEndian::read_u16(&self.buffer.as_ref()[field::ID]) & field::ID_PRIORITY_MASK << 7) as u8;
+        let id = NetworkEndian::read_u16(&self.buffer.as_ref()[field::ID]);
+        let prio = ((id & field::ID_MINOR_PRIORITY_MASK) >> field::ID_PRIORITY_SHIFT) as u8;
 
         Priority::from_primitive(prio & Priority::MASK)
     }
 
+    /// Return the whole 4-bit priority field, major and minor priority together.
+    pub fn full_priority(&self) -> u8 {
+        let id = NetworkEndian::read_u16(&self.buffer.as_ref()[field::ID]);
+        ((id & field::ID_PRIORITY_MASK) >> field::ID_PRIORITY_SHIFT) as u8
+    }
+
     // Indicate whether the frame is a CAN RTR frame
     pub fn is_rtr(&self) -> bool {
         NetworkEndian::read_u16(&self.buffer.as_ref()[field::ID]) & HEADER_RTR_MASK != 0
@@ -148,15 +134,31 @@ impl<T: AsRef<[u8]> + BorrowMut<[u8]>> Frame<T> {
             vlcb_core::mask_and_insert_value!(data[field::ID_CANID], value, CANID_MASK, u8);
     }
 
-    /// Set the priority field.
+    /// Set the (minor) priority field.
     #[inline]
     pub fn set_priority(&mut self, priority: Priority) {
         let data = self.buffer.borrow_mut();
-        let val: u8 = priority as u8;
+        let val = (priority as u16) << field::ID_PRIORITY_SHIFT;
+        let new_data = vlcb_core::mask_and_insert_value!(
+            NetworkEndian::read_u16(&data[field::ID]),
+            val,
+            field::ID_MINOR_PRIORITY_MASK,
+            u16
+        );
+        NetworkEndian::write_u16(&mut data[field::ID], new_data);
+    }
+
+    /// Set the major priority field.
+    ///
+    /// Only the two least significant bits of `value` are used.
+    #[inline]
+    pub fn set_major_priority(&mut self, value: u8) {
+        let data = self.buffer.borrow_mut();
+        let val = ((value & 0x03) as u16) << (field::ID_PRIORITY_SHIFT + 2);
         let new_data = vlcb_core::mask_and_insert_value!(
             NetworkEndian::read_u16(&data[field::ID]),
-            (val << 7),
-            field::ID_PRIORITY_MASK,
+            val,
+            field::ID_MAJOR_PRIORITY_MASK,
             u16
         );
         NetworkEndian::write_u16(&mut data[field::ID], new_data);
@@ -164,11 +166,14 @@ impl<T: AsRef<[u8]> + BorrowMut<[u8]>> Frame<T> {
 
     #[inline]
     pub fn set_rtr(&mut self, value: bool) {
-        if value {
-            let data = self.buffer.borrow_mut();
-            let old_val = NetworkEndian::read_u16(&data[field::ID]);
-            NetworkEndian::write_u16(&mut data[field::ID], old_val | HEADER_RTR_MASK);
-        }
+        let data = self.buffer.borrow_mut();
+        let old_val = NetworkEndian::read_u16(&data[field::ID]);
+        let new_val = if value {
+            old_val | HEADER_RTR_MASK
+        } else {
+            old_val & !HEADER_RTR_MASK
+        };
+        NetworkEndian::write_u16(&mut data[field::ID], new_val);
     }
 
     /// Return a mutable pointer to the payload.
@@ -196,6 +201,145 @@ impl<T: AsRef<[u8]> + BorrowMut<[u8]>> fmt::Display for Frame<T> {
     }
 }
 
+/// A read/write wrapper around a CAN frame buffer with an extended (29-bit) identifier.
+///
+/// VLCB itself never uses these frames, but other protocols sharing the bus do, e.g. the CBUS
+/// bootloader. A device that [accepts extended frames] hands them over in this layout:
+/// 4 octets of header, holding the RTR flag, the [extended marker] and the CAN ID, followed
+/// by up to 8 octets of payload.
+///
+/// [accepts extended frames]: crate::phy::DeviceCapabilities::accepts_extended
+/// [extended marker]: ExtendedFrame::is_extended
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExtendedFrame<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod extended_field {
+    use crate::wire::field::*;
+
+    pub(crate) const ID_MASK: u32 = 0x1FFF_FFFF;
+    pub(crate) const RTR_MASK: u32 = (super::HEADER_RTR_MASK as u32) << 16;
+    pub(crate) const EXTENDED_MASK: u32 = (super::HEADER_EXTENDED_MASK as u32) << 16;
+
+    pub const ID: Field = 0..4;
+    pub const PAYLOAD: Rest = 4..;
+}
+
+/// The extended CAN header length
+pub const EXTENDED_HEADER_LEN: usize = extended_field::PAYLOAD.start;
+
+impl<T: AsRef<[u8]>> ExtendedFrame<T> {
+    /// Construct raw extended CAN frame without checking anything.
+    pub const fn new_unchecked(buffer: T) -> ExtendedFrame<T> {
+        ExtendedFrame { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked], [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<ExtendedFrame<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short or too long, or it does not hold
+    /// an extended frame.
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < EXTENDED_HEADER_LEN || len - EXTENDED_HEADER_LEN > 8 || !self.is_extended() {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consumes the frame, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of a frame header.
+    pub const fn header_len() -> usize {
+        EXTENDED_HEADER_LEN
+    }
+
+    /// Return the length of a buffer required to hold a frame with the payload
+    /// of a given length.
+    pub const fn buffer_len(payload_len: usize) -> usize {
+        EXTENDED_HEADER_LEN + payload_len
+    }
+
+    /// Check whether the buffer is marked as an extended frame.
+    ///
+    /// Only the first two octets are read, so a buffer of a standard [`Frame`] is told apart
+    /// from an extended frame as well.
+    pub fn is_extended(&self) -> bool {
+        let data = self.buffer.as_ref();
+        data.len() >= 2 && NetworkEndian::read_u16(data) & HEADER_EXTENDED_MASK != 0
+    }
+
+    /// Return the 29-bit CAN ID.
+    pub fn id(&self) -> u32 {
+        NetworkEndian::read_u32(&self.buffer.as_ref()[extended_field::ID]) & extended_field::ID_MASK
+    }
+
+    /// Indicate whether the frame is a CAN RTR frame
+    pub fn is_rtr(&self) -> bool {
+        NetworkEndian::read_u32(&self.buffer.as_ref()[extended_field::ID]) & extended_field::RTR_MASK != 0
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> ExtendedFrame<&'a T> {
+    /// Return a pointer to the payload.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer.as_ref()[extended_field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]> + BorrowMut<[u8]>> ExtendedFrame<T> {
+    /// Set the 29-bit CAN ID and mark the buffer as an extended frame.
+    ///
+    /// Only the 29 least significant bits of `id` are used.
+    #[inline]
+    pub fn set_id(&mut self, id: u32) {
+        let data = self.buffer.borrow_mut();
+        let header = NetworkEndian::read_u32(&data[extended_field::ID]) & extended_field::RTR_MASK;
+        let header = header | extended_field::EXTENDED_MASK | (id & extended_field::ID_MASK);
+        NetworkEndian::write_u32(&mut data[extended_field::ID], header);
+    }
+
+    #[inline]
+    pub fn set_rtr(&mut self, value: bool) {
+        let data = self.buffer.borrow_mut();
+        let old_val = NetworkEndian::read_u32(&data[extended_field::ID]);
+        let new_val = if value {
+            old_val | extended_field::RTR_MASK
+        } else {
+            old_val & !extended_field::RTR_MASK
+        };
+        NetworkEndian::write_u32(&mut data[extended_field::ID], new_val);
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.borrow_mut();
+        &mut data[extended_field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for ExtendedFrame<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -214,15 +358,67 @@ mod test {
         assert_eq!(NetworkEndian::read_u32(&frame.buffer[field::ID]), 0x0);
     }
 
-    // #[test]
-    // fn test_priority() {
-    //     let mut frame = Frame::new_unchecked([0u8; 10]);
+    #[test]
+    fn test_priority() {
+        let mut frame = Frame::new_unchecked([0u8; 10]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x7F]));
+
+        frame.set_major_priority(0b10);
+        frame.set_priority(Priority::Low);
+        assert_eq!(frame.priority(), Priority::Low);
+        assert_eq!(frame.full_priority(), 0xB);
+        assert_eq!(NetworkEndian::read_u16(&frame.buffer[field::ID]), 0x05FF);
+
+        frame.set_priority(Priority::High);
+        assert_eq!(frame.priority(), Priority::High);
+        assert_eq!(NetworkEndian::read_u16(&frame.buffer[field::ID]), 0x047F);
+    }
+
+    #[test]
+    fn test_rtr() {
+        let mut frame = Frame::new_unchecked([0u8; 2]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x7F]));
 
-    //     frame.set_priority(Prio0);
-    //     assert_eq!(frame.priority(), Prio0);
-    //     assert_eq!(NetworkEndian::read_u16(&frame.buffer[field::ID]), 0x0580);
+        frame.set_rtr(true);
+        assert!(frame.is_rtr());
+        assert!(frame.check_len().is_ok(), "header-only frames are valid");
 
-    //     frame.set_priority(Prio11);
-    //     assert_eq!(NetworkEndian::read_u16(&frame.buffer[field::ID]), 0x0);
-    // }
+        frame.set_rtr(false);
+        assert!(!frame.is_rtr());
+        assert_eq!(frame.src_addr(), VlcbCanId::from_bytes(&[0x7F]));
+    }
+
+    #[test]
+    fn test_extended_frame() {
+        let mut buffer = [0u8; 6];
+        let mut frame = ExtendedFrame::new_unchecked(&mut buffer[..]);
+        frame.set_id(0xFFFF_FFFF);
+        frame.payload_mut().copy_from_slice(&[0x0D, 0x0E]);
+        assert_eq!(buffer[..4], [0x5F, 0xFF, 0xFF, 0xFF]);
+
+        let frame = ExtendedFrame::new_checked(&buffer[..]).unwrap();
+        assert_eq!(frame.id(), 0x1FFF_FFFF);
+        assert!(!frame.is_rtr());
+        assert_eq!(frame.payload(), &[0x0D, 0x0E]);
+
+        let mut frame = ExtendedFrame::new_unchecked(&mut buffer[..]);
+        frame.set_rtr(true);
+        frame.set_id(0x0000_0105);
+        assert!(frame.is_rtr());
+        assert_eq!(frame.id(), 0x105);
+    }
+
+    #[test]
+    fn test_standard_frame_is_not_extended() {
+        let mut buffer = [0u8; 3];
+        let mut frame = Frame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(VlcbCanId::from_bytes(&[0x7F]));
+        frame.set_major_priority(0b10);
+        frame.set_rtr(true);
+
+        assert!(!ExtendedFrame::new_unchecked(&buffer[..]).is_extended());
+        assert!(ExtendedFrame::new_checked(&[0x40, 0x00, 0x00, 0x00][..]).is_ok());
+        assert!(ExtendedFrame::new_checked(&[0x40, 0x00, 0x00][..]).is_err());
+        assert!(ExtendedFrame::new_checked(&[0x40; 13][..]).is_err());
+    }
 }
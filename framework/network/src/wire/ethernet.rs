@@ -0,0 +1,168 @@
+use core::fmt;
+
+use super::{Error, Result};
+
+/// An Ethernet MAC address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Address(pub [u8; ADDR_SIZE]);
+
+/// Size of an Ethernet MAC address in octets.
+pub const ADDR_SIZE: usize = 6;
+
+impl Address {
+    /// Construct an Ethernet address from a sequence of octets.
+    ///
+    /// # Panics
+    /// The function panics if `data` is not six octets long.
+    pub fn from_bytes(data: &[u8]) -> Address {
+        let mut bytes = [0; ADDR_SIZE];
+        bytes.copy_from_slice(data);
+        Address(bytes)
+    }
+
+    /// Construct an Ethernet address from a sequence of octets.
+    ///
+    /// Returns `Err(Error)` if `data` is not six octets long.
+    pub fn try_from_bytes(data: &[u8]) -> Result<Address> {
+        if data.len() != ADDR_SIZE {
+            return Err(Error);
+        }
+        Ok(Address::from_bytes(data))
+    }
+
+    /// Return an Ethernet address as a sequence of octets.
+    pub const fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    }
+}
+
+/// A read/write wrapper around a VLCB over Ethernet frame buffer.
+///
+/// The frame consists of the 6 octets of the source MAC address followed by the VLCB packet,
+/// that is the opcode and its data octets. Framing of the link layer itself, e.g. the
+/// destination address and the EtherType, is left to the device.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    use crate::wire::field::*;
+
+    pub const SRC_ADDR: Field = 0..6;
+    pub const PAYLOAD: Rest = 6..;
+}
+
+/// The Ethernet frame header length
+pub const HEADER_LEN: usize = field::PAYLOAD.start;
+
+impl<T: AsRef<[u8]>> Frame<T> {
+    /// Construct raw Ethernet frame without checking anything.
+    pub const fn new_unchecked(buffer: T) -> Frame<T> {
+        Frame { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked], [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Frame<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < HEADER_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consumes the frame, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of a buffer required to hold a packet with the payload
+    /// of a given length.
+    pub const fn buffer_len(payload_len: usize) -> usize {
+        HEADER_LEN + payload_len
+    }
+
+    /// Return the source address field.
+    #[inline]
+    pub fn src_addr(&self) -> Address {
+        Address::from_bytes(&self.buffer.as_ref()[field::SRC_ADDR])
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Frame<&'a T> {
+    /// Return a pointer to the payload, the VLCB packet.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.buffer.as_ref()[field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
+    /// Set the source address field.
+    #[inline]
+    pub fn set_src_addr(&mut self, value: Address) {
+        self.buffer.as_mut()[field::SRC_ADDR].copy_from_slice(value.as_bytes())
+    }
+
+    /// Return a mutable pointer to the payload, the VLCB packet.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[field::PAYLOAD]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let addr = Address([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let mut buffer = [0u8; 7];
+        let mut frame = Frame::new_unchecked(&mut buffer[..]);
+        frame.set_src_addr(addr);
+        frame.payload_mut()[0] = 0x0D;
+
+        let frame = Frame::new_checked(&buffer[..]).unwrap();
+        assert_eq!(frame.src_addr(), addr);
+        assert_eq!(frame.payload(), &[0x0D]);
+        assert!(Frame::new_checked(&buffer[..5]).is_err());
+        assert_eq!(addr.to_string(), "02:00:00:00:00:01");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let addr = Address([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), addr);
+
+        let bytes = postcard::to_allocvec(&addr).unwrap();
+        assert_eq!(postcard::from_bytes::<Address>(&bytes).unwrap(), addr);
+    }
+}
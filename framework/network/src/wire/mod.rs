@@ -17,13 +17,30 @@ cfg_if! {
         pub(crate) mod can;
 
         pub use self::can::{
+            ExtendedFrame as CanExtendedFrame,
             Frame as CanFrame,
+            EXTENDED_HEADER_LEN as CAN_EXTENDED_HEADER_LEN,
             HEADER_LEN as CAN_HEADER_LEN,
         };
     }
 }
 
-pub use self::vlcb::{Packet as VlcbPacketWire, Repr as VlcbRepr, VLCB_MAX_PAYLOAD};
+cfg_if! {
+    if #[cfg(feature = "medium-eth")] {
+        pub(crate) mod ethernet;
+
+        pub use self::ethernet::{
+            Address as EthernetAddress,
+            Frame as EthernetFrame,
+            HEADER_LEN as ETHERNET_HEADER_LEN,
+        };
+    }
+}
+
+pub use self::vlcb::{
+    is_event_opcode, Packet as VlcbPacketWire, Priority as CanPriority, Protocol as VlcbProtocol,
+    Repr as VlcbRepr, VLCB_MAX_PAYLOAD,
+};
 
 /// Parsing of a packet failed.
 ///
@@ -41,12 +58,15 @@ impl fmt::Display for Error {
 pub type Result<T> = core::result::Result<T, Error>;
 
 /// Representation of a hardware address, such as an CBUS CAN ID.
-#[cfg(feature = "medium-can")]
+#[cfg(any(feature = "medium-can", feature = "medium-eth"))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HardwareAddress {
     #[cfg(feature = "medium-can")]
     CAN(VlcbCanId),
+    #[cfg(feature = "medium-eth")]
+    ETH(EthernetAddress),
 }
 
 impl Default for HardwareAddress {
@@ -55,6 +75,8 @@ impl Default for HardwareAddress {
         cfg_if! {
             if #[cfg(feature = "medium-can")] {
                 return Self::CAN(VlcbCanId::default());
+            } else if #[cfg(feature = "medium-eth")] {
+                return Self::ETH(EthernetAddress::default());
             } else {
                 compile_error! (
                     "You must enable at least one medium feature"
@@ -64,12 +86,23 @@ impl Default for HardwareAddress {
     }
 }
 
-#[cfg(feature = "medium-can")]
+#[cfg(any(feature = "medium-can", feature = "medium-eth"))]
 impl HardwareAddress {
     pub const fn as_bytes(&self) -> &[u8] {
         match self {
             #[cfg(feature = "medium-can")]
             HardwareAddress::CAN(node) => node.as_bytes(),
+            #[cfg(feature = "medium-eth")]
+            HardwareAddress::ETH(addr) => addr.as_bytes(),
+        }
+    }
+
+    #[cfg(feature = "medium-eth")]
+    pub(crate) fn ethernet_or_panic(&self) -> EthernetAddress {
+        match self {
+            HardwareAddress::ETH(addr) => *addr,
+            #[allow(unreachable_patterns)]
+            _ => panic!("HardwareAddress is not Ethernet."),
         }
     }
 
@@ -87,6 +120,8 @@ impl HardwareAddress {
         match self {
             #[cfg(feature = "medium-can")]
             HardwareAddress::CAN(_) => Medium::CAN,
+            #[cfg(feature = "medium-eth")]
+            HardwareAddress::ETH(_) => Medium::ETH,
         }
     }
 }
@@ -96,6 +131,8 @@ impl fmt::Display for HardwareAddress {
         match self {
             #[cfg(feature = "medium-can")]
             HardwareAddress::CAN(node) => write!(f, "{}", node),
+            #[cfg(feature = "medium-eth")]
+            HardwareAddress::ETH(addr) => write!(f, "{}", addr),
         }
     }
 }
@@ -107,8 +144,17 @@ impl From<VlcbCanId> for HardwareAddress {
     }
 }
 
+#[cfg(feature = "medium-eth")]
+impl From<EthernetAddress> for HardwareAddress {
+    fn from(addr: EthernetAddress) -> Self {
+        HardwareAddress::ETH(addr)
+    }
+}
+
 cfg_if! {
-    if #[cfg(feature = "medium-can")] {
+    if #[cfg(feature = "medium-eth")] {
+        pub const MAX_HARDWARE_ADDRESS_LEN: usize = ethernet::ADDR_SIZE;
+    } else if #[cfg(feature = "medium-can")] {
         pub const MAX_HARDWARE_ADDRESS_LEN: usize = 2;
     } else {
         core::compile_error!("At least one medium feature needs to be enabled for deciding which MAX_HARDWARE_ADDRESS_LEN value to use");
@@ -150,13 +196,12 @@ impl RawHardwareAddress {
         match medium {
             #[cfg(feature = "medium-can")]
             Medium::CAN => {
-                if self.len() < 2 {
-                    return Err(Error);
-                }
-                let addr = VlcbCanId::from_bytes(self.as_bytes());
+                let addr = VlcbCanId::try_from_bytes(self.as_bytes()).map_err(|_| Error)?;
 
                 Ok(addr.into())
             }
+            #[cfg(feature = "medium-eth")]
+            Medium::ETH => Ok(EthernetAddress::try_from_bytes(self.as_bytes())?.into()),
         }
     }
 }
@@ -185,3 +230,50 @@ impl From<HardwareAddress> for RawHardwareAddress {
         Self::from_bytes(addr.as_bytes())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "medium-can")]
+    #[test]
+    fn test_parse_can_hardware_address() {
+        let raw = RawHardwareAddress::from_bytes(&[0x05]);
+        assert_eq!(
+            raw.parse(Medium::CAN),
+            Ok(HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05])))
+        );
+
+        assert_eq!(RawHardwareAddress::from_bytes(&[]).parse(Medium::CAN), Err(Error));
+        assert_eq!(
+            RawHardwareAddress::from_bytes(&[0x05, 0x06]).parse(Medium::CAN),
+            Err(Error)
+        );
+    }
+
+    #[cfg(feature = "medium-eth")]
+    #[test]
+    fn test_parse_ethernet_hardware_address() {
+        let mac = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+        assert_eq!(MAX_HARDWARE_ADDRESS_LEN, mac.len());
+
+        let addr = HardwareAddress::ETH(EthernetAddress(mac));
+        let raw = RawHardwareAddress::from(addr);
+        assert_eq!(raw.as_bytes(), &mac);
+        assert_eq!(raw.parse(Medium::ETH), Ok(addr));
+        assert_eq!(addr.medium(), Medium::ETH);
+
+        assert_eq!(RawHardwareAddress::from_bytes(&[0x05]).parse(Medium::ETH), Err(Error));
+    }
+
+    #[cfg(all(feature = "serde", feature = "medium-can"))]
+    #[test]
+    fn test_serde_round_trip() {
+        let addr = HardwareAddress::CAN(VlcbCanId::from_bytes(&[0x05]));
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(serde_json::from_str::<HardwareAddress>(&json).unwrap(), addr);
+
+        let bytes = postcard::to_allocvec(&addr).unwrap();
+        assert_eq!(postcard::from_bytes::<HardwareAddress>(&bytes).unwrap(), addr);
+    }
+}
@@ -1,6 +1,60 @@
 use super::{Error, Result};
+use vlcb_core::opcode::{opcode_info, PriorityClass};
 use vlcb_defs::OpCode;
 use core::fmt;
+use core::fmt::Debug;
+use num_enum::{FromPrimitive, IntoPrimitive};
+
+/// VLCB packet priority.
+///
+/// Static priority based on message and node type, sent as the minor priority in
+/// bits 7 - 8 of the CAN header. Packets carry it on every medium, so they keep it
+/// when they are bridged to CAN.
+///
+/// VLCB unlike CBUS does not support priority ratcheting in case
+/// of transport failure
+#[derive(Debug, Eq, PartialEq, Copy, Clone, IntoPrimitive, FromPrimitive, Default)]
+#[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+    High = 0x00,
+    AboveNormal = 0x01,
+    Normal = 0x02,
+    #[default]
+    Low = 0x03,
+}
+
+impl Priority {
+    pub const MASK: u8 = 0x03;
+    pub const MIN: Self = Self::Low;
+    pub const MAX: Self = Self::High;
+
+    /// Return the default priority of packets with the given opcode.
+    ///
+    /// The priority follows the [`PriorityClass`] of the opcode: DCC (loco control) packets
+    /// are sent with high priority, accessory events with normal priority and everything else,
+    /// mostly module configuration, with low priority.
+    pub fn for_opcode(opcode: OpCode) -> Self {
+        opcode_info(opcode.into()).priority.into()
+    }
+}
+
+impl From<PriorityClass> for Priority {
+    fn from(value: PriorityClass) -> Self {
+        match value {
+            PriorityClass::High => Self::High,
+            PriorityClass::Normal => Self::Normal,
+            PriorityClass::Low => Self::Low,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self, f)
+    }
+}
 
 /// VLCB sub-protocol.
 ///
@@ -9,15 +63,61 @@ use core::fmt;
 /// for easier handling.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Protocol {
     // Every other opcode that is not tied to a specific protocol
     Module,
 
+    /// Accessory event protocol
+    Event,
+
     /// Stream protocol
     Stream,
 }
 
-//TODO: we need to properly test this and check for data_len constraints
+/// Check whether the opcode belongs to the accessory event family.
+///
+/// This covers long and short accessory events with and without data,
+/// the accessory state requests and their responses.
+pub fn is_event_opcode(opcode: OpCode) -> bool {
+    matches!(
+        opcode,
+        OpCode::LongEventAccessoryOn
+            | OpCode::LongEventAccessoryOff
+            | OpCode::QueryLongEventAccessoryState
+            | OpCode::LongEventAccessoryStateOn
+            | OpCode::LongEventAccessoryStateOff
+            | OpCode::ShortEventAccessoryOn
+            | OpCode::ShortEventAccessoryOff
+            | OpCode::QueryShortEventAccessoryState
+            | OpCode::ShortEventAccessoryStateOn
+            | OpCode::ShortEventAccessoryStateOff
+            | OpCode::LongEventAccessoryOn1
+            | OpCode::LongEventAccessoryOff1
+            | OpCode::LongEventAccessoryStateOn1
+            | OpCode::LongEventAccessoryStateOff1
+            | OpCode::ShortEventAccessoryOn1
+            | OpCode::ShortEventAccessoryOff1
+            | OpCode::ShortEventAccessoryStateOn1
+            | OpCode::ShortEventAccessoryStateOff1
+            | OpCode::LongEventAccessoryOn2
+            | OpCode::LongEventAccessoryOff2
+            | OpCode::LongEventAccessoryStateOn2
+            | OpCode::LongEventAccessoryStateOff2
+            | OpCode::ShortEventAccessoryOn2
+            | OpCode::ShortEventAccessoryOff2
+            | OpCode::ShortEventAccessoryStateOn2
+            | OpCode::ShortEventAccessoryStateOff2
+            | OpCode::LongEventAccessoryOn3
+            | OpCode::LongEventAccessoryOff3
+            | OpCode::LongEventAccessoryStateOn3
+            | OpCode::LongEventAccessoryStateOff3
+            | OpCode::ShortEventAccessoryOn3
+            | OpCode::ShortEventAccessoryOff3
+            | OpCode::ShortEventAccessoryStateOn3
+            | OpCode::ShortEventAccessoryStateOff3
+    )
+}
 
 /// Size of an VLCB address in octets. (The address is 11bit wide)
 pub const ADDR_SIZE: usize = 2;
@@ -76,7 +176,6 @@ mod field {
     use crate::wire::field::*;
 
     pub const OPCODE: Single = 0;
-    pub const OPCODE_MASK: u8 = 0x1F;
     pub const DATA_LEN: Single = 0;
     pub const DATA_LEN_MASK: u8 = 0xE0;
 }
@@ -100,23 +199,12 @@ impl<T: AsRef<[u8]>> Packet<T> {
     }
 
     /// Ensure that no accessor method will panic if called.
-    /// Returns `Err(Error)` if the buffer is too short.
-    /// Returns `Err(Error)` if the header length is greater
-    /// than total length.
     ///
-    /// The result of this check is invalidated by calling [set_header_len]
-    /// and [set_total_len].
-    ///
-    /// [set_header_len]: #method.set_header_len
-    /// [set_total_len]: #method.set_total_len
-    #[allow(clippy::if_same_then_else)]
+    /// Returns `Err(Error)` if the buffer is shorter than the opcode and the
+    /// data octets encoded in it.
     pub fn check_len(&self) -> Result<()> {
         let len = self.buffer.as_ref().len();
-        if len < self.header_len() as usize {
-            Err(Error)
-        } else if self.header_len() > self.total_len() {
-            Err(Error)
-        } else if len < self.total_len() as usize {
+        if len < self.header_len() as usize || len < self.total_len() as usize {
             Err(Error)
         } else {
             Ok(())
@@ -134,16 +222,18 @@ impl<T: AsRef<[u8]>> Packet<T> {
         HEADER_LEN as u8
     }
 
-    /// Return the total length field.
+    /// Return the length of the opcode and its data octets.
     #[inline]
     pub fn total_len(&self) -> u8 {
-        self.payload_len()
+        self.header_len() + self.payload_len()
     }
 
     /// Return the VLCB OpCode
+    ///
+    /// The data length bits are part of the opcode, so the whole octet is returned.
     #[inline]
     pub fn opcode(&self) -> u8 {
-        self.buffer.as_ref()[field::OPCODE] & field::OPCODE_MASK
+        self.buffer.as_ref()[field::OPCODE]
     }
 
     /// Return the payload len for current OpCode
@@ -154,8 +244,9 @@ impl<T: AsRef<[u8]>> Packet<T> {
 
     /// Return the next header protocol type
     pub fn next_header(&self) -> Protocol {
-        match OpCode::try_from(self.opcode()).unwrap() {
-            OpCode::StreamPacket => Protocol::Stream,
+        match OpCode::try_from(self.opcode()) {
+            Ok(OpCode::StreamPacket) => Protocol::Stream,
+            Ok(opcode) if is_event_opcode(opcode) => Protocol::Event,
             _ => Protocol::Module,
         }
     }
@@ -174,8 +265,7 @@ impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
 impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
     #[inline]
     pub fn set_opcode(&mut self, value: u8) {
-        let data = self.buffer.as_ref()[field::OPCODE] & !field::OPCODE_MASK;
-        self.buffer.as_mut()[field::OPCODE] = data | (value & field::OPCODE_MASK);
+        self.buffer.as_mut()[field::OPCODE] = value;
     }
 
     #[inline]
@@ -202,22 +292,49 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
 /// A high-level representation of an VLCB packet header.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Repr {
     pub data_len: u8,
+    #[cfg_attr(feature = "serde", serde(with = "opcode_serde"))]
     pub opcode: OpCode,
     pub next_header: Protocol,
 }
 
+/// Serialize the opcode as its octet, the opcodes are not serializable themselves.
+#[cfg(feature = "serde")]
+mod opcode_serde {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use vlcb_defs::OpCode;
+
+    pub fn serialize<S: Serializer>(opcode: &OpCode, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*opcode).into())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> core::result::Result<OpCode, D::Error> {
+        let value = u8::deserialize(deserializer)?;
+        OpCode::try_from(value).map_err(|_| D::Error::custom("unknown opcode"))
+    }
+}
+
 impl Repr {
     pub fn new(opcode: OpCode, data_len: u8, next_header: Protocol) -> Self {
         Self { opcode, data_len, next_header }
     }
 
     /// Parse an VLCB packet and return a high-level representation.
+    ///
+    /// Returns `Err(Error)` for opcodes unknown to the library, or when the data length
+    /// does not match the opcode metadata.
     pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        let opcode = OpCode::try_from(packet.opcode()).map_err(|_| Error)?;
+        if opcode_info(packet.opcode()).data_len != packet.payload_len() {
+            return Err(Error);
+        }
+
         Ok(Repr {
             data_len: packet.payload_len(),
-            opcode: OpCode::try_from(packet.opcode()).unwrap(),
+            opcode,
             next_header: packet.next_header(),
         })
     }
@@ -275,4 +392,133 @@ impl fmt::Display for Repr {
 #[cfg(test)]
 mod test {
     use super::*;
+
+    #[test]
+    fn test_next_header() {
+        let acon = [u8::from(OpCode::LongEventAccessoryOn), 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(Packet::new_unchecked(&acon[..]).next_header(), Protocol::Event);
+
+        let asrq = [u8::from(OpCode::QueryShortEventAccessoryState), 0x00, 0x00, 0x00, 0x02];
+        assert_eq!(Packet::new_unchecked(&asrq[..]).next_header(), Protocol::Event);
+
+        let nvset = [u8::from(OpCode::SetNodeVariable), 0x00, 0x01, 0x01, 0x05];
+        assert_eq!(Packet::new_unchecked(&nvset[..]).next_header(), Protocol::Module);
+
+        let dtxc = [u8::from(OpCode::StreamPacket), 0x01, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(Packet::new_unchecked(&dtxc[..]).next_header(), Protocol::Stream);
+    }
+
+    #[test]
+    fn test_next_header_unknown_opcode() {
+        let unknown = [0x0B];
+        assert_eq!(Packet::new_unchecked(&unknown[..]).next_header(), Protocol::Module);
+    }
+
+    #[test]
+    fn test_payload_len() {
+        let data = [1, 2, 3, 4, 5, 6, 7];
+        for data_len in 0..=7u8 {
+            let mut buffer = [0u8; 8];
+            buffer[0] = (data_len << 5) | 0x0F;
+            buffer[1..].copy_from_slice(&data);
+
+            let packet = Packet::new_checked(&buffer[..=data_len as usize]).unwrap();
+            assert_eq!(packet.payload_len(), data_len);
+            assert_eq!(packet.total_len(), data_len + 1);
+            assert_eq!(packet.payload(), &data[..data_len as usize]);
+
+            if data_len > 0 {
+                assert_eq!(Packet::new_checked(&buffer[..data_len as usize]).err(), Some(Error));
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        assert_eq!(Packet::new_checked(&[][..]).err(), Some(Error));
+    }
+
+    #[test]
+    fn test_emit_parse() {
+        for data_len in 0..=7u8 {
+            let opcode = (0..=u8::MAX)
+                .filter_map(|op| OpCode::try_from(op).ok())
+                .find(|&op| opcode_info(op.into()).data_len == data_len)
+                .unwrap();
+            let repr = Repr::new(opcode, data_len, Protocol::Module);
+
+            let mut buffer = [0u8; 8];
+            let mut packet = Packet::new_unchecked(&mut buffer[..=data_len as usize]);
+            repr.emit(&mut packet, |payload| {
+                assert_eq!(payload.len(), data_len as usize);
+                payload.fill(0xA5);
+            });
+
+            let packet = Packet::new_checked(&buffer[..=data_len as usize]).unwrap();
+            let parsed = Repr::parse(&packet).unwrap();
+            assert_eq!(parsed.opcode, opcode);
+            assert_eq!(parsed.data_len, data_len);
+            assert!(packet.payload().iter().all(|&octet| octet == 0xA5));
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let acon = [u8::from(OpCode::LongEventAccessoryOn), 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(
+            Repr::parse(&Packet::new_unchecked(&acon[..])),
+            Ok(Repr::new(OpCode::LongEventAccessoryOn, 4, Protocol::Event))
+        );
+
+        let unknown = [0x0B];
+        assert_eq!(Repr::parse(&Packet::new_unchecked(&unknown[..])), Err(Error));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let repr = Repr::new(OpCode::LongEventAccessoryOn, 4, Protocol::Event);
+        let json = serde_json::to_string(&repr).unwrap();
+        assert_eq!(json, r#"{"data_len":4,"opcode":144,"next_header":"Event"}"#);
+        assert_eq!(serde_json::from_str::<Repr>(&json).unwrap(), repr);
+
+        let unknown = r#"{"data_len":0,"opcode":11,"next_header":"Module"}"#;
+        assert!(serde_json::from_str::<Repr>(unknown).is_err());
+
+        let bytes = postcard::to_allocvec(&repr).unwrap();
+        assert_eq!(postcard::from_bytes::<Repr>(&bytes).unwrap(), repr);
+        assert!(postcard::from_bytes::<Repr>(&[0x00, 0x0B, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_priority_for_opcode() {
+        assert_eq!(Priority::for_opcode(OpCode::DccEmergencyStop), Priority::High);
+        assert_eq!(Priority::for_opcode(OpCode::DccSetLocoThrottle), Priority::High);
+        assert_eq!(Priority::for_opcode(OpCode::LongEventAccessoryOn), Priority::Normal);
+        assert_eq!(Priority::for_opcode(OpCode::ShortEventAccessoryStateOff3), Priority::Normal);
+        assert_eq!(Priority::for_opcode(OpCode::SetNodeVariable), Priority::Low);
+        assert_eq!(Priority::for_opcode(OpCode::QueryNodeInfo), Priority::Low);
+        assert_eq!(Priority::for_opcode(OpCode::QueryNodeVariable), Priority::Low);
+        assert_eq!(Priority::for_opcode(OpCode::NodeVariableValue), Priority::Low);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_priority_serde_round_trip() {
+        let json = serde_json::to_string(&Priority::AboveNormal).unwrap();
+        assert_eq!(serde_json::from_str::<Priority>(&json).unwrap(), Priority::AboveNormal);
+
+        let bytes = postcard::to_allocvec(&Priority::AboveNormal).unwrap();
+        assert_eq!(postcard::from_bytes::<Priority>(&bytes).unwrap(), Priority::AboveNormal);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_protocol_serde_round_trip() {
+        let json = serde_json::to_string(&Protocol::Event).unwrap();
+        assert_eq!(serde_json::from_str::<Protocol>(&json).unwrap(), Protocol::Event);
+
+        let bytes = postcard::to_allocvec(&Protocol::Event).unwrap();
+        assert_eq!(postcard::from_bytes::<Protocol>(&bytes).unwrap(), Protocol::Event);
+    }
 }
@@ -2,12 +2,30 @@
 #![deny(unsafe_code)]
 
 pub mod node_config;
+pub mod snapshot;
+pub mod transfer;
 
 pub trait Storage {
     /// Wipe storage clean
     fn wipe(&mut self);
 }
 
+/// What [`PersistentStorage::load`] restored from the storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOutcome {
+    /// Everything was restored.
+    Restored,
+    /// The storage was blank and got initialised with the defaults.
+    Blank,
+    /// The stored node state failed its check and fell back to the defaults,
+    /// the rest was restored.
+    Corrupted,
+    /// The storage was written with another layout, e.g. by a firmware with a different
+    /// number of events. Only the node state independent of the layout was restored,
+    /// the rest is rewritten with the defaults by the next flush.
+    LayoutMismatch,
+}
+
 /// A persistent storage trait for loading and storing data.
 pub trait PersistentStorage {
     /// Loads the necessary data into the object.
@@ -15,7 +33,7 @@ pub trait PersistentStorage {
     /// This method is used to load the required data into the object.
     /// It should be called before using any other methods that rely on the data being loaded.
     #[must_use]
-    fn load(&mut self);
+    fn load(&mut self) -> LoadOutcome;
 
     fn is_dirty(&self) -> bool;
 
@@ -1,12 +1,12 @@
-use crate::{PersistentStorage, Storage};
+use crate::{LoadOutcome, PersistentStorage, Storage};
+use bitflags::bitflags;
 use delegate::delegate;
 use embedded_storage::Storage as StorageDriver;
 use vlcb_core::can::{VlcbCanId, CANID_SIZE};
 use vlcb_core::vlcb::{EventId, VlcbNodeNumber, EVENT_SIZE, NODENUM_SIZE};
 use vlcb_core::module::NodeFlags;
-use vlcb_defs::ModuleMode;
+use vlcb_defs::{CommandError, ModuleMode};
 use core::cell::{RefCell};
-use core::mem::MaybeUninit;
 use heapless::{FnvIndexMap, Vec};
 use rclite::Rc;
 
@@ -16,6 +16,23 @@ pub enum Error {
     Exhausted,
     OutOfRange,
     OccupiedEntry,
+    /// The event is not stored
+    NotFound,
+}
+
+/// Map the error to the CMDERR reported to the configuration tool.
+///
+/// [`Error::OutOfRange`] is returned for node variable indexes, which makes it
+/// [`CommandError::InvalidNvIndex`].
+impl From<Error> for CommandError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Exhausted => CommandError::TooManyEvents,
+            Error::OutOfRange => CommandError::InvalidNvIndex,
+            Error::OccupiedEntry => CommandError::InvalidEventIndex,
+            Error::NotFound => CommandError::InvalidEvent,
+        }
+    }
 }
 
 pub trait NodeConfig {
@@ -26,9 +43,13 @@ pub trait NodeConfig {
 
     fn stored_event_count(&self) -> u8;
     /// Saves the current event in the data store.
+    ///
+    /// Returns [`Error::OutOfRange`] unless there is exactly one value for every EV.
     fn save_event(&mut self, evt: &EventId, evs: &[u8]) -> Result<(), Error>;
 
     fn has_event_with_index(&self, index: u8) -> bool;
+    /// Finds the event stored in the slot with the given index.
+    fn get_event_by_index(&self, index: u8) -> Option<(&EventId, &Self::Event)>;
     fn restore_event(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error>;
     fn restore_event_unchecked(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error>;
 
@@ -36,6 +57,12 @@ pub trait NodeConfig {
     fn delete_event(&mut self, evt: &EventId);
     fn get_event(&self, evt: &EventId) -> Option<&Self::Event>;
     fn has_event(&self, evt: &EventId) -> bool;
+    /// EVs are indexed from 1
+    fn get_ev(&self, evt: &EventId, ev_index: u8) -> Result<u8, Error>;
+    /// Sets a single EV, an event that is not stored yet is saved with the other EVs zeroed.
+    fn set_ev(&mut self, evt: &EventId, ev_index: u8, value: u8) -> Result<(), Error>;
+    /// Iterates the learned events, in no particular order.
+    fn events(&self) -> impl Iterator<Item = (&EventId, &Self::Event)>;
     /// NVs are indexed from 1
     fn get_nv(&self, index: u8) -> Result<u8, Error>;
     fn set_nv(&mut self, index: u8, value: u8) -> Result<(), Error>;
@@ -55,6 +82,21 @@ pub trait NodeConfig {
     fn is_event_ack_on(&self) -> bool;
     fn flags(&self) -> NodeFlags;
     fn set_flags(&mut self, flags: NodeFlags);
+    /// Forgets the learned events and restores the NV defaults.
+    ///
+    /// Unlike [`Storage::wipe`] the mode, node number and CAN ID are kept.
+    fn reset_to_factory(&mut self);
+    /// Sets every NV to its default and keeps the defaults for [`Self::reset_to_factory`].
+    ///
+    /// Returns [`Error::OutOfRange`] unless there is exactly one default for every NV.
+    fn apply_nv_defaults(&mut self, defaults: &[u8]) -> Result<(), Error>;
+    /// Returns the slot index of a stored event.
+    ///
+    /// The index is kept until the event is deleted, teaching or deleting other events
+    /// does not change it.
+    fn event_index(&self, evt: &EventId) -> Option<u8>;
+    /// Forgets all learned events and frees their slot indexes.
+    fn clear_all_events(&mut self);
 }
 
 pub trait LearnedEvent {
@@ -85,6 +127,50 @@ impl<const EVENT_VAR_COUNT: usize> LearnedEvent for HeaplessLearnedEvent<EVENT_V
     }
 }
 
+/// Bitmap of the event slot indexes in use.
+///
+/// There are at most 255 event slots, so eight words cover them whatever `MAX_EVENTS` is.
+#[derive(Debug, Default)]
+pub(crate) struct EventSlots {
+    used: [u32; 8],
+    /// Words inspected by the allocations, to check their cost in the tests
+    #[cfg(test)]
+    scanned: usize,
+}
+
+impl EventSlots {
+    pub(crate) fn take(&mut self, index: u8) {
+        self.used[index as usize / 32] |= 1 << (index % 32);
+    }
+
+    fn free(&mut self, index: u8) {
+        self.used[index as usize / 32] &= !(1 << (index % 32));
+    }
+
+    pub(crate) fn is_taken(&self, index: u8) -> bool {
+        self.used[index as usize / 32] & (1 << (index % 32)) != 0
+    }
+
+    fn clear(&mut self) {
+        self.used = [0; 8];
+    }
+
+    /// Return the lowest free index below `limit`.
+    fn lowest_free(&mut self, limit: usize) -> Option<u8> {
+        for (word_index, word) in self.used.iter().enumerate().take(limit.div_ceil(32)) {
+            #[cfg(test)]
+            {
+                self.scanned += 1;
+            }
+            if *word != u32::MAX {
+                let index = word_index * 32 + word.trailing_ones() as usize;
+                return (index < limit).then_some(index as u8);
+            }
+        }
+        None
+    }
+}
+
 pub struct NodeConfigStorage<
     const MAX_EVENTS: usize,
     const EVENT_VAR_COUNT: usize,
@@ -95,7 +181,11 @@ pub struct NodeConfigStorage<
     can_id: VlcbCanId,
     node_number: VlcbNodeNumber,
     nvs: [u8; NODE_VAR_COUNT],
+    /// NV values of a blank storage and after a factory reset
+    nv_defaults: [u8; NODE_VAR_COUNT],
     events: FnvIndexMap<EventId, HeaplessLearnedEvent<EVENT_VAR_COUNT>, MAX_EVENTS>,
+    /// Slot indexes of the stored events
+    slots: EventSlots,
     reset_flag: bool,
 }
 
@@ -109,9 +199,11 @@ impl<
             flags: NodeFlags::empty(),
             current_mode: ModuleMode::Uninitialized,
             nvs: [UNINITIALISED_VALUE; NODE_VAR_COUNT],
+            nv_defaults: [UNINITIALISED_VALUE; NODE_VAR_COUNT],
             can_id: VlcbCanId::default(),
             node_number: VlcbNodeNumber::default(),
             events: FnvIndexMap::new(),
+            slots: EventSlots::default(),
             reset_flag: false,
         }
     }
@@ -123,27 +215,40 @@ impl<
     const NODE_VAR_COUNT: usize,
 > NodeConfigStorage<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT> {
     fn set_event_item(&mut self, event_id: EventId, item: HeaplessLearnedEvent<EVENT_VAR_COUNT>) {
-        self.events[&event_id] = item
+        let _ = self.insert_event(event_id, item);
     }
 
-    fn find_free_event_slot(&self) -> Option<u8> {
-        // The map is full, no need to evaluate
-        if self.events.len() == MAX_EVENTS {
-            return None;
+    /// Insert the event and take its slot, the slot of a replaced event is freed.
+    fn insert_event(&mut self, event_id: EventId, item: HeaplessLearnedEvent<EVENT_VAR_COUNT>) -> Result<(), Error> {
+        let index = item.index;
+        let previous = self.events.insert(event_id, item).map_err(|_| Error::Exhausted)?;
+        if let Some(previous) = previous {
+            self.slots.free(previous.index);
         }
-        // First index is 0
-        let mut i = 0;
+        self.slots.take(index);
+        Ok(())
+    }
 
-        // The map is empty, no need to evaluate
-        if self.events.is_empty() {
-            return Some(i);
+    /// Return the position of an EV in the event vars, EVs are indexed from 1.
+    fn ev_position(ev_index: u8) -> Result<usize, Error> {
+        let i = (ev_index as usize).checked_sub(1).ok_or(Error::OutOfRange)?;
+        if i < EVENT_VAR_COUNT {
+            Ok(i)
+        } else {
+            Err(Error::OutOfRange)
         }
+    }
 
-        // Loop over all indices and try to find them in an array
-        while self.events.values().any(|v| v.index == i) {
-            i += 1;
+    /// Return the lowest slot index not taken by an event.
+    ///
+    /// The cost is bounded by the eight words of the bitmap, whatever the number of
+    /// stored events.
+    fn find_free_event_slot(&mut self) -> Option<u8> {
+        // The map is full, no need to evaluate
+        if self.events.len() == MAX_EVENTS {
+            return None;
         }
-        Some(i)
+        self.slots.lowest_free(MAX_EVENTS)
     }
 }
 
@@ -153,7 +258,7 @@ impl<
     const NODE_VAR_COUNT: usize,
 > Storage for NodeConfigStorage<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT> {
     fn wipe(&mut self) {
-        self.events.clear();
+        self.clear_all_events();
         self.nvs.iter_mut().for_each(|v| *v = 0);
         self.can_id = VlcbCanId::default();
         self.node_number = VlcbNodeNumber::default();
@@ -180,19 +285,26 @@ impl<
     }
 
     fn save_event(&mut self, evt: &EventId, evs: &[u8]) -> Result<(), Error> {
+        if evs.len() != EVENT_VAR_COUNT {
+            return Err(Error::OutOfRange);
+        }
+        let vars = Vec::from_slice(evs).map_err(|_| Error::OutOfRange)?;
+
         if let Some(item) = self.events.get_mut(evt) {
-            item.vars.copy_from_slice(evs);
+            item.vars = vars;
             return Ok(());
         }
         if let Some(i) = self.find_free_event_slot() {
-            self.events[evt] = HeaplessLearnedEvent{ index: i, vars: Vec::from_slice(&evs).unwrap() };
-            return Ok(());
+            let item = HeaplessLearnedEvent{ index: i, vars };
+            return self.insert_event(*evt, item);
         }
         Err(Error::Exhausted)
     }
 
     fn delete_event(&mut self, evt: &EventId) {
-        self.events.remove(evt);
+        if let Some(event) = self.events.remove(evt) {
+            self.slots.free(event.index);
+        }
     }
 
     fn get_event(&self, evt: &EventId) -> Option<&Self::Event> {
@@ -203,12 +315,38 @@ impl<
         self.events.contains_key(evt)
     }
 
+    fn get_ev(&self, evt: &EventId, ev_index: u8) -> Result<u8, Error> {
+        let i = Self::ev_position(ev_index)?;
+        let event = self.events.get(evt).ok_or(Error::NotFound)?;
+        Ok(event.vars.get(i).copied().unwrap_or(0))
+    }
+
+    fn set_ev(&mut self, evt: &EventId, ev_index: u8, value: u8) -> Result<(), Error> {
+        let i = Self::ev_position(ev_index)?;
+        if !self.events.contains_key(evt) {
+            self.save_event(evt, &[0; EVENT_VAR_COUNT])?;
+        }
+
+        let event = self.events.get_mut(evt).ok_or(Error::NotFound)?;
+        if event.vars.len() <= i {
+            event.vars.resize_default(i + 1).map_err(|_| Error::OutOfRange)?;
+        }
+        event.vars[i] = value;
+        Ok(())
+    }
+
+    fn events(&self) -> impl Iterator<Item = (&EventId, &Self::Event)> {
+        self.events.iter()
+    }
+
     fn get_nv(&self, index: u8) -> Result<u8, Error> {
+        let index = index.checked_sub(1).ok_or(Error::OutOfRange)?;
         self.nvs.get(index as usize).copied()
             .ok_or(Error::OutOfRange)
     }
 
     fn set_nv(&mut self, index: u8, value: u8) -> Result<(), Error> {
+        let index = index.checked_sub(1).ok_or(Error::OutOfRange)?;
         self.nvs.get_mut(index as usize)
             .map(|nv| {
                 *nv = value;
@@ -288,14 +426,36 @@ impl<
         self.flags = flags
     }
 
+    fn reset_to_factory(&mut self) {
+        self.clear_all_events();
+        self.nvs = self.nv_defaults;
+    }
+
+    fn apply_nv_defaults(&mut self, defaults: &[u8]) -> Result<(), Error> {
+        self.nv_defaults = defaults.try_into().map_err(|_| Error::OutOfRange)?;
+        self.nvs = self.nv_defaults;
+        Ok(())
+    }
+
+    fn event_index(&self, evt: &EventId) -> Option<u8> {
+        self.events.get(evt).map(|e| e.index)
+    }
+
+    fn clear_all_events(&mut self) {
+        self.events.clear();
+        self.slots.clear();
+    }
+
     fn restore_event_unchecked(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error> {
-        self.events.insert(evt, data)
-            .map(|_|())
-            .map_err(|_| Error::Exhausted)
+        self.insert_event(evt, data)
     }
 
     fn has_event_with_index(&self, index: u8) -> bool {
-        self.events.values().any(|e| e.index == index)
+        (index as usize) < MAX_EVENTS && self.slots.is_taken(index)
+    }
+
+    fn get_event_by_index(&self, index: u8) -> Option<(&EventId, &Self::Event)> {
+        self.events.iter().find(|(_, e)| e.index == index)
     }
 
     fn restore_event(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error> {
@@ -311,6 +471,58 @@ pub const fn bytes_per_event(event_var_count: usize) -> usize {
     EVENT_SIZE + event_var_count
 }
 
+/// Name the [`PersistentNodeConfigStorage`] type with the `BYTES_PER_EVENT` parameter
+/// computed from the number of event variables.
+///
+/// The arguments are the storage driver, the offset of the config block in the storage,
+/// the maximum number of events, the number of event variables and the number of node
+/// variables, optionally followed by the value of an erased byte of the storage.
+///
+/// ```ignore
+/// type Config = vlcb_persistence::node_config_storage!(MemFlash<128, 1, 1>, 0, 32, 4, 32);
+/// type FramConfig = vlcb_persistence::node_config_storage!(Fram, 0, 32, 4, 32, 0x00);
+/// ```
+#[macro_export]
+macro_rules! node_config_storage {
+    ($driver:ty, $offset:expr, $max_events:expr, $event_vars:expr, $node_vars:expr $(,)?) => {
+        $crate::node_config::PersistentNodeConfigStorage<
+            $driver,
+            { $offset },
+            { $max_events },
+            { $event_vars },
+            { $crate::node_config::bytes_per_event($event_vars) },
+            { $node_vars },
+        >
+    };
+    ($driver:ty, $offset:expr, $max_events:expr, $event_vars:expr, $node_vars:expr, $erased:expr $(,)?) => {
+        $crate::node_config::PersistentNodeConfigStorage<
+            $driver,
+            { $offset },
+            { $max_events },
+            { $event_vars },
+            { $crate::node_config::bytes_per_event($event_vars) },
+            { $node_vars },
+            { $erased },
+        >
+    };
+}
+
+
+/// CRC-8 with the polynomial 0x07.
+const fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    let mut i = 0;
+    while i < data.len() {
+        crc ^= data[i];
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
 
 /// Helper function for picking up readout buffer size
 ///
@@ -319,11 +531,103 @@ const fn cmax(a: usize, b: usize) -> usize {
     [a, b][(a < b) as usize]
 }
 
+bitflags! {
+    /// Fields of the persistent sub block changed since the last flush.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct DirtyFields: u8 {
+        /// The mode together with the node number
+        const Mode = 0b00000001;
+        const Flags = 0b00000010;
+        const CanId = 0b00000100;
+        const ResetFlag = 0b00001000;
+    }
+}
+
+/// A region of the config block changed by a mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Field(DirtyFields),
+    /// Event slot with the given index
+    Event(u8),
+    /// Node variable, indexed from 1
+    Nv(u8),
+}
+
+/// Regions of the config block changed since the last flush.
+struct DirtyRegions<const MAX_EVENTS: usize, const NODE_VAR_COUNT: usize> {
+    fields: DirtyFields,
+    events: [bool; MAX_EVENTS],
+    nvs: [bool; NODE_VAR_COUNT],
+}
+
+impl<const MAX_EVENTS: usize, const NODE_VAR_COUNT: usize> DirtyRegions<MAX_EVENTS, NODE_VAR_COUNT> {
+    const fn clean() -> Self {
+        Self {
+            fields: DirtyFields::empty(),
+            events: [false; MAX_EVENTS],
+            nvs: [false; NODE_VAR_COUNT],
+        }
+    }
+
+    const fn all() -> Self {
+        Self {
+            fields: DirtyFields::all(),
+            events: [true; MAX_EVENTS],
+            nvs: [true; NODE_VAR_COUNT],
+        }
+    }
+
+    fn mark(&mut self, region: Region) {
+        let dirty = match region {
+            Region::Field(fields) => {
+                self.fields.insert(fields);
+                return;
+            }
+            Region::Event(index) => self.events.get_mut(index as usize),
+            Region::Nv(index) => index
+                .checked_sub(1)
+                .and_then(|index| self.nvs.get_mut(index as usize)),
+        };
+        if let Some(dirty) = dirty {
+            *dirty = true;
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        !self.fields.is_empty() || self.events.contains(&true) || self.nvs.contains(&true)
+    }
+}
+
 const UNINITIALISED_VALUE: u8 = 0xff;
-const PERSISTENT_BLOCK_SIZE: u8 = 10;
+/// Size of the sub block persisted over multiple resets, ending with the layout header.
+///
+/// Grown from 10 octets with the layout version 2, the fields before the header kept their
+/// offsets, so the node number of a block of the version 1 survives the update. The events
+/// and NVs moved, they are reset by the update, see [`LoadOutcome::LayoutMismatch`].
+const PERSISTENT_BLOCK_SIZE: u8 = 12;
 const FLAGGED_AS_RESET: u8 = 99;
 const RESET_FLAG_CLEARED: u8 = 0;
+/// Version of the config block layout, bump it whenever the layout changes
+const LAYOUT_VERSION: u8 = 2;
+/// The layout version 1 stored the version alone, without the rest of the header.
+const LEGACY_LAYOUT_VERSION: u8 = 1;
+/// Marks a storage holding a config block, neither of the erased values.
+const LAYOUT_MAGIC: u8 = 0xCB;
+/// The layout version, the magic and the three dimensions of the block.
+const LAYOUT_HEADER_SIZE: usize = 5;
+
+/// Layout of the block found in the storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoredLayout {
+    Blank,
+    Current,
+    Other,
+}
 
+/// Node config stored at the `OFFSET` of a storage driver.
+///
+/// `ERASED` is the value of an erased byte of the storage, `0xFF` for NOR flash and most
+/// EEPROMs, some FRAM parts erase to `0x00`.
 pub struct PersistentNodeConfigStorage<
     D: StorageDriver,
     const OFFSET: usize,
@@ -331,9 +635,10 @@ pub struct PersistentNodeConfigStorage<
     const EVENT_VAR_COUNT: usize,
     const BYTES_PER_EVENT: usize,
     const NODE_VAR_COUNT: usize,
+    const ERASED: u8 = UNINITIALISED_VALUE,
 > {
     driver: Rc<RefCell<D>>,
-    dirty: bool,
+    dirty: DirtyRegions<MAX_EVENTS, NODE_VAR_COUNT>,
     inner: NodeConfigStorage<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT>,
 }
 
@@ -346,23 +651,52 @@ impl<
         const EVENT_VAR_COUNT: usize,
         const BYTES_PER_EVENT: usize,
         const NODE_VAR_COUNT: usize,
-    > PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT>
+        const ERASED: u8,
+    > PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT, ERASED>
 {
+    /// Compile time check of the generic parameters.
+    ///
+    /// Rust doesn't support generic const expressions yet, so the user has to pass
+    /// `BYTES_PER_EVENT` otherwise computed from `EVENT_VAR_COUNT`, see [`bytes_per_event`]
+    /// and [`node_config_storage!`](crate::node_config_storage). The counts are exchanged
+    /// over VLCB as single octets.
+    const SANITY: () = {
+        assert!(
+            BYTES_PER_EVENT == EVENT_VAR_COUNT + EVENT_SIZE,
+            "BYTES_PER_EVENT must be EVENT_SIZE + EVENT_VAR_COUNT, use `bytes_per_event`"
+        );
+        assert!(MAX_EVENTS <= 255, "MAX_EVENTS must fit in an octet");
+        assert!(NODE_VAR_COUNT <= 255, "NODE_VAR_COUNT must fit in an octet");
+        // event variable index 0 is reserved for reading the number of event variables
+        assert!(EVENT_VAR_COUNT <= 254, "EVENT_VAR_COUNT must be at most 254");
+        assert!(
+            Self::layout_dims_addr() + 2 == Self::persistent_sub_block_end()
+                && Self::layout_magic_addr() == Self::layout_version_addr() + 1,
+            "the layout header must end the persistent sub block"
+        );
+    };
+
     pub fn new(driver: Rc<RefCell<D>>) -> Self {
+        let () = Self::SANITY;
         Self {
             driver,
-            dirty: false,
+            dirty: DirtyRegions::clean(),
             inner: NodeConfigStorage::default(),
         }
     }
 
+    /// Use `defaults` as the NV values of a blank storage and after a factory reset.
+    ///
+    /// The defaults are written by [`PersistentStorage::load`] when it finds the storage
+    /// blank, NVs already stored are kept.
+    pub fn with_nv_defaults(mut self, defaults: [u8; NODE_VAR_COUNT]) -> Self {
+        self.inner.nv_defaults = defaults;
+        self
+    }
+
+    /// Checked by [`Self::SANITY`] to be `EVENT_SIZE + EVENT_VAR_COUNT`.
     const fn bytes_per_event() -> usize {
-        // rust doesn't support generic const expressions yet so this is a workaround by having user to pass the value
-        // otherwise calculated in this function. The assert serves as an sanity check.
-        // TODO: fix this as soon as possible and change the API
-        let expected = EVENT_VAR_COUNT + EVENT_SIZE;
-        debug_assert!(BYTES_PER_EVENT == expected, "Generic parameter BYTES_PER_EVENT is different from the expected value (result of EVENT_SIZE + EVENT_VAR_COUNT)");
-        expected
+        BYTES_PER_EVENT
     }
 
     const fn mode_addr() -> usize {
@@ -389,11 +723,43 @@ impl<
         Self::flags_addr() + 1
     }
 
-    /// Ten bytes from the start left for persistence over multiple resets
+    /// Checksum of the fields stored before it, see [`Self::stored_checksum`]
+    const fn checksum_addr() -> usize {
+        Self::reset_flag_addr() + 1
+    }
+
+    /// Start of the layout header, the version of the layout the block was written with,
+    /// see [`LAYOUT_VERSION`]
+    const fn layout_version_addr() -> usize {
+        Self::checksum_addr() + 1
+    }
+
+    /// [`LAYOUT_MAGIC`], present since the layout version 2
+    const fn layout_magic_addr() -> usize {
+        Self::layout_version_addr() + 1
+    }
+
+    /// `MAX_EVENTS`, `EVENT_VAR_COUNT` and `NODE_VAR_COUNT` of the layout
+    const fn layout_dims_addr() -> usize {
+        Self::layout_magic_addr() + 1
+    }
+
+    /// [`PERSISTENT_BLOCK_SIZE`] bytes from the start left for persistence over multiple resets
     const fn persistent_sub_block_end() -> usize {
         OFFSET + PERSISTENT_BLOCK_SIZE as usize - 1
     }
 
+    /// The layout header written with the block.
+    const fn layout_header() -> [u8; LAYOUT_HEADER_SIZE] {
+        [
+            LAYOUT_VERSION,
+            LAYOUT_MAGIC,
+            MAX_EVENTS as u8,
+            EVENT_VAR_COUNT as u8,
+            NODE_VAR_COUNT as u8,
+        ]
+    }
+
     const fn event_addr_start() -> usize {
         Self::persistent_sub_block_end() + 1
     }
@@ -420,22 +786,26 @@ impl<
         // if we are to support other storage types we should
         // add more flexible API support, preferably out of scope
         // of this implementation and into a separate reader abstraction
-        const UNUSED_ENTRY: [u8; EVENT_SIZE] = [UNINITIALISED_VALUE; EVENT_SIZE];
+        let unused_entry = [ERASED; EVENT_SIZE];
 
-        // SAFETY: get block of memory for readout, we don't care about initializing it
-        #[allow(unsafe_code, clippy::uninit_assumed_init)]
-        let mut buf = unsafe {[const { MaybeUninit::<u8>::uninit().assume_init() }; BYTES_PER_EVENT]};
+        let mut buf = [0u8; BYTES_PER_EVENT];
 
         let mut storage = self.driver.borrow_mut();
-        for (index, addr) in (Self::event_addr_start()..=Self::event_addr_end())
+        for (index, addr) in (Self::event_addr_start()..Self::event_addr_end())
             .step_by(Self::bytes_per_event())
             .enumerate()
         {
 
             let _ = storage.read(addr as u32, &mut buf);
             // filter off slots in memory that have no value stored
-            if buf[..EVENT_SIZE] != UNUSED_ENTRY {
-                let event_id = EventId::from_bytes(&buf[..EVENT_SIZE]);
+            if buf[..EVENT_SIZE] == unused_entry {
+                continue;
+            }
+            if let Ok(mut event_id) = EventId::try_from_bytes(&buf[..EVENT_SIZE]) {
+                // the short flag is not stored, short events are the ones without a node number
+                if event_id.node_num() == VlcbNodeNumber::new(0, 0) {
+                    event_id = EventId::short(event_id.device_number());
+                }
                 self.inner.set_event_item(
                     event_id,
                     HeaplessLearnedEvent { index: index as u8, vars: Vec::from_slice(&buf[EVENT_SIZE..]).unwrap()}
@@ -444,105 +814,133 @@ impl<
         }
     }
 
-    /// Checks if the module is in it's first setup
+    /// Reads the layout header to tell the layout of the stored block.
     ///
-    /// This is done by comparing values read in the [`PERSISTENT_BLOCK_SIZE`] from the [`OFFSET`].
-    /// At the moment the method expects all values in the block to have value of `0xFF`
-    fn detect_virgin_storage_state(&mut self) -> bool {
-        let mut storage = self.driver.borrow_mut();
-
-        // SAFETY: get block of memory for readout, we don't care about initializing it
-        #[allow(unsafe_code, clippy::uninit_assumed_init)]
-        let mut buf = unsafe {[const { MaybeUninit::<u8>::uninit().assume_init() }; PERSISTENT_BLOCK_SIZE as usize]};
+    /// A storage without the magic is blank, e.g. in the first setup, unless it holds
+    /// a block of the version 1 which had no magic.
+    fn stored_layout(&mut self) -> StoredLayout {
+        let mut header = [0u8; LAYOUT_HEADER_SIZE];
+        let _ = self.driver.borrow_mut().read(Self::layout_version_addr() as u32, &mut header);
+
+        match header {
+            header if header == Self::layout_header() => StoredLayout::Current,
+            [_, LAYOUT_MAGIC, ..] | [LEGACY_LAYOUT_VERSION, ..] => StoredLayout::Other,
+            _ => StoredLayout::Blank,
+        }
+    }
 
-        // TODO: maybe instead just compare mode and node num ranges?
-        let _ = storage.read(0, &mut buf);
+    /// Compute the checksum of the fields currently stored in the persistent sub-block.
+    fn stored_checksum(storage: &mut D) -> u8 {
+        let mut buf = [0u8; PERSISTENT_BLOCK_SIZE as usize];
+        let fields = &mut buf[..Self::checksum_addr() - Self::mode_addr()];
+        let _ = storage.read(Self::mode_addr() as u32, fields);
+        crc8(fields)
+    }
 
-        buf.iter().all(|v| *v == UNINITIALISED_VALUE)
+    /// Checks the stored fields against their checksum, e.g. to detect a write
+    /// interrupted by a brownout.
+    fn verify_checksum(&mut self) -> bool {
+        let mut storage = self.driver.borrow_mut();
+        let mut buf = [0u8; 1];
+        let _ = storage.read(Self::checksum_addr() as u32, &mut buf);
+        buf[0] == Self::stored_checksum(&mut storage)
     }
 
     /// Reloads node variables from persistent memory
     fn reload_nv(&mut self) {
         let mut storage = self.driver.borrow_mut();
 
-        // SAFETY: get block of memory for readout, we don't care about initializing it
-        #[allow(unsafe_code, clippy::uninit_assumed_init)]
-        let mut buf = unsafe {[const { MaybeUninit::<u8>::uninit().assume_init() }; 1]};
+        let mut buf = [0u8; 1];
 
-        for (index, addr) in (Self::nv_addr_start()..=Self::nv_addr_end()).enumerate() {
+        for (index, addr) in (Self::nv_addr_start()..Self::nv_addr_end()).enumerate() {
             let _ = storage.read(addr as u32, &mut buf);
             self.inner.set_nv((index + 1) as u8, buf[0]).unwrap();
         }
     }
 
+    /// Mark the `region` as changed, so it is written by the next flush.
     #[inline]
-    fn mark_as_dirty(&mut self) -> &mut NodeConfigStorage<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT> {
-        self.dirty = true;
+    fn mark_as_dirty(&mut self, region: Region) -> &mut NodeConfigStorage<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT> {
+        self.dirty.mark(region);
         &mut self.inner
     }
 
-    fn flush_to_storage(&mut self) {
-        let mut storage = self.driver.borrow_mut();
-
-        // the memory block should be as big as the biggest chunk we are going to read
-        // SAFETY: get block of memory for readout, we don't care about initializing it
-        #[allow(unsafe_code, clippy::uninit_assumed_init)]
-        let mut buf = unsafe {[const { MaybeUninit::<u8>::uninit().assume_init() }; { cmax(1, cmax(CANID_SIZE, NODENUM_SIZE)) }]};
+    /// Mark the slot of a restored event, and the slot it was stored in before, which is erased.
+    fn mark_event_moved(&mut self, previous: Option<u8>, index: u8) {
+        if let Some(previous) = previous {
+            self.mark_as_dirty(Region::Event(previous));
+        }
+        self.mark_as_dirty(Region::Event(index));
+    }
 
-        // readout the mode and save if the current mode is different from the stored one
-        let _ = storage.read(Self::mode_addr() as u32, &mut buf[..1]);
-        {
-            let mode = self.inner.mode() as u8;
-            if mode != buf[0] {
-                buf[0] = mode;
-                let _ = storage.write(Self::mode_addr() as u32, &buf[..1]);
-            }
+    /// Write `bytes` at `addr` unless the storage already holds them, saving a write cycle.
+    fn write_changed(storage: &mut D, addr: usize, bytes: &[u8]) {
+        // no region is bigger than an event slot
+        let mut buf = [0u8; BYTES_PER_EVENT];
+        let stored = &mut buf[..bytes.len()];
+        if storage.read(addr as u32, stored).is_ok() && stored == bytes {
+            return;
         }
+        let _ = storage.write(addr as u32, bytes);
+    }
+
+    /// Write the `dirty` regions to the storage.
+    fn flush_to_storage(&mut self, dirty: &DirtyRegions<MAX_EVENTS, NODE_VAR_COUNT>) {
+        let mut storage = self.driver.borrow_mut();
+        let storage = &mut *storage;
+
+        if dirty.fields.contains(DirtyFields::Mode) {
+            Self::write_changed(storage, Self::mode_addr(), &[self.inner.mode() as u8]);
 
-        // if the current mode is NORMAL we can store the current node number if it's different
-        // ignore otherwise as it's considered as trash values and it won't be loaded
-        if self.mode() == ModuleMode::Normal {
-            // read out the stored node number
-            let _ = storage.read(Self::node_num_addr_start() as u32, &mut buf[..NODENUM_SIZE]);
-            let node_num = self.inner.node_number().as_bytes();
-            if buf[..NODENUM_SIZE] != *node_num {
-                buf[..NODENUM_SIZE].copy_from_slice(node_num);
-                let _ = storage.write(Self::node_num_addr_start() as u32, &buf[..NODENUM_SIZE]);
+            // if the current mode is NORMAL we can store the current node number
+            // ignore otherwise as it's considered as trash values and it won't be loaded
+            if self.inner.mode() == ModuleMode::Normal {
+                let node_num = self.inner.node_number().as_bytes();
+                Self::write_changed(storage, Self::node_num_addr_start(), node_num);
             }
         }
 
-        // save the flags if they differ from persisted values
-        let _ = storage.read(Self::flags_addr() as u32, &mut buf[..1]);
-        {
-            let bits = self.inner.flags().bits();
-            if bits != buf[0] {
-                buf[0] = bits;
-                let _ = storage.write(Self::flags_addr() as u32, &buf[..1]);
-            }
+        if dirty.fields.contains(DirtyFields::Flags) {
+            Self::write_changed(storage, Self::flags_addr(), &[self.inner.flags().bits()]);
         }
 
-        // store the can_id
-        let _ = storage.read(Self::can_id_addr() as u32, &mut buf[..CANID_SIZE]);
-        {
-            let can_id = self.inner.can_id().as_bytes();
-            if buf[..CANID_SIZE] != *can_id {
-                buf[..CANID_SIZE].copy_from_slice(can_id);
-                let _ = storage.write(Self::can_id_addr() as u32, &buf);
-            }
+        if dirty.fields.contains(DirtyFields::CanId) {
+            Self::write_changed(storage, Self::can_id_addr(), self.inner.can_id().as_bytes());
         }
 
-        // save the reset flag
-        let _ = storage.read(Self::reset_flag_addr() as u32, &mut buf[..1]);
-        {
+        if dirty.fields.contains(DirtyFields::ResetFlag) {
             let flag = match self.inner.was_reset() {
                 true => FLAGGED_AS_RESET,
                 false => RESET_FLAG_CLEARED,
             };
-            if buf[0] != flag {
-                buf[0] = flag;
-                let _ = storage.write(Self::reset_flag_addr() as u32, &buf[..1]);
+            Self::write_changed(storage, Self::reset_flag_addr(), &[flag]);
+        }
+
+        if !dirty.fields.is_empty() {
+            let checksum = Self::stored_checksum(storage);
+            Self::write_changed(storage, Self::checksum_addr(), &[checksum]);
+        }
+
+        for (index, _) in dirty.events.iter().enumerate().filter(|(_, dirty)| **dirty) {
+            // slots without an event are erased
+            let mut buf = [ERASED; BYTES_PER_EVENT];
+            if let Some((event_id, event)) = self.inner.get_event_by_index(index as u8) {
+                buf[..EVENT_SIZE].copy_from_slice(event_id.as_bytes());
+                buf[EVENT_SIZE..].fill(0);
+                buf[EVENT_SIZE..EVENT_SIZE + event.vars.len()].copy_from_slice(&event.vars);
             }
+            let addr = Self::event_addr_start() + index * Self::bytes_per_event();
+            Self::write_changed(storage, addr, &buf);
+        }
+
+        for (index, _) in dirty.nvs.iter().enumerate().filter(|(_, dirty)| **dirty) {
+            let addr = Self::nv_addr_start() + index;
+            Self::write_changed(storage, addr, &[self.inner.nvs[index]]);
         }
+
+        // written last, a flush rewriting another layout and interrupted midway is
+        // detected again by the next load
+        Self::write_changed(storage, Self::layout_version_addr(), &Self::layout_header());
     }
 }
 
@@ -553,23 +951,41 @@ impl<
         const EVENT_VAR_COUNT: usize,
         const BYTES_PER_EVENT: usize,
         const NODE_VAR_COUNT: usize,
-    > PersistentStorage for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT>
+        const ERASED: u8,
+    > PersistentStorage for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT, ERASED>
 {
-    #[allow(clippy::must_use_unit)]
-    #[must_use]
-    fn load(&mut self) {
+    fn load(&mut self) -> LoadOutcome {
+        let layout = self.stored_layout();
+        let mut outcome = LoadOutcome::Restored;
         {
-            if  self.detect_virgin_storage_state() {
+            if layout == StoredLayout::Blank {
+                self.inner.nvs = self.inner.nv_defaults;
                 self.clear_reset_flag();
                 self.force_flush();
+                outcome = LoadOutcome::Blank;
+            }
+
+            // a corrupted block falls back to the defaults instead of loading garbage
+            if !self.verify_checksum() {
+                self.inner.set_mode_uninitialized();
+                self.inner.set_can_id(VlcbCanId::default());
+                self.inner.set_flags(NodeFlags::empty());
+                self.inner.raise_reset_flag();
+                // a block of another layout is rewritten as a whole, with the header last
+                if layout != StoredLayout::Other {
+                    let fields = DirtyRegions {
+                        fields: DirtyFields::all(),
+                        ..DirtyRegions::clean()
+                    };
+                    self.flush_to_storage(&fields);
+                }
+                outcome = LoadOutcome::Corrupted;
             }
 
             let mut storage = self.driver.borrow_mut();
 
             // the memory block should be as big as the biggest chunk we are going to read
-            // SAFETY: get block of memory for readout, we don't care about initializing it
-            #[allow(unsafe_code, clippy::uninit_assumed_init)]
-            let mut buf = unsafe {[const { MaybeUninit::<u8>::uninit().assume_init() }; { cmax(1, cmax(CANID_SIZE, NODENUM_SIZE)) }]};
+            let mut buf = [0u8; { cmax(1, cmax(CANID_SIZE, NODENUM_SIZE)) }];
 
             // readout the mode and initialize the mode based on it's current status
             let _ = storage.read(Self::mode_addr() as u32, &mut buf[..1]);
@@ -577,7 +993,10 @@ impl<
                 ModuleMode::Normal => {
                     // read out the stored node number
                     let _ = storage.read(Self::node_num_addr_start() as u32, &mut buf[..NODENUM_SIZE]);
-                    self.inner.set_mode_normal(VlcbNodeNumber::from_bytes(&buf[..NODENUM_SIZE]))
+                    match VlcbNodeNumber::try_from_bytes(&buf[..NODENUM_SIZE]) {
+                        Ok(node_num) => self.inner.set_mode_normal(node_num),
+                        Err(_) => self.inner.set_mode_uninitialized(),
+                    }
                 },
                 _ => self.inner.set_mode_uninitialized(),// other modes are unsupported here
             }
@@ -588,7 +1007,7 @@ impl<
 
             // read out the stored can_id
             let _ = storage.read(Self::can_id_addr() as u32, &mut buf[..CANID_SIZE]);
-            self.inner.set_can_id(VlcbCanId::from_bytes(&buf[..CANID_SIZE]));
+            self.inner.set_can_id(VlcbCanId::try_from_bytes(&buf[..CANID_SIZE]).unwrap_or_default());
 
             // read out the reset flag position and check if it has been set
             let _ = storage.read(Self::reset_flag_addr() as u32, &mut buf[..1]);
@@ -597,26 +1016,38 @@ impl<
             }
         }
 
+        // the node state keeps its offsets in every layout, the events and NVs of another
+        // layout would be misread
+        if layout == StoredLayout::Other {
+            self.inner.clear_all_events();
+            self.inner.nvs = self.inner.nv_defaults;
+            self.dirty = DirtyRegions::all();
+            return LoadOutcome::LayoutMismatch;
+        }
+
         self.reload_event_hash_table();
         self.reload_nv();
+        outcome
     }
 
     fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty.is_dirty()
     }
 
+    /// Write the regions changed since the last flush.
     fn flush(&mut self) {
-        if ! self.dirty {
+        if !self.dirty.is_dirty() {
             return
         }
 
-        self.flush_to_storage();
-
-        self.dirty = false
+        let dirty = core::mem::replace(&mut self.dirty, DirtyRegions::clean());
+        self.flush_to_storage(&dirty);
     }
 
+    /// Write the whole config block.
     fn force_flush(&mut self) {
-        self.flush_to_storage();
+        self.dirty = DirtyRegions::clean();
+        self.flush_to_storage(&DirtyRegions::all());
     }
 }
 
@@ -627,7 +1058,8 @@ impl<
         const EVENT_VAR_COUNT: usize,
         const BYTES_PER_EVENT: usize,
         const NODE_VAR_COUNT: usize,
-    > NodeConfig for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT>
+        const ERASED: u8,
+    > NodeConfig for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT, ERASED>
 {
     type Event = HeaplessLearnedEvent<EVENT_VAR_COUNT>;
     const MAX_EVENTS: u8 = MAX_EVENTS as u8;
@@ -638,8 +1070,12 @@ impl<
         to self.inner {
             fn stored_event_count(&self) -> u8;
             fn has_event_with_index(&self, index: u8) -> bool;
+            fn event_index(&self, evt: &EventId) -> Option<u8>;
+            fn get_event_by_index(&self, index: u8) -> Option<(&EventId, &Self::Event)>;
             fn get_event(&self, evt: &EventId) -> Option<&Self::Event>;
             fn has_event(&self, evt: &EventId) -> bool;
+            fn get_ev(&self, evt: &EventId, ev_index: u8) -> Result<u8, Error>;
+            fn events(&self) -> impl Iterator<Item = (&EventId, &Self::Event)>;
             fn get_nv(&self, index: u8) -> Result<u8, Error>;
             fn can_id(&self) -> &VlcbCanId;
             fn mode(&self) -> ModuleMode;
@@ -649,23 +1085,86 @@ impl<
             fn is_event_ack_on(&self) -> bool;
             fn flags(&self) -> NodeFlags;
         }
-        // Mutations should mark this implementation as dirty so it can be flushed to storage
-        to self.mark_as_dirty() {
-            fn save_event(&mut self, evt: &EventId, evs: &[u8]) -> Result<(), Error>;
-            fn restore_event(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error>;
-            fn restore_event_unchecked(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error>;
-            fn delete_event(&mut self, evt: &EventId);
-            fn set_nv(&mut self, index: u8, value: u8) -> Result<(), Error>;
-            fn set_can_id(&mut self, can_id: VlcbCanId);
+        // Mutations should mark the changed region as dirty so it can be flushed to storage
+        to self.mark_as_dirty(Region::Field(DirtyFields::Mode)) {
             fn set_mode_normal(&mut self, node_num: VlcbNodeNumber);
             fn set_mode_uninitialized(&mut self);
             fn set_node_number(&mut self, node_num: VlcbNodeNumber);
-            fn raise_reset_flag(&mut self);
-            fn clear_reset_flag(&mut self);
+        }
+        to self.mark_as_dirty(Region::Field(DirtyFields::Flags)) {
             fn set_heartbeat(&mut self, state: bool);
             fn set_event_ack(&mut self, state: bool);
             fn set_flags(&mut self, flags: NodeFlags);
         }
+        to self.mark_as_dirty(Region::Field(DirtyFields::CanId)) {
+            fn set_can_id(&mut self, can_id: VlcbCanId);
+        }
+        to self.mark_as_dirty(Region::Field(DirtyFields::ResetFlag)) {
+            fn raise_reset_flag(&mut self);
+            fn clear_reset_flag(&mut self);
+        }
+    }
+
+    fn save_event(&mut self, evt: &EventId, evs: &[u8]) -> Result<(), Error> {
+        self.inner.save_event(evt, evs)?;
+        if let Some(index) = self.inner.event_index(evt) {
+            self.mark_as_dirty(Region::Event(index));
+        }
+        Ok(())
+    }
+
+    fn restore_event(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error> {
+        let previous = self.inner.event_index(&evt);
+        let index = data.index;
+        self.inner.restore_event(evt, data)?;
+        self.mark_event_moved(previous, index);
+        Ok(())
+    }
+
+    fn restore_event_unchecked(&mut self, evt: EventId, data: Self::Event) -> Result<(), Error> {
+        let previous = self.inner.event_index(&evt);
+        let index = data.index;
+        self.inner.restore_event_unchecked(evt, data)?;
+        self.mark_event_moved(previous, index);
+        Ok(())
+    }
+
+    fn delete_event(&mut self, evt: &EventId) {
+        if let Some(index) = self.inner.event_index(evt) {
+            self.mark_as_dirty(Region::Event(index)).delete_event(evt);
+        }
+    }
+
+    fn set_ev(&mut self, evt: &EventId, ev_index: u8, value: u8) -> Result<(), Error> {
+        self.inner.set_ev(evt, ev_index, value)?;
+        if let Some(index) = self.inner.event_index(evt) {
+            self.mark_as_dirty(Region::Event(index));
+        }
+        Ok(())
+    }
+
+    fn set_nv(&mut self, index: u8, value: u8) -> Result<(), Error> {
+        self.inner.set_nv(index, value)?;
+        self.mark_as_dirty(Region::Nv(index));
+        Ok(())
+    }
+
+    fn reset_to_factory(&mut self) {
+        self.inner.reset_to_factory();
+        self.dirty.events = [true; MAX_EVENTS];
+        self.dirty.nvs = [true; NODE_VAR_COUNT];
+    }
+
+    fn clear_all_events(&mut self) {
+        self.inner.clear_all_events();
+        // the freed slots are erased by the next flush
+        self.dirty.events = [true; MAX_EVENTS];
+    }
+
+    fn apply_nv_defaults(&mut self, defaults: &[u8]) -> Result<(), Error> {
+        self.inner.apply_nv_defaults(defaults)?;
+        self.dirty.nvs = [true; NODE_VAR_COUNT];
+        Ok(())
     }
 }
 
@@ -676,11 +1175,539 @@ impl<
         const EVENT_VAR_COUNT: usize,
         const BYTES_PER_EVENT: usize,
         const NODE_VAR_COUNT: usize,
-    > Storage for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT>
+        const ERASED: u8,
+    > Storage for PersistentNodeConfigStorage<D, OFFSET, MAX_EVENTS, EVENT_VAR_COUNT, BYTES_PER_EVENT, NODE_VAR_COUNT, ERASED>
 {
     fn wipe(&mut self) {
         self.inner.wipe();
-        self.dirty = true;
+        self.dirty = DirtyRegions::all();
         self.flush();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use embedded_storage::ReadStorage;
+
+    use super::*;
+
+    /// RAM backed storage driver counting the write operations.
+    struct CountingStorage {
+        data: [u8; 64],
+        writes: usize,
+    }
+
+    impl ReadStorage for CountingStorage {
+        type Error = ();
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(self.data.get(offset..offset + bytes.len()).ok_or(())?);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.data.len()
+        }
+    }
+
+    impl StorageDriver for CountingStorage {
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            self.data.get_mut(offset..offset + bytes.len()).ok_or(())?.copy_from_slice(bytes);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    type TestConfig = crate::node_config_storage!(CountingStorage, 0, 4, 2, 4);
+
+    const EVENT: EventId = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+
+    /// Return a loaded config and its storage, with the write counter reset.
+    fn config() -> (TestConfig, Rc<RefCell<CountingStorage>>) {
+        let driver = Rc::new(RefCell::new(CountingStorage {
+            data: [UNINITIALISED_VALUE; 64],
+            writes: 0,
+        }));
+        let mut config = TestConfig::new(driver.clone());
+        let _ = config.load();
+        driver.borrow_mut().writes = 0;
+        (config, driver)
+    }
+
+    #[test]
+    fn test_flush_single_nv() {
+        let (mut config, driver) = config();
+
+        config.set_nv(2, 0x10).unwrap();
+        assert!(config.is_dirty());
+        config.flush();
+        assert!(!config.is_dirty());
+        assert_eq!(driver.borrow().writes, 1);
+
+        // unchanged value is not written again
+        config.set_nv(2, 0x10).unwrap();
+        config.flush();
+        assert_eq!(driver.borrow().writes, 1);
+    }
+
+    #[test]
+    fn test_flush_single_event() {
+        let (mut config, driver) = config();
+
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.flush();
+        assert_eq!(driver.borrow().writes, 1, "the node variables are not rewritten");
+
+        config.delete_event(&EVENT);
+        config.flush();
+        assert_eq!(driver.borrow().writes, 2);
+    }
+
+    #[test]
+    fn test_flush_reload() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.set_can_id(VlcbCanId::from_bytes(&[0x05]));
+        config.set_nv(1, 0x10).unwrap();
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.flush();
+
+        let mut reloaded = TestConfig::new(driver);
+        assert_eq!(reloaded.load(), LoadOutcome::Restored);
+        assert_eq!(reloaded.mode(), ModuleMode::Normal);
+        assert_eq!(reloaded.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+        assert_eq!(reloaded.can_id(), &VlcbCanId::from_bytes(&[0x05]));
+        assert_eq!(reloaded.get_nv(1), Ok(0x10));
+        assert_eq!(reloaded.get_event(&EVENT).unwrap().vars(), &[0x05, 0x06]);
+        assert_eq!(reloaded.stored_event_count(), 1);
+    }
+
+    #[test]
+    fn test_nvs_indexed_from_one() {
+        let (mut config, driver) = config();
+        for index in 1..=4 {
+            config.set_nv(index, 0x10 + index).unwrap();
+        }
+        assert_eq!(config.set_nv(0, 0x10), Err(Error::OutOfRange));
+        assert_eq!(config.set_nv(5, 0x10), Err(Error::OutOfRange));
+        assert_eq!(config.get_nv(0), Err(Error::OutOfRange));
+        config.flush();
+
+        // exactly NODE_VAR_COUNT NVs are reloaded, the last one included
+        let mut reloaded = TestConfig::new(driver);
+        assert_eq!(reloaded.load(), LoadOutcome::Restored);
+        for index in 1..=4 {
+            assert_eq!(reloaded.get_nv(index), Ok(0x10 + index));
+        }
+        assert_eq!(reloaded.get_nv(5), Err(Error::OutOfRange));
+    }
+
+    #[test]
+    fn test_zero_erased_storage() {
+        type ZeroErasedConfig = crate::node_config_storage!(CountingStorage, 0, 4, 2, 4, 0x00);
+
+        let driver = Rc::new(RefCell::new(CountingStorage {
+            data: [0x00; 64],
+            writes: 0,
+        }));
+        let mut config = ZeroErasedConfig::new(driver.clone());
+        assert_eq!(config.load(), LoadOutcome::Blank);
+        assert!(driver.borrow().writes > 0, "the erased storage is initialised");
+        assert_eq!(config.stored_event_count(), 0);
+
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.flush();
+        config.delete_event(&EVENT);
+        config.flush();
+        let slot = ZeroErasedConfig::event_addr_start()..ZeroErasedConfig::event_addr_start() + EVENT_SIZE + 2;
+        assert!(driver.borrow().data[slot].iter().all(|v| *v == 0x00), "the deleted slot is erased");
+
+        let mut reloaded = ZeroErasedConfig::new(driver);
+        let _ = reloaded.load();
+        assert_eq!(reloaded.stored_event_count(), 0);
+        assert!(!reloaded.has_event(&EVENT));
+    }
+
+    #[test]
+    fn test_reset_to_factory() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.set_can_id(VlcbCanId::from_bytes(&[0x05]));
+        config.set_nv(1, 0x10).unwrap();
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.flush();
+
+        config.reset_to_factory();
+        config.flush();
+
+        let mut reloaded = TestConfig::new(driver);
+        let _ = reloaded.load();
+        assert_eq!(reloaded.mode(), ModuleMode::Normal);
+        assert_eq!(reloaded.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+        assert_eq!(reloaded.can_id(), &VlcbCanId::from_bytes(&[0x05]));
+        assert_eq!(reloaded.get_nv(1), Ok(UNINITIALISED_VALUE));
+        assert!(!reloaded.has_event(&EVENT));
+        assert_eq!(reloaded.stored_event_count(), 0);
+    }
+
+    #[test]
+    fn test_nv_defaults_on_first_boot() {
+        let driver = Rc::new(RefCell::new(CountingStorage {
+            data: [UNINITIALISED_VALUE; 64],
+            writes: 0,
+        }));
+        let mut config = TestConfig::new(driver.clone()).with_nv_defaults([1, 2, 3, 4]);
+        let _ = config.load();
+        assert_eq!(config.get_nv(3), Ok(3));
+
+        // the defaults are stored, and do not overwrite the changed NVs on the next boot
+        config.set_nv(3, 0x10).unwrap();
+        config.flush();
+        let mut reloaded = TestConfig::new(driver).with_nv_defaults([1, 2, 3, 4]);
+        let _ = reloaded.load();
+        assert_eq!(reloaded.get_nv(1), Ok(1));
+        assert_eq!(reloaded.get_nv(3), Ok(0x10));
+
+        reloaded.reset_to_factory();
+        assert_eq!(reloaded.get_nv(3), Ok(3));
+    }
+
+    #[test]
+    fn test_apply_nv_defaults() {
+        let (mut config, _) = config();
+        assert_eq!(config.apply_nv_defaults(&[1, 2]), Err(Error::OutOfRange));
+        config.apply_nv_defaults(&[1, 2, 3, 4]).unwrap();
+        assert!(config.is_dirty());
+        assert_eq!(config.get_nv(4), Ok(4));
+
+        config.set_nv(4, 0x10).unwrap();
+        config.reset_to_factory();
+        assert_eq!(config.get_nv(4), Ok(4));
+    }
+
+    #[test]
+    fn test_corrupted_block_falls_back_to_defaults() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.set_can_id(VlcbCanId::from_bytes(&[0x05]));
+        config.set_nv(1, 0x10).unwrap();
+        config.flush();
+
+        // half of the node number got written
+        driver.borrow_mut().data[TestConfig::node_num_addr_end()] = 0x07;
+
+        let mut reloaded = TestConfig::new(driver.clone());
+        assert_eq!(reloaded.load(), LoadOutcome::Corrupted);
+        assert_eq!(reloaded.mode(), ModuleMode::Uninitialized);
+        assert_eq!(reloaded.can_id(), &VlcbCanId::default());
+        assert!(reloaded.was_reset());
+        assert_eq!(reloaded.get_nv(1), Ok(0x10), "the node variables are kept");
+
+        // the fallback is stored with a valid checksum
+        let mut reloaded = TestConfig::new(driver);
+        let _ = reloaded.load();
+        assert_eq!(reloaded.mode(), ModuleMode::Uninitialized);
+        assert!(reloaded.was_reset());
+    }
+
+    #[test]
+    fn test_older_layout_version_keeps_node_number() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.set_nv(1, 0x10).unwrap();
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.flush();
+
+        // the block was written by an older firmware
+        driver.borrow_mut().data[TestConfig::layout_version_addr()] = LAYOUT_VERSION - 1;
+
+        let mut reloaded = TestConfig::new(driver.clone());
+        assert_eq!(reloaded.load(), LoadOutcome::LayoutMismatch);
+        assert_eq!(reloaded.mode(), ModuleMode::Normal);
+        assert_eq!(reloaded.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+        assert_eq!(reloaded.get_nv(1), Ok(UNINITIALISED_VALUE));
+        assert_eq!(reloaded.stored_event_count(), 0);
+        assert!(reloaded.is_dirty(), "the new layout is written by the next flush");
+        reloaded.flush();
+        assert_eq!(driver.borrow().data[TestConfig::layout_version_addr()], LAYOUT_VERSION);
+
+        let mut reloaded = TestConfig::new(driver);
+        assert_eq!(reloaded.load(), LoadOutcome::Restored);
+        assert_eq!(reloaded.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+        assert!(!reloaded.has_event(&EVENT), "the dropped events stay erased");
+    }
+
+    #[test]
+    fn test_short_event_restored() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.save_event(&EventId::short(3), &[0x05, 0x06]).unwrap();
+        config.save_event(&EVENT, &[0x07, 0x08]).unwrap();
+        config.flush();
+
+        let mut reloaded = TestConfig::new(driver);
+        assert_eq!(reloaded.load(), LoadOutcome::Restored);
+        assert_eq!(
+            reloaded.get_event(&EventId::short(3)).map(|e| e.vars()),
+            Some(&[0x05, 0x06][..])
+        );
+        assert!(!reloaded.has_event(&EventId::long(VlcbNodeNumber::new(0, 0), 3)));
+        assert_eq!(reloaded.get_event(&EVENT).map(|e| e.vars()), Some(&[0x07, 0x08][..]));
+    }
+
+    #[test]
+    fn test_legacy_layout_without_magic() {
+        let (mut config, driver) = config();
+        config.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        config.flush();
+
+        // the version 1 had the version byte alone, the rest of the header was never written
+        let header = TestConfig::layout_version_addr();
+        let mut data = driver.borrow_mut();
+        data.data[header] = LEGACY_LAYOUT_VERSION;
+        data.data[header + 1..header + LAYOUT_HEADER_SIZE].fill(UNINITIALISED_VALUE);
+        drop(data);
+
+        let mut reloaded = TestConfig::new(driver);
+        assert_eq!(reloaded.load(), LoadOutcome::LayoutMismatch);
+        assert_eq!(reloaded.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+    }
+
+    #[test]
+    fn test_layout_mismatch_drops_events() {
+        type OldConfig = crate::node_config_storage!(CountingStorage, 0, 2, 3, 2);
+
+        let driver = Rc::new(RefCell::new(CountingStorage {
+            data: [UNINITIALISED_VALUE; 64],
+            writes: 0,
+        }));
+        let mut old = OldConfig::new(driver.clone());
+        assert_eq!(old.load(), LoadOutcome::Blank);
+        old.set_mode_normal(VlcbNodeNumber::new(0x01, 0x02));
+        old.set_can_id(VlcbCanId::from_bytes(&[0x05]));
+        old.set_nv(2, 0x10).unwrap();
+        old.save_event(&EVENT, &[0x05, 0x06, 0x07]).unwrap();
+        old.save_event(&EventId::short(3), &[0x01, 0x02, 0x03]).unwrap();
+        old.flush();
+
+        // the firmware update changed the number of events and variables
+        let mut config = TestConfig::new(driver.clone()).with_nv_defaults([1, 2, 3, 4]);
+        assert_eq!(config.load(), LoadOutcome::LayoutMismatch);
+        assert_eq!(config.mode(), ModuleMode::Normal);
+        assert_eq!(config.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+        assert_eq!(config.can_id(), &VlcbCanId::from_bytes(&[0x05]));
+        assert_eq!(config.stored_event_count(), 0);
+        assert_eq!(config.get_nv(2), Ok(2), "the NVs fall back to the defaults");
+        config.flush();
+
+        let mut reloaded = TestConfig::new(driver.clone());
+        assert_eq!(reloaded.load(), LoadOutcome::Restored);
+        assert_eq!(reloaded.stored_event_count(), 0);
+        assert_eq!(reloaded.get_nv(2), Ok(2));
+
+        // and the other way round
+        let mut old = OldConfig::new(driver);
+        assert_eq!(old.load(), LoadOutcome::LayoutMismatch);
+        assert_eq!(old.node_number(), &VlcbNodeNumber::new(0x01, 0x02));
+    }
+
+    #[test]
+    fn test_event_index_allocation() {
+        const TAUGHT: u16 = 200;
+        // the capacity of the event map has to be a power of two
+        let mut config = NodeConfigStorage::<256, 1, 1>::default();
+        let event = |n: u16| EventId::long(VlcbNodeNumber::new(0x01, 0x02), n);
+
+        for n in 0..TAUGHT {
+            config.save_event(&event(n), &[0x01]).unwrap();
+            assert_eq!(config.event_index(&event(n)), Some(n as u8));
+        }
+        // every allocation scans at most the words covering the taught events
+        assert!(config.slots.scanned <= TAUGHT as usize * (TAUGHT as usize).div_ceil(32));
+
+        for n in (0..TAUGHT).step_by(3) {
+            config.delete_event(&event(n));
+            assert!(!config.has_event_with_index(n as u8), "the index is freed");
+        }
+        for n in (0..TAUGHT).filter(|n| n % 3 != 0) {
+            assert_eq!(config.event_index(&event(n)), Some(n as u8), "other indexes are kept");
+        }
+
+        // re-teaching takes the lowest free indexes
+        for (i, n) in (TAUGHT..TAUGHT + 10).enumerate() {
+            config.save_event(&event(n), &[0x01]).unwrap();
+            assert_eq!(config.event_index(&event(n)), Some(i as u8 * 3));
+        }
+    }
+
+    #[test]
+    fn test_event_slot_reuse() {
+        let mut config = NodeConfigStorage::<128, 1, 1>::default();
+        let event = |n: u16| EventId::long(VlcbNodeNumber::new(0x01, 0x02), n);
+        for n in 0..128 {
+            config.save_event(&event(n), &[0x01]).unwrap();
+        }
+        assert_eq!(config.save_event(&event(128), &[0x01]), Err(Error::Exhausted));
+
+        // relearning events one by one in a full table, a nested scan would be quadratic
+        for n in 128..1128 {
+            let freed = (n * 7 % 128) as u8;
+            let (&stored, _) = config.get_event_by_index(freed).unwrap();
+            config.delete_event(&stored);
+
+            config.slots.scanned = 0;
+            config.save_event(&event(n), &[0x01]).unwrap();
+            assert_eq!(config.event_index(&event(n)), Some(freed));
+            assert!(config.slots.scanned <= 4, "at most the words of 128 slots are scanned");
+        }
+        assert_eq!(config.stored_event_count(), 128);
+    }
+
+    #[test]
+    fn test_clear_all_events() {
+        let (mut config, driver) = config();
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        config.save_event(&EventId::short(0x0102), &[0x07, 0x08]).unwrap();
+        config.flush();
+
+        config.clear_all_events();
+        assert_eq!(config.stored_event_count(), 0);
+        assert!(!config.has_event_with_index(0));
+        config.flush();
+        assert!(
+            driver.borrow().data[TestConfig::event_addr_start()..TestConfig::event_addr_end()]
+                .iter()
+                .all(|v| *v == UNINITIALISED_VALUE),
+            "the freed slots are erased"
+        );
+
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        assert_eq!(config.event_index(&EVENT), Some(0));
+    }
+
+    #[test]
+    fn test_crc8() {
+        assert_eq!(crc8(&[]), 0x00);
+        assert_eq!(crc8(b"123456789"), 0xF4);
+    }
+
+    #[test]
+    fn test_force_flush_writes_everything() {
+        let (mut config, driver) = config();
+        config.set_nv(1, 0x10).unwrap();
+        config.flush();
+        driver.borrow_mut().data = [0xAA; 64];
+        driver.borrow_mut().writes = 0;
+
+        config.force_flush();
+        // mode, flags, CAN ID, reset flag, checksum, 4 event slots, 4 node variables and
+        // the layout header
+        assert_eq!(driver.borrow().writes, 14);
+        assert!(!config.is_dirty());
+    }
+
+    #[test]
+    fn test_events() {
+        let (mut config, _) = config();
+        let events = [
+            EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1),
+            EventId::long(VlcbNodeNumber::new(0x01, 0x02), 2),
+            EventId::short(3),
+        ];
+        for (evt, ev) in events.iter().zip(1..) {
+            config.save_event(evt, &[ev, 0x00]).unwrap();
+        }
+        config.delete_event(&events[1]);
+        config.save_event(&events[1], &[0x02, 0x00]).unwrap();
+
+        let mut learned: std::vec::Vec<_> = config
+            .events()
+            .map(|(evt, e)| (e.index(), *evt, e.vars()[0]))
+            .collect();
+        learned.sort_by_key(|(index, ..)| *index);
+        assert_eq!(
+            learned,
+            [(0, events[0], 1), (1, events[1], 2), (2, events[2], 3)]
+        );
+    }
+
+    #[test]
+    fn test_get_event_by_index() {
+        let (mut config, _) = config();
+        let other = EventId::short(3);
+        config.save_event(&other, &[0x01, 0x00]).unwrap();
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+
+        let index = config.get_event(&EVENT).unwrap().index();
+        assert_eq!(index, 1);
+        let (evt, event) = config.get_event_by_index(index).unwrap();
+        assert_eq!(evt, &EVENT);
+        assert_eq!(event.vars(), &[0x05, 0x06]);
+        assert!(config.get_event_by_index(2).is_none());
+    }
+
+    #[test]
+    fn test_set_ev() {
+        let (mut config, driver) = config();
+
+        config.set_ev(&EVENT, 2, 0x42).unwrap();
+        assert_eq!(config.get_ev(&EVENT, 1), Ok(0x00));
+        assert_eq!(config.get_ev(&EVENT, 2), Ok(0x42));
+        assert_eq!(config.stored_event_count(), 1);
+
+        config.flush();
+        let mut reloaded = TestConfig::new(driver);
+        let _ = reloaded.load();
+        assert_eq!(reloaded.get_event(&EVENT).unwrap().vars(), &[0x00, 0x42]);
+    }
+
+    #[test]
+    fn test_ev_out_of_range() {
+        let (mut config, _) = config();
+
+        assert_eq!(config.set_ev(&EVENT, 0, 0x01), Err(Error::OutOfRange));
+        assert_eq!(config.set_ev(&EVENT, 3, 0x01), Err(Error::OutOfRange));
+        assert!(!config.has_event(&EVENT), "no event is created for an invalid EV");
+
+        assert_eq!(config.get_ev(&EVENT, 1), Err(Error::NotFound));
+        config.save_event(&EVENT, &[0x05, 0x06]).unwrap();
+        assert_eq!(config.get_ev(&EVENT, 3), Err(Error::OutOfRange));
+    }
+
+    #[test]
+    fn test_save_event_vars_len() {
+        let (mut config, _) = config();
+
+        assert_eq!(config.save_event(&EVENT, &[0x01, 0x02, 0x03]), Err(Error::OutOfRange));
+        assert_eq!(config.save_event(&EVENT, &[0x01]), Err(Error::OutOfRange));
+        assert!(!config.has_event(&EVENT));
+
+        config.save_event(&EVENT, &[0x01, 0x02]).unwrap();
+        assert_eq!(config.save_event(&EVENT, &[0x03]), Err(Error::OutOfRange));
+        assert_eq!(config.get_event(&EVENT).unwrap().vars(), &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_command_error() {
+        assert_eq!(CommandError::from(Error::Exhausted), CommandError::TooManyEvents);
+        assert_eq!(CommandError::from(Error::OutOfRange), CommandError::InvalidNvIndex);
+        assert_eq!(CommandError::from(Error::OccupiedEntry), CommandError::InvalidEventIndex);
+        assert_eq!(CommandError::from(Error::NotFound), CommandError::InvalidEvent);
+    }
+
+    #[test]
+    fn test_nv_error_as_command_error() {
+        let mut storage = NodeConfigStorage::<2, 1, 2>::default();
+        assert_eq!(storage.set_nv(2, 0x10), Ok(()));
+        assert_eq!(
+            storage.set_nv(3, 0x10).map_err(CommandError::from),
+            Err(CommandError::InvalidNvIndex)
+        );
+        assert_eq!(
+            storage.get_nv(0).map_err(CommandError::from),
+            Err(CommandError::InvalidNvIndex)
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Owned copy of a whole node configuration, e.g. for a backup on a PC.
+
+use heapless::Vec;
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::module::NodeFlags;
+use vlcb_core::vlcb::{EventId, VlcbNodeNumber};
+use vlcb_defs::ModuleMode;
+
+use crate::node_config::{Error, LearnedEvent, NodeConfig};
+
+/// A learned event of a [`NodeConfigSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEvent<const EVENT_VAR_COUNT: usize> {
+    pub event: EventId,
+    /// The event slot index, kept so the configuration tool sees the same indexes.
+    pub index: u8,
+    pub vars: Vec<u8, EVENT_VAR_COUNT>,
+}
+
+/// A copy of the node configuration detached from its storage.
+///
+/// Taken from a config with [`From`], it can be exported (with the `serde` feature),
+/// edited and written back with [`apply_to`](NodeConfigSnapshot::apply_to). The events
+/// are ordered by their slot index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeConfigSnapshot<
+    const MAX_EVENTS: usize,
+    const EVENT_VAR_COUNT: usize,
+    const NODE_VAR_COUNT: usize,
+> {
+    /// The node number in normal mode, `None` for an uninitialized node.
+    pub node_number: Option<VlcbNodeNumber>,
+    pub can_id: VlcbCanId,
+    pub flags: NodeFlags,
+    pub events: Vec<SnapshotEvent<EVENT_VAR_COUNT>, MAX_EVENTS>,
+    /// NVs starting with the NV 1.
+    pub node_vars: Vec<u8, NODE_VAR_COUNT>,
+}
+
+impl<const MAX_EVENTS: usize, const EVENT_VAR_COUNT: usize, const NODE_VAR_COUNT: usize>
+    NodeConfigSnapshot<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT>
+{
+    /// Write the snapshot into the `config`, replacing its learned events.
+    ///
+    /// Returns [`Error::OutOfRange`] and leaves the `config` untouched unless the snapshot
+    /// has one value for every NV and EV of the `config`, and every event index is within
+    /// its event slots. The caller is responsible for flushing the `config`.
+    pub fn apply_to<S: NodeConfig>(&self, config: &mut S) -> Result<(), Error> {
+        let fits = self.node_vars.len() == S::NODE_VAR_COUNT as usize
+            && self.events.iter().all(|e| {
+                e.vars.len() == S::EVENT_VAR_COUNT as usize && e.index < S::MAX_EVENTS
+            });
+        if !fits {
+            return Err(Error::OutOfRange);
+        }
+
+        match self.node_number {
+            Some(node_num) => config.set_mode_normal(node_num),
+            None => config.set_mode_uninitialized(),
+        }
+        config.set_can_id(self.can_id);
+        config.set_flags(self.flags);
+        for (index, &value) in (1..).zip(self.node_vars.iter()) {
+            config.set_nv(index, value)?;
+        }
+
+        config.clear_all_events();
+        for e in &self.events {
+            config.restore_event(e.event, S::Event::new(e.index, &e.vars))?;
+        }
+        Ok(())
+    }
+}
+
+/// Take a snapshot of the config.
+///
+/// Events and NVs past the capacity of the snapshot are left out.
+impl<S, const MAX_EVENTS: usize, const EVENT_VAR_COUNT: usize, const NODE_VAR_COUNT: usize>
+    From<&S> for NodeConfigSnapshot<MAX_EVENTS, EVENT_VAR_COUNT, NODE_VAR_COUNT>
+where
+    S: NodeConfig,
+{
+    fn from(config: &S) -> Self {
+        let mut events = Vec::new();
+        for (event, data) in config.events() {
+            let Ok(vars) = Vec::from_slice(data.vars()) else {
+                continue;
+            };
+            let _ = events.push(SnapshotEvent {
+                event: *event,
+                index: data.index(),
+                vars,
+            });
+        }
+        events.sort_unstable_by_key(|e| e.index);
+
+        let node_vars = (1..=S::NODE_VAR_COUNT)
+            .map_while(|index| config.get_nv(index).ok())
+            .take(NODE_VAR_COUNT)
+            .collect();
+
+        Self {
+            node_number: (config.mode() == ModuleMode::Normal).then(|| *config.node_number()),
+            can_id: *config.can_id(),
+            flags: config.flags(),
+            events,
+            node_vars,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node_config::NodeConfigStorage;
+
+    type TestConfig = NodeConfigStorage<16, 3, 8>;
+    type TestSnapshot = NodeConfigSnapshot<16, 3, 8>;
+
+    const NODE_NUM: VlcbNodeNumber = VlcbNodeNumber::new(0x01, 0x02);
+
+    /// A node with 10 events and 8 NVs.
+    fn config() -> TestConfig {
+        let mut config = TestConfig::default();
+        config.set_mode_normal(NODE_NUM);
+        config.set_can_id(VlcbCanId::from_bytes(&[42]));
+        config.set_heartbeat(true);
+        for n in 0..10u8 {
+            let event = EventId::long(VlcbNodeNumber::new(0x03, n), n as u16);
+            config.save_event(&event, &[n, n + 1, n + 2]).unwrap();
+        }
+        for index in 1..=8 {
+            config.set_nv(index, index * 10).unwrap();
+        }
+        config
+    }
+
+    #[test]
+    fn test_snapshot_apply() {
+        let snapshot = TestSnapshot::from(&config());
+        assert_eq!(snapshot.node_number, Some(NODE_NUM));
+        assert_eq!(snapshot.events.len(), 10);
+        assert!(snapshot.events.windows(2).all(|w| w[0].index < w[1].index));
+        assert_eq!(&snapshot.node_vars[..], &[10, 20, 30, 40, 50, 60, 70, 80]);
+
+        let mut restored = TestConfig::default();
+        restored.save_event(&EventId::short(99), &[0, 0, 0]).unwrap();
+        snapshot.apply_to(&mut restored).unwrap();
+
+        assert!(!restored.has_event(&EventId::short(99)), "the events are replaced");
+        assert_eq!(restored.node_number(), &NODE_NUM);
+        assert!(restored.is_heartbeat_on());
+        assert_eq!(TestSnapshot::from(&restored), snapshot);
+    }
+
+    #[test]
+    fn test_apply_mismatched_snapshot() {
+        let mut snapshot = TestSnapshot::from(&config());
+        snapshot.node_vars.pop();
+
+        let mut restored = TestConfig::default();
+        assert_eq!(snapshot.apply_to(&mut restored), Err(Error::OutOfRange));
+        assert_eq!(restored.mode(), ModuleMode::Uninitialized, "nothing is written");
+        assert_eq!(restored.stored_event_count(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let snapshot = TestSnapshot::from(&config());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<TestSnapshot>(&json).unwrap(), snapshot);
+
+        let bytes = postcard::to_allocvec(&snapshot).unwrap();
+        assert_eq!(postcard::from_bytes::<TestSnapshot>(&bytes).unwrap(), snapshot);
+    }
+}
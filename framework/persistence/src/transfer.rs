@@ -0,0 +1,417 @@
+//! Compact binary format of a whole node configuration, to clone a node into its replacement.
+//!
+//! The format starts with a header followed by the records, all single octets unless noted:
+//!
+//! | Field                                             | Size                  |
+//! |---------------------------------------------------|-----------------------|
+//! | [`MAGIC`]                                         | 4                     |
+//! | [`FORMAT_VERSION`]                                | 1                     |
+//! | `MAX_EVENTS`, `EVENT_VAR_COUNT`, `NODE_VAR_COUNT` | 3                     |
+//! | node number, zero for a node not in normal mode   | 2                     |
+//! | CAN ID                                            | 1                     |
+//! | [`NodeFlags`]                                     | 1                     |
+//! | NVs starting with the NV 1                        | `NODE_VAR_COUNT`      |
+//! | number of the learned events                      | 1                     |
+//! | event index, event, EVs for each event            | 5 + `EVENT_VAR_COUNT` |
+//!
+//! The events are written in the order of their indexes. A short event is written with a
+//! zero node number, the same way the node config stores it.
+//!
+//! The exported configuration is meant to be streamed over the long message (DTXC) socket,
+//! until it exists the application moves it to the other node itself.
+
+use vlcb_core::can::VlcbCanId;
+use vlcb_core::module::NodeFlags;
+use vlcb_core::vlcb::{EventId, VlcbNodeNumber, EVENT_SIZE, NODENUM_SIZE};
+use vlcb_defs::ModuleMode;
+
+use crate::node_config::{Error, EventSlots, LearnedEvent, NodeConfig};
+
+/// Octets the exported configuration starts with.
+pub const MAGIC: [u8; 4] = *b"VCFG";
+/// Version of the format written by [`export`].
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Length of the header up to the NVs.
+const HEADER_SIZE: usize = MAGIC.len() + 4 + NODENUM_SIZE + 2;
+
+/// Length of the exported configuration of a node with the dimensions of `S` and `events`
+/// learned events.
+pub const fn export_len<S: NodeConfig>(events: u8) -> usize {
+    HEADER_SIZE + S::NODE_VAR_COUNT as usize + 1 + events as usize * record_len::<S>()
+}
+
+const fn record_len<S: NodeConfig>() -> usize {
+    1 + EVENT_SIZE + S::EVENT_VAR_COUNT as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportError {
+    /// The buffer can't hold the configuration, [`export_len`] octets are needed.
+    BufferTooSmall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportError {
+    /// The data ends before the configuration does.
+    Truncated,
+    /// The data goes on past the configuration.
+    TrailingData,
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    /// The format version is not known.
+    UnsupportedVersion(u8),
+    /// The configuration has another number of EVs than the node.
+    EventVarCount { expected: u8, found: u8 },
+    /// The configuration has another number of NVs than the node.
+    NodeVarCount { expected: u8, found: u8 },
+    /// The configuration has more events than the node has event slots.
+    TooManyEvents,
+    /// An event index is out of the event slots of the node, or used twice.
+    InvalidEventIndex(u8),
+    /// An event is learned twice.
+    DuplicateEvent(EventId),
+    /// The node config refused the configuration.
+    Storage(Error),
+}
+
+/// What [`import`] wrote into the node config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportReport {
+    pub node_number: Option<VlcbNodeNumber>,
+    pub node_vars: u8,
+    pub events: u8,
+}
+
+/// Write the configuration of the node into the `buffer`.
+///
+/// Returns the length of the exported configuration, see [`export_len`].
+pub fn export<S: NodeConfig>(config: &S, buffer: &mut [u8]) -> Result<usize, ExportError> {
+    let len = export_len::<S>(config.stored_event_count());
+    let buffer = buffer.get_mut(..len).ok_or(ExportError::BufferTooSmall)?;
+
+    let node_num = match config.mode() {
+        ModuleMode::Normal => *config.node_number(),
+        _ => VlcbNodeNumber::new(0, 0),
+    };
+    let (header, rest) = buffer.split_at_mut(HEADER_SIZE);
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..8].copy_from_slice(&[
+        FORMAT_VERSION,
+        S::MAX_EVENTS,
+        S::EVENT_VAR_COUNT,
+        S::NODE_VAR_COUNT,
+    ]);
+    header[8..10].copy_from_slice(node_num.as_bytes());
+    header[10] = config.can_id().as_bytes()[0];
+    header[11] = config.flags().bits();
+
+    let (node_vars, rest) = rest.split_at_mut(S::NODE_VAR_COUNT as usize);
+    for (index, value) in (1..).zip(node_vars.iter_mut()) {
+        *value = config.get_nv(index).unwrap_or_default();
+    }
+
+    let (count, records) = rest.split_at_mut(1);
+    count[0] = config.stored_event_count();
+    let events = (0..S::MAX_EVENTS).filter_map(|index| config.get_event_by_index(index));
+    for ((event, data), record) in events.zip(records.chunks_exact_mut(record_len::<S>())) {
+        record[0] = data.index();
+        record[1..=EVENT_SIZE].copy_from_slice(event.as_bytes());
+        record[1 + EVENT_SIZE..].copy_from_slice(data.vars());
+    }
+
+    Ok(len)
+}
+
+/// Replace the configuration of the node with the `received` one.
+///
+/// The whole configuration is checked against the dimensions of the node before anything
+/// is written, the `config` is left untouched when it is refused. The learned events are
+/// replaced. The caller is responsible for flushing the `config`.
+pub fn import<S: NodeConfig>(
+    config: &mut S,
+    received: &[u8],
+) -> Result<ImportReport, ImportError> {
+    let parsed = Parsed::parse::<S>(received)?;
+
+    match parsed.node_number {
+        Some(node_num) => config.set_mode_normal(node_num),
+        None => config.set_mode_uninitialized(),
+    }
+    config.set_can_id(parsed.can_id);
+    config.set_flags(parsed.flags);
+    for (index, &value) in (1..).zip(parsed.node_vars) {
+        config.set_nv(index, value).map_err(ImportError::Storage)?;
+    }
+
+    config.clear_all_events();
+    for (index, event, vars) in parsed.events::<S>() {
+        config
+            .restore_event(event, S::Event::new(index, vars))
+            .map_err(ImportError::Storage)?;
+    }
+
+    Ok(ImportReport {
+        node_number: parsed.node_number,
+        node_vars: S::NODE_VAR_COUNT,
+        events: parsed.event_count,
+    })
+}
+
+/// A received configuration checked against the dimensions of the node.
+struct Parsed<'a> {
+    node_number: Option<VlcbNodeNumber>,
+    can_id: VlcbCanId,
+    flags: NodeFlags,
+    node_vars: &'a [u8],
+    event_count: u8,
+    records: &'a [u8],
+}
+
+impl<'a> Parsed<'a> {
+    fn parse<S: NodeConfig>(data: &'a [u8]) -> Result<Self, ImportError> {
+        let (header, rest) = split(data, HEADER_SIZE)?;
+        if header[..4] != MAGIC {
+            return Err(ImportError::BadMagic);
+        }
+        let [version, _max_events, event_var_count, node_var_count] =
+            [header[4], header[5], header[6], header[7]];
+        if version != FORMAT_VERSION {
+            return Err(ImportError::UnsupportedVersion(version));
+        }
+        if event_var_count != S::EVENT_VAR_COUNT {
+            return Err(ImportError::EventVarCount {
+                expected: S::EVENT_VAR_COUNT,
+                found: event_var_count,
+            });
+        }
+        if node_var_count != S::NODE_VAR_COUNT {
+            return Err(ImportError::NodeVarCount {
+                expected: S::NODE_VAR_COUNT,
+                found: node_var_count,
+            });
+        }
+
+        let (node_vars, rest) = split(rest, node_var_count as usize)?;
+        let (count, records) = split(rest, 1)?;
+        let event_count = count[0];
+        if event_count > S::MAX_EVENTS {
+            return Err(ImportError::TooManyEvents);
+        }
+        let len = event_count as usize * record_len::<S>();
+        match records.len() {
+            n if n < len => return Err(ImportError::Truncated),
+            n if n > len => return Err(ImportError::TrailingData),
+            _ => {}
+        }
+
+        let node_num = VlcbNodeNumber::from_bytes(&header[8..10]);
+        let parsed = Self {
+            node_number: (node_num != VlcbNodeNumber::new(0, 0)).then_some(node_num),
+            can_id: VlcbCanId::from_bytes(&header[10..11]),
+            flags: NodeFlags::from_bits_truncate(header[11]),
+            node_vars,
+            event_count,
+            records,
+        };
+        parsed.check_events::<S>()?;
+        Ok(parsed)
+    }
+
+    fn check_events<S: NodeConfig>(&self) -> Result<(), ImportError> {
+        let mut slots = EventSlots::default();
+        for (n, (index, event, _)) in self.events::<S>().enumerate() {
+            if index >= S::MAX_EVENTS || slots.is_taken(index) {
+                return Err(ImportError::InvalidEventIndex(index));
+            }
+            slots.take(index);
+            if self.events::<S>().take(n).any(|(_, other, _)| other == event) {
+                return Err(ImportError::DuplicateEvent(event));
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate the event index, the event and its EVs of the event records.
+    fn events<S: NodeConfig>(&self) -> impl Iterator<Item = (u8, EventId, &'a [u8])> {
+        self.records.chunks_exact(record_len::<S>()).map(|record| {
+            let (index, record) = (record[0], &record[1..]);
+            let (event, vars) = record.split_at(EVENT_SIZE);
+            let mut event = EventId::from_bytes(event);
+            if event.node_num() == VlcbNodeNumber::new(0, 0) {
+                event = EventId::short(event.device_number());
+            }
+            (index, event, vars)
+        })
+    }
+}
+
+fn split(data: &[u8], len: usize) -> Result<(&[u8], &[u8]), ImportError> {
+    (data.len() >= len)
+        .then(|| data.split_at(len))
+        .ok_or(ImportError::Truncated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node_config::NodeConfigStorage;
+    use crate::snapshot::NodeConfigSnapshot;
+
+    type TestConfig = NodeConfigStorage<16, 3, 8>;
+
+    const NODE_NUM: VlcbNodeNumber = VlcbNodeNumber::new(0x01, 0x02);
+
+    /// A node with 10 long events, a short event and 8 NVs.
+    fn config() -> TestConfig {
+        let mut config = TestConfig::default();
+        config.set_mode_normal(NODE_NUM);
+        config.set_can_id(VlcbCanId::from_bytes(&[42]));
+        config.set_heartbeat(true);
+        for n in 0..10u8 {
+            let event = EventId::long(VlcbNodeNumber::new(0x03, n), n as u16);
+            config.save_event(&event, &[n, n + 1, n + 2]).unwrap();
+        }
+        config.save_event(&EventId::short(7), &[0x07, 0x08, 0x09]).unwrap();
+        for index in 1..=8 {
+            config.set_nv(index, index * 10).unwrap();
+        }
+        config
+    }
+
+    fn exported<S: NodeConfig>(config: &S) -> std::vec::Vec<u8> {
+        let mut buffer = [0u8; 256];
+        let len = export(config, &mut buffer).unwrap();
+        buffer[..len].to_vec()
+    }
+
+    #[test]
+    fn test_export_layout() {
+        let mut config = NodeConfigStorage::<4, 1, 2>::default();
+        config.set_mode_normal(NODE_NUM);
+        config.set_can_id(VlcbCanId::from_bytes(&[5]));
+        config.set_event_ack(true);
+        config.set_nv(1, 0x11).unwrap();
+        config.set_nv(2, 0x22).unwrap();
+        config.save_event(&EventId::short(7), &[0x42]).unwrap();
+
+        let data = exported(&config);
+        assert_eq!(data.len(), export_len::<NodeConfigStorage<4, 1, 2>>(1));
+        assert_eq!(
+            data,
+            [
+                b'V', b'C', b'F', b'G', FORMAT_VERSION, 4, 1, 2, 0x01, 0x02, 5, 0b10, 0x11, 0x22, 1,
+                0, 0x00, 0x00, 0x00, 0x07, 0x42,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = config();
+        let data = exported(&config);
+
+        let mut imported = TestConfig::default();
+        imported.save_event(&EventId::short(99), &[0, 0, 0]).unwrap();
+        let report = import(&mut imported, &data).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                node_number: Some(NODE_NUM),
+                node_vars: 8,
+                events: 11
+            }
+        );
+
+        assert!(!imported.has_event(&EventId::short(99)), "the events are replaced");
+        assert_eq!(imported.get_event(&EventId::short(7)).unwrap().vars(), [0x07, 0x08, 0x09]);
+        assert_eq!(
+            NodeConfigSnapshot::<16, 3, 8>::from(&imported),
+            NodeConfigSnapshot::<16, 3, 8>::from(&config)
+        );
+    }
+
+    #[test]
+    fn test_export_buffer_too_small() {
+        let config = config();
+        let mut buffer = [0u8; 64];
+        assert_eq!(export(&config, &mut buffer), Err(ExportError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_import_mismatched_event_var_count() {
+        let data = exported(&config());
+
+        let mut other = NodeConfigStorage::<16, 2, 8>::default();
+        other.save_event(&EventId::short(99), &[1, 2]).unwrap();
+        assert_eq!(
+            import(&mut other, &data),
+            Err(ImportError::EventVarCount {
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(other.mode(), ModuleMode::Uninitialized, "nothing is written");
+        assert!(other.has_event(&EventId::short(99)));
+    }
+
+    #[test]
+    fn test_import_rejected() {
+        let data = exported(&config());
+        let mut imported = TestConfig::default();
+
+        let mut nv_count = NodeConfigStorage::<16, 3, 4>::default();
+        assert_eq!(
+            import(&mut nv_count, &data),
+            Err(ImportError::NodeVarCount {
+                expected: 4,
+                found: 8
+            })
+        );
+        let mut few_slots = NodeConfigStorage::<8, 3, 8>::default();
+        assert_eq!(import(&mut few_slots, &data), Err(ImportError::TooManyEvents));
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(import(&mut imported, &bad_magic), Err(ImportError::BadMagic));
+        let mut version = data.clone();
+        version[4] = 9;
+        assert_eq!(import(&mut imported, &version), Err(ImportError::UnsupportedVersion(9)));
+
+        assert_eq!(import(&mut imported, &data[..data.len() - 1]), Err(ImportError::Truncated));
+        assert_eq!(import(&mut imported, &data[..5]), Err(ImportError::Truncated));
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(import(&mut imported, &trailing), Err(ImportError::TrailingData));
+
+        assert_eq!(imported.mode(), ModuleMode::Uninitialized, "nothing is written");
+    }
+
+    #[test]
+    fn test_import_invalid_events() {
+        let mut config = NodeConfigStorage::<4, 1, 1>::default();
+        config.save_event(&EventId::short(1), &[0x01]).unwrap();
+        config.save_event(&EventId::short(2), &[0x02]).unwrap();
+        let data = exported(&config);
+        let first = HEADER_SIZE + 1 + 1;
+        let second = first + record_len::<NodeConfigStorage<4, 1, 1>>();
+        let mut imported = NodeConfigStorage::<4, 1, 1>::default();
+
+        let mut index = data.clone();
+        index[second] = 4;
+        assert_eq!(import(&mut imported, &index), Err(ImportError::InvalidEventIndex(4)));
+        index[second] = index[first];
+        assert_eq!(
+            import(&mut imported, &index),
+            Err(ImportError::InvalidEventIndex(index[first]))
+        );
+
+        let mut duplicate = data.clone();
+        duplicate.copy_within(first + 1..first + 1 + EVENT_SIZE, second + 1);
+        assert_eq!(
+            import(&mut imported, &duplicate),
+            Err(ImportError::DuplicateEvent(EventId::short(1)))
+        );
+        assert_eq!(imported.stored_event_count(), 0, "nothing is written");
+    }
+}
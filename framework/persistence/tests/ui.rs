@@ -0,0 +1,6 @@
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/config-storage-valid.rs");
+    t.compile_fail("tests/ui/config-storage-bad-bytes-per-event.rs");
+}
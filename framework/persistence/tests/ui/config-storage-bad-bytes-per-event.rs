@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use embedded_storage::{ReadStorage, Storage};
+use rclite::Rc;
+use vlcb_persistence::node_config::PersistentNodeConfigStorage;
+
+struct NoStorage;
+
+impl ReadStorage for NoStorage {
+    type Error = ();
+
+    fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
+        Err(())
+    }
+
+    fn capacity(&self) -> usize {
+        0
+    }
+}
+
+impl Storage for NoStorage {
+    fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+        Err(())
+    }
+}
+
+fn main() {
+    // BYTES_PER_EVENT is missing the size of the event itself
+    let _config =
+        PersistentNodeConfigStorage::<_, 0, 32, 4, 4, 16>::new(Rc::new(RefCell::new(NoStorage)));
+}
@@ -0,0 +1,35 @@
+use std::cell::RefCell;
+
+use embedded_storage::{ReadStorage, Storage};
+use rclite::Rc;
+use vlcb_persistence::node_config::{bytes_per_event, PersistentNodeConfigStorage};
+use vlcb_persistence::node_config_storage;
+
+struct NoStorage;
+
+impl ReadStorage for NoStorage {
+    type Error = ();
+
+    fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
+        Err(())
+    }
+
+    fn capacity(&self) -> usize {
+        0
+    }
+}
+
+impl Storage for NoStorage {
+    fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+        Err(())
+    }
+}
+
+type Config = node_config_storage!(NoStorage, 0, 32, 4, 16);
+
+fn main() {
+    let driver = Rc::new(RefCell::new(NoStorage));
+    let _config = Config::new(driver.clone());
+    let _config =
+        PersistentNodeConfigStorage::<_, 0, 32, 4, { bytes_per_event(4) }, 16>::new(driver);
+}
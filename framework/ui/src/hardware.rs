@@ -0,0 +1,565 @@
+use core::marker::PhantomData;
+use heapless::Deque;
+use embedded_simple_ui::{led::{effects::{blink, pulse, LedEffect}, Led}, switch::Switch};
+use embedded_time::{duration::Milliseconds, Clock, Instant};
+use vlcb_core::time::millis;
+use vlcb_defs::ModuleMode;
+
+use crate::{config, UserAction, VlcbUi};
+
+/// Maximum amount of user actions kept until the module takes them.
+const USER_ACTION_QUEUE_SIZE: usize = 4;
+
+/// Guards the factory reset, which is requested by holding the main switch at power up.
+enum ResetGuard {
+    /// The switch was not polled yet.
+    Startup,
+    /// The switch is held since the power up.
+    Armed,
+    /// The reset was requested, the release of the switch does not request anything else.
+    Requested,
+    Disarmed,
+}
+
+/// How long the main switch of a [`HardwareUi`] has to be held for the user actions.
+///
+/// The defaults are the thresholds in [`config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiTimings {
+    /// A longer press changes the mode, holding the switch at power up for longer resets
+    /// the node.
+    pub long_hold: Milliseconds<u32>,
+    /// A press within `short_hold_low..short_hold_high` requests a new node number.
+    pub short_hold_low: Milliseconds<u32>,
+    pub short_hold_high: Milliseconds<u32>,
+    /// A shorter press starts the CAN ID self-enumeration.
+    pub very_short_hold: Milliseconds<u32>,
+    /// Two such presses released within the window are a double tap.
+    pub double_tap_window: Milliseconds<u32>,
+}
+
+impl Default for UiTimings {
+    fn default() -> Self {
+        Self {
+            long_hold: config::SW_LONG_HOLD,
+            short_hold_low: config::SW_SHORT_RANGE_HOLD_LOW,
+            short_hold_high: config::SW_SHORT_RANGE_HOLD_HIGH,
+            very_short_hold: config::SW_VERY_SHORT_HOLD,
+            double_tap_window: config::SW_DOUBLE_TAP_WINDOW,
+        }
+    }
+}
+
+pub struct HardwareUi<LED: Led<C>, SW: Switch<C>, C: Clock> {
+    led_green: LED,
+    led_yellow: LED,
+    main_switch: SW,
+    timings: UiTimings,
+    reset_guard: ResetGuard,
+    /// Actions requested by the user and not taken by the module yet, oldest first.
+    requested_actions: Deque<UserAction, USER_ACTION_QUEUE_SIZE>,
+    /// The indicated mode, restored when the link is up again.
+    mode: Option<ModuleMode>,
+    /// The link to the bus is indicated down, on top of the mode.
+    link_down: bool,
+    /// The current press of the main switch is long enough to change the mode, and it is
+    /// indicated.
+    long_hold_indicated: bool,
+    /// Release of the last very short press, not yet part of a double tap.
+    last_tap: Option<Instant<C>>,
+    _clock: PhantomData<C>,
+}
+
+impl<LED: Led<C>, SW: Switch<C>, C: Clock> HardwareUi<LED, SW, C> {
+    pub fn new(led_green: LED, led_yellow: LED, main_switch: SW, timings: UiTimings) -> Self {
+        let mut led_green = led_green;
+        let mut led_yellow = led_yellow;
+        led_green.clear_effect();
+        led_green.turn_off();
+        led_yellow.clear_effect();
+        led_yellow.turn_off();
+
+        Self {
+            led_green,
+            led_yellow,
+            main_switch,
+            timings,
+            reset_guard: ResetGuard::Startup,
+            requested_actions: Deque::new(),
+            mode: None,
+            link_down: false,
+            long_hold_indicated: false,
+            last_tap: None,
+            _clock: PhantomData,
+        }
+    }
+
+    /// Check whether the user requested a reset by holding the main switch since the power up
+    ///
+    /// The reset is requested with the switch still pressed, once it was held for longer than
+    /// the [`UiTimings::long_hold`].
+    fn check_reset_requested(&mut self, now: Instant<C>) -> Option<UserAction> {
+        match self.reset_guard {
+            ResetGuard::Startup if self.main_switch.is_pressed() => {
+                self.reset_guard = ResetGuard::Armed;
+                None
+            }
+            ResetGuard::Startup => {
+                self.reset_guard = ResetGuard::Disarmed;
+                None
+            }
+            ResetGuard::Armed | ResetGuard::Requested if self.main_switch.is_released() => {
+                self.reset_guard = ResetGuard::Disarmed;
+                None
+            }
+            ResetGuard::Armed if self.main_switch.current_state(now) > millis::<C>(self.timings.long_hold) => {
+                self.reset_guard = ResetGuard::Requested;
+                Some(UserAction::Reset)
+            }
+            _ => None,
+        }
+    }
+
+    /// Indicate the setup mode once the main switch is held for longer than
+    /// the [`UiTimings::long_hold`], telling the user to release it to change the mode.
+    ///
+    /// The indicated mode is kept, the module indicates the new one after the release.
+    fn indicate_long_hold(&mut self, now: Instant<C>) {
+        if self.main_switch.is_released() {
+            self.long_hold_indicated = false;
+            return;
+        }
+        if self.long_hold_indicated || self.main_switch.current_state(now) <= millis::<C>(self.timings.long_hold) {
+            return;
+        }
+
+        self.long_hold_indicated = true;
+        let mode = self.mode;
+        self.indicate_mode(ModuleMode::InSetup);
+        self.mode = mode;
+    }
+
+    /// Check whether the user requested an action by releasing the main switch
+    ///
+    /// The action depends on how long the switch was pressed, presses outside of the ranges
+    /// request nothing. A very short press released within the double tap window after the
+    /// previous one requests [`UserAction::DoubleTap`] instead of the CAN enumeration.
+    fn check_user_requested_action(&mut self, now: Instant<C>) -> Option<UserAction> {
+        if !self.main_switch.has_changed() || !self.main_switch.is_released() {
+            return None;
+        }
+
+        let timings = &self.timings;
+        let press_time = self.main_switch.prev_state_lasted_for();
+        if press_time > millis::<C>(timings.long_hold) {
+            return Some(UserAction::ChangeMode);
+        }
+        if press_time >= millis::<C>(timings.short_hold_low) && press_time < millis::<C>(timings.short_hold_high) {
+            return Some(UserAction::Renegotiate);
+        }
+        if press_time < millis::<C>(timings.very_short_hold) {
+            let window = millis::<C>(timings.double_tap_window);
+            let gap: Option<Milliseconds<C::T>> = self
+                .last_tap
+                .and_then(|last| now.checked_duration_since(&last)?.try_into().ok());
+            if gap.is_some_and(|gap| gap <= window) {
+                self.last_tap = None;
+                return Some(UserAction::DoubleTap);
+            }
+            self.last_tap = Some(now);
+            return Some(UserAction::StartCanEnumeration);
+        }
+        None
+    }
+}
+
+impl<LED: Led<C>, SW: Switch<C>, C: Clock> VlcbUi<C> for HardwareUi<LED, SW, C> {
+    fn poll(&mut self, now: Instant<C>) {
+        self.led_green.poll(now);
+        self.led_yellow.poll(now);
+        self.main_switch.poll(now);
+
+        let action = match self.reset_guard {
+            ResetGuard::Disarmed => {
+                self.indicate_long_hold(now);
+                self.check_user_requested_action(now)
+            }
+            _ => self.check_reset_requested(now),
+        };
+        if let Some(action) = action {
+            // the oldest request makes room, like with the interface events
+            if self.requested_actions.is_full() {
+                self.requested_actions.pop_front();
+            }
+            let _ = self.requested_actions.push_back(action);
+        }
+    }
+
+    fn is_main_sw_pressed(&self) -> bool {
+        self.main_switch.is_pressed()
+    }
+
+    fn indicate_activity(&mut self) {
+        self.led_green.set_effect(LedEffect::new(pulse::<C>(config::ACTIVITY_PULSE_MS as u16)));
+    }
+
+    fn indicate_error(&mut self) {
+        self.led_yellow.set_effect(LedEffect::new(pulse::<C>(config::ERROR_PULSE_MS)));
+    }
+
+    /// Indicate the `mode` with both LEDs, starting from both of them off.
+    ///
+    /// The changes of the settings of a node in normal mode are indicated as the normal mode,
+    /// the learn mode as changing with both LEDs blinking in turns.
+    fn indicate_mode(&mut self, mode: ModuleMode) {
+        for led in [&mut self.led_green, &mut self.led_yellow] {
+            led.clear_effect();
+            led.turn_off();
+        }
+
+        let mode = match mode {
+            ModuleMode::DisableLearnMode
+            | ModuleMode::EnableEventAck
+            | ModuleMode::DisableEventAck
+            | ModuleMode::EnableHeartbeat
+            | ModuleMode::DisableHeartbeat => ModuleMode::Normal,
+            mode => mode,
+        };
+        self.mode = Some(mode);
+        match mode {
+            ModuleMode::Normal => self.led_yellow.turn_on(),
+            ModuleMode::Uninitialized => self.led_green.turn_on(),
+            ModuleMode::InSetup => {
+                self.led_yellow.set_effect(LedEffect::new(blink::<C>(config::SETUP_MODE_BLINK_RATE_HZ)));
+            }
+            ModuleMode::EnableLearnMode => {
+                // the LEDs blink in turns from the opposite states
+                self.led_green.turn_on();
+                let changing = blink::<C>(config::CHANGING_MODE_BLINK_RATE_HZ);
+                self.led_green.set_effect(LedEffect::new(changing));
+                self.led_yellow.set_effect(LedEffect::new(changing));
+            }
+            ModuleMode::Bootloader => {
+                self.led_green.turn_on();
+                self.led_yellow.turn_on();
+            }
+            // mapped to the normal mode above
+            _ => {}
+        }
+
+        if self.link_down {
+            self.led_green.set_effect(LedEffect::new(blink::<C>(config::LINK_DOWN_BLINK_RATE_HZ)));
+        }
+    }
+
+    fn indicate_link(&mut self, up: bool) {
+        self.link_down = !up;
+        if !up {
+            self.led_green.set_effect(LedEffect::new(blink::<C>(config::LINK_DOWN_BLINK_RATE_HZ)));
+            return;
+        }
+
+        self.led_green.clear_effect();
+        self.led_green.turn_off();
+        if let Some(mode) = self.mode {
+            self.indicate_mode(mode);
+        }
+    }
+
+    fn take_requested_action(&mut self) -> Option<UserAction> {
+        self.requested_actions.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use std::rc::Rc;
+
+    use embedded_hal::digital::{ErrorType, InputPin};
+    use embedded_simple_ui::switch::{switch_state::PressedOnHigh, PinSwitch};
+    use vlcb_core::time::TestClock;
+
+    use super::*;
+
+    /// A push button input, pressed while the shared level is high.
+    #[derive(Clone, Default)]
+    struct TestPin(Rc<Cell<bool>>);
+
+    impl ErrorType for TestPin {
+        type Error = Infallible;
+    }
+
+    impl InputPin for TestPin {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.0.get())
+        }
+    }
+
+    /// A LED without any hardware.
+    #[derive(Default)]
+    struct TestLed {
+        on: bool,
+        effect: Option<LedEffect<TestClock>>,
+    }
+
+    impl Led<TestClock> for TestLed {
+        fn is_on(&mut self) -> bool {
+            self.on
+        }
+
+        fn turn_on(&mut self) {
+            self.on = true;
+        }
+
+        fn turn_off(&mut self) {
+            self.on = false;
+        }
+
+        fn toggle(&mut self) {
+            self.on = !self.on;
+        }
+
+        fn set_effect(&mut self, effect: LedEffect<TestClock>) {
+            self.effect = Some(effect);
+        }
+
+        fn set_effect_duration(&mut self, _dur: Milliseconds<u64>) {}
+
+        fn get_effect(&self) -> Option<&LedEffect<TestClock>> {
+            self.effect.as_ref()
+        }
+
+        fn clear_effect(&mut self) {
+            self.effect = None;
+        }
+
+        fn poll(&mut self, _now: Instant<TestClock>) {}
+    }
+
+    type TestSwitch = PinSwitch<TestPin, PressedOnHigh, TestClock>;
+
+    struct Bench {
+        clock: TestClock,
+        pin: TestPin,
+        ui: HardwareUi<TestLed, TestSwitch, TestClock>,
+    }
+
+    impl Bench {
+        /// A UI powered up with the switch in the given state.
+        fn new(pressed: bool) -> Self {
+            Self::with_timings(pressed, UiTimings::default())
+        }
+
+        fn with_timings(pressed: bool, timings: UiTimings) -> Self {
+            let clock = TestClock::new();
+            let pin = TestPin::default();
+            pin.0.set(pressed);
+            let switch = TestSwitch::new(pin.clone());
+            let mut ui = HardwareUi::new(TestLed::default(), TestLed::default(), switch, timings);
+            ui.poll(clock.now());
+            Self { clock, pin, ui }
+        }
+
+        fn set_pressed(&mut self, pressed: bool) {
+            self.pin.0.set(pressed);
+            self.ui.poll(self.clock.now());
+        }
+
+        fn hold(&mut self, ms: u64) {
+            self.set_pressed(true);
+            self.clock.advance(ms);
+            self.set_pressed(false);
+        }
+    }
+
+    /// Whether the green and yellow LED are on and have an effect.
+    fn leds(ui: &HardwareUi<TestLed, TestSwitch, TestClock>) -> [(bool, bool); 2] {
+        [&ui.led_green, &ui.led_yellow].map(|led| (led.on, led.effect.is_some()))
+    }
+
+    #[test]
+    fn test_indicate_every_mode() {
+        use ModuleMode::*;
+
+        const NORMAL: [(bool, bool); 2] = [(false, false), (true, false)];
+        let table = [
+            (Uninitialized, [(true, false), (false, false)]),
+            (InSetup, [(false, false), (false, true)]),
+            (Normal, NORMAL),
+            (EnableLearnMode, [(true, true), (false, true)]),
+            (DisableLearnMode, NORMAL),
+            (EnableEventAck, NORMAL),
+            (DisableEventAck, NORMAL),
+            (EnableHeartbeat, NORMAL),
+            (DisableHeartbeat, NORMAL),
+            (Bootloader, [(true, false), (true, false)]),
+        ];
+
+        let mut bench = Bench::new(false);
+        for (mode, expected) in table {
+            // every mode is indicated the same regardless of the previous one
+            for previous in [InSetup, EnableLearnMode, Bootloader] {
+                bench.ui.indicate_mode(previous);
+                bench.ui.indicate_mode(mode);
+                assert_eq!(leds(&bench.ui), expected, "{:?} after {:?}", mode, previous);
+            }
+        }
+    }
+
+    #[test]
+    fn test_link_down_kept_over_mode() {
+        let mut bench = Bench::new(false);
+        bench.ui.indicate_link(false);
+        bench.ui.indicate_mode(ModuleMode::Normal);
+        assert_eq!(leds(&bench.ui), [(false, true), (true, false)]);
+
+        bench.ui.indicate_link(true);
+        assert_eq!(leds(&bench.ui), [(false, false), (true, false)]);
+    }
+
+    #[test]
+    fn test_main_switch_pressed() {
+        let mut bench = Bench::new(false);
+        assert!(!bench.ui.is_main_sw_pressed());
+        bench.set_pressed(true);
+        assert!(bench.ui.is_main_sw_pressed());
+        bench.set_pressed(false);
+        assert!(!bench.ui.is_main_sw_pressed());
+    }
+
+    #[test]
+    fn test_long_hold_changes_mode() {
+        let mut bench = Bench::new(false);
+        bench.hold(6_500);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::ChangeMode));
+        assert_eq!(bench.ui.take_requested_action(), None, "served once");
+    }
+
+    #[test]
+    fn test_long_hold_indicated_while_held() {
+        let mut bench = Bench::new(false);
+        bench.ui.indicate_mode(ModuleMode::Normal);
+        bench.set_pressed(true);
+
+        bench.clock.advance(6_000);
+        bench.set_pressed(true);
+        assert!(bench.ui.led_yellow.effect.is_none(), "not yet at the threshold");
+
+        bench.clock.advance(1);
+        bench.set_pressed(true);
+        assert!(bench.ui.led_yellow.effect.is_some(), "the setup mode is indicated");
+        assert!(!bench.ui.led_green.on);
+        assert_eq!(bench.ui.take_requested_action(), None, "nothing before the release");
+
+        bench.set_pressed(false);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::ChangeMode));
+        assert_eq!(bench.ui.mode, Some(ModuleMode::Normal), "left to the module to indicate");
+    }
+
+    #[test]
+    fn test_short_hold_renegotiates() {
+        let mut bench = Bench::new(false);
+        bench.hold(1_500);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::Renegotiate));
+    }
+
+    #[test]
+    fn test_tap_starts_enumeration() {
+        let mut bench = Bench::new(false);
+        bench.hold(300);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+    }
+
+    #[test]
+    fn test_presses_between_ranges_request_nothing() {
+        let mut bench = Bench::new(false);
+        bench.hold(700);
+        bench.hold(3_000);
+        assert_eq!(bench.ui.take_requested_action(), None);
+    }
+
+    #[test]
+    fn test_double_tap() {
+        let mut bench = Bench::new(false);
+        bench.hold(100);
+        bench.clock.advance(150);
+        bench.hold(100);
+
+        // the first tap is a single press until the second one arrives
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::DoubleTap));
+
+        // a third tap starts over
+        bench.clock.advance(100);
+        bench.hold(100);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+    }
+
+    #[test]
+    fn test_slow_taps_are_single_presses() {
+        let mut bench = Bench::new(false);
+        bench.hold(100);
+        bench.clock.advance(400);
+        bench.hold(100);
+
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+        assert_eq!(bench.ui.take_requested_action(), None);
+    }
+
+    #[test]
+    fn test_actions_queued_in_order() {
+        let mut bench = Bench::new(false);
+        bench.hold(300);
+        bench.clock.advance(1_000);
+        bench.hold(1_500);
+
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::Renegotiate));
+        assert_eq!(bench.ui.take_requested_action(), None);
+    }
+
+    #[test]
+    fn test_custom_timings() {
+        let timings = UiTimings {
+            long_hold: Milliseconds(3_000),
+            short_hold_low: Milliseconds(200),
+            short_hold_high: Milliseconds(400),
+            very_short_hold: Milliseconds(100),
+            double_tap_window: Milliseconds(400),
+        };
+        let mut bench = Bench::with_timings(false, timings);
+
+        bench.hold(3_500);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::ChangeMode));
+        bench.hold(300);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::Renegotiate));
+        bench.hold(50);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+
+        let mut bench = Bench::with_timings(true, timings);
+        bench.clock.advance(3_001);
+        bench.set_pressed(true);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::Reset));
+    }
+
+    #[test]
+    fn test_hold_at_power_up_resets() {
+        let mut bench = Bench::new(true);
+        bench.clock.advance(6_001);
+        bench.set_pressed(true);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::Reset), "while still held");
+
+        // the release after the reset is not a mode change
+        bench.set_pressed(false);
+        assert_eq!(bench.ui.take_requested_action(), None);
+        bench.hold(300);
+        assert_eq!(bench.ui.take_requested_action(), Some(UserAction::StartCanEnumeration));
+    }
+}
@@ -0,0 +1,100 @@
+//! A module without a front panel on a simulated CAN bus, built on the `vlcb` facade alone.
+//!
+//! Run with `cargo run --example minimalistic --features alloc`.
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant as StdInstant};
+
+use vlcb::module::{str_to_array, CpuId, Processor};
+use vlcb::net::phy::VirtualBus;
+use vlcb::net::SocketStorage;
+use vlcb::persist::embedded_storage::{ReadStorage, Storage};
+use vlcb::persist::{node_config_storage, Rc};
+use vlcb::prelude::*;
+use vlcb::time::{clock, fraction::Fraction, Clock, Instant};
+use vlcb::ui::NullUi;
+
+fn processor_id_resolver() -> CpuId {
+    str_to_array!("328P")
+}
+
+/// A millisecond clock counting from its creation.
+struct SystemClock(StdInstant);
+
+impl Clock for SystemClock {
+    type T = u64;
+    const SCALING_FACTOR: Fraction = Fraction::new(1, 1000);
+
+    fn try_now(&self) -> Result<Instant<Self>, clock::Error> {
+        Ok(Instant::new(self.0.elapsed().as_millis() as u64))
+    }
+}
+
+/// Real module should use EEPROM or flash or similar for persistence
+struct RamStorage([u8; 128]);
+
+impl ReadStorage for RamStorage {
+    type Error = ();
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get(offset..offset + bytes.len()).ok_or(())?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Storage for RamStorage {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        let data = self.0.get_mut(offset..offset + bytes.len()).ok_or(())?;
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+fn main() -> ! {
+    let storage_driver = Rc::new(RefCell::new(RamStorage([0xff; 128])));
+
+    // The config block starts at the offset 0 of the memory of `storage_driver`.
+    // rust doesn't support const expressions in generics yet, the macro computes the
+    // `BYTES_PER_EVENT` parameter of [`PersistentNodeConfigStorage`] from the number of event vars
+    const EVENT_VARS: usize = 4;
+    type Config = node_config_storage!(RamStorage, 0, 16, EVENT_VARS, 8);
+    let config = Config::new(storage_driver);
+
+    let clock = SystemClock(StdInstant::now());
+    let bus = VirtualBus::new(&clock);
+    let mut device = bus.port();
+    let hw_addr = HardwareAddress::CAN(VlcbCanId::default());
+    let mut interface = Interface::new(&device, None, hw_addr);
+
+    // The module has no front panel
+    let ui = NullUi;
+
+    let mut module = Module::builder()
+        .name("MINI")
+        .version(ModuleVersion::new(1, 'a', 33))
+        .manufacturer(vlcb::defs::Manufacturer::Development)
+        .ui(ui)
+        .config(config)
+        .cpu(Processor::Atmel)
+        .cpu_id_resolver(processor_id_resolver)
+        .interface(&interface)
+        .build()
+        .unwrap()
+        .init();
+
+    let mut storage: [SocketStorage; 1] = Default::default();
+    let mut sockets = SocketSet::new(&mut storage[..]);
+
+    loop {
+        let now = clock.try_now().unwrap();
+        module.poll(now, &mut interface, &mut device, &mut sockets);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
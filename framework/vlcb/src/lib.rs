@@ -0,0 +1,86 @@
+//! The VLCB SDK in a single crate.
+//!
+//! Re-exports the crates of the stack under one set of paths, so an application depends
+//! on `vlcb` alone and enables the features of the whole stack in one place:
+//!
+//! * [`module`] - the module runtime tying everything together,
+//! * [`net`] - the network interface, devices and sockets,
+//! * [`persist`] - the node configuration storage,
+//! * [`ui`] - the user interface of the module,
+//! * [`defs`] - the opcodes and other definitions of the VLCB specification.
+//!
+//! The most used items are gathered in the [`prelude`].
+//!
+//! # Features
+//!
+//! The crate builds on `no_std` with the default features, `medium-can` and `socket-module`.
+//! The persistent node configuration shares its storage driver in an [`Rc`](persist::Rc),
+//! so the application has to provide a global allocator.
+//!
+//! The `alloc` feature enables the heap allocated socket and service storage and the
+//! virtual bus. The `std` feature links the standard library into every crate of the
+//! stack, e.g. for the host tooling, and implies `alloc`. No other feature enables them.
+//!
+//! `defmt`, `phy-embedded_can` and `hardware-ui` are forwarded to the crates they belong to.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+pub use vlcb_defs as defs;
+
+/// Time keeping of the stack, everything time related is generic over a [`Clock`](time::Clock).
+pub use embedded_time as time;
+
+/// The module runtime and its parameters.
+pub mod module {
+    pub use vlcb_core::module::{ModuleParams, NodeFlags, ParamFlags};
+    pub use vlcb_macros::{module_name, str_to_array};
+    pub use vlcb_module::*;
+}
+
+/// Node numbers, events and other types shared by the whole stack.
+pub mod types {
+    pub use vlcb_core::can::VlcbCanId;
+    pub use vlcb_core::vlcb::{EventId, EventType, VlcbNodeNumber};
+}
+
+/// The network interface, devices and sockets.
+pub mod net {
+    pub use vlcb_network::config;
+    pub use vlcb_network::data;
+    pub use vlcb_network::iface::*;
+    pub use vlcb_network::phy;
+    pub use vlcb_network::socket;
+    pub use vlcb_network::wire;
+}
+
+/// The node configuration storage.
+pub mod persist {
+    pub use vlcb_persistence::*;
+
+    /// The storage driver traits the persistent node configuration is built on.
+    pub use embedded_storage;
+
+    /// The shared pointer the persistent node configuration holds its storage driver with.
+    pub use rclite::Rc;
+}
+
+/// The user interface of the module.
+pub mod ui {
+    pub use vlcb_ui::*;
+}
+
+/// The most used items of the stack.
+///
+/// ```
+/// use vlcb::prelude::*;
+/// ```
+pub mod prelude {
+    pub use crate::defs::{ModuleMode, OpCode};
+    pub use crate::module::{Module, ModuleBuilder, ModuleVersion, ParamFlags};
+    pub use crate::net::phy::Device;
+    pub use crate::net::wire::HardwareAddress;
+    pub use crate::net::{Interface, PollContext, SocketSet};
+    pub use crate::persist::node_config::NodeConfig;
+    pub use crate::types::{EventId, VlcbCanId, VlcbNodeNumber};
+    pub use crate::ui::VlcbUi;
+}
@@ -0,0 +1,26 @@
+//! The facade must build for `no_std` targets with the default features off.
+//!
+//! Checks a `no_std` application of the facade, which does not build when any crate of
+//! the stack links the standard library (duplicate `panic_impl` lang item).
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn no_std() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/no_std/Cargo.toml");
+    let output = Command::new(env!("CARGO"))
+        .arg("check")
+        .arg("--manifest-path")
+        .arg(manifest)
+        .arg("--target-dir")
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
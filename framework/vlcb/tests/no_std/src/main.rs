@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+use core::alloc::{GlobalAlloc, Layout};
+
+use vlcb::prelude::*;
+
+// The persistent node config shares its storage driver in an `Rc`
+struct NoAlloc;
+
+unsafe impl GlobalAlloc for NoAlloc {
+    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+        core::ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+#[global_allocator]
+static ALLOCATOR: NoAlloc = NoAlloc;
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let event = EventId::long(VlcbNodeNumber::new(0x01, 0x02), 1);
+    match event.node_num() == VlcbNodeNumber::new(0x01, 0x02) {
+        true => u8::from(OpCode::QueryNodeInfo) as i32,
+        false => 0,
+    }
+}
@@ -1,26 +1,97 @@
-pub enum Service {
-    Mns(vlcb_svc_mns::Service)
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostic, Diagnostics, VlcbService};
+use vlcb_defs::ServiceType;
+use vlcb_svc_discovery::ServiceRecord;
+
+/// A service of the module.
+///
+/// The lifetime `'a` is the lifetime of the application data borrowed by a service,
+/// e.g. the state provider of the event producer.
+pub enum Service<'a> {
+    Mns(vlcb_svc_mns::Service),
+    Nv(vlcb_svc_nv::Service),
+    Discovery(vlcb_svc_discovery::Service),
+    Diagnostics(vlcb_svc_diagnostics::Service),
+    EventProducer(vlcb_svc_event_producer::Service<'a>),
+    EventTeaching(vlcb_svc_event_teaching::Service),
+}
+
+impl<'a> Service<'a> {
+    /// Return the ID of the wrapped service.
+    pub fn service_id(&self) -> ServiceType {
+        match self {
+            Service::Mns(_) => vlcb_svc_mns::Service::service_id(),
+            Service::Nv(_) => vlcb_svc_nv::Service::service_id(),
+            Service::Discovery(_) => vlcb_svc_discovery::Service::service_id(),
+            Service::Diagnostics(_) => vlcb_svc_diagnostics::Service::service_id(),
+            Service::EventProducer(_) => vlcb_svc_event_producer::Service::service_id(),
+            Service::EventTeaching(_) => vlcb_svc_event_teaching::Service::service_id(),
+        }
+    }
+
+    /// Return the version of the wrapped service.
+    pub fn service_version(&self) -> u8 {
+        match self {
+            Service::Mns(_) => vlcb_svc_mns::Service::service_version(),
+            Service::Nv(_) => vlcb_svc_nv::Service::service_version(),
+            Service::Discovery(_) => vlcb_svc_discovery::Service::service_version(),
+            Service::Diagnostics(_) => vlcb_svc_diagnostics::Service::service_version(),
+            Service::EventProducer(_) => vlcb_svc_event_producer::Service::service_version(),
+            Service::EventTeaching(_) => vlcb_svc_event_teaching::Service::service_version(),
+        }
+    }
+
+    /// Describe the wrapped service for the service discovery.
+    pub fn record(&self) -> ServiceRecord {
+        ServiceRecord::new(self.service_id(), self.service_version())
+    }
+}
+
+impl<'a> Diagnostics for Service<'a> {
+    fn diagnostic_count(&self) -> u8 {
+        match self {
+            Service::Mns(service) => service.diagnostic_count(),
+            Service::Nv(service) => service.diagnostic_count(),
+            Service::Discovery(service) => service.diagnostic_count(),
+            Service::Diagnostics(service) => service.diagnostic_count(),
+            Service::EventProducer(service) => service.diagnostic_count(),
+            Service::EventTeaching(service) => service.diagnostic_count(),
+        }
+    }
+
+    fn diagnostic(&self, code: u8) -> Option<Diagnostic> {
+        match self {
+            Service::Mns(service) => service.diagnostic(code),
+            Service::Nv(service) => service.diagnostic(code),
+            Service::Discovery(service) => service.diagnostic(code),
+            Service::Diagnostics(service) => service.diagnostic(code),
+            Service::EventProducer(service) => service.diagnostic(code),
+            Service::EventTeaching(service) => service.diagnostic(code),
+        }
+    }
 }
 
 /// A conversion trait for module services.
-pub trait AnyService{
-    fn upcast(self) -> Service;
-    fn downcast<'c>(service: &'c Service) -> Option<&'c Self>
+pub trait AnyService<'a> {
+    fn upcast(self) -> Service<'a>;
+    fn downcast<'c>(service: &'c Service<'a>) -> Option<&'c Self>
     where
         Self: Sized;
-    fn downcast_mut<'c>(service: &'c mut Service) -> Option<&'c mut Self>
+    fn downcast_mut<'c>(service: &'c mut Service<'a>) -> Option<&'c mut Self>
     where
         Self: Sized;
 }
 
 macro_rules! from_service {
     ($service:ty, $variant:ident) => {
-        impl AnyService for $service {
-            fn upcast(self) -> Service {
+        impl<'a> AnyService<'a> for $service {
+            fn upcast(self) -> Service<'a> {
                 Service::$variant(self)
             }
 
-            fn downcast<'c>(socket: &'c Service) -> Option<&'c Self> {
+            fn downcast<'c>(socket: &'c Service<'a>) -> Option<&'c Self> {
                 #[allow(unreachable_patterns)]
                 match socket {
                     Service::$variant(socket) => Some(socket),
@@ -28,7 +99,7 @@ macro_rules! from_service {
                 }
             }
 
-            fn downcast_mut<'c>(socket: &'c mut Service) -> Option<&'c mut Self> {
+            fn downcast_mut<'c>(socket: &'c mut Service<'a>) -> Option<&'c mut Self> {
                 #[allow(unreachable_patterns)]
                 match socket {
                     Service::$variant(socket) => Some(socket),
@@ -39,4 +110,9 @@ macro_rules! from_service {
     };
 }
 
-from_service!(vlcb_svc_mns::Service, Mns);
\ No newline at end of file
+from_service!(vlcb_svc_mns::Service, Mns);
+from_service!(vlcb_svc_nv::Service, Nv);
+from_service!(vlcb_svc_discovery::Service, Discovery);
+from_service!(vlcb_svc_diagnostics::Service, Diagnostics);
+from_service!(vlcb_svc_event_producer::Service<'a>, EventProducer);
+from_service!(vlcb_svc_event_teaching::Service, EventTeaching);
@@ -0,0 +1,228 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{VlcbNodeNumber, VlcbResultCode, NODENUM_SIZE};
+use vlcb_defs::{OpCode, ServiceType};
+use vlcb_network::data::packet::construct::{module_cfg::response, OutgoingPacket};
+
+/// Diagnostics service.
+///
+/// Answers the diagnostic data requests (RDGN) with the diagnostic counters of the services.
+/// The services are indexed from 1 in the order they are passed in, which should be the same
+/// order as reported by the service discovery.
+///
+/// Diagnostics are a part of the minimum node service in VLCB, so this service has no
+/// service ID of its own and is not reported by the service discovery.
+#[derive(Default)]
+pub struct Service {}
+
+impl Service {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the responses to transmit when the packet is an RDGN for this node:
+    ///
+    /// * the diagnostic code 0 is answered with the number of diagnostics of the service
+    ///   (as the diagnostic 0), followed by each of its diagnostics,
+    /// * the service index 0 is answered like the code 0 for every service,
+    /// * an unknown service index or diagnostic code is answered with a GRSP error.
+    pub fn process<'s, D, I>(
+        &self,
+        node_num: Option<VlcbNodeNumber>,
+        services: I,
+        packet: &[u8],
+    ) -> Option<impl Iterator<Item = OutgoingPacket> + 's>
+    where
+        D: Diagnostics + ?Sized + 's,
+        I: IntoIterator<Item = &'s D>,
+        I::IntoIter: 's,
+    {
+        let (&opcode, data) = packet.split_first()?;
+        if OpCode::try_from(opcode).ok()? != OpCode::QueryDiagnosticData {
+            return None;
+        }
+
+        let node_num = node_num?;
+        let &[hi, lo, index, code] = data.get(..NODENUM_SIZE + 2)? else {
+            return None;
+        };
+        if node_num.as_bytes() != [hi, lo] {
+            return None;
+        }
+
+        let mut services = services.into_iter();
+        let (selected, all, error) = match index {
+            0 => (None, Some(services), None),
+            _ => match services.nth(index as usize - 1) {
+                Some(service) => (Some((index, service, code)), None, None),
+                None => (None, None, Some(error(node_num, VlcbResultCode::InvalidService))),
+            },
+        };
+
+        let responses = selected
+            .into_iter()
+            .chain(
+                all.into_iter()
+                    .flatten()
+                    .zip(1..=u8::MAX)
+                    .map(|(service, index)| (index, service, 0)),
+            )
+            .flat_map(move |(index, service, code)| diagnostics(node_num, index, service, code));
+
+        Some(error.into_iter().chain(responses))
+    }
+}
+
+/// Return the responses with the diagnostic `code` of the service, or all of them for the code 0.
+fn diagnostics<D: Diagnostics + ?Sized>(
+    node_num: VlcbNodeNumber,
+    index: u8,
+    service: &D,
+    code: u8,
+) -> impl Iterator<Item = OutgoingPacket> + '_ {
+    let codes = match code {
+        0 => 0..=service.diagnostic_count(),
+        _ => code..=code,
+    };
+
+    codes.map(move |code| match code {
+        0 => response::diagnostic(node_num, index, 0, service.diagnostic_count().into()),
+        _ => match service.diagnostic(code) {
+            Some(diagnostic) => response::diagnostic(node_num, index, code, diagnostic.value),
+            None => error(node_num, VlcbResultCode::InvalidDiagnostic),
+        },
+    })
+}
+
+fn error(node_num: VlcbNodeNumber, result: VlcbResultCode) -> OutgoingPacket {
+    response::generic_response(
+        node_num,
+        OpCode::QueryDiagnosticData,
+        ServiceType::MinimumNodeService,
+        result,
+    )
+}
+
+impl VlcbService for Service {
+    fn service_version() -> u8 {
+        1
+    }
+}
+
+impl Diagnostics for Service {}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::service::Diagnostic;
+
+    use super::*;
+
+    const NODE_NUM: Option<VlcbNodeNumber> = Some(VlcbNodeNumber::new(0x01, 0x02));
+
+    #[derive(Default)]
+    struct Counters {
+        rx: u16,
+        errors: u16,
+    }
+
+    impl Diagnostics for Counters {
+        fn diagnostic_count(&self) -> u8 {
+            2
+        }
+
+        fn diagnostic(&self, code: u8) -> Option<Diagnostic> {
+            match code {
+                1 => Some(Diagnostic::new("rx", self.rx)),
+                2 => Some(Diagnostic::new("errors", self.errors)),
+                _ => None,
+            }
+        }
+    }
+
+    struct NoCounters;
+
+    impl Diagnostics for NoCounters {}
+
+    fn request(index: u8, code: u8) -> [u8; 5] {
+        [OpCode::QueryDiagnosticData.into(), 0x01, 0x02, index, code]
+    }
+
+    fn dgn(index: u8, code: u8, value: u16) -> [u8; 7] {
+        let [hi, lo] = value.to_be_bytes();
+        [OpCode::DiagnosticData.into(), 0x01, 0x02, index, code, hi, lo]
+    }
+
+    fn grsp(result: VlcbResultCode) -> [u8; 6] {
+        [
+            OpCode::GenericResponse.into(),
+            0x01,
+            0x02,
+            OpCode::QueryDiagnosticData.into(),
+            ServiceType::MinimumNodeService.into(),
+            result.into(),
+        ]
+    }
+
+    fn payloads(responses: impl Iterator<Item = OutgoingPacket>) -> Vec<Vec<u8>> {
+        responses.map(|p| p.payload.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_read_service_diagnostics() {
+        let service = Service::new();
+        let counters = Counters { rx: 0x0102, errors: 3 };
+        let services: [&dyn Diagnostics; 2] = [&NoCounters, &counters];
+
+        let single = service.process(NODE_NUM, services, &request(2, 1)).unwrap();
+        assert_eq!(payloads(single), [dgn(2, 1, 0x0102)]);
+
+        let all = service.process(NODE_NUM, services, &request(2, 0)).unwrap();
+        assert_eq!(payloads(all), [dgn(2, 0, 2), dgn(2, 1, 0x0102), dgn(2, 2, 3)]);
+
+        let invalid = service.process(NODE_NUM, services, &request(2, 3)).unwrap();
+        assert_eq!(payloads(invalid), [grsp(VlcbResultCode::InvalidDiagnostic)]);
+    }
+
+    #[test]
+    fn test_read_all_services() {
+        let service = Service::new();
+        let counters = [Counters { rx: 5, errors: 0 }, Counters::default()];
+
+        let all = service.process(NODE_NUM, &counters, &request(0, 1)).unwrap();
+        assert_eq!(
+            payloads(all),
+            [
+                dgn(1, 0, 2),
+                dgn(1, 1, 5),
+                dgn(1, 2, 0),
+                dgn(2, 0, 2),
+                dgn(2, 1, 0),
+                dgn(2, 2, 0),
+            ]
+        );
+
+        let invalid = service.process(NODE_NUM, &counters, &request(3, 0)).unwrap();
+        assert_eq!(payloads(invalid), [grsp(VlcbResultCode::InvalidService)]);
+    }
+
+    #[test]
+    fn test_ignores_other_packets() {
+        let service = Service::new();
+        let counters = [Counters::default()];
+
+        assert!(service.process(None, &counters, &request(1, 1)).is_none());
+        assert!(service
+            .process(NODE_NUM, &counters, &[OpCode::QueryDiagnosticData.into(), 0x09, 0x09, 1, 1])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &counters, &[OpCode::QueryDiagnosticData.into(), 0x01, 0x02, 1])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &counters, &[OpCode::QueryNodeInfo.into(), 0x01, 0x02, 1, 1])
+            .is_none());
+    }
+}
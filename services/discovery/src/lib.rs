@@ -0,0 +1,196 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{VlcbNodeNumber, VlcbResultCode, NODENUM_SIZE};
+use vlcb_defs::{OpCode, ServiceType};
+use vlcb_network::data::packet::construct::{module_cfg::response, OutgoingPacket};
+
+/// A service of the module, as reported by the service discovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceRecord {
+    pub id: ServiceType,
+    pub version: u8,
+    /// Service specific data reported by ESD.
+    pub data: [u8; 3],
+}
+
+impl ServiceRecord {
+    pub const fn new(id: ServiceType, version: u8) -> Self {
+        Self {
+            id,
+            version,
+            data: [0; 3],
+        }
+    }
+
+    /// Describe the service `S` by its service ID and version.
+    pub fn of<S: VlcbService>() -> Self {
+        Self::new(S::service_id(), S::service_version())
+    }
+
+    /// Set the service specific data reported by ESD.
+    pub const fn with_data(mut self, data: [u8; 3]) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+/// Service discovery service.
+///
+/// Answers the service discovery requests (RQSD) with the services of the module.
+/// [`ServiceType::Internal`] services are never reported, the remaining ones are
+/// indexed from 1 in the order they are passed in.
+#[derive(Default)]
+pub struct Service {}
+
+impl Service {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the responses to transmit when the packet is an RQSD for this node. The
+    /// service index 0 is answered with the number of services followed by the SD of
+    /// every service, any other index is answered with the ESD of the service at that
+    /// index.
+    pub fn process<'s, I>(
+        &self,
+        node_num: Option<VlcbNodeNumber>,
+        services: I,
+        packet: &[u8],
+    ) -> Option<impl Iterator<Item = OutgoingPacket> + 's>
+    where
+        I: IntoIterator<Item = ServiceRecord>,
+        I::IntoIter: Clone + 's,
+    {
+        let (&opcode, data) = packet.split_first()?;
+        if OpCode::try_from(opcode).ok()? != OpCode::ServiceDiscoveryQuery {
+            return None;
+        }
+
+        let node_num = node_num?;
+        let (&index, nn) = data.get(..NODENUM_SIZE + 1)?.split_last()?;
+        if nn != node_num.as_bytes() {
+            return None;
+        }
+
+        let services = reported(services.into_iter());
+        let (first, records) = match index {
+            0 => {
+                let count = services.clone().count();
+                (response::service_count(node_num, count as u8), Some(services))
+            }
+            _ => (esd(node_num, index, services.clone().nth(index as usize - 1)), None),
+        };
+
+        let records = records.into_iter().flatten().zip(1..).map(move |(service, index)| {
+            response::service_discovery(node_num, index, service.id, service.version)
+        });
+        Some(core::iter::once(first).chain(records))
+    }
+}
+
+/// Iterate the services reported by the service discovery.
+fn reported<I>(services: I) -> impl Iterator<Item = ServiceRecord> + Clone
+where
+    I: Iterator<Item = ServiceRecord> + Clone,
+{
+    services.filter(|s| s.id != ServiceType::Internal)
+}
+
+/// Return the ESD of the service at the `index`, or an error if there is no such service.
+fn esd(node_num: VlcbNodeNumber, index: u8, service: Option<ServiceRecord>) -> OutgoingPacket {
+    match service {
+        Some(service) => response::extended_service_discovery(node_num, index, service.id, service.data),
+        None => response::generic_response(
+            node_num,
+            OpCode::ServiceDiscoveryQuery,
+            ServiceType::MinimumNodeService,
+            VlcbResultCode::InvalidService,
+        ),
+    }
+}
+
+impl VlcbService for Service {
+    fn service_version() -> u8 {
+        1
+    }
+}
+
+impl Diagnostics for Service {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NODE_NUM: Option<VlcbNodeNumber> = Some(VlcbNodeNumber::new(0x01, 0x02));
+
+    fn services() -> [ServiceRecord; 3] {
+        [
+            ServiceRecord::of::<vlcb_svc_mns::Service>(),
+            ServiceRecord::of::<Service>(),
+            ServiceRecord::new(ServiceType::NodeVariable, 1).with_data([0x10, 0x00, 0x00]),
+        ]
+    }
+
+    fn request(index: u8) -> [u8; 4] {
+        [OpCode::ServiceDiscoveryQuery.into(), 0x01, 0x02, index]
+    }
+
+    fn payloads(responses: impl Iterator<Item = OutgoingPacket>) -> Vec<Vec<u8>> {
+        responses.map(|p| p.payload.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_service_count_and_records() {
+        let service = Service::new();
+
+        let responses = payloads(service.process(NODE_NUM, services(), &request(0)).unwrap());
+        assert_eq!(
+            responses,
+            [
+                vec![OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 0, 0, 2],
+                vec![OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 1, 1, 1],
+                vec![OpCode::ServiceDiscoveryResponse.into(), 0x01, 0x02, 2, 2, 1],
+            ],
+            "internal services are not reported"
+        );
+    }
+
+    #[test]
+    fn test_extended_service_discovery() {
+        let service = Service::new();
+
+        let esd = payloads(service.process(NODE_NUM, services(), &request(2)).unwrap());
+        assert_eq!(
+            esd,
+            [vec![OpCode::ExtendedServiceDiscoveryResponse.into(), 0x01, 0x02, 2, 2, 0x10, 0x00, 0x00]]
+        );
+
+        let invalid = payloads(service.process(NODE_NUM, services(), &request(3)).unwrap());
+        assert_eq!(
+            invalid,
+            [vec![
+                OpCode::GenericResponse.into(),
+                0x01,
+                0x02,
+                OpCode::ServiceDiscoveryQuery.into(),
+                ServiceType::MinimumNodeService.into(),
+                VlcbResultCode::InvalidService.into(),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_ignores_other_packets() {
+        let service = Service::new();
+
+        assert!(service.process(None, services(), &request(0)).is_none());
+        assert!(service.process(NODE_NUM, services(), &[OpCode::ServiceDiscoveryQuery.into(), 0x09, 0x09, 0]).is_none());
+        assert!(service.process(NODE_NUM, services(), &[OpCode::ServiceDiscoveryQuery.into(), 0x01, 0x02]).is_none());
+        assert!(service.process(NODE_NUM, services(), &[OpCode::QueryNodeInfo.into(), 0x01, 0x02, 0]).is_none());
+        assert!(service.process(NODE_NUM, services(), &[]).is_none());
+    }
+}
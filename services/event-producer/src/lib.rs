@@ -0,0 +1,155 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{EventId, EventType, EVENT_SIZE};
+use vlcb_defs::OpCode;
+use vlcb_network::data::packet::construct::{layout_ctrl::produce, OutgoingPacket};
+
+/// Source of the current state of events produced by the module.
+///
+/// Implemented by the application code, which is the only one knowing
+/// the state of its inputs.
+pub trait ProducerStateProvider {
+    /// Return the current state of a produced event.
+    ///
+    /// `Some(true)` means the event is "on", `Some(false)` "off". If the event is
+    /// not produced by this module `None` should be returned.
+    ///
+    /// Short events are passed in with the node number part zeroed,
+    /// so they match on device number only.
+    fn state_of(&self, event: &EventId) -> Option<bool>;
+}
+
+/// Event producer service.
+///
+/// Answers accessory state requests (AREQ/ASRQ) for events produced by the module
+/// with the state reported by the registered [`ProducerStateProvider`].
+#[derive(Default)]
+pub struct Service<'a> {
+    provider: Option<&'a dyn ProducerStateProvider>,
+}
+
+impl<'a> Service<'a> {
+    pub fn new() -> Self {
+        Self { provider: None }
+    }
+
+    /// Register the provider of the produced events state.
+    pub fn register_provider(&mut self, provider: &'a dyn ProducerStateProvider) {
+        self.provider = Some(provider)
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the state response to transmit when the packet is an accessory state request
+    /// for one of our produced events.
+    pub fn process(&self, packet: &[u8]) -> Option<OutgoingPacket> {
+        let (&opcode, data) = packet.split_first()?;
+        let data: &[u8; EVENT_SIZE] = data.get(..EVENT_SIZE)?.try_into().ok()?;
+
+        let event = match OpCode::try_from(opcode).ok()? {
+            OpCode::QueryLongEventAccessoryState => EventId::from_bytes(data),
+            OpCode::QueryShortEventAccessoryState => EventId::short_from_wire_bytes(data),
+            _ => return None,
+        };
+
+        let event_type = match self.provider?.state_of(&event)? {
+            true => EventType::AccessoryStatusOn,
+            false => EventType::AccessoryStatusOff,
+        };
+
+        Some(produce::accessory(event_type, event, None))
+    }
+}
+
+impl<'a> VlcbService for Service<'a> {
+    fn service_id() -> vlcb_defs::ServiceType {
+        vlcb_defs::ServiceType::EventProducer
+    }
+
+    fn service_version() -> u8 {
+        1
+    }
+}
+
+impl<'a> Diagnostics for Service<'a> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Inputs;
+
+    impl ProducerStateProvider for Inputs {
+        fn state_of(&self, event: &EventId) -> Option<bool> {
+            match event.as_bytes() {
+                [0x01, 0x02, 0x00, 0x01] if event.is_long() => Some(true),
+                [0x01, 0x02, 0x00, 0x02] if event.is_long() => Some(false),
+                [0x00, 0x00, 0x00, 0x03] if event.is_short() => Some(true),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_state_requests() {
+        let inputs = Inputs;
+        let mut service = Service::new();
+        service.register_provider(&inputs);
+
+        let on = service
+            .process(&[OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02, 0x00, 0x01])
+            .unwrap();
+        assert_eq!(
+            &on.payload[..],
+            &[OpCode::LongEventAccessoryStateOn.into(), 0x01, 0x02, 0x00, 0x01]
+        );
+
+        let off = service
+            .process(&[OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02, 0x00, 0x02])
+            .unwrap();
+        assert_eq!(
+            &off.payload[..],
+            &[OpCode::LongEventAccessoryStateOff.into(), 0x01, 0x02, 0x00, 0x02]
+        );
+
+        assert!(service
+            .process(&[OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02, 0x00, 0x09])
+            .is_none());
+    }
+
+    #[test]
+    fn test_short_state_request_matches_device_number() {
+        let inputs = Inputs;
+        let mut service = Service::new();
+        service.register_provider(&inputs);
+
+        // the node number of the request is ignored and zeroed in the response
+        let on = service
+            .process(&[OpCode::QueryShortEventAccessoryState.into(), 0x0A, 0x0B, 0x00, 0x03])
+            .unwrap();
+        assert_eq!(
+            &on.payload[..],
+            &[OpCode::ShortEventAccessoryStateOn.into(), 0x00, 0x00, 0x00, 0x03]
+        );
+    }
+
+    #[test]
+    fn test_ignores_other_packets() {
+        let inputs = Inputs;
+        let mut service = Service::new();
+
+        let request = [OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02, 0x00, 0x01];
+        assert!(service.process(&request).is_none(), "no provider registered");
+
+        service.register_provider(&inputs);
+        assert!(service
+            .process(&[OpCode::LongEventAccessoryOn.into(), 0x01, 0x02, 0x00, 0x01])
+            .is_none());
+        assert!(service
+            .process(&[OpCode::QueryLongEventAccessoryState.into(), 0x01, 0x02])
+            .is_none());
+        assert!(service.process(&[]).is_none());
+    }
+}
@@ -0,0 +1,524 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{EventId, VlcbNodeNumber, VlcbResultCode, EVENT_SIZE};
+use vlcb_defs::{CommandError, OpCode, ServiceType};
+use vlcb_network::data::packet::construct::{layout_ctrl, module_cfg::response, OutgoingPacket};
+use vlcb_persistence::node_config::{Error, LearnedEvent, NodeConfig};
+
+/// Event teaching service.
+///
+/// Keeps track of the learn mode (NNLRN/NNULN), reports the event counts (NNEVN/RQEVN),
+/// reads back the learned events and their variables (NERD/REVAL), teaches and unlearns
+/// events (EVLRN/EVLRNI/EVULN) and forgets the learned events on NNCLR. Teaching and NNCLR
+/// are only accepted in learn mode to safeguard against an accidental change.
+#[derive(Default)]
+pub struct Service {
+    learn_mode: bool,
+}
+
+impl Service {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return `true` while the node is in learn mode.
+    pub fn is_in_learn_mode(&self) -> bool {
+        self.learn_mode
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the responses to transmit when the packet is an event teaching request for
+    /// this node:
+    ///
+    /// * NNLRN puts the node into learn mode, NNLRN addressed to another node takes it out
+    ///   as only one node may be in learn mode,
+    /// * NNULN takes the node out of learn mode,
+    /// * NNEVN is answered with EVNLF, the amount of free event slots,
+    /// * RQEVN is answered with NUMEV, the amount of learned events,
+    /// * NERD is answered with ENRSP for each learned event, in the order of their indexes,
+    /// * REVAL is answered with NEVAL, the value of an EV of the event at the event index,
+    ///   the EV index 0 with the number of EVs followed by all of them,
+    /// * NNCLR forgets all learned events and is acknowledged with WRACK, outside of learn
+    ///   mode it is answered with CMDERR and nothing is cleared,
+    /// * EVLRN sets an event variable of an event, learning the event first when it is
+    ///   not known yet, an event with a zero node number is taught as a short event,
+    /// * EVLRNI does the same for the event at the given event index, replacing the event
+    ///   previously stored there,
+    /// * EVULN forgets a learned event.
+    ///
+    /// The teaching opcodes carry the event instead of the node number, so they are only
+    /// handled while the node is in learn mode. They are acknowledged with WRACK once the
+    /// change is made, or answered with CMDERR when it can't be. NNCLR and the teaching
+    /// opcodes follow up with GRSP carrying the result, as does a failed REVAL.
+    ///
+    /// The caller is responsible for flushing the changed `config`.
+    pub fn process<'s, S: NodeConfig>(
+        &mut self,
+        node_num: Option<VlcbNodeNumber>,
+        config: &'s mut S,
+        packet: &[u8],
+    ) -> Option<impl Iterator<Item = OutgoingPacket> + 's> {
+        let (&opcode, data) = packet.split_first()?;
+        let opcode = OpCode::try_from(opcode).ok()?;
+        if !matches!(
+            opcode,
+            OpCode::PutNodeIntoLearnMode
+                | OpCode::ReleaseNodeFromLearnMode
+                | OpCode::ForgetAllLearnedEvents
+                | OpCode::QueryAvailableEventSlots
+                | OpCode::QueryLearnedEventCount
+                | OpCode::QueryAllLearnedEvents
+                | OpCode::QueryEventVariable
+                | OpCode::TeachEvent
+                | OpCode::TeachEventByIndex
+                | OpCode::ForgetLearnedEvent
+        ) {
+            return None;
+        }
+
+        let node_num = node_num?;
+        // indexes of the events read back with ENRSP, for NERD
+        let mut events = 0..0;
+        // index of the event whose EVs are read back with NEVAL, for REVAL of all of them
+        let mut vars = None;
+        // GRSP following the response
+        let mut grsp = None;
+
+        let response = if matches!(
+            opcode,
+            OpCode::TeachEvent | OpCode::TeachEventByIndex | OpCode::ForgetLearnedEvent
+        ) {
+            if !self.learn_mode {
+                return None;
+            }
+            let result = match opcode {
+                OpCode::TeachEvent => Self::teach(config, data),
+                OpCode::TeachEventByIndex => Self::teach_by_index(config, data),
+                _ => Self::unlearn(config, data),
+            }?;
+            grsp = Some(Self::generic_response(node_num, opcode, result));
+            Some(match result {
+                Ok(()) => response::write_ack(node_num),
+                Err(err) => response::config_error(node_num, err),
+            })
+        } else {
+            let &[hi, lo, ..] = data else {
+                return None;
+            };
+            let addressed = node_num.as_bytes() == [hi, lo];
+
+            match opcode {
+                OpCode::PutNodeIntoLearnMode => {
+                    self.learn_mode = addressed;
+                    None
+                }
+                OpCode::ReleaseNodeFromLearnMode if addressed => {
+                    self.learn_mode = false;
+                    None
+                }
+                OpCode::ForgetAllLearnedEvents if addressed && self.learn_mode => {
+                    config.clear_all_events();
+                    grsp = Some(Self::generic_response(node_num, opcode, Ok(())));
+                    Some(response::write_ack(node_num))
+                }
+                OpCode::ForgetAllLearnedEvents if addressed => {
+                    let err = CommandError::NotInLearnMode;
+                    grsp = Some(Self::generic_response(node_num, opcode, Err(err)));
+                    Some(response::config_error(node_num, err))
+                }
+                OpCode::QueryAvailableEventSlots if addressed => {
+                    let free = S::MAX_EVENTS.saturating_sub(config.stored_event_count());
+                    Some(response::available_event_slots(node_num, free))
+                }
+                OpCode::QueryLearnedEventCount if addressed => Some(response::saved_events_amount(
+                    node_num,
+                    config.stored_event_count(),
+                )),
+                OpCode::QueryAllLearnedEvents if addressed => {
+                    if config.stored_event_count() > 0 {
+                        events = 0..S::MAX_EVENTS;
+                    }
+                    None
+                }
+                OpCode::QueryEventVariable if addressed => {
+                    let &[index, ev_index] = data.get(2..4)? else {
+                        return None;
+                    };
+                    let result = Self::read_ev(config, index, ev_index);
+                    if result.is_ok() && ev_index == 0 {
+                        vars = Some(index);
+                    }
+                    Some(match result {
+                        Ok(value) => {
+                            layout_ctrl::response::event_variable(node_num, index, ev_index, value)
+                        }
+                        Err(err) => {
+                            grsp = Some(Self::generic_response(node_num, opcode, Err(err)));
+                            response::config_error(node_num, err)
+                        }
+                    })
+                }
+                _ => None,
+            }
+        };
+
+        if response.is_none() && events.is_empty() {
+            return None;
+        }
+
+        let config = &*config;
+        let events = events.filter_map(move |index| {
+            let (&event, _) = config.get_event_by_index(index)?;
+            Some(layout_ctrl::response::event(node_num, event, index))
+        });
+        let vars = vars.into_iter().flat_map(move |index| {
+            (1..=S::EVENT_VAR_COUNT).filter_map(move |ev_index| {
+                let value = Self::read_ev(config, index, ev_index).ok()?;
+                Some(layout_ctrl::response::event_variable(node_num, index, ev_index, value))
+            })
+        });
+        Some(response.into_iter().chain(grsp).chain(events).chain(vars))
+    }
+
+    /// GRSP reporting the `result` of the command `opcode`.
+    fn generic_response(
+        node_num: VlcbNodeNumber,
+        opcode: OpCode,
+        result: Result<(), CommandError>,
+    ) -> OutgoingPacket {
+        let result = result.map_or_else(VlcbResultCode::from, |()| VlcbResultCode::Ok);
+        response::generic_response(node_num, opcode, ServiceType::EventTeaching, result)
+    }
+
+    /// REVAL: the EV `ev_index` of the event at the event `index`, the EV index 0 reads the
+    /// number of EVs.
+    fn read_ev<S: NodeConfig>(config: &S, index: u8, ev_index: u8) -> Result<u8, CommandError> {
+        let (event, _) = config
+            .get_event_by_index(index)
+            .ok_or(CommandError::InvalidEventIndex)?;
+        match ev_index {
+            0 => Ok(S::EVENT_VAR_COUNT),
+            _ => config.get_ev(event, ev_index).map_err(Self::ev_error),
+        }
+    }
+
+    /// Parse the taught event, an event with a zero node number is a short event.
+    fn event(data: &[u8]) -> Option<EventId> {
+        let event = EventId::try_from_bytes(data.get(..EVENT_SIZE)?).ok()?;
+        if event.node_num() == VlcbNodeNumber::new(0, 0) {
+            return Some(EventId::short(event.device_number()));
+        }
+        Some(event)
+    }
+
+    /// Map the storage error of an EV write to the command error reported for it.
+    fn ev_error(err: Error) -> CommandError {
+        match err {
+            Error::OutOfRange => CommandError::InvalidEvIndex,
+            err => err.into(),
+        }
+    }
+
+    /// EVLRN: NN, EN, EV#, EV value
+    fn teach<S: NodeConfig>(config: &mut S, data: &[u8]) -> Option<Result<(), CommandError>> {
+        let event = Self::event(data)?;
+        let &[ev_index, value] = data.get(EVENT_SIZE..EVENT_SIZE + 2)? else {
+            return None;
+        };
+        Some(config.set_ev(&event, ev_index, value).map_err(Self::ev_error))
+    }
+
+    /// EVLRNI: NN, EN, EN#, EV#, EV value
+    fn teach_by_index<S: NodeConfig>(config: &mut S, data: &[u8]) -> Option<Result<(), CommandError>> {
+        let event = Self::event(data)?;
+        let &[index, ev_index, value] = data.get(EVENT_SIZE..EVENT_SIZE + 3)? else {
+            return None;
+        };
+        if index >= S::MAX_EVENTS {
+            return Some(Err(CommandError::InvalidEventIndex));
+        }
+        if ev_index == 0 || ev_index > S::EVENT_VAR_COUNT {
+            return Some(Err(CommandError::InvalidEvIndex));
+        }
+
+        if config.event_index(&event) != Some(index) {
+            if let Some((&previous, _)) = config.get_event_by_index(index) {
+                config.delete_event(&previous);
+            }
+            config.delete_event(&event);
+            if let Err(err) = config.restore_event(event, S::Event::new(index, &[])) {
+                return Some(Err(err.into()));
+            }
+        }
+        Some(config.set_ev(&event, ev_index, value).map_err(Self::ev_error))
+    }
+
+    /// EVULN: NN, EN
+    fn unlearn<S: NodeConfig>(config: &mut S, data: &[u8]) -> Option<Result<(), CommandError>> {
+        let event = Self::event(data)?;
+        if !config.has_event(&event) {
+            return Some(Err(CommandError::InvalidEvent));
+        }
+        config.delete_event(&event);
+        Some(Ok(()))
+    }
+}
+
+impl VlcbService for Service {
+    fn service_id() -> ServiceType {
+        ServiceType::EventTeaching
+    }
+
+    fn service_version() -> u8 {
+        1
+    }
+}
+
+impl Diagnostics for Service {}
+
+#[cfg(test)]
+mod test {
+    use vlcb_core::vlcb::EventId;
+    use vlcb_network::data::packet::construct::module_cfg::{command, query};
+    use vlcb_persistence::node_config::NodeConfigStorage;
+
+    use super::*;
+
+    const NODE_NUM: VlcbNodeNumber = VlcbNodeNumber::new(0x01, 0x02);
+    const EVENT: EventId = EventId::long(VlcbNodeNumber::new(0x03, 0x04), 1);
+
+    fn config() -> NodeConfigStorage<4, 1, 1> {
+        let mut config = NodeConfigStorage::default();
+        config.save_event(&EVENT, &[0x01]).unwrap();
+        config
+    }
+
+    fn payloads(responses: impl Iterator<Item = OutgoingPacket>) -> Vec<Vec<u8>> {
+        responses.map(|p| p.payload.to_vec()).collect()
+    }
+
+    fn wrack() -> Vec<u8> {
+        vec![OpCode::WriteAck.into(), 0x01, 0x02]
+    }
+
+    fn cmderr(err: CommandError) -> Vec<u8> {
+        vec![OpCode::NodeConfigurationError.into(), 0x01, 0x02, err.into()]
+    }
+
+    /// GRSP from the event teaching service (0x07) for the `opcode` with the `result`.
+    fn grsp(opcode: u8, result: u8) -> Vec<u8> {
+        vec![0xAF, 0x01, 0x02, opcode, 0x07, result]
+    }
+
+    #[test]
+    fn test_nnclr_in_learn_mode() {
+        let mut service = Service::new();
+        let mut config = config();
+
+        let nnlrn = command::start_learn_mode(NODE_NUM);
+        assert!(service.process(Some(NODE_NUM), &mut config, &nnlrn.payload).is_none());
+        assert!(service.is_in_learn_mode());
+
+        let nnclr = command::clear_all_events(NODE_NUM);
+        let response = service.process(Some(NODE_NUM), &mut config, &nnclr.payload).unwrap();
+        assert_eq!(payloads(response), [wrack(), grsp(0x55, 0x00)]);
+        assert_eq!(config.stored_event_count(), 0);
+
+        let nnuln = command::end_learn_mode(NODE_NUM);
+        assert!(service.process(Some(NODE_NUM), &mut config, &nnuln.payload).is_none());
+        assert!(!service.is_in_learn_mode());
+    }
+
+    #[test]
+    fn test_nnclr_rejected_outside_learn_mode() {
+        let mut service = Service::new();
+        let mut config = config();
+
+        let nnclr = command::clear_all_events(NODE_NUM);
+        let response = service.process(Some(NODE_NUM), &mut config, &nnclr.payload).unwrap();
+        assert_eq!(
+            payloads(response),
+            [cmderr(CommandError::NotInLearnMode), grsp(0x55, 0x02)]
+        );
+        assert!(config.has_event(&EVENT), "nothing is cleared");
+    }
+
+    #[test]
+    fn test_event_counts() {
+        let mut service = Service::new();
+        let mut config = config();
+        let other = EventId::short(7);
+        config.save_event(&other, &[0x02]).unwrap();
+
+        let nnevn = query::available_event_slots(NODE_NUM);
+        let response = service.process(Some(NODE_NUM), &mut config, &nnevn.payload).unwrap();
+        assert_eq!(payloads(response), [vec![OpCode::AvailableEventSlots.into(), 0x01, 0x02, 2]]);
+
+        let rqevn = query::learned_event_count(NODE_NUM);
+        let response = service.process(Some(NODE_NUM), &mut config, &rqevn.payload).unwrap();
+        assert_eq!(payloads(response), [vec![OpCode::LearnedEventCount.into(), 0x01, 0x02, 2]]);
+
+        let other_node = query::available_event_slots(VlcbNodeNumber::new(0x09, 0x09));
+        assert!(service.process(Some(NODE_NUM), &mut config, &other_node.payload).is_none());
+    }
+
+    #[test]
+    fn test_other_node_in_learn_mode() {
+        let mut service = Service::new();
+        let mut config = config();
+
+        let nnlrn = command::start_learn_mode(NODE_NUM);
+        service.process(Some(NODE_NUM), &mut config, &nnlrn.payload);
+        let other = command::start_learn_mode(VlcbNodeNumber::new(0x09, 0x09));
+        service.process(Some(NODE_NUM), &mut config, &other.payload);
+        assert!(!service.is_in_learn_mode());
+
+        let nnclr = command::clear_all_events(VlcbNodeNumber::new(0x09, 0x09));
+        assert!(service.process(Some(NODE_NUM), &mut config, &nnclr.payload).is_none());
+        assert!(service.process(None, &mut config, &nnclr.payload).is_none());
+        assert!(config.has_event(&EVENT));
+    }
+
+    fn learn_mode(service: &mut Service, config: &mut NodeConfigStorage<4, 1, 1>) {
+        let nnlrn = command::start_learn_mode(NODE_NUM);
+        service.process(Some(NODE_NUM), config, &nnlrn.payload);
+    }
+
+    #[test]
+    fn test_teach_event() {
+        let mut service = Service::new();
+        let mut config = config();
+        learn_mode(&mut service, &mut config);
+
+        let evlrn = [OpCode::TeachEvent.into(), 0x03, 0x04, 0x00, 0x02, 1, 0x42];
+        let response = service.process(Some(NODE_NUM), &mut config, &evlrn).unwrap();
+        assert_eq!(payloads(response), [wrack(), grsp(0xD2, 0x00)]);
+        let taught = EventId::long(VlcbNodeNumber::new(0x03, 0x04), 2);
+        assert_eq!(config.get_ev(&taught, 1), Ok(0x42));
+
+        let short = [OpCode::TeachEvent.into(), 0x00, 0x00, 0x00, 0x07, 1, 0x43];
+        let response = service.process(Some(NODE_NUM), &mut config, &short).unwrap();
+        assert_eq!(payloads(response), [wrack(), grsp(0xD2, 0x00)]);
+        assert_eq!(config.get_ev(&EventId::short(7), 1), Ok(0x43));
+    }
+
+    #[test]
+    fn test_teach_event_rejected() {
+        let mut service = Service::new();
+        let mut config = config();
+
+        let evlrn = [OpCode::TeachEvent.into(), 0x03, 0x04, 0x00, 0x02, 1, 0x42];
+        assert!(service.process(Some(NODE_NUM), &mut config, &evlrn).is_none(), "not in learn mode");
+        assert_eq!(config.stored_event_count(), 1);
+
+        learn_mode(&mut service, &mut config);
+        let invalid_ev = [OpCode::TeachEvent.into(), 0x03, 0x04, 0x00, 0x01, 2, 0x42];
+        let response = service.process(Some(NODE_NUM), &mut config, &invalid_ev).unwrap();
+        assert_eq!(payloads(response), [cmderr(CommandError::InvalidEvIndex), grsp(0xD2, 0x06)]);
+        assert_eq!(config.get_ev(&EVENT, 1), Ok(0x01), "the EVs are left as they were");
+
+        for device in 2..=4 {
+            let evlrn = [OpCode::TeachEvent.into(), 0x00, 0x00, 0x00, device, 1, 0x42];
+            let response = service.process(Some(NODE_NUM), &mut config, &evlrn).unwrap();
+            assert_eq!(payloads(response), [wrack(), grsp(0xD2, 0x00)]);
+        }
+        let response = service.process(Some(NODE_NUM), &mut config, &evlrn).unwrap();
+        assert_eq!(payloads(response), [cmderr(CommandError::TooManyEvents), grsp(0xD2, 0x04)]);
+    }
+
+    #[test]
+    fn test_teach_event_by_index() {
+        let mut service = Service::new();
+        let mut config = config();
+        learn_mode(&mut service, &mut config);
+        let index = config.event_index(&EVENT).unwrap();
+
+        let evlrni = [OpCode::TeachEventByIndex.into(), 0x00, 0x00, 0x00, 0x07, index, 1, 0x42];
+        let response = service.process(Some(NODE_NUM), &mut config, &evlrni).unwrap();
+        assert_eq!(payloads(response), [wrack(), grsp(0xF5, 0x00)]);
+        assert!(!config.has_event(&EVENT), "the event at the index is replaced");
+        assert_eq!(config.event_index(&EventId::short(7)), Some(index));
+        assert_eq!(config.get_ev(&EventId::short(7), 1), Ok(0x42));
+
+        let invalid_index = [OpCode::TeachEventByIndex.into(), 0x00, 0x00, 0x00, 0x07, 4, 1, 0x42];
+        let response = service.process(Some(NODE_NUM), &mut config, &invalid_index).unwrap();
+        assert_eq!(
+            payloads(response),
+            [cmderr(CommandError::InvalidEventIndex), grsp(0xF5, 0x08)]
+        );
+
+        let invalid_ev = [OpCode::TeachEventByIndex.into(), 0x00, 0x00, 0x00, 0x07, index, 2, 0x42];
+        let response = service.process(Some(NODE_NUM), &mut config, &invalid_ev).unwrap();
+        assert_eq!(payloads(response), [cmderr(CommandError::InvalidEvIndex), grsp(0xF5, 0x06)]);
+        assert_eq!(config.get_ev(&EventId::short(7), 1), Ok(0x42));
+    }
+
+    #[test]
+    fn test_unlearn_event() {
+        let mut service = Service::new();
+        let mut config = config();
+        learn_mode(&mut service, &mut config);
+
+        let evuln = [OpCode::ForgetLearnedEvent.into(), 0x03, 0x04, 0x00, 0x01];
+        let response = service.process(Some(NODE_NUM), &mut config, &evuln).unwrap();
+        assert_eq!(payloads(response), [wrack(), grsp(0x95, 0x00)]);
+        assert!(!config.has_event(&EVENT));
+
+        let response = service.process(Some(NODE_NUM), &mut config, &evuln).unwrap();
+        assert_eq!(payloads(response), [cmderr(CommandError::InvalidEvent), grsp(0x95, 0x07)]);
+    }
+
+    #[test]
+    fn test_read_learned_events() {
+        let mut service = Service::new();
+        let mut config = config();
+        config.save_event(&EventId::short(7), &[0x02]).unwrap();
+        let long_index = config.event_index(&EVENT).unwrap();
+        let short_index = config.event_index(&EventId::short(7)).unwrap();
+        let enrsp: u8 = OpCode::LearnedEventResponse.into();
+        let mut expected = [
+            vec![enrsp, 0x01, 0x02, 0x03, 0x04, 0x00, 0x01, long_index],
+            vec![enrsp, 0x01, 0x02, 0x00, 0x00, 0x00, 0x07, short_index],
+        ];
+        expected.sort_by_key(|payload| payload[7]);
+
+        let nerd = layout_ctrl::query::all_learned_events(NODE_NUM);
+        let response = service.process(Some(NODE_NUM), &mut config, &nerd.payload).unwrap();
+        assert_eq!(payloads(response), expected, "in the order of the event indexes");
+
+        config.clear_all_events();
+        assert!(service.process(Some(NODE_NUM), &mut config, &nerd.payload).is_none());
+    }
+
+    #[test]
+    fn test_read_event_variables() {
+        let mut service = Service::new();
+        let mut config = config();
+        let index = config.event_index(&EVENT).unwrap();
+
+        let reval = layout_ctrl::query::event_variable(NODE_NUM, index, 1);
+        let response = service.process(Some(NODE_NUM), &mut config, &reval.payload).unwrap();
+        let neval = |ev_index, value| {
+            vec![OpCode::EventVariableValue.into(), 0x01, 0x02, index, ev_index, value]
+        };
+        assert_eq!(payloads(response), [neval(1, 0x01)]);
+
+        let reval = layout_ctrl::query::event_variable(NODE_NUM, index, 0);
+        let response = service.process(Some(NODE_NUM), &mut config, &reval.payload).unwrap();
+        assert_eq!(payloads(response), [neval(0, 1), neval(1, 0x01)], "the count comes first");
+
+        let reval = layout_ctrl::query::event_variable(NODE_NUM, index, 2);
+        let response = service.process(Some(NODE_NUM), &mut config, &reval.payload).unwrap();
+        assert_eq!(payloads(response), [cmderr(CommandError::InvalidEvIndex), grsp(0x9C, 0x06)]);
+
+        let reval = layout_ctrl::query::event_variable(NODE_NUM, index + 1, 1);
+        let response = service.process(Some(NODE_NUM), &mut config, &reval.payload).unwrap();
+        assert_eq!(
+            payloads(response),
+            [cmderr(CommandError::InvalidEventIndex), grsp(0x9C, 0x08)]
+        );
+    }
+}
@@ -1,7 +1,53 @@
-use vlcb_core::service::VlcbService;
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
 
-pub struct Service {
+use vlcb_core::module::ModuleParams;
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{VlcbNodeNumber, NODENUM_SIZE};
+use vlcb_defs::{CommandError, OpCode};
+use vlcb_network::data::packet::construct::{module_cfg::response, OutgoingPacket};
 
+/// Minimum node service.
+///
+/// Answers the node parameter requests (RQNP/RQNPN) from the module parameter block.
+#[derive(Default)]
+pub struct Service {}
+
+impl Service {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the response to transmit when the packet is a parameter request for this node.
+    /// RQNP carries no node number, so it should only be passed in while the node
+    /// is in setup mode. Requests addressed to a node are ignored while `node_num` is `None`.
+    pub fn process(
+        &self,
+        node_num: Option<VlcbNodeNumber>,
+        params: &ModuleParams,
+        packet: &[u8],
+    ) -> Option<OutgoingPacket> {
+        let (&opcode, data) = packet.split_first()?;
+
+        match OpCode::try_from(opcode).ok()? {
+            OpCode::QueryNodeParameters => Some(response::node_params(params.first_seven())),
+            OpCode::QueryNodeParameterByIndex => {
+                let node_num = node_num?;
+                let (&index, nn) = data.get(..NODENUM_SIZE + 1)?.split_last()?;
+                if nn != node_num.as_bytes() {
+                    return None;
+                }
+
+                Some(match params.get(index) {
+                    Some(value) => response::node_parameter(node_num, index, value),
+                    None => response::config_error(node_num, CommandError::InvalidParamIndex),
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 impl VlcbService for Service {
@@ -13,3 +59,105 @@ impl VlcbService for Service {
         1
     }
 }
+
+impl Diagnostics for Service {}
+
+#[cfg(test)]
+mod test {
+    use vlcb_defs::ModuleParam;
+
+    use super::*;
+
+    const NODE_NUM: Option<VlcbNodeNumber> = Some(VlcbNodeNumber::new(0x01, 0x02));
+
+    fn params() -> ModuleParams {
+        let mut params = ModuleParams::new();
+        params.set_param(ModuleParam::ModuleManufacturer, 0xA5);
+        params.set_param(ModuleParam::MinorVersion, b'a');
+        params.set_param(ModuleParam::ModuleType, 0xFC);
+        params.set_param(ModuleParam::MaxEventCount, 8);
+        params.set_param(ModuleParam::EventVariableCount, 2);
+        params.set_param(ModuleParam::NodeVariableCount, 4);
+        params.set_param(ModuleParam::MajorVersion, 1);
+        params.set_param(ModuleParam::BetaVersion, 3);
+        params
+    }
+
+    fn request(index: u8) -> [u8; 4] {
+        [OpCode::QueryNodeParameterByIndex.into(), 0x01, 0x02, index]
+    }
+
+    #[test]
+    fn test_node_params() {
+        let service = Service::new();
+
+        let response = service
+            .process(NODE_NUM, &params(), &[OpCode::QueryNodeParameters.into()])
+            .unwrap();
+        assert_eq!(
+            &response.payload[..],
+            &[OpCode::NodeParametersReport.into(), 0xA5, b'a', 0xFC, 8, 2, 4, 1]
+        );
+    }
+
+    #[test]
+    fn test_node_parameter_by_index() {
+        let service = Service::new();
+        let params = params();
+
+        let count = service.process(NODE_NUM, &params, &request(0)).unwrap();
+        assert_eq!(
+            &count.payload[..],
+            &[OpCode::NodeParameterValue.into(), 0x01, 0x02, 0, 20]
+        );
+
+        let beta = service.process(NODE_NUM, &params, &request(20)).unwrap();
+        assert_eq!(
+            &beta.payload[..],
+            &[OpCode::NodeParameterValue.into(), 0x01, 0x02, 20, 3]
+        );
+
+        let invalid = service.process(NODE_NUM, &params, &request(21)).unwrap();
+        assert_eq!(
+            &invalid.payload[..],
+            &[OpCode::NodeConfigurationError.into(), 0x01, 0x02, CommandError::InvalidParamIndex.into()]
+        );
+    }
+
+    #[test]
+    fn test_ignores_other_packets() {
+        let service = Service::new();
+        let params = params();
+
+        assert!(service
+            .process(NODE_NUM, &params, &[OpCode::QueryNodeParameterByIndex.into(), 0x09, 0x09, 1])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &params, &[OpCode::QueryNodeParameterByIndex.into(), 0x01, 0x02])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &params, &[OpCode::QueryNodeInfo.into()])
+            .is_none());
+        assert!(service.process(NODE_NUM, &params, &[]).is_none());
+    }
+
+    #[test]
+    fn test_addressed_requests_need_node_number() {
+        let service = Service::new();
+        let params = params();
+        let request = [OpCode::QueryNodeParameterByIndex.into(), 0x00, 0x00, 1];
+
+        assert!(service.process(None, &params, &request).is_none());
+        assert!(service
+            .process(None, &params, &[OpCode::QueryNodeParameters.into()])
+            .is_some());
+
+        let response = service
+            .process(Some(VlcbNodeNumber::new(0x00, 0x00)), &params, &request)
+            .unwrap();
+        assert_eq!(
+            &response.payload[..],
+            &[OpCode::NodeParameterValue.into(), 0x00, 0x00, 1, 0xA5]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![deny(unsafe_code)]
+
+use vlcb_core::service::{Diagnostics, VlcbService};
+use vlcb_core::vlcb::{VlcbNodeNumber, VlcbResultCode};
+use vlcb_defs::{CommandError, OpCode, ServiceType};
+use vlcb_network::data::packet::construct::{module_cfg::response, OutgoingPacket};
+use vlcb_persistence::node_config::NodeConfig;
+
+/// Node variable service.
+///
+/// Answers the node variable reads (NVRD) and writes (NVSET, NVSETRD) addressed to the node.
+/// Node variables are indexed from 1, NVRD with the index 0 reads all of them.
+#[derive(Default)]
+pub struct Service {}
+
+impl Service {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Process an incoming VLCB packet (the opcode followed by its data octets).
+    ///
+    /// Returns the responses to transmit when the packet is a node variable request for
+    /// this node:
+    ///
+    /// * NVSET is acknowledged with WRACK and GRSP(OK) once the node variable is written,
+    /// * NVSETRD is answered with the written value,
+    /// * NVRD is answered with the value, or for the index 0 with the number of node
+    ///   variables followed by each of them,
+    /// * an invalid index is answered with CMDERR and GRSP with the error, and nothing
+    ///   is written.
+    ///
+    /// The caller is responsible for flushing the written `config`.
+    pub fn process<'s, S: NodeConfig>(
+        &self,
+        node_num: Option<VlcbNodeNumber>,
+        config: &'s mut S,
+        packet: &[u8],
+    ) -> Option<impl Iterator<Item = OutgoingPacket> + 's> {
+        let (&opcode, data) = packet.split_first()?;
+        let opcode = OpCode::try_from(opcode).ok()?;
+        if !matches!(
+            opcode,
+            OpCode::LegacySetNodeVariable | OpCode::SetNodeVariable | OpCode::QueryNodeVariable
+        ) {
+            return None;
+        }
+
+        let node_num = node_num?;
+        let &[hi, lo, index, ref value @ ..] = data else {
+            return None;
+        };
+        if node_num.as_bytes() != [hi, lo] {
+            return None;
+        }
+
+        // number of node variables following the response, for NVRD of all of them
+        let mut following = 0;
+        let response = match opcode {
+            OpCode::QueryNodeVariable if index == 0 => {
+                following = S::NODE_VAR_COUNT;
+                Ok(response::node_variable(node_num, 0, S::NODE_VAR_COUNT))
+            }
+            OpCode::QueryNodeVariable => config
+                .get_nv(index)
+                .map(|value| response::node_variable(node_num, index, value)),
+            _ => {
+                let &value = value.first()?;
+                config.set_nv(index, value).map(|()| match opcode {
+                    OpCode::SetNodeVariable => response::node_variable(node_num, index, value),
+                    _ => response::write_ack(node_num),
+                })
+            }
+        };
+        let (response, result) = match response {
+            Ok(response) => {
+                let ok = opcode == OpCode::LegacySetNodeVariable;
+                (response, ok.then_some(VlcbResultCode::Ok))
+            }
+            Err(err) => {
+                let err = CommandError::from(err);
+                (response::config_error(node_num, err), Some(err.into()))
+            }
+        };
+        let grsp = result.map(|result| {
+            response::generic_response(node_num, opcode, ServiceType::NodeVariable, result)
+        });
+
+        let config = &*config;
+        let all = (1..=following).filter_map(move |index| {
+            let value = config.get_nv(index).ok()?;
+            Some(response::node_variable(node_num, index, value))
+        });
+        Some(core::iter::once(response).chain(grsp).chain(all))
+    }
+}
+
+impl VlcbService for Service {
+    fn service_id() -> ServiceType {
+        ServiceType::NodeVariable
+    }
+
+    fn service_version() -> u8 {
+        1
+    }
+}
+
+impl Diagnostics for Service {}
+
+#[cfg(test)]
+mod test {
+    use vlcb_persistence::node_config::NodeConfigStorage;
+
+    use super::*;
+
+    const NODE_NUM: Option<VlcbNodeNumber> = Some(VlcbNodeNumber::new(0x01, 0x02));
+
+    fn payloads(responses: impl Iterator<Item = OutgoingPacket>) -> Vec<Vec<u8>> {
+        responses.map(|p| p.payload.to_vec()).collect()
+    }
+
+    fn cmderr() -> Vec<u8> {
+        vec![
+            OpCode::NodeConfigurationError.into(),
+            0x01,
+            0x02,
+            CommandError::InvalidNvIndex.into(),
+        ]
+    }
+
+    fn grsp(opcode: OpCode, result: VlcbResultCode) -> Vec<u8> {
+        let service = ServiceType::NodeVariable.into();
+        vec![OpCode::GenericResponse.into(), 0x01, 0x02, opcode.into(), service, result.into()]
+    }
+
+    #[test]
+    fn test_write_ack_after_nvset() {
+        let service = Service::new();
+        let mut config = NodeConfigStorage::<2, 1, 2>::default();
+
+        let nvset = [OpCode::LegacySetNodeVariable.into(), 0x01, 0x02, 2, 0x10];
+        let responses = service.process(NODE_NUM, &mut config, &nvset).unwrap();
+        assert_eq!(
+            payloads(responses),
+            [
+                vec![OpCode::WriteAck.into(), 0x01, 0x02],
+                vec![0xAF, 0x01, 0x02, 0x96, 0x02, 0x00],
+            ]
+        );
+        assert_eq!(config.get_nv(2), Ok(0x10));
+
+        let nvset = [OpCode::LegacySetNodeVariable.into(), 0x01, 0x02, 3, 0x10];
+        let responses = service.process(NODE_NUM, &mut config, &nvset).unwrap();
+        assert_eq!(
+            payloads(responses),
+            [cmderr(), vec![0xAF, 0x01, 0x02, 0x96, 0x02, 0x0A]],
+            "no WRACK after a failed write"
+        );
+    }
+
+    #[test]
+    fn test_nvsetrd() {
+        let service = Service::new();
+        let mut config = NodeConfigStorage::<2, 1, 2>::default();
+
+        let nvsetrd = [OpCode::SetNodeVariable.into(), 0x01, 0x02, 1, 0x20];
+        let responses = service.process(NODE_NUM, &mut config, &nvsetrd).unwrap();
+        assert_eq!(
+            payloads(responses),
+            [vec![OpCode::NodeVariableValue.into(), 0x01, 0x02, 1, 0x20]]
+        );
+
+        let invalid = [OpCode::SetNodeVariable.into(), 0x01, 0x02, 3, 0x20];
+        let responses = service.process(NODE_NUM, &mut config, &invalid).unwrap();
+        assert_eq!(
+            payloads(responses),
+            [cmderr(), grsp(OpCode::SetNodeVariable, VlcbResultCode::InvalidNvIndex)]
+        );
+    }
+
+    #[test]
+    fn test_nvrd() {
+        let service = Service::new();
+        let mut config = NodeConfigStorage::<2, 1, 2>::default();
+        config.set_nv(1, 0x05).unwrap();
+        config.set_nv(2, 0x06).unwrap();
+
+        let nvrd = |index| [OpCode::QueryNodeVariable.into(), 0x01, 0x02, index];
+        let nvans = |index, value| [OpCode::NodeVariableValue.into(), 0x01, 0x02, index, value];
+
+        let single = service.process(NODE_NUM, &mut config, &nvrd(2)).unwrap();
+        assert_eq!(payloads(single), [nvans(2, 0x06)]);
+
+        let all = service.process(NODE_NUM, &mut config, &nvrd(0)).unwrap();
+        assert_eq!(payloads(all), [nvans(0, 2), nvans(1, 0x05), nvans(2, 0x06)]);
+
+        let invalid = service.process(NODE_NUM, &mut config, &nvrd(3)).unwrap();
+        assert_eq!(
+            payloads(invalid),
+            [cmderr(), grsp(OpCode::QueryNodeVariable, VlcbResultCode::InvalidNvIndex)]
+        );
+    }
+
+    #[test]
+    fn test_ignores_other_packets() {
+        let service = Service::new();
+        let mut config = NodeConfigStorage::<2, 1, 2>::default();
+
+        let nvset = [OpCode::LegacySetNodeVariable.into(), 0x01, 0x02, 1, 0x10];
+        assert!(service.process(None, &mut config, &nvset).is_none());
+        assert!(service
+            .process(NODE_NUM, &mut config, &[OpCode::LegacySetNodeVariable.into(), 0x09, 0x09, 1, 0x10])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &mut config, &[OpCode::LegacySetNodeVariable.into(), 0x01, 0x02, 1])
+            .is_none());
+        assert!(service
+            .process(NODE_NUM, &mut config, &[OpCode::QueryNodeInfo.into(), 0x01, 0x02, 1])
+            .is_none());
+        assert_eq!(config.get_nv(1), Ok(0xFF), "nothing is written");
+    }
+}